@@ -0,0 +1,263 @@
+//! Client for the bundle JSON-RPC API of a Jito block engine.
+//!
+//! A bundle is up to five transactions executed in order and all-or-nothing, in a slot of a
+//! Jito validator. It only gets in when one of them tips one of the block engine's tip
+//! accounts.
+
+use std::{str::FromStr, time::Instant};
+
+use anyhow::Context;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use metrics::{counter, histogram};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use solana_sdk::{pubkey::Pubkey, transaction::VersionedTransaction};
+use tokio::sync::OnceCell;
+
+use crate::telemetry;
+
+/// Where a bundle is, as reported by `getInflightBundleStatuses`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize)]
+pub enum BundleStatus {
+    /// Unknown to the block engine: not seen yet, or dropped more than 5 minutes ago.
+    Invalid,
+    /// Not landed and not failed yet.
+    Pending,
+    /// Every region it was sent to has given up on it.
+    Failed,
+    /// Landed in a block, possibly not confirmed yet.
+    Landed,
+}
+
+#[derive(Debug, Deserialize)]
+struct InflightBundleStatuses {
+    value: Vec<InflightBundleStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InflightBundleStatus {
+    status: BundleStatus,
+}
+
+pub struct JitoClient {
+    url: String,
+    http_client: reqwest::Client,
+    /// Tip accounts are fixed, so they are fetched once.
+    tip_accounts: OnceCell<Vec<Pubkey>>,
+}
+
+impl JitoClient {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            http_client: reqwest::Client::new(),
+            tip_accounts: OnceCell::new(),
+        }
+    }
+
+    /// The accounts a bundle may tip.
+    pub async fn get_tip_accounts(&self) -> anyhow::Result<&[Pubkey]> {
+        let tip_accounts = self
+            .tip_accounts
+            .get_or_try_init(|| async {
+                let accounts = self
+                    .request::<Vec<String>>("getTipAccounts", json!([]))
+                    .await?;
+                let accounts = accounts
+                    .iter()
+                    .map(|account| Pubkey::from_str(account))
+                    .collect::<Result<Vec<_>, _>>()
+                    .with_context(|| "Invalid tip account")?;
+                anyhow::ensure!(!accounts.is_empty(), "The block engine has no tip account");
+
+                Ok(accounts)
+            })
+            .await
+            .with_context(|| "Failed to get tip accounts")?;
+
+        Ok(tip_accounts)
+    }
+
+    /// Submits `transactions` as one bundle and returns its id.
+    pub async fn send_bundle(
+        &self,
+        transactions: &[VersionedTransaction],
+    ) -> anyhow::Result<String> {
+        let encoded = transactions
+            .iter()
+            .map(|transaction| Ok(STANDARD.encode(bincode::serialize(transaction)?)))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .with_context(|| "Failed to serialize bundle")?;
+
+        let bundle_id = self
+            .request::<String>("sendBundle", json!([encoded, { "encoding": "base64" }]))
+            .await
+            .with_context(|| "Failed to send bundle")?;
+
+        Ok(bundle_id)
+    }
+
+    /// Where `bundle_id` is, as far as the block engine knows.
+    pub async fn get_inflight_bundle_status(
+        &self,
+        bundle_id: &str,
+    ) -> anyhow::Result<BundleStatus> {
+        let statuses = self
+            .request::<InflightBundleStatuses>("getInflightBundleStatuses", json!([[bundle_id]]))
+            .await
+            .with_context(|| format!("Failed to get status of bundle {}", bundle_id))?;
+
+        Ok(statuses
+            .value
+            .first()
+            .map_or(BundleStatus::Invalid, |status| status.status))
+    }
+
+    /// Sends one JSON-RPC request and returns its result, recorded like the Solana RPC ones.
+    async fn request<T: DeserializeOwned>(
+        &self,
+        method: &'static str,
+        params: Value,
+    ) -> anyhow::Result<T> {
+        let started = Instant::now();
+        let result = self.try_request(method, params).await;
+
+        histogram!(telemetry::RPC_REQUEST_DURATION, "method" => method)
+            .record(started.elapsed().as_secs_f64());
+        if result.is_err() {
+            counter!(telemetry::RPC_ERRORS, "method" => method).increment(1);
+        }
+
+        result
+    }
+
+    async fn try_request<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: Value,
+    ) -> anyhow::Result<T> {
+        let response = self
+            .http_client
+            .post(&self.url)
+            .json(&json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": method,
+                "params": params,
+            }))
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach block engine {}", self.url))?;
+
+        let status = response.status();
+        let mut body = response
+            .json::<Value>()
+            .await
+            .with_context(|| format!("Invalid {} response ({})", method, status))?;
+        if let Some(error) = body.get("error") {
+            anyhow::bail!("{} failed: {}", method, error);
+        }
+        anyhow::ensure!(status.is_success(), "{} failed: {}", method, status);
+
+        serde_json::from_value(body["result"].take())
+            .with_context(|| format!("Unexpected {} result", method))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::{Matcher, Server};
+    use solana_sdk::{hash::Hash, signature::Keypair, signer::Signer, transaction::Transaction};
+
+    use super::*;
+
+    fn result(result: Value) -> String {
+        json!({ "jsonrpc": "2.0", "id": 1, "result": result }).to_string()
+    }
+
+    #[tokio::test]
+    async fn sends_bundles_and_follows_them() {
+        let mut server = Server::new_async().await;
+        let payer = Keypair::new();
+        let transaction: VersionedTransaction = Transaction::new_signed_with_payer(
+            &[solana_system_interface::instruction::transfer(
+                &payer.pubkey(),
+                &Pubkey::new_unique(),
+                1_000,
+            )],
+            Some(&payer.pubkey()),
+            &[&payer],
+            Hash::default(),
+        )
+        .into();
+        let encoded = STANDARD.encode(bincode::serialize(&transaction).unwrap());
+
+        let send = server
+            .mock("POST", "/")
+            .match_body(Matcher::PartialJson(json!({
+                "method": "sendBundle",
+                "params": [[encoded], { "encoding": "base64" }],
+            })))
+            .with_body(result(json!("bundle")))
+            .create_async()
+            .await;
+        let status = server
+            .mock("POST", "/")
+            .match_body(Matcher::PartialJson(json!({
+                "method": "getInflightBundleStatuses",
+                "params": [["bundle"]],
+            })))
+            .with_body(result(json!({
+                "context": { "slot": 1 },
+                "value": [{ "bundle_id": "bundle", "status": "Landed", "landed_slot": 1 }],
+            })))
+            .create_async()
+            .await;
+
+        let client = JitoClient::new(&server.url());
+        let bundle_id = client.send_bundle(&[transaction]).await.unwrap();
+        let landed = client.get_inflight_bundle_status(&bundle_id).await.unwrap();
+
+        assert_eq!(bundle_id, "bundle");
+        assert_eq!(landed, BundleStatus::Landed);
+        send.assert_async().await;
+        status.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn surfaces_block_engine_errors() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("POST", "/")
+            .with_body(
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "error": { "code": -32602, "message": "bundle contains an expired blockhash" },
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let client = JitoClient::new(&server.url());
+        let error = client.send_bundle(&[]).await.unwrap_err();
+
+        assert!(format!("{:#}", error).contains("expired blockhash"));
+    }
+
+    #[tokio::test]
+    async fn bundles_unknown_to_the_block_engine_are_invalid() {
+        let mut server = Server::new_async().await;
+        server
+            .mock("POST", "/")
+            .with_body(result(json!({ "context": { "slot": 1 }, "value": [] })))
+            .create_async()
+            .await;
+
+        let client = JitoClient::new(&server.url());
+        let status = client.get_inflight_bundle_status("bundle").await.unwrap();
+
+        assert_eq!(status, BundleStatus::Invalid);
+    }
+}
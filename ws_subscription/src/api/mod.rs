@@ -1 +1,3 @@
+pub mod jito;
+pub mod raydium;
 pub mod solana_rpc;
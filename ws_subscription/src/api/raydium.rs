@@ -0,0 +1,123 @@
+//! Client for the public Raydium API, which lists the keys of pools once they are indexed.
+
+use std::{str::FromStr, time::Instant};
+
+use anyhow::Context;
+use metrics::{counter, histogram};
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::telemetry;
+
+#[derive(Debug, Deserialize)]
+struct Response<T> {
+    success: bool,
+    data: T,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PoolKeys {
+    lookup_table_account: Option<String>,
+}
+
+pub struct RaydiumApi {
+    url: String,
+    http_client: reqwest::Client,
+}
+
+impl RaydiumApi {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            http_client: reqwest::Client::new(),
+        }
+    }
+
+    /// The address lookup table Raydium made for `pool`. `None` when the pool is not listed
+    /// (yet), or has no table.
+    pub async fn get_pool_lookup_table(&self, pool: &Pubkey) -> anyhow::Result<Option<Pubkey>> {
+        let started = Instant::now();
+        let result = self.try_get_pool_keys(pool).await;
+
+        histogram!(telemetry::RPC_REQUEST_DURATION, "method" => "raydiumPoolKeys")
+            .record(started.elapsed().as_secs_f64());
+        if result.is_err() {
+            counter!(telemetry::RPC_ERRORS, "method" => "raydiumPoolKeys").increment(1);
+        }
+
+        let lookup_table = result
+            .with_context(|| format!("Failed to get keys of pool {}", pool))?
+            .and_then(|keys| keys.lookup_table_account)
+            .filter(|address| !address.is_empty());
+
+        lookup_table
+            .map(|address| {
+                Pubkey::from_str(&address)
+                    .with_context(|| format!("Invalid lookup table {} of pool {}", address, pool))
+            })
+            .transpose()
+    }
+
+    async fn try_get_pool_keys(&self, pool: &Pubkey) -> anyhow::Result<Option<PoolKeys>> {
+        let response = self
+            .http_client
+            .get(format!("{}/pools/key/ids", self.url))
+            .query(&[("ids", pool.to_string())])
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach the Raydium API {}", self.url))?
+            .error_for_status()?
+            .json::<Response<Vec<Option<PoolKeys>>>>()
+            .await
+            .with_context(|| "Invalid pool keys response")?;
+        anyhow::ensure!(response.success, "The Raydium API refused the request");
+
+        Ok(response.data.into_iter().next().flatten())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::{Matcher, Server};
+    use serde_json::json;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn reads_the_lookup_table_of_listed_pools_only() {
+        let mut server = Server::new_async().await;
+        let (listed, unlisted, lookup_table) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        server
+            .mock("GET", "/pools/key/ids")
+            .match_query(Matcher::UrlEncoded("ids".into(), listed.to_string()))
+            .with_body(
+                json!({
+                    "id": "request",
+                    "success": true,
+                    "data": [{ "id": listed.to_string(), "lookupTableAccount": lookup_table.to_string() }],
+                })
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/pools/key/ids")
+            .match_query(Matcher::UrlEncoded("ids".into(), unlisted.to_string()))
+            .with_body(json!({ "id": "request", "success": true, "data": [null] }).to_string())
+            .create_async()
+            .await;
+
+        let api = RaydiumApi::new(&server.url());
+
+        assert_eq!(
+            api.get_pool_lookup_table(&listed).await.unwrap(),
+            Some(lookup_table)
+        );
+        assert_eq!(api.get_pool_lookup_table(&unlisted).await.unwrap(), None);
+    }
+}
@@ -1,15 +1,41 @@
 use anyhow::Context;
+use metrics::{counter, histogram};
 use serde::{Deserialize, Serialize};
-use solana_account_decoder::parse_token::UiTokenAmount;
-use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcTransactionConfig};
+use solana_account_decoder::{parse_token::UiTokenAmount, UiAccountEncoding};
+use solana_client::{
+    client_error::Result as ClientResult,
+    nonblocking::rpc_client::RpcClient,
+    rpc_client::{GetConfirmedSignaturesForAddress2Config, SerializableTransaction},
+    rpc_config::{
+        RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcSendTransactionConfig,
+        RpcTransactionConfig,
+    },
+    rpc_filter::RpcFilterType,
+    rpc_response::{RpcConfirmedTransactionStatusWithSignature, RpcSimulateTransactionResult},
+};
 use solana_sdk::{
-    commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature,
-    transaction::TransactionVersion,
+    account::Account, commitment_config::CommitmentConfig, hash::Hash, pubkey::Pubkey,
+    signature::Signature, transaction::TransactionVersion,
 };
 use solana_transaction_status_client_types::{
-    EncodedTransaction, UiTransactionEncoding, UiTransactionStatusMeta,
+    EncodedTransaction, TransactionStatus, UiTransactionEncoding, UiTransactionStatusMeta,
+};
+use std::{
+    future::Future,
+    str::FromStr,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+use tokio::{
+    task::JoinHandle,
+    time::{interval, MissedTickBehavior},
 };
-use std::{str::FromStr, time::Duration};
+use tracing::{debug, warn};
+
+use crate::telemetry;
+
+/// Slot time the network targets, to tell how many blocks went by since a blockhash was fetched.
+const SLOT_DURATION: Duration = Duration::from_millis(400);
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Transaction {
@@ -22,8 +48,39 @@ pub struct Transaction {
     pub signature: Signature,
 }
 
+/// The latest blockhash, as last fetched by the background refresh.
+#[derive(Clone, Copy, Debug)]
+pub struct RecentBlockhash {
+    pub blockhash: Hash,
+    pub last_valid_block_height: u64,
+    /// Block height when the blockhash was fetched.
+    pub block_height: u64,
+    pub fetched_at: Instant,
+    /// Close enough to expiring, or too old, that a transaction signed on it may not land.
+    pub stale: bool,
+}
+
+impl RecentBlockhash {
+    /// Blocks left before the blockhash expires, estimated from the time since it was fetched.
+    pub fn remaining_blocks(&self) -> u64 {
+        let elapsed_blocks =
+            (self.fetched_at.elapsed().as_millis() / SLOT_DURATION.as_millis()) as u64;
+
+        self.last_valid_block_height
+            .saturating_sub(self.block_height + elapsed_blocks)
+    }
+}
+
+#[derive(Debug, Default)]
+struct BlockhashCache {
+    latest: Option<RecentBlockhash>,
+    /// The blockhash is stale with fewer blocks than this left.
+    min_remaining_blocks: u64,
+}
+
 pub struct SolanaApi {
     rpc_client: RpcClient,
+    blockhash: RwLock<BlockhashCache>,
 }
 
 impl SolanaApi {
@@ -37,24 +94,108 @@ impl SolanaApi {
             commitment_config.unwrap_or_default(),
         );
 
-        Self { rpc_client }
+        Self {
+            rpc_client,
+            blockhash: RwLock::new(BlockhashCache::default()),
+        }
+    }
+
+    /// Keeps the latest blockhash at `commitment` cached, fetching it every `every`, for as
+    /// long as the API is alive. It is marked stale with fewer than `min_remaining_blocks`
+    /// blocks left.
+    pub fn spawn_blockhash_refresh(
+        self: &Arc<Self>,
+        every: Duration,
+        commitment: CommitmentConfig,
+        min_remaining_blocks: u64,
+    ) -> JoinHandle<()> {
+        self.blockhash.write().unwrap().min_remaining_blocks = min_remaining_blocks;
+        let api = Arc::downgrade(self);
+
+        tokio::spawn(async move {
+            let mut ticker = interval(every);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            let mut failing = false;
+
+            loop {
+                ticker.tick().await;
+                let Some(api) = api.upgrade() else {
+                    break;
+                };
+
+                match api.refresh_blockhash(commitment).await {
+                    Ok(()) => failing = false,
+                    // Logged once per outage rather than on every tick.
+                    Err(e) if !failing => {
+                        warn!("Failed to refresh the cached blockhash: {:#}", e);
+                        failing = true;
+                    }
+                    Err(e) => debug!("Failed to refresh the cached blockhash: {:#}", e),
+                }
+            }
+        })
+    }
+
+    /// The cached blockhash, without waiting on the network. `None` until it was first
+    /// fetched.
+    pub fn recent_blockhash(&self) -> Option<RecentBlockhash> {
+        let cache = self.blockhash.read().unwrap();
+        cache.latest.map(|latest| RecentBlockhash {
+            stale: latest.remaining_blocks() < cache.min_remaining_blocks,
+            ..latest
+        })
+    }
+
+    async fn refresh_blockhash(&self, commitment: CommitmentConfig) -> anyhow::Result<()> {
+        let fetched_at = Instant::now();
+        let ((blockhash, last_valid_block_height), block_height) = tokio::try_join!(
+            async {
+                observe(
+                    "getLatestBlockhash",
+                    self.rpc_client
+                        .get_latest_blockhash_with_commitment(commitment),
+                )
+                .await
+                .with_context(|| "Failed to get latest blockhash")
+            },
+            async {
+                observe(
+                    "getBlockHeight",
+                    self.rpc_client.get_block_height_with_commitment(commitment),
+                )
+                .await
+                .with_context(|| "Failed to get block height")
+            },
+        )?;
+
+        self.blockhash.write().unwrap().latest = Some(RecentBlockhash {
+            blockhash,
+            last_valid_block_height,
+            block_height,
+            fetched_at,
+            stale: false,
+        });
+
+        Ok(())
     }
 
     pub async fn get_transaction(&self, signature: &str) -> anyhow::Result<Transaction> {
-        let signature = Signature::from_str(signature).unwrap();
+        let signature = Signature::from_str(signature)
+            .with_context(|| format!("Invalid signature {}", signature))?;
 
-        let transaction = self
-            .rpc_client
-            .get_transaction_with_config(
+        let transaction = observe(
+            "getTransaction",
+            self.rpc_client.get_transaction_with_config(
                 &signature,
                 RpcTransactionConfig {
                     encoding: Some(UiTransactionEncoding::JsonParsed),
                     commitment: None,
                     max_supported_transaction_version: Some(0),
                 },
-            )
-            .await
-            .with_context(|| format!("Failed to get transaction: {}", signature))?;
+            ),
+        )
+        .await
+        .with_context(|| format!("Failed to get transaction: {}", signature))?;
 
         Ok(Transaction {
             slot: transaction.slot,
@@ -66,23 +207,52 @@ impl SolanaApi {
         })
     }
 
+    /// One page of signatures involving `address`, newest first, starting right before `before`.
+    pub async fn get_signatures_for_address(
+        &self,
+        address: &Pubkey,
+        before: Option<Signature>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+        let signatures = observe(
+            "getSignaturesForAddress",
+            self.rpc_client.get_signatures_for_address_with_config(
+                address,
+                GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until: None,
+                    limit: Some(limit),
+                    commitment: None,
+                },
+            ),
+        )
+        .await
+        .with_context(|| format!("Failed to get signatures for address {}", address))?;
+
+        Ok(signatures)
+    }
+
+    #[allow(dead_code)]
     pub async fn get_token_balance(&self, token_account: &str) -> anyhow::Result<UiTokenAmount> {
-        let token_account_balance = self
-            .rpc_client
-            .get_token_account_balance(&Pubkey::from_str(token_account).unwrap())
-            .await
-            .with_context(|| "Failed to get token account balance")?;
+        let token_account = Pubkey::from_str(token_account)
+            .with_context(|| format!("Invalid token account {}", token_account))?;
+
+        let token_account_balance = observe(
+            "getTokenAccountBalance",
+            self.rpc_client.get_token_account_balance(&token_account),
+        )
+        .await
+        .with_context(|| "Failed to get token account balance")?;
 
         Ok(token_account_balance)
     }
 
+    #[allow(dead_code)]
     pub async fn get_account<T>(&self, account: &Pubkey) -> anyhow::Result<T>
     where
         T: Clone,
     {
-        let account = self
-            .rpc_client
-            .get_account(account)
+        let account = observe("getAccountInfo", self.rpc_client.get_account(account))
             .await
             .with_context(|| format!("Error getting account {:?}", account))?;
 
@@ -93,12 +263,238 @@ impl SolanaApi {
     }
 
     pub async fn get_account_data(&self, account: &Pubkey) -> anyhow::Result<Vec<u8>> {
-        let data = self
-            .rpc_client
-            .get_account_data(account)
+        let data = observe("getAccountInfo", self.rpc_client.get_account_data(account))
             .await
             .with_context(|| format!("Error getting account data for account {:?}", account))?;
 
         Ok(data)
     }
+
+    /// The whole account, to tell which program owns it.
+    pub async fn get_account_info(&self, account: &Pubkey) -> anyhow::Result<Account> {
+        let account = observe("getAccountInfo", self.rpc_client.get_account(account))
+            .await
+            .with_context(|| format!("Error getting account {:?}", account))?;
+
+        Ok(account)
+    }
+
+    /// Each account, `None` for those that do not exist, fetched in a single request.
+    pub async fn get_multiple_accounts(
+        &self,
+        accounts: &[Pubkey],
+    ) -> anyhow::Result<Vec<Option<Account>>> {
+        let accounts = observe(
+            "getMultipleAccounts",
+            self.rpc_client.get_multiple_accounts(accounts),
+        )
+        .await
+        .with_context(|| format!("Error getting accounts {:?}", accounts))?;
+
+        Ok(accounts)
+    }
+
+    /// Data of each account, `None` for those that do not exist, fetched in a single request.
+    pub async fn get_multiple_accounts_data(
+        &self,
+        accounts: &[Pubkey],
+    ) -> anyhow::Result<Vec<Option<Vec<u8>>>> {
+        Ok(self
+            .get_multiple_accounts(accounts)
+            .await?
+            .into_iter()
+            .map(|account| account.map(|account| account.data))
+            .collect())
+    }
+
+    /// Accounts of `program_id` matching all of `filters`.
+    pub async fn get_program_accounts(
+        &self,
+        program_id: &Pubkey,
+        filters: Vec<RpcFilterType>,
+    ) -> anyhow::Result<Vec<(Pubkey, Account)>> {
+        let accounts = observe(
+            "getProgramAccounts",
+            self.rpc_client.get_program_accounts_with_config(
+                program_id,
+                RpcProgramAccountsConfig {
+                    filters: Some(filters),
+                    account_config: RpcAccountInfoConfig {
+                        encoding: Some(UiAccountEncoding::Base64),
+                        ..RpcAccountInfoConfig::default()
+                    },
+                    ..RpcProgramAccountsConfig::default()
+                },
+            ),
+        )
+        .await
+        .with_context(|| format!("Error getting accounts of program {}", program_id))?;
+
+        Ok(accounts)
+    }
+
+    /// The latest blockhash and the last block height at which it is still valid.
+    pub async fn get_latest_blockhash(&self) -> anyhow::Result<(Hash, u64)> {
+        let blockhash = observe(
+            "getLatestBlockhash",
+            self.rpc_client
+                .get_latest_blockhash_with_commitment(self.rpc_client.commitment()),
+        )
+        .await
+        .with_context(|| "Failed to get latest blockhash")?;
+
+        Ok(blockhash)
+    }
+
+    pub async fn get_minimum_balance_for_rent_exemption(&self, size: usize) -> anyhow::Result<u64> {
+        let lamports = observe(
+            "getMinimumBalanceForRentExemption",
+            self.rpc_client.get_minimum_balance_for_rent_exemption(size),
+        )
+        .await
+        .with_context(|| "Failed to get minimum balance for rent exemption")?;
+
+        Ok(lamports)
+    }
+
+    pub async fn get_block_height(&self) -> anyhow::Result<u64> {
+        let block_height = observe("getBlockHeight", self.rpc_client.get_block_height())
+            .await
+            .with_context(|| "Failed to get block height")?;
+
+        Ok(block_height)
+    }
+
+    pub async fn get_slot(&self) -> anyhow::Result<u64> {
+        let slot = observe("getSlot", self.rpc_client.get_slot())
+            .await
+            .with_context(|| "Failed to get slot")?;
+
+        Ok(slot)
+    }
+
+    pub async fn get_signature_statuses(
+        &self,
+        signatures: &[Signature],
+    ) -> anyhow::Result<Vec<Option<TransactionStatus>>> {
+        let statuses = observe(
+            "getSignatureStatuses",
+            self.rpc_client.get_signature_statuses(signatures),
+        )
+        .await
+        .with_context(|| "Failed to get signature statuses")?;
+
+        Ok(statuses.value)
+    }
+
+    pub async fn simulate_transaction(
+        &self,
+        transaction: &impl SerializableTransaction,
+    ) -> anyhow::Result<RpcSimulateTransactionResult> {
+        let result = observe(
+            "simulateTransaction",
+            self.rpc_client.simulate_transaction(transaction),
+        )
+        .await
+        .with_context(|| "Failed to simulate transaction")?;
+
+        Ok(result.value)
+    }
+
+    pub async fn send_transaction(
+        &self,
+        transaction: &impl SerializableTransaction,
+        skip_preflight: bool,
+    ) -> anyhow::Result<Signature> {
+        let signature = observe(
+            "sendTransaction",
+            self.rpc_client.send_transaction_with_config(
+                transaction,
+                RpcSendTransactionConfig {
+                    skip_preflight,
+                    ..RpcSendTransactionConfig::default()
+                },
+            ),
+        )
+        .await
+        .with_context(|| "Failed to send transaction")?;
+
+        Ok(signature)
+    }
+
+    /// Sends `transaction` with preflight, then waits for it to be confirmed.
+    pub async fn send_and_confirm_transaction(
+        &self,
+        transaction: &impl SerializableTransaction,
+    ) -> anyhow::Result<Signature> {
+        let signature = observe(
+            "sendTransaction",
+            self.rpc_client.send_and_confirm_transaction(transaction),
+        )
+        .await
+        .with_context(|| "Failed to send and confirm transaction")?;
+
+        Ok(signature)
+    }
+}
+
+/// Records the latency and failures of one RPC request under its method name.
+async fn observe<T>(
+    method: &'static str,
+    request: impl Future<Output = ClientResult<T>>,
+) -> ClientResult<T> {
+    let started = Instant::now();
+    let result = request.await;
+
+    histogram!(telemetry::RPC_REQUEST_DURATION, "method" => method)
+        .record(started.elapsed().as_secs_f64());
+    if result.is_err() {
+        counter!(telemetry::RPC_ERRORS, "method" => method).increment(1);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cached_blockhashes_go_stale_as_blocks_go_by() {
+        let api = SolanaApi::new("http://127.0.0.1:1", None, None);
+        assert!(api.recent_blockhash().is_none());
+
+        let fetched = RecentBlockhash {
+            blockhash: Hash::new_unique(),
+            last_valid_block_height: 1_150,
+            block_height: 1_000,
+            fetched_at: Instant::now(),
+            stale: false,
+        };
+        *api.blockhash.write().unwrap() = BlockhashCache {
+            latest: Some(fetched),
+            min_remaining_blocks: 30,
+        };
+        assert!(!api.recent_blockhash().unwrap().stale);
+
+        // 130 slots later, 20 blocks are left.
+        api.blockhash.write().unwrap().latest = Some(RecentBlockhash {
+            fetched_at: Instant::now() - SLOT_DURATION * 130,
+            ..fetched
+        });
+        let cached = api.recent_blockhash().unwrap();
+        assert_eq!(cached.remaining_blocks(), 20);
+        assert!(cached.stale);
+    }
+
+    #[tokio::test]
+    async fn rejects_malformed_addresses_before_calling_the_node() {
+        let api = SolanaApi::new("http://127.0.0.1:1", None, None);
+
+        let error = api.get_transaction("not-a-signature").await.unwrap_err();
+        assert_eq!(error.to_string(), "Invalid signature not-a-signature");
+
+        let error = api.get_token_balance("not-a-pubkey").await.unwrap_err();
+        assert_eq!(error.to_string(), "Invalid token account not-a-pubkey");
+    }
 }
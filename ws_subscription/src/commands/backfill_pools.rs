@@ -0,0 +1,367 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use clap::{Arg, ArgAction, ArgMatches};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature};
+use tracing::{info, warn};
+
+use crate::{
+    api::solana_rpc::SolanaApi,
+    config::Config,
+    dex::Dex,
+    raydium::{dex::RaydiumV4, event_processors::backfill::SIGNATURES_PAGE_LIMIT, models::Pool},
+    shutdown::Shutdown,
+    storage::sqlite::Store,
+};
+
+use super::Command;
+
+/// Raydium AMM V4 create-pool fee account. Every `initialize2` pays into it and little else
+/// touches it, so paging its signatures is far cheaper than paging the whole program.
+const CREATE_POOL_FEE_ACCOUNT: &str = "7YttLkHDoNj9wyDur5pM1ejNaAvT9X4eqaYcHQqtj2G5";
+
+const CSV_HEADER: &str = "amm_pool,amm_coin_mint,amm_pc_mint,amm_lp_mint,amm_coin_vault,\
+amm_pc_vault,amm_open_order,amm_target,amm_authority,market_program,market,\
+initial_coin_balance,initial_pc_balance,slot,creation_signature";
+
+pub struct BackfillPools;
+
+/// Where a previous run stopped. Signatures are paged newest first, so resuming means paging
+/// again from right before the last signature fully processed.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Checkpoint {
+    before: Option<String>,
+    scanned: u64,
+    pools: u64,
+}
+
+enum PoolSink {
+    Csv(BufWriter<File>),
+    Jsonl(BufWriter<File>),
+    Store(Store),
+}
+
+#[async_trait]
+impl Command for BackfillPools {
+    async fn execute(
+        &self,
+        config: &Config,
+        args: &ArgMatches,
+        shutdown: &Shutdown,
+    ) -> anyhow::Result<()> {
+        let address = Pubkey::from_str(
+            args.get_one::<String>("address")
+                .with_context(|| "Address is required")?,
+        )
+        .with_context(|| "Failed to parse address")?;
+        let start_slot = slot_arg(args, "start-slot")?;
+        let end_slot = slot_arg(args, "end-slot")?;
+        if let (Some(start_slot), Some(end_slot)) = (start_slot, end_slot)
+            && start_slot > end_slot
+        {
+            bail!(
+                "--start-slot {} is after --end-slot {}",
+                start_slot,
+                end_slot
+            );
+        }
+
+        let checkpoint_path = args.get_one::<String>("checkpoint");
+        let mut checkpoint = match checkpoint_path {
+            Some(path) => load_checkpoint(path)?,
+            None => Checkpoint::default(),
+        };
+        let mut sink = PoolSink::open(
+            args.get_one::<String>("format")
+                .with_context(|| "Format is required")?,
+            args.get_one::<String>("output").map(String::as_str),
+            config,
+        )?;
+
+        let solana_api = SolanaApi::new(
+            config.rpc_url()?,
+            None,
+            Some(CommitmentConfig {
+                commitment: config.endpoints.commitment,
+            }),
+        );
+        let raydium_v4 = RaydiumV4 {
+            program_id: config.programs.raydium_liquidity_pool_v4,
+        };
+
+        info!(
+            %address,
+            before = checkpoint.before.as_deref().unwrap_or("the tip"),
+            ?start_slot,
+            ?end_slot,
+            "Paging signatures"
+        );
+
+        let mut before = checkpoint
+            .before
+            .as_deref()
+            .map(Signature::from_str)
+            .transpose()
+            .with_context(|| "Invalid signature in checkpoint")?;
+
+        while !shutdown.is_requested() {
+            let page = solana_api
+                .get_signatures_for_address(&address, before, SIGNATURES_PAGE_LIMIT)
+                .await?;
+            let Some(last) = page.last() else {
+                break;
+            };
+            let last_signature = Signature::from_str(&last.signature)?;
+            let is_last_page = page.len() < SIGNATURES_PAGE_LIMIT;
+
+            // The checkpoint advances one signature at a time, so stopping mid-page (on a
+            // signal or an error) neither skips nor duplicates pools when resuming.
+            let mut reached_start = false;
+            let mut result = Ok(());
+            for status in &page {
+                if shutdown.is_requested() {
+                    break;
+                }
+                if start_slot.is_some_and(|start_slot| status.slot < start_slot) {
+                    reached_start = true;
+                    break;
+                }
+
+                if status.err.is_none() && end_slot.is_none_or(|end_slot| status.slot <= end_slot) {
+                    let scanned = async {
+                        let transaction = solana_api.get_transaction(&status.signature).await?;
+                        if let Some(pool) = raydium_v4.decode_creation(&transaction)? {
+                            sink.write(&pool)?;
+                            checkpoint.pools += 1;
+                        }
+                        anyhow::Ok(())
+                    }
+                    .await;
+                    if let Err(e) = scanned {
+                        result = Err(e);
+                        break;
+                    }
+                }
+
+                checkpoint.scanned += 1;
+                checkpoint.before = Some(status.signature.clone());
+            }
+
+            sink.flush()?;
+            if let Some(path) = checkpoint_path {
+                save_checkpoint(path, &checkpoint)?;
+            }
+            result.with_context(|| {
+                format!(
+                    "Backfill stopped at {}",
+                    checkpoint.before.as_deref().unwrap_or("the tip")
+                )
+            })?;
+            before = Some(last_signature);
+
+            info!(
+                scanned = checkpoint.scanned,
+                pools = checkpoint.pools,
+                slot = last.slot,
+                "Page done"
+            );
+
+            if reached_start || is_last_page {
+                break;
+            }
+        }
+
+        if shutdown.is_requested() {
+            warn!(
+                pools = checkpoint.pools,
+                resume_from = checkpoint.before.as_deref().unwrap_or("the tip"),
+                "Backfill interrupted"
+            );
+        } else {
+            info!(pools = checkpoint.pools, "Backfill done");
+        }
+
+        Ok(())
+    }
+
+    fn create(&self) -> clap::Command {
+        clap::Command::new("backfill-pools")
+            .about("Replay the Raydium pool creations in a slot range")
+            .long_flag("backfill-pools")
+            .arg(
+                Arg::new("rpc-url")
+                    .long("rpc-url")
+                    .action(ArgAction::Set)
+                    .help("The URL of the Solana RPC endpoint"),
+            )
+            .arg(
+                Arg::new("address")
+                    .long("address")
+                    .default_value(CREATE_POOL_FEE_ACCOUNT)
+                    .action(ArgAction::Set)
+                    .help("The address whose signatures are paged (the AMM program works too, slowly)"),
+            )
+            .arg(
+                Arg::new("start-slot")
+                    .long("start-slot")
+                    .action(ArgAction::Set)
+                    .help("The oldest slot to replay"),
+            )
+            .arg(
+                Arg::new("end-slot")
+                    .long("end-slot")
+                    .action(ArgAction::Set)
+                    .help("The newest slot to replay"),
+            )
+            .arg(
+                Arg::new("format")
+                    .long("format")
+                    .short('f')
+                    .value_parser(["csv", "jsonl", "store"])
+                    .default_value("jsonl")
+                    .action(ArgAction::Set)
+                    .help("Where to write the pools: a CSV or JSONL file, or the local store"),
+            )
+            .arg(
+                Arg::new("output")
+                    .long("output")
+                    .short('o')
+                    .action(ArgAction::Set)
+                    .help("The CSV or JSONL file to append the pools to"),
+            )
+            .arg(
+                Arg::new("checkpoint")
+                    .long("checkpoint")
+                    .action(ArgAction::Set)
+                    .help("The file to resume from and record progress to"),
+            )
+            .arg(
+                Arg::new("db-path")
+                    .long("db-path")
+                    .action(ArgAction::Set)
+                    .help("The path to the SQLite store"),
+            )
+    }
+
+    fn name(&self) -> String {
+        "backfill-pools".to_string()
+    }
+}
+
+impl PoolSink {
+    fn open(format: &str, output: Option<&str>, config: &Config) -> anyhow::Result<Self> {
+        if format == "store" {
+            return Ok(PoolSink::Store(Store::open(&config.storage.path)?));
+        }
+
+        let path = output.with_context(|| format!("--output is required for {}", format))?;
+        let is_new = fs::metadata(path).map_or(true, |metadata| metadata.len() == 0);
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open output {}", path))?;
+        let mut writer = BufWriter::new(file);
+
+        match format {
+            "csv" => {
+                if is_new {
+                    writeln!(writer, "{}", CSV_HEADER)?;
+                }
+                Ok(PoolSink::Csv(writer))
+            }
+            "jsonl" => Ok(PoolSink::Jsonl(writer)),
+            _ => bail!("Unsupported format {}", format),
+        }
+    }
+
+    fn write(&mut self, pool: &Pool) -> anyhow::Result<()> {
+        let amm = &pool.amm;
+
+        match self {
+            PoolSink::Csv(writer) => writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                amm.amm_pool,
+                amm.amm_coin_mint,
+                amm.amm_pc_mint,
+                amm.amm_lp_mint,
+                amm.amm_coin_vault,
+                amm.amm_pc_vault,
+                amm.amm_open_order,
+                amm.amm_target,
+                amm.amm_authority,
+                amm.market_program,
+                amm.market,
+                pool.initial_coin_balance,
+                pool.initial_pc_balance,
+                pool.slot,
+                pool.creation_signature
+            )?,
+            PoolSink::Jsonl(writer) => {
+                let line = serde_json::json!({
+                    "amm_pool": amm.amm_pool.to_string(),
+                    "amm_coin_mint": amm.amm_coin_mint.to_string(),
+                    "amm_pc_mint": amm.amm_pc_mint.to_string(),
+                    "amm_lp_mint": amm.amm_lp_mint.to_string(),
+                    "amm_coin_vault": amm.amm_coin_vault.to_string(),
+                    "amm_pc_vault": amm.amm_pc_vault.to_string(),
+                    "amm_open_order": amm.amm_open_order.to_string(),
+                    "amm_target": amm.amm_target.to_string(),
+                    "amm_authority": amm.amm_authority.to_string(),
+                    "market_program": amm.market_program.to_string(),
+                    "market": amm.market.to_string(),
+                    "initial_coin_balance": pool.initial_coin_balance,
+                    "initial_pc_balance": pool.initial_pc_balance,
+                    "slot": pool.slot,
+                    "creation_signature": pool.creation_signature.to_string(),
+                });
+                writeln!(writer, "{}", line)?
+            }
+            PoolSink::Store(store) => store.save_pool(pool)?,
+        }
+
+        Ok(())
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        match self {
+            PoolSink::Csv(writer) | PoolSink::Jsonl(writer) => writer
+                .flush()
+                .with_context(|| "Failed to flush backfill output"),
+            PoolSink::Store(_) => Ok(()),
+        }
+    }
+}
+
+fn slot_arg(args: &ArgMatches, id: &str) -> anyhow::Result<Option<u64>> {
+    args.get_one::<String>(id)
+        .map(|slot| slot.parse::<u64>())
+        .transpose()
+        .with_context(|| format!("Failed to parse --{}", id))
+}
+
+fn load_checkpoint(path: &str) -> anyhow::Result<Checkpoint> {
+    if !Path::new(path).exists() {
+        return Ok(Checkpoint::default());
+    }
+
+    let raw =
+        fs::read_to_string(path).with_context(|| format!("Failed to read checkpoint {}", path))?;
+    serde_json::from_str(&raw).with_context(|| format!("Failed to parse checkpoint {}", path))
+}
+
+/// Writes to a temporary file first so an interrupted run never leaves a truncated checkpoint.
+fn save_checkpoint(path: &str, checkpoint: &Checkpoint) -> anyhow::Result<()> {
+    let temporary = format!("{}.tmp", path);
+    fs::write(&temporary, serde_json::to_string_pretty(checkpoint)?)
+        .with_context(|| format!("Failed to write checkpoint {}", temporary))?;
+    fs::rename(&temporary, path).with_context(|| format!("Failed to write checkpoint {}", path))
+}
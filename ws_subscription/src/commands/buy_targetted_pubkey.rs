@@ -1,9 +1,14 @@
+use std::sync::Arc;
+
 use anyhow::Context;
 use async_trait::async_trait;
 use clap::{Arg, ArgAction, ArgMatches};
-use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::EncodableKey};
+use tracing::info;
 
-use crate::raydium::event_processors::EventProcessor;
+use crate::{
+    config::Config, raydium::event_processors::EventProcessor, shutdown::Shutdown, telemetry,
+    wallets,
+};
 
 use super::Command;
 
@@ -11,34 +16,31 @@ pub struct BuyOnCreationTargettedPubkey;
 
 #[async_trait]
 impl Command for BuyOnCreationTargettedPubkey {
-    async fn execute(&self, args: &ArgMatches) -> anyhow::Result<()> {
-        let rpc_url = args
-            .get_one::<String>("rpc-url")
-            .with_context(|| "RPC URL is required")?;
-        let ws_url = args
-            .get_one::<String>("ws-url")
-            .with_context(|| "WS URL is required")?;
-        let target_pubkey = args
-            .get_one::<String>("target-pubkey")
-            .with_context(|| "Target pubkey is required")?
-            .parse::<Pubkey>()
-            .with_context(|| "Failed to parse target pubkey")?;
-        let amount = args
-            .get_one::<String>("amount")
-            .with_context(|| "Amount is required")?
-            .parse::<u64>()
-            .with_context(|| "Failed to parse amount")?;
-        let owner_file_path = args
-            .get_one::<String>("owner-file-path")
-            .with_context(|| "Owner file path is required")?;
+    async fn execute(
+        &self,
+        config: &Config,
+        _args: &ArgMatches,
+        shutdown: &Shutdown,
+    ) -> anyhow::Result<()> {
+        let target_pubkey = config
+            .filters
+            .target
+            .with_context(|| "Target pubkey is required (--target-pubkey or filters.target)")?;
+        let wallets = wallets::load(config)?;
 
-        let owner = Keypair::read_from_file(owner_file_path)
-            .map_err(|e| anyhow::Error::msg(e.to_string()))
-            .with_context(|| "Error parsing private key")?;
+        if let Some(listen) = config.metrics.listen {
+            telemetry::install(listen)?;
+            info!(%listen, "Serving metrics");
+        }
 
-        let raydium_processor = EventProcessor::new(rpc_url, ws_url).await?;
+        let raydium_processor = Arc::new(EventProcessor::new(config).await?);
         raydium_processor
-            .execute_on_creation(owner, target_pubkey, amount, true)
+            .execute_on_creation(
+                wallets,
+                target_pubkey,
+                config.strategy.simulate_only,
+                shutdown,
+            )
             .await?;
 
         Ok(())
@@ -52,21 +54,18 @@ impl Command for BuyOnCreationTargettedPubkey {
                 Arg::new("target-pubkey")
                     .long("target-pubkey")
                     .short('t')
-                    .required(true)
                     .action(ArgAction::Set)
                     .help("The pubkey of the target token or pool"),
             )
             .arg(
                 Arg::new("ws-url")
                     .long("ws-url")
-                    .required(true)
                     .action(ArgAction::Set)
                     .help("The URL of the Solana WebSocket endpoint"),
             )
             .arg(
                 Arg::new("rpc-url")
                     .long("rpc-url")
-                    .required(true)
                     .action(ArgAction::Set)
                     .help("The URL of the Solana RPC endpoint"),
             )
@@ -74,15 +73,51 @@ impl Command for BuyOnCreationTargettedPubkey {
                 Arg::new("amount")
                     .long("amount")
                     .short('a')
-                    .required(true)
                     .action(ArgAction::Set)
-                    .help("The amount of the target token to buy"),
+                    .help("The amount of the quote token each wallet spends on the buy"),
+            )
+            .arg(
+                Arg::new("slippage-bps")
+                    .long("slippage-bps")
+                    .action(ArgAction::Set)
+                    .help("The maximum slippage allowed, in basis points"),
+            )
+            .arg(
+                Arg::new("compute-unit-price")
+                    .long("compute-unit-price")
+                    .action(ArgAction::Set)
+                    .help("The priority fee, in micro-lamports per compute unit"),
+            )
+            .arg(
+                Arg::new("block-engine-url")
+                    .long("block-engine-url")
+                    .action(ArgAction::Set)
+                    .help("The Jito block engine to send the transaction as a bundle through"),
+            )
+            .arg(
+                Arg::new("lookup-table")
+                    .long("lookup-table")
+                    .action(ArgAction::Set)
+                    .help("An address lookup table to build the transaction with"),
+            )
+            .arg(
+                Arg::new("use-nonce")
+                    .long("use-nonce")
+                    .action(ArgAction::SetTrue)
+                    .help("Sign on the owner's durable nonce instead of a recent blockhash"),
             )
             .arg(
                 Arg::new("simulate-only")
                     .long("simulate-only")
-                    .action(ArgAction::SetFalse)
-                    .help("Simulate the buy without actually executing it"),
+                    .action(ArgAction::SetTrue)
+                    .help("Simulate the buy without actually executing it, the default"),
+            )
+            .arg(
+                Arg::new("live")
+                    .long("live")
+                    .conflicts_with("simulate-only")
+                    .action(ArgAction::SetTrue)
+                    .help("Send the buy for real, even if strategy.simulate_only is set"),
             )
             .arg(
                 Arg::new("owner-file-path")
@@ -90,6 +125,24 @@ impl Command for BuyOnCreationTargettedPubkey {
                     .action(ArgAction::Set)
                     .help("The file path to the owner keypair"),
             )
+            .arg(
+                Arg::new("wallets-dir")
+                    .long("wallets-dir")
+                    .action(ArgAction::Set)
+                    .help("A directory of keypair files, each a wallet buying alongside the owner"),
+            )
+            .arg(
+                Arg::new("metrics-listen")
+                    .long("metrics-listen")
+                    .action(ArgAction::Set)
+                    .help("The address to serve Prometheus metrics on, e.g. 127.0.0.1:9100"),
+            )
+            .arg(
+                Arg::new("db-path")
+                    .long("db-path")
+                    .action(ArgAction::Set)
+                    .help("The path to the SQLite store"),
+            )
     }
 
     fn name(&self) -> String {
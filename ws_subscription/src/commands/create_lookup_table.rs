@@ -0,0 +1,162 @@
+use std::str::FromStr;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use clap::{Arg, ArgAction, ArgMatches};
+use solana_address_lookup_table_interface::{
+    instruction::{create_lookup_table, extend_lookup_table},
+    state::AddressLookupTable,
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::{EncodableKey, Signer},
+    transaction::Transaction as SignedTransaction,
+};
+
+use crate::{
+    api::solana_rpc::SolanaApi,
+    config::Config,
+    raydium::event_processors::lookup_tables::{decode_lookup_table, static_accounts},
+    shutdown::Shutdown,
+};
+
+use super::Command;
+
+/// Addresses added per transaction, keeping it well under the size limit.
+const ADDRESSES_PER_EXTEND: usize = 20;
+
+pub struct CreateLookupTable;
+
+#[async_trait]
+impl Command for CreateLookupTable {
+    async fn execute(
+        &self,
+        config: &Config,
+        args: &ArgMatches,
+        _shutdown: &Shutdown,
+    ) -> anyhow::Result<()> {
+        let existing = args
+            .get_one::<String>("lookup-table")
+            .map(|value| {
+                Pubkey::from_str(value)
+                    .with_context(|| format!("{} is not a valid lookup table", value))
+            })
+            .transpose()?;
+        let owner_file_path = config.wallet.owner_file_path.as_ref().with_context(|| {
+            "Owner file path is required (--owner-file-path or wallet.owner_file_path)"
+        })?;
+
+        let owner = Keypair::read_from_file(owner_file_path)
+            .map_err(|e| anyhow::Error::msg(e.to_string()))
+            .with_context(|| "Error parsing private key")?;
+        let solana_api = SolanaApi::new(
+            config.rpc_url()?,
+            None,
+            Some(CommitmentConfig {
+                commitment: config.endpoints.commitment,
+            }),
+        );
+
+        let (lookup_table, listed) = match existing {
+            Some(lookup_table) => {
+                let account = solana_api.get_account_info(&lookup_table).await?;
+                let authority = AddressLookupTable::deserialize(&account.data)
+                    .map_err(|e| anyhow::anyhow!("Invalid address lookup table: {}", e))?
+                    .meta
+                    .authority;
+                anyhow::ensure!(
+                    authority == Some(owner.pubkey()),
+                    "{} can only be extended by its authority {:?}",
+                    lookup_table,
+                    authority.map(|authority| authority.to_string())
+                );
+
+                (
+                    lookup_table,
+                    decode_lookup_table(&lookup_table, &account)?.addresses,
+                )
+            }
+            None => {
+                let recent_slot = solana_api.get_slot().await?;
+                let (instruction, lookup_table) =
+                    create_lookup_table(owner.pubkey(), owner.pubkey(), recent_slot);
+                send(&solana_api, &owner, instruction).await?;
+                println!("Created address lookup table {}", lookup_table);
+
+                (lookup_table, Vec::new())
+            }
+        };
+
+        let mut missing = static_accounts(&config.programs);
+        missing.retain(|address| !listed.contains(address));
+        for addresses in missing.chunks(ADDRESSES_PER_EXTEND) {
+            let instruction = extend_lookup_table(
+                lookup_table,
+                owner.pubkey(),
+                Some(owner.pubkey()),
+                addresses.to_vec(),
+            );
+            send(&solana_api, &owner, instruction).await?;
+        }
+
+        println!(
+            "Address lookup table {}: {} addresses, {} added. Add it to lookup_tables.addresses.",
+            lookup_table,
+            listed.len() + missing.len(),
+            missing.len()
+        );
+
+        Ok(())
+    }
+
+    fn create(&self) -> clap::Command {
+        clap::Command::new("create-lookup-table")
+            .about("Create or extend an address lookup table with the accounts every buy lists")
+            .long_flag("create-lookup-table")
+            .arg(
+                Arg::new("lookup-table")
+                    .long("lookup-table")
+                    .action(ArgAction::Set)
+                    .help("An existing lookup table to extend, instead of creating one"),
+            )
+            .arg(
+                Arg::new("owner-file-path")
+                    .long("owner-file-path")
+                    .action(ArgAction::Set)
+                    .help("The file path to the owner keypair, paying for and owning the table"),
+            )
+            .arg(
+                Arg::new("rpc-url")
+                    .long("rpc-url")
+                    .action(ArgAction::Set)
+                    .help("The URL of the Solana RPC endpoint"),
+            )
+    }
+
+    fn name(&self) -> String {
+        "create-lookup-table".to_string()
+    }
+}
+
+async fn send(
+    solana_api: &SolanaApi,
+    owner: &Keypair,
+    instruction: Instruction,
+) -> anyhow::Result<()> {
+    let (recent_blockhash, _) = solana_api.get_latest_blockhash().await?;
+    let transaction = SignedTransaction::new_signed_with_payer(
+        &[instruction],
+        Some(&owner.pubkey()),
+        &[owner],
+        recent_blockhash,
+    );
+    let signature = solana_api
+        .send_and_confirm_transaction(&transaction)
+        .await?;
+    println!("{}", signature);
+
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use clap::{Arg, ArgAction, ArgMatches};
+use solana_client::nonce_utils;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    signature::Keypair,
+    signer::{EncodableKey, Signer},
+    transaction::Transaction as SignedTransaction,
+};
+
+use crate::{
+    api::solana_rpc::SolanaApi,
+    config::Config,
+    raydium::event_processors::nonces::{nonce_account_address, NONCE_ACCOUNT_SIZE},
+    shutdown::Shutdown,
+};
+
+use super::Command;
+
+pub struct CreateNonceAccount;
+
+#[async_trait]
+impl Command for CreateNonceAccount {
+    async fn execute(
+        &self,
+        config: &Config,
+        args: &ArgMatches,
+        _shutdown: &Shutdown,
+    ) -> anyhow::Result<()> {
+        let lamports = args
+            .get_one::<String>("lamports")
+            .map(|value| {
+                value
+                    .parse::<u64>()
+                    .with_context(|| format!("{} is not a valid amount of lamports", value))
+            })
+            .transpose()?;
+        let owner_file_path = config.wallet.owner_file_path.as_ref().with_context(|| {
+            "Owner file path is required (--owner-file-path or wallet.owner_file_path)"
+        })?;
+
+        let owner = Keypair::read_from_file(owner_file_path)
+            .map_err(|e| anyhow::Error::msg(e.to_string()))
+            .with_context(|| "Error parsing private key")?;
+        let solana_api = SolanaApi::new(
+            config.rpc_url()?,
+            None,
+            Some(CommitmentConfig {
+                commitment: config.endpoints.commitment,
+            }),
+        );
+
+        let seed = &config.nonce.seed;
+        let nonce_account = nonce_account_address(&owner.pubkey(), seed)?;
+        let existing = solana_api
+            .get_multiple_accounts(&[nonce_account])
+            .await?
+            .remove(0);
+
+        let instructions = match (&existing, lamports) {
+            (Some(_), None) => Vec::new(),
+            // Funds the existing account.
+            (Some(_), Some(lamports)) => {
+                vec![solana_system_interface::instruction::transfer(
+                    &owner.pubkey(),
+                    &nonce_account,
+                    lamports,
+                )]
+            }
+            (None, lamports) => {
+                let rent = solana_api
+                    .get_minimum_balance_for_rent_exemption(NONCE_ACCOUNT_SIZE)
+                    .await?;
+                solana_system_interface::instruction::create_nonce_account_with_seed(
+                    &owner.pubkey(),
+                    &nonce_account,
+                    &owner.pubkey(),
+                    seed,
+                    &owner.pubkey(),
+                    rent + lamports.unwrap_or_default(),
+                )
+            }
+        };
+
+        if !instructions.is_empty() {
+            let (recent_blockhash, _) = solana_api.get_latest_blockhash().await?;
+            let transaction = SignedTransaction::new_signed_with_payer(
+                &instructions,
+                Some(&owner.pubkey()),
+                &[&owner],
+                recent_blockhash,
+            );
+            let signature = solana_api
+                .send_and_confirm_transaction(&transaction)
+                .await?;
+            println!("{}", signature);
+        }
+
+        let account = solana_api.get_account_info(&nonce_account).await?;
+        let data = nonce_utils::data_from_account(&account)
+            .with_context(|| format!("{} is not a nonce account", nonce_account))?;
+        println!(
+            "Nonce account {} (seed '{}', authority {}): {} lamports, nonce {}",
+            nonce_account,
+            seed,
+            data.authority,
+            account.lamports,
+            data.blockhash()
+        );
+
+        Ok(())
+    }
+
+    fn create(&self) -> clap::Command {
+        clap::Command::new("create-nonce-account")
+            .about("Create and fund the durable nonce account buys are signed on")
+            .long_flag("create-nonce-account")
+            .arg(
+                Arg::new("nonce-seed")
+                    .long("nonce-seed")
+                    .action(ArgAction::Set)
+                    .help("The seed the nonce account is derived from the owner with"),
+            )
+            .arg(
+                Arg::new("lamports")
+                    .long("lamports")
+                    .action(ArgAction::Set)
+                    .help("Lamports to fund the account with, on top of its rent when creating it"),
+            )
+            .arg(
+                Arg::new("owner-file-path")
+                    .long("owner-file-path")
+                    .action(ArgAction::Set)
+                    .help("The file path to the owner keypair, paying for and advancing the nonce"),
+            )
+            .arg(
+                Arg::new("rpc-url")
+                    .long("rpc-url")
+                    .action(ArgAction::Set)
+                    .help("The URL of the Solana RPC endpoint"),
+            )
+    }
+
+    fn name(&self) -> String {
+        "create-nonce-account".to_string()
+    }
+}
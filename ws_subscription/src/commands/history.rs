@@ -0,0 +1,88 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use clap::{Arg, ArgAction, ArgMatches};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{config::Config, shutdown::Shutdown, storage::sqlite::Store};
+
+use super::Command;
+
+pub struct History;
+
+#[async_trait]
+impl Command for History {
+    async fn execute(
+        &self,
+        config: &Config,
+        args: &ArgMatches,
+        _shutdown: &Shutdown,
+    ) -> anyhow::Result<()> {
+        let limit = args
+            .get_one::<String>("limit")
+            .with_context(|| "Limit is required")?
+            .parse::<usize>()
+            .with_context(|| "Failed to parse limit")?;
+        let pool = args
+            .get_one::<String>("pool")
+            .map(|pool| pool.parse::<Pubkey>())
+            .transpose()
+            .with_context(|| "Failed to parse pool")?;
+
+        let store = Store::open(&config.storage.path)?;
+        let trades = store.trades(limit, pool.as_ref())?;
+
+        println!(
+            "{:>6} {:>11} {:<5} {:<10} {:<44} {:>16} {:>16}  signature / error",
+            "id", "created_at", "side", "status", "pool", "amount_in", "min_amount_out"
+        );
+        for trade in trades {
+            println!(
+                "{:>6} {:>11} {:<5} {:<10} {:<44} {:>16} {:>16}  {}",
+                trade.id.unwrap_or_default(),
+                trade.created_at,
+                trade.side,
+                trade.status,
+                trade.amm_pool.to_string(),
+                trade.amount_in,
+                trade.min_amount_out,
+                trade
+                    .error
+                    .or(trade.signature.map(|signature| signature.to_string()))
+                    .unwrap_or_default()
+            );
+        }
+
+        Ok(())
+    }
+
+    fn create(&self) -> clap::Command {
+        clap::Command::new("history")
+            .about("List the trades recorded in the local store")
+            .long_flag("history")
+            .arg(
+                Arg::new("limit")
+                    .long("limit")
+                    .short('l')
+                    .default_value("50")
+                    .action(ArgAction::Set)
+                    .help("The maximum number of trades to list"),
+            )
+            .arg(
+                Arg::new("pool")
+                    .long("pool")
+                    .short('p')
+                    .action(ArgAction::Set)
+                    .help("Only list trades on this pool"),
+            )
+            .arg(
+                Arg::new("db-path")
+                    .long("db-path")
+                    .action(ArgAction::Set)
+                    .help("The path to the SQLite store"),
+            )
+    }
+
+    fn name(&self) -> String {
+        "history".to_string()
+    }
+}
@@ -1,16 +1,35 @@
+mod backfill_pools;
 mod buy_targetted_pubkey;
+mod create_lookup_table;
+mod create_nonce_account;
+mod history;
+mod positions;
+mod swap;
 mod test_quote;
 
 use std::collections::HashMap;
 
 use async_trait::async_trait;
+use backfill_pools::BackfillPools;
 use buy_targetted_pubkey::BuyOnCreationTargettedPubkey;
 use clap::ArgMatches;
+use create_lookup_table::CreateLookupTable;
+use create_nonce_account::CreateNonceAccount;
+use history::History;
+use positions::Positions;
+use swap::Swap;
 use test_quote::TestQuote;
 
+use crate::{config::Config, shutdown::Shutdown};
+
 #[async_trait]
 pub trait Command {
-    async fn execute(&self, args: &ArgMatches) -> anyhow::Result<()>;
+    async fn execute(
+        &self,
+        config: &Config,
+        args: &ArgMatches,
+        shutdown: &Shutdown,
+    ) -> anyhow::Result<()>;
 
     fn create(&self) -> clap::Command;
 
@@ -23,6 +42,12 @@ pub fn get_commands() -> HashMap<String, Box<dyn Command>> {
     let commands: Vec<Box<dyn Command>> = vec![
         Box::new(BuyOnCreationTargettedPubkey {}),
         Box::new(TestQuote {}),
+        Box::new(History {}),
+        Box::new(Positions {}),
+        Box::new(BackfillPools {}),
+        Box::new(Swap {}),
+        Box::new(CreateNonceAccount {}),
+        Box::new(CreateLookupTable {}),
     ];
 
     for command in commands {
@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context};
+use async_trait::async_trait;
+use clap::{Arg, ArgAction, ArgMatches};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{config::Config, shutdown::Shutdown, storage::sqlite::Store};
+
+use super::Command;
+
+pub struct Positions;
+
+#[async_trait]
+impl Command for Positions {
+    async fn execute(
+        &self,
+        config: &Config,
+        args: &ArgMatches,
+        _shutdown: &Shutdown,
+    ) -> anyhow::Result<()> {
+        let pubkey_arg = |name: &str| {
+            args.get_one::<String>(name)
+                .map(|value| {
+                    value
+                        .parse::<Pubkey>()
+                        .with_context(|| format!("{} is not a valid pubkey", value))
+                })
+                .transpose()
+        };
+        let store = Store::open(&config.storage.path)?;
+
+        if let Some(mint) = pubkey_arg("close")? {
+            let owner = pubkey_arg("owner")?;
+            let proceeds = args
+                .get_one::<String>("proceeds")
+                .with_context(|| "Proceeds are required")?
+                .parse::<u64>()
+                .with_context(|| "Failed to parse proceeds")?;
+            let closing = store
+                .open_positions()?
+                .into_iter()
+                .filter(|position| {
+                    position.mint == mint && owner.is_none_or(|owner| owner == position.owner)
+                })
+                .collect::<Vec<_>>();
+
+            match closing.as_slice() {
+                [] => println!("No open position on {}", mint),
+                [position] => {
+                    store.close_position(&position.owner, &position.mint, proceeds)?;
+                    println!(
+                        "Closed the position of {} on {}, realising {} lamports",
+                        position.owner,
+                        mint,
+                        proceeds as i64 - position.quote_amount as i64
+                    );
+                }
+                _ => bail!(
+                    "{} wallets hold {}: pass --owner to close their positions one at a time",
+                    closing.len(),
+                    mint
+                ),
+            }
+
+            return Ok(());
+        }
+
+        let positions = store.open_positions()?;
+
+        println!(
+            "{:<44} {:<44} {:<44} {:>16} {:>20} {:>11}",
+            "owner", "mint", "pool", "quote_amount", "token_amount", "opened_at"
+        );
+        for position in &positions {
+            println!(
+                "{:<44} {:<44} {:<44} {:>16} {:>20} {:>11}",
+                position.owner.to_string(),
+                position.mint.to_string(),
+                position.amm_pool.to_string(),
+                position.quote_amount,
+                position.token_amount,
+                position.opened_at
+            );
+        }
+        println!("{} open positions", positions.len());
+        println!("Realised PnL: {} lamports", store.realised_pnl()?);
+
+        let mut by_mint = BTreeMap::<_, (usize, u64, u64)>::new();
+        for position in &positions {
+            let total = by_mint.entry(position.mint).or_default();
+            total.0 += 1;
+            total.1 += position.quote_amount;
+            total.2 += position.token_amount;
+        }
+        println!();
+        println!(
+            "{:<44} {:>7} {:>16} {:>20}",
+            "mint", "wallets", "quote_amount", "token_amount"
+        );
+        for (mint, (wallets, quote_amount, token_amount)) in &by_mint {
+            println!(
+                "{:<44} {:>7} {:>16} {:>20}",
+                mint.to_string(),
+                wallets,
+                quote_amount,
+                token_amount
+            );
+        }
+
+        Ok(())
+    }
+
+    fn create(&self) -> clap::Command {
+        clap::Command::new("positions")
+            .about("List the open positions recorded in the local store and their totals per mint, or close them")
+            .long_flag("positions")
+            .arg(
+                Arg::new("close")
+                    .long("close")
+                    .action(ArgAction::Set)
+                    .requires("proceeds")
+                    .help("Mark the open position on this mint closed, e.g. once sold elsewhere"),
+            )
+            .arg(
+                Arg::new("owner")
+                    .long("owner")
+                    .requires("close")
+                    .action(ArgAction::Set)
+                    .help("Only close the position of this wallet"),
+            )
+            .arg(
+                Arg::new("proceeds")
+                    .long("proceeds")
+                    .requires("close")
+                    .action(ArgAction::Set)
+                    .help("What the position was sold for, in lamports of the quote mint"),
+            )
+            .arg(
+                Arg::new("db-path")
+                    .long("db-path")
+                    .action(ArgAction::Set)
+                    .help("The path to the SQLite store"),
+            )
+    }
+
+    fn name(&self) -> String {
+        "positions".to_string()
+    }
+}
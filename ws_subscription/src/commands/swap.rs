@@ -0,0 +1,161 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use clap::{Arg, ArgAction, ArgMatches};
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::EncodableKey};
+
+use crate::{config::Config, raydium::event_processors::EventProcessor, shutdown::Shutdown};
+
+use super::Command;
+
+pub struct Swap;
+
+#[async_trait]
+impl Command for Swap {
+    async fn execute(
+        &self,
+        config: &Config,
+        args: &ArgMatches,
+        _shutdown: &Shutdown,
+    ) -> anyhow::Result<()> {
+        let pubkey_arg = |name: &str| {
+            args.get_one::<String>(name)
+                .map(|value| {
+                    value
+                        .parse::<Pubkey>()
+                        .with_context(|| format!("{} is not a valid pubkey", value))
+                })
+                .transpose()
+        };
+        let pool = pubkey_arg("pool")?;
+        let mint = pubkey_arg("mint")?;
+        let amount = config
+            .strategy
+            .amount
+            .with_context(|| "Amount is required (--amount or strategy.amount)")?;
+        let owner_file_path = config.wallet.owner_file_path.as_ref().with_context(|| {
+            "Owner file path is required (--owner-file-path or wallet.owner_file_path)"
+        })?;
+
+        let owner = Keypair::read_from_file(owner_file_path)
+            .map_err(|e| anyhow::Error::msg(e.to_string()))
+            .with_context(|| "Error parsing private key")?;
+
+        let raydium_processor = EventProcessor::new(config).await?;
+        let simulate_only = config.strategy.simulate_only;
+        match (pool, mint) {
+            (Some(pool), _) => {
+                raydium_processor
+                    .swap_on_pool(&owner, pool, amount, simulate_only)
+                    .await
+            }
+            (None, Some(mint)) => {
+                raydium_processor
+                    .swap_on_best_route(&owner, mint, amount, simulate_only)
+                    .await
+            }
+            (None, None) => Err(anyhow::anyhow!("Either a pool or a mint is required")),
+        }
+    }
+
+    fn create(&self) -> clap::Command {
+        clap::Command::new("swap")
+            .about("Swap the quote token on an existing pool, or across the best pools of a mint")
+            .long_flag("swap")
+            .arg(
+                Arg::new("pool")
+                    .long("pool")
+                    .short('p')
+                    .required_unless_present("mint")
+                    .conflicts_with("mint")
+                    .action(ArgAction::Set)
+                    .help("The pubkey of the pool, whose venue is told from its owner"),
+            )
+            .arg(
+                Arg::new("mint")
+                    .long("mint")
+                    .short('m')
+                    .action(ArgAction::Set)
+                    .help(
+                        "The mint to buy through the best pools of its pair with the quote token",
+                    ),
+            )
+            .arg(
+                Arg::new("amount")
+                    .long("amount")
+                    .short('a')
+                    .action(ArgAction::Set)
+                    .help("The amount of the quote token to swap"),
+            )
+            .arg(
+                Arg::new("slippage-bps")
+                    .long("slippage-bps")
+                    .action(ArgAction::Set)
+                    .help("The maximum slippage allowed, in basis points"),
+            )
+            .arg(
+                Arg::new("compute-unit-price")
+                    .long("compute-unit-price")
+                    .action(ArgAction::Set)
+                    .help("The priority fee, in micro-lamports per compute unit"),
+            )
+            .arg(
+                Arg::new("block-engine-url")
+                    .long("block-engine-url")
+                    .action(ArgAction::Set)
+                    .help("The Jito block engine to send the transaction as a bundle through"),
+            )
+            .arg(
+                Arg::new("lookup-table")
+                    .long("lookup-table")
+                    .action(ArgAction::Set)
+                    .help("An address lookup table to build the transaction with"),
+            )
+            .arg(
+                Arg::new("use-nonce")
+                    .long("use-nonce")
+                    .action(ArgAction::SetTrue)
+                    .help("Sign on the owner's durable nonce instead of a recent blockhash"),
+            )
+            .arg(
+                Arg::new("simulate-only")
+                    .long("simulate-only")
+                    .action(ArgAction::SetTrue)
+                    .help("Simulate the swap without actually executing it, the default"),
+            )
+            .arg(
+                Arg::new("live")
+                    .long("live")
+                    .conflicts_with("simulate-only")
+                    .action(ArgAction::SetTrue)
+                    .help("Send the swap for real, even if strategy.simulate_only is set"),
+            )
+            .arg(
+                Arg::new("owner-file-path")
+                    .long("owner-file-path")
+                    .action(ArgAction::Set)
+                    .help("The file path to the owner keypair"),
+            )
+            .arg(
+                Arg::new("ws-url")
+                    .long("ws-url")
+                    .action(ArgAction::Set)
+                    .help("The URL of the Solana WebSocket endpoint"),
+            )
+            .arg(
+                Arg::new("rpc-url")
+                    .long("rpc-url")
+                    .action(ArgAction::Set)
+                    .help("The URL of the Solana RPC endpoint"),
+            )
+            .arg(
+                Arg::new("db-path")
+                    .long("db-path")
+                    .action(ArgAction::Set)
+                    .help("The path to the SQLite store"),
+            )
+    }
+
+    fn name(&self) -> String {
+        "swap".to_string()
+    }
+}
@@ -1,8 +1,9 @@
 use anyhow::Context;
 use async_trait::async_trait;
 use clap::{Arg, ArgAction, ArgMatches};
+use tracing::info;
 
-use crate::raydium::event_processors::EventProcessor;
+use crate::{config::Config, raydium::event_processors::EventProcessor, shutdown::Shutdown};
 
 use super::Command;
 
@@ -10,18 +11,17 @@ pub struct TestQuote;
 
 #[async_trait]
 impl Command for TestQuote {
-    async fn execute(&self, args: &ArgMatches) -> anyhow::Result<()> {
-        let rpc_url = args
-            .get_one::<String>("rpc-url")
-            .with_context(|| "RPC URL is required")?;
-        let ws_url = args
-            .get_one::<String>("ws-url")
-            .with_context(|| "WS URL is required")?;
+    async fn execute(
+        &self,
+        config: &Config,
+        args: &ArgMatches,
+        _shutdown: &Shutdown,
+    ) -> anyhow::Result<()> {
         let signature = args
             .get_one::<String>("signature")
             .with_context(|| "Signature is not valid")?;
 
-        let raydium_processor = EventProcessor::new(rpc_url, ws_url).await?;
+        let raydium_processor = EventProcessor::new(config).await?;
 
         let pool = raydium_processor
             .get_pool_from_create_transaction(signature)
@@ -29,7 +29,7 @@ impl Command for TestQuote {
 
         let quote = raydium_processor.get_market_keys(&pool).await?;
 
-        println!("RAYDIUM - Quote: {:?}", quote);
+        info!(?quote, "Market keys");
 
         Ok(())
     }
@@ -49,17 +49,21 @@ impl Command for TestQuote {
             .arg(
                 Arg::new("ws-url")
                     .long("ws-url")
-                    .required(true)
                     .action(ArgAction::Set)
                     .help("The URL of the Solana WebSocket endpoint"),
             )
             .arg(
                 Arg::new("rpc-url")
                     .long("rpc-url")
-                    .required(true)
                     .action(ArgAction::Set)
                     .help("The URL of the Solana RPC endpoint"),
             )
+            .arg(
+                Arg::new("db-path")
+                    .long("db-path")
+                    .action(ArgAction::Set)
+                    .help("The path to the SQLite store"),
+            )
     }
 
     fn name(&self) -> String {
@@ -0,0 +1,999 @@
+use std::{fs, net::SocketAddr, path::Path, str::FromStr};
+
+use anyhow::{bail, Context};
+use clap::ArgMatches;
+use serde::{Deserialize, Deserializer};
+use solana_sdk::{commitment_config::CommitmentLevel, pubkey::Pubkey};
+
+use crate::{
+    orca::WHIRLPOOL_PROGRAM_ID,
+    pump_fun::PUMP_FUN_PROGRAM_ID,
+    raydium::event_processors::{
+        OPENBOOK_MARKET_PROGRAM_ID, RAYDIUM_CLMM_PROGRAM_ID, RAYDIUM_CPMM_PROGRAM_ID,
+        RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID, WSOL,
+    },
+};
+
+/// Maximum compute units a single transaction may request.
+const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+const MAX_BPS: u64 = 10_000;
+/// Upper bound of the TPU client on the leaders sent to.
+const MAX_TPU_FANOUT_SLOTS: u64 = 100;
+/// Blocks a blockhash stays valid for.
+const MAX_BLOCKHASH_AGE: u64 = 150;
+/// Longest seed an address can be derived with.
+const MAX_SEED_LEN: usize = 32;
+/// Block engines drop bundles tipping less than this.
+const MIN_JITO_TIP_LAMPORTS: u64 = 1_000;
+
+/// Bot configuration, loaded from a TOML or YAML file and overridden by CLI flags.
+///
+/// Precedence is: CLI flag > `SNIPER_*` environment variable > config file > default.
+/// String values in the file may reference environment variables as `${NAME}`, which is the
+/// recommended way to keep secrets (RPC API keys, wallet paths) out of the file itself.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub endpoints: Endpoints,
+    pub wallet: Wallet,
+    pub strategy: Strategy,
+    pub filters: Filters,
+    pub fees: FeePolicy,
+    pub exit: ExitRules,
+    pub programs: Programs,
+    pub storage: Storage,
+    pub subscriptions: Subscriptions,
+    pub markets: Markets,
+    pub pump_fun: PumpFunPolicy,
+    pub metrics: Metrics,
+    pub senders: Senders,
+    pub jito: Jito,
+    pub nonce: DurableNonces,
+    pub blockhash: BlockhashRefresh,
+    pub lookup_tables: LookupTables,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Endpoints {
+    pub rpc_url: Option<String>,
+    pub ws_url: Option<String>,
+    /// Commitment used for RPC reads and WS subscriptions.
+    pub commitment: CommitmentLevel,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self {
+            rpc_url: None,
+            ws_url: None,
+            commitment: CommitmentLevel::Processed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Wallet {
+    pub owner_file_path: Option<String>,
+    /// Directory of keypair files (`*.json`), each a wallet buying `strategy.amount`.
+    pub wallets_dir: Option<String>,
+    /// Keypair files of wallets buying their own amount. Snipes buy with every wallet
+    /// listed here, in `wallets_dir` and in `owner_file_path`, each in its own transaction.
+    pub wallets: Vec<WalletFile>,
+    /// Lamports a wallet keeps on top of what it spends, for fees and account rent. Wallets
+    /// that cannot afford a buy and this are left out of it.
+    pub min_sol_reserve: u64,
+}
+
+impl Default for Wallet {
+    fn default() -> Self {
+        Self {
+            owner_file_path: None,
+            wallets_dir: None,
+            wallets: Vec::new(),
+            min_sol_reserve: 10_000_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WalletFile {
+    pub path: String,
+    /// Amount of the quote mint this wallet spends per buy, `strategy.amount` when unset.
+    pub amount: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Strategy {
+    /// Amount of the quote mint (lamports for WSOL) to spend per buy.
+    pub amount: Option<u64>,
+    pub slippage_bps: u64,
+    /// Fixed minimum output. When unset it is derived from the quote and `slippage_bps`.
+    pub min_output_amount: Option<u64>,
+    /// Only simulate buys. On unless turned off here or with `--live`, so nothing is sent
+    /// by accident.
+    pub simulate_only: bool,
+    pub skip_preflight: bool,
+}
+
+impl Default for Strategy {
+    fn default() -> Self {
+        Self {
+            amount: None,
+            slippage_bps: 1000,
+            min_output_amount: None,
+            simulate_only: true,
+            skip_preflight: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Filters {
+    /// Token mint or pool to snipe.
+    #[serde(deserialize_with = "deserialize_optional_pubkey")]
+    pub target: Option<Pubkey>,
+    /// Mint we pay with. Only pools paired against it are bought.
+    #[serde(deserialize_with = "deserialize_pubkey")]
+    pub quote_mint: Pubkey,
+    /// Minimum quote-side reserve (in base units) the pool must be created with.
+    pub min_quote_liquidity: u64,
+}
+
+impl Default for Filters {
+    fn default() -> Self {
+        Self {
+            target: None,
+            quote_mint: WSOL,
+            min_quote_liquidity: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeePolicy {
+    /// Priority fee in micro-lamports per compute unit.
+    pub compute_unit_price: u64,
+    pub compute_unit_limit: Option<u32>,
+}
+
+impl Default for FeePolicy {
+    fn default() -> Self {
+        Self {
+            compute_unit_price: 100_000,
+            compute_unit_limit: None,
+        }
+    }
+}
+
+/// When to sell a position. Validated, but not acted on yet: see [`Config::warnings`].
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExitRules {
+    pub take_profit_bps: Option<u64>,
+    pub stop_loss_bps: Option<u64>,
+    pub max_hold_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Programs {
+    #[serde(deserialize_with = "deserialize_pubkey")]
+    pub raydium_liquidity_pool_v4: Pubkey,
+    /// OpenBook (Serum V3 fork) program the Raydium pools' markets are listed on.
+    #[serde(deserialize_with = "deserialize_pubkey")]
+    pub openbook_market: Pubkey,
+    /// Raydium constant product AMM, without order book and with Token-2022 support.
+    #[serde(deserialize_with = "deserialize_pubkey")]
+    pub raydium_cpmm: Pubkey,
+    /// Raydium concentrated liquidity AMM.
+    #[serde(deserialize_with = "deserialize_pubkey")]
+    pub raydium_clmm: Pubkey,
+    /// Pump.fun bonding curves, whose tokens migrate to Raydium once sold out.
+    #[serde(deserialize_with = "deserialize_pubkey")]
+    pub pump_fun: Pubkey,
+    /// Orca Whirlpools, concentrated liquidity pools quoted like Raydium CLMM ones.
+    #[serde(deserialize_with = "deserialize_pubkey")]
+    pub orca_whirlpool: Pubkey,
+}
+
+impl Default for Programs {
+    fn default() -> Self {
+        Self {
+            raydium_liquidity_pool_v4: RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID,
+            openbook_market: OPENBOOK_MARKET_PROGRAM_ID,
+            raydium_cpmm: RAYDIUM_CPMM_PROGRAM_ID,
+            raydium_clmm: RAYDIUM_CLMM_PROGRAM_ID,
+            pump_fun: PUMP_FUN_PROGRAM_ID,
+            orca_whirlpool: WHIRLPOOL_PROGRAM_ID,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Storage {
+    /// SQLite database holding pools, swaps, trades and positions.
+    pub path: String,
+    /// Pools flagged for watching are resubscribed on start-up for this long after being flagged.
+    /// Pools with an open position are always resubscribed.
+    pub watch_retention_secs: u64,
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Self {
+            path: "sniper.db".to_string(),
+            watch_retention_secs: 24 * 60 * 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Subscriptions {
+    /// Maximum number of pools streamed at once. New pools are refused past it.
+    pub max_concurrent: usize,
+    /// A pool with no logs for this long is unsubscribed and no longer watched.
+    pub inactivity_timeout_secs: u64,
+    /// Also stream the vaults and AMM account of each pool to keep its reserves live.
+    pub track_reserves: bool,
+}
+
+impl Default for Subscriptions {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 50,
+            inactivity_timeout_secs: 30 * 60,
+            track_reserves: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Markets {
+    /// Decode the keys of every new OpenBook market quoted in `filters.quote_mint` as soon as it
+    /// is created, which is before its Raydium pool, so buys never wait for the market fetch.
+    pub prefetch: bool,
+    /// Cached market keys older than this are dropped on start-up.
+    pub cache_retention_secs: u64,
+}
+
+impl Default for Markets {
+    fn default() -> Self {
+        Self {
+            prefetch: true,
+            cache_retention_secs: 7 * 24 * 60 * 60,
+        }
+    }
+}
+
+/// Where to buy a target launched on Pump.fun.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BuyOn {
+    /// On the bonding curve, as soon as the token is created. Its migration is then not
+    /// bought again.
+    Launch,
+    /// On the Raydium pool the curve migrates to once sold out.
+    #[default]
+    Migration,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PumpFunPolicy {
+    pub buy_on: BuyOn,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Metrics {
+    /// Address to serve the Prometheus `/metrics` endpoint on. Disabled when unset.
+    pub listen: Option<SocketAddr>,
+}
+
+/// Ways transactions are sent, all at once when several are enabled.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Senders {
+    /// `sendTransaction` through `endpoints.rpc_url`, skipping preflight per
+    /// `strategy.skip_preflight`.
+    pub rpc: bool,
+    /// Straight to the TPUs of the upcoming leaders over QUIC.
+    pub tpu: bool,
+    /// Number of upcoming slots whose leaders the TPU sender sends to.
+    pub tpu_fanout_slots: u64,
+}
+
+impl Default for Senders {
+    fn default() -> Self {
+        Self {
+            rpc: true,
+            tpu: false,
+            tpu_fanout_slots: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Jito {
+    /// JSON-RPC endpoint of a Jito block engine, e.g.
+    /// `https://mainnet.block-engine.jito.wtf/api/v1/bundles`. Buys are sent as bundles through
+    /// it when set, and through `senders` otherwise.
+    pub block_engine_url: Option<String>,
+    /// Lamports tipped to the block engine with every bundle.
+    pub tip_lamports: u64,
+    /// A bundle that has not landed after this long is sent again through `senders`.
+    pub status_timeout_ms: u64,
+}
+
+impl Default for Jito {
+    fn default() -> Self {
+        Self {
+            block_engine_url: None,
+            tip_lamports: 100_000,
+            status_timeout_ms: 5_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DurableNonces {
+    /// Sign buys on the owner's durable nonce instead of a recent blockhash, so they can be
+    /// signed ahead of time and sent without fetching one.
+    pub enabled: bool,
+    /// The nonce account is derived from the owner and this seed. Create it with
+    /// `create-nonce-account`.
+    pub seed: String,
+}
+
+impl Default for DurableNonces {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            seed: "sniper-nonce".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BlockhashRefresh {
+    /// The latest blockhash is fetched in the background this often, so buys sign on it
+    /// without waiting for one.
+    pub refresh_interval_ms: u64,
+    /// Commitment the blockhash is fetched at.
+    pub commitment: CommitmentLevel,
+    /// With fewer blocks than this left before it expires, the cached blockhash is stale and
+    /// a fresh one is fetched for the buy instead.
+    pub min_remaining_blocks: u64,
+}
+
+impl Default for BlockhashRefresh {
+    fn default() -> Self {
+        Self {
+            refresh_interval_ms: 400,
+            commitment: CommitmentLevel::Confirmed,
+            min_remaining_blocks: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LookupTables {
+    /// Address lookup tables every buy may use, e.g. one made with `create-lookup-table`
+    /// listing the programs and mints all swaps go through. Buys are v0 transactions
+    /// referencing their accounts by index when any table is available.
+    #[serde(deserialize_with = "deserialize_pubkeys")]
+    pub addresses: Vec<Pubkey>,
+    /// Also use the lookup table of the pool swapped on, when the Raydium API lists one.
+    /// Pools are only listed a while after they are created, so snipes rely on `addresses`.
+    pub pool_tables: bool,
+    /// Raydium API the lookup tables of pools are looked up on.
+    pub raydium_api_url: String,
+}
+
+impl Default for LookupTables {
+    fn default() -> Self {
+        Self {
+            addresses: Vec::new(),
+            pool_tables: true,
+            raydium_api_url: "https://api-v3.raydium.io".to_string(),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config file at `path`, or the defaults when no path is given.
+    ///
+    /// `var` looks up the environment variables the file references.
+    pub fn load(path: Option<&str>, var: impl Fn(&str) -> Option<String>) -> anyhow::Result<Self> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+
+        let raw = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path))?;
+        let raw =
+            expand_env_vars(&raw, var).with_context(|| format!("Invalid config file {}", path))?;
+
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+
+        match extension {
+            "toml" => toml::from_str(&raw)
+                .with_context(|| format!("Failed to parse config file {}", path)),
+            "yaml" | "yml" => serde_yaml::from_str(&raw)
+                .with_context(|| format!("Failed to parse config file {}", path)),
+            _ => bail!(
+                "Unsupported config file {}: expected a .toml, .yaml or .yml extension",
+                path
+            ),
+        }
+    }
+
+    /// Applies the `SNIPER_*` environment variables, looked up with `var`, on top of the loaded
+    /// file.
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> anyhow::Result<()> {
+        if let Some(rpc_url) = var("SNIPER_RPC_URL") {
+            self.endpoints.rpc_url = Some(rpc_url);
+        }
+        if let Some(ws_url) = var("SNIPER_WS_URL") {
+            self.endpoints.ws_url = Some(ws_url);
+        }
+        if let Some(owner_file_path) = var("SNIPER_OWNER_FILE_PATH") {
+            self.wallet.owner_file_path = Some(owner_file_path);
+        }
+        if let Some(wallets_dir) = var("SNIPER_WALLETS_DIR") {
+            self.wallet.wallets_dir = Some(wallets_dir);
+        }
+        if let Some(path) = var("SNIPER_DB_PATH") {
+            self.storage.path = path;
+        }
+        if let Some(url) = var("SNIPER_BLOCK_ENGINE_URL") {
+            self.jito.block_engine_url = Some(url);
+        }
+        if let Some(listen) = var("SNIPER_METRICS_LISTEN") {
+            self.metrics.listen = Some(listen.parse().map_err(|e| {
+                anyhow::anyhow!("Failed to parse SNIPER_METRICS_LISTEN '{}': {}", listen, e)
+            })?);
+        }
+
+        Ok(())
+    }
+
+    /// Applies the flags of the invoked subcommand on top of the loaded file.
+    ///
+    /// Flags that the subcommand does not define are ignored, so every command can share this.
+    pub fn apply_args(&mut self, args: &ArgMatches) -> anyhow::Result<()> {
+        if let Some(rpc_url) = string_arg(args, "rpc-url") {
+            self.endpoints.rpc_url = Some(rpc_url);
+        }
+        if let Some(ws_url) = string_arg(args, "ws-url") {
+            self.endpoints.ws_url = Some(ws_url);
+        }
+        if let Some(owner_file_path) = string_arg(args, "owner-file-path") {
+            self.wallet.owner_file_path = Some(owner_file_path);
+        }
+        if let Some(wallets_dir) = string_arg(args, "wallets-dir") {
+            self.wallet.wallets_dir = Some(wallets_dir);
+        }
+        if let Some(path) = string_arg(args, "db-path") {
+            self.storage.path = path;
+        }
+        if let Some(seed) = string_arg(args, "nonce-seed") {
+            self.nonce.seed = seed;
+        }
+        if let Ok(Some(true)) = args.try_get_one::<bool>("use-nonce") {
+            self.nonce.enabled = true;
+        }
+        if let Some(address) = parsed_arg(args, "lookup-table")?
+            && !self.lookup_tables.addresses.contains(&address)
+        {
+            self.lookup_tables.addresses.push(address);
+        }
+        if let Some(url) = string_arg(args, "block-engine-url") {
+            self.jito.block_engine_url = Some(url);
+        }
+        if let Some(listen) = parsed_arg(args, "metrics-listen")? {
+            self.metrics.listen = Some(listen);
+        }
+        if let Some(target) = parsed_arg(args, "target-pubkey")? {
+            self.filters.target = Some(target);
+        }
+        if let Some(amount) = parsed_arg(args, "amount")? {
+            self.strategy.amount = Some(amount);
+        }
+        if let Some(slippage_bps) = parsed_arg(args, "slippage-bps")? {
+            self.strategy.slippage_bps = slippage_bps;
+        }
+        if let Some(compute_unit_price) = parsed_arg(args, "compute-unit-price")? {
+            self.fees.compute_unit_price = compute_unit_price;
+        }
+        if let Ok(Some(true)) = args.try_get_one::<bool>("simulate-only") {
+            self.strategy.simulate_only = true;
+        }
+        if let Ok(Some(true)) = args.try_get_one::<bool>("live") {
+            self.strategy.simulate_only = false;
+        }
+
+        Ok(())
+    }
+
+    /// Checks the whole config and reports every problem found at once.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut errors = Vec::new();
+
+        if let Some(url) = &self.endpoints.rpc_url
+            && !url.starts_with("http://")
+            && !url.starts_with("https://")
+        {
+            errors.push(format!(
+                "endpoints.rpc_url must be an http(s) URL, got '{}'",
+                url
+            ));
+        }
+
+        if let Some(url) = &self.endpoints.ws_url
+            && !url.starts_with("ws://")
+            && !url.starts_with("wss://")
+        {
+            errors.push(format!(
+                "endpoints.ws_url must be a ws(s) URL, got '{}'",
+                url
+            ));
+        }
+
+        if self.storage.path.is_empty() {
+            errors.push("storage.path must not be empty".to_string());
+        }
+
+        if let Some(path) = &self.wallet.owner_file_path
+            && !Path::new(path).is_file()
+        {
+            errors.push(format!("wallet.owner_file_path '{}' does not exist", path));
+        }
+        if let Some(path) = &self.wallet.wallets_dir
+            && !Path::new(path).is_dir()
+        {
+            errors.push(format!("wallet.wallets_dir '{}' is not a directory", path));
+        }
+        for wallet in &self.wallet.wallets {
+            if !Path::new(&wallet.path).is_file() {
+                errors.push(format!("wallet.wallets: '{}' does not exist", wallet.path));
+            }
+            if wallet.amount == Some(0) {
+                errors.push(format!(
+                    "wallet.wallets: amount of '{}' must be greater than 0",
+                    wallet.path
+                ));
+            }
+        }
+
+        if self.strategy.amount == Some(0) {
+            errors.push("strategy.amount must be greater than 0".to_string());
+        }
+        if self.strategy.slippage_bps > MAX_BPS {
+            errors.push(format!(
+                "strategy.slippage_bps must be at most {}, got {}",
+                MAX_BPS, self.strategy.slippage_bps
+            ));
+        }
+
+        if let Some(limit) = self.fees.compute_unit_limit
+            && (limit == 0 || limit > MAX_COMPUTE_UNIT_LIMIT)
+        {
+            errors.push(format!(
+                "fees.compute_unit_limit must be between 1 and {}, got {}",
+                MAX_COMPUTE_UNIT_LIMIT, limit
+            ));
+        }
+
+        if self.subscriptions.max_concurrent == 0 {
+            errors.push("subscriptions.max_concurrent must be greater than 0".to_string());
+        }
+        if self.subscriptions.inactivity_timeout_secs == 0 {
+            errors.push("subscriptions.inactivity_timeout_secs must be greater than 0".to_string());
+        }
+
+        if self.markets.cache_retention_secs == 0 {
+            errors.push("markets.cache_retention_secs must be greater than 0".to_string());
+        }
+
+        if self.exit.take_profit_bps == Some(0) {
+            errors.push("exit.take_profit_bps must be greater than 0".to_string());
+        }
+        if let Some(stop_loss_bps) = self.exit.stop_loss_bps
+            && (stop_loss_bps == 0 || stop_loss_bps > MAX_BPS)
+        {
+            errors.push(format!(
+                "exit.stop_loss_bps must be between 1 and {}, got {}",
+                MAX_BPS, stop_loss_bps
+            ));
+        }
+        if self.exit.max_hold_secs == Some(0) {
+            errors.push("exit.max_hold_secs must be greater than 0".to_string());
+        }
+
+        if !self.senders.rpc && !self.senders.tpu {
+            errors.push("senders: at least one of rpc and tpu must be enabled".to_string());
+        }
+        if self.senders.tpu_fanout_slots == 0
+            || self.senders.tpu_fanout_slots > MAX_TPU_FANOUT_SLOTS
+        {
+            errors.push(format!(
+                "senders.tpu_fanout_slots must be between 1 and {}, got {}",
+                MAX_TPU_FANOUT_SLOTS, self.senders.tpu_fanout_slots
+            ));
+        }
+
+        if self.blockhash.refresh_interval_ms == 0 {
+            errors.push("blockhash.refresh_interval_ms must be greater than 0".to_string());
+        }
+        if self.blockhash.min_remaining_blocks >= MAX_BLOCKHASH_AGE {
+            errors.push(format!(
+                "blockhash.min_remaining_blocks must be less than {}, got {}",
+                MAX_BLOCKHASH_AGE, self.blockhash.min_remaining_blocks
+            ));
+        }
+
+        if self.nonce.seed.is_empty() || self.nonce.seed.len() > MAX_SEED_LEN {
+            errors.push(format!(
+                "nonce.seed must be between 1 and {} bytes, got '{}'",
+                MAX_SEED_LEN, self.nonce.seed
+            ));
+        }
+
+        if let Some(url) = &self.jito.block_engine_url
+            && !url.starts_with("http://")
+            && !url.starts_with("https://")
+        {
+            errors.push(format!(
+                "jito.block_engine_url must be an http(s) URL, got '{}'",
+                url
+            ));
+        }
+        if self.jito.tip_lamports < MIN_JITO_TIP_LAMPORTS {
+            errors.push(format!(
+                "jito.tip_lamports must be at least {}, got {}",
+                MIN_JITO_TIP_LAMPORTS, self.jito.tip_lamports
+            ));
+        }
+        if self.jito.status_timeout_ms == 0 {
+            errors.push("jito.status_timeout_ms must be greater than 0".to_string());
+        }
+
+        if !self.lookup_tables.raydium_api_url.starts_with("http://")
+            && !self.lookup_tables.raydium_api_url.starts_with("https://")
+        {
+            errors.push(format!(
+                "lookup_tables.raydium_api_url must be an http(s) URL, got '{}'",
+                self.lookup_tables.raydium_api_url
+            ));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            bail!("Invalid configuration:\n  - {}", errors.join("\n  - "))
+        }
+    }
+
+    /// Settings that are accepted but have no effect yet, worth telling the user about.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+
+        let exit = &self.exit;
+        if exit.take_profit_bps.is_some()
+            || exit.stop_loss_bps.is_some()
+            || exit.max_hold_secs.is_some()
+        {
+            warnings.push(
+                "exit rules are not acted on yet: positions are never sold automatically"
+                    .to_string(),
+            );
+        }
+
+        warnings
+    }
+
+    pub fn rpc_url(&self) -> anyhow::Result<&str> {
+        self.endpoints.rpc_url.as_deref().with_context(|| {
+            "endpoints.rpc_url is required (config file, --rpc-url or SNIPER_RPC_URL)"
+        })
+    }
+
+    pub fn ws_url(&self) -> anyhow::Result<&str> {
+        self.endpoints.ws_url.as_deref().with_context(|| {
+            "endpoints.ws_url is required (config file, --ws-url or SNIPER_WS_URL)"
+        })
+    }
+}
+
+fn string_arg(args: &ArgMatches, id: &str) -> Option<String> {
+    args.try_get_one::<String>(id).ok().flatten().cloned()
+}
+
+fn parsed_arg<T>(args: &ArgMatches, id: &str) -> anyhow::Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    string_arg(args, id)
+        .map(|value| {
+            value
+                .parse::<T>()
+                .map_err(|e| anyhow::anyhow!("Failed to parse --{} '{}': {}", id, value, e))
+        })
+        .transpose()
+}
+
+/// Replaces every `${NAME}` with the value `var` finds for the environment variable `NAME`,
+/// except in comments.
+fn expand_env_vars(raw: &str, var: impl Fn(&str) -> Option<String>) -> anyhow::Result<String> {
+    let mut result = String::with_capacity(raw.len());
+
+    for line in raw.split_inclusive('\n') {
+        let (value, comment) = line.split_at(comment_start(line).unwrap_or(line.len()));
+        let mut rest = value;
+        while let Some(start) = rest.find("${") {
+            result.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            let end = after.find('}').with_context(|| {
+                format!("Unterminated '${{' near '{}'", rest[start..].trim_end())
+            })?;
+            let name = &after[..end];
+            let value =
+                var(name).with_context(|| format!("Environment variable {} is not set", name))?;
+            result.push_str(&value);
+            rest = &after[end + 1..];
+        }
+        result.push_str(rest);
+        result.push_str(comment);
+    }
+
+    Ok(result)
+}
+
+/// Offset of the `#` starting a comment on `line`, outside of quoted strings. TOML and YAML
+/// both comment that way.
+fn comment_start(line: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut escaped = false;
+
+    for (index, character) in line.char_indices() {
+        match (quote, character) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(open), _) if character == open && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(character),
+            (None, '#') => return Some(index),
+            _ => {}
+        }
+        escaped = false;
+    }
+
+    None
+}
+
+fn deserialize_pubkey<'de, D>(deserializer: D) -> Result<Pubkey, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    Pubkey::from_str(&value).map_err(serde::de::Error::custom)
+}
+
+fn deserialize_optional_pubkey<'de, D>(deserializer: D) -> Result<Option<Pubkey>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|value| Pubkey::from_str(&value).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+fn deserialize_pubkeys<'de, D>(deserializer: D) -> Result<Vec<Pubkey>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|value| Pubkey::from_str(value).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, env};
+
+    use clap::{Arg, ArgAction};
+
+    use super::*;
+
+    /// Looks variables up in `vars` instead of the process environment.
+    fn vars(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<HashMap<_, _>>();
+
+        move |name| vars.get(name).cloned()
+    }
+
+    fn write_config(name: &str, contents: &str) -> String {
+        let path = env::temp_dir().join(format!("sniper-{}-{}", Pubkey::new_unique(), name));
+        fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    /// The flags `apply_args` reads that these tests exercise.
+    fn command() -> clap::Command {
+        clap::Command::new("test")
+            .arg(Arg::new("rpc-url").long("rpc-url").action(ArgAction::Set))
+            .arg(
+                Arg::new("simulate-only")
+                    .long("simulate-only")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(
+                Arg::new("live")
+                    .long("live")
+                    .conflicts_with("simulate-only")
+                    .action(ArgAction::SetTrue),
+            )
+    }
+
+    #[test]
+    fn expands_env_vars_outside_comments_only() {
+        let expanded = expand_env_vars(
+            "# ${SNIPER_TEST_UNSET} in a comment\n\
+             url = \"https://rpc/?key=${SNIPER_TEST_EXPANDED}\" # ${SNIPER_TEST_UNSET}\n\
+             name = \"a # b ${SNIPER_TEST_EXPANDED}\"\n",
+            vars(&[("SNIPER_TEST_EXPANDED", "secret")]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            expanded,
+            "# ${SNIPER_TEST_UNSET} in a comment\n\
+             url = \"https://rpc/?key=secret\" # ${SNIPER_TEST_UNSET}\n\
+             name = \"a # b secret\"\n"
+        );
+    }
+
+    #[test]
+    fn fails_on_unset_or_unterminated_env_vars() {
+        let unset = expand_env_vars("url = \"${SNIPER_TEST_UNSET}\"", vars(&[])).unwrap_err();
+        let unterminated = expand_env_vars("url = \"${SNIPER_TEST_UNSET\"", vars(&[])).unwrap_err();
+
+        assert!(unset.to_string().contains("SNIPER_TEST_UNSET is not set"));
+        assert!(unterminated.to_string().contains("Unterminated"));
+    }
+
+    #[test]
+    fn validate_reports_every_problem_at_once() {
+        assert!(Config::default().validate().is_ok());
+
+        let mut config = Config::default();
+        config.endpoints.rpc_url = Some("ftp://rpc".to_string());
+        config.strategy.slippage_bps = MAX_BPS + 1;
+        config.exit.stop_loss_bps = Some(0);
+        config.senders.rpc = false;
+        let error = config.validate().unwrap_err().to_string();
+
+        for field in [
+            "endpoints.rpc_url",
+            "strategy.slippage_bps",
+            "exit.stop_loss_bps",
+            "senders: at least one",
+        ] {
+            assert!(error.contains(field), "{} not reported in {}", field, error);
+        }
+    }
+
+    #[test]
+    fn flags_override_the_environment_which_overrides_the_file() {
+        let path = write_config(
+            "precedence.toml",
+            "[endpoints]\nrpc_url = \"https://file\"\n\n[strategy]\nsimulate_only = false\n",
+        );
+        let load = |env: &[(&str, &str)], args: &[&str]| {
+            let mut config = Config::load(Some(&path), vars(&[])).unwrap();
+            config.apply_env(vars(env)).unwrap();
+            config
+                .apply_args(&command().try_get_matches_from(args).unwrap())
+                .unwrap();
+            config
+        };
+
+        let env = [("SNIPER_RPC_URL", "https://env")];
+        let from_file = load(&[], &["test"]);
+        let from_env = load(&env, &["test"]);
+        let from_flags = load(
+            &env,
+            &["test", "--rpc-url", "https://flag", "--simulate-only"],
+        );
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(from_file.endpoints.rpc_url.as_deref(), Some("https://file"));
+        assert_eq!(from_env.endpoints.rpc_url.as_deref(), Some("https://env"));
+        assert_eq!(
+            from_flags.endpoints.rpc_url.as_deref(),
+            Some("https://flag")
+        );
+        assert!(!from_file.strategy.simulate_only);
+        assert!(from_flags.strategy.simulate_only);
+    }
+
+    #[test]
+    fn applies_every_sniper_env_var() {
+        let mut config = Config::default();
+        config
+            .apply_env(vars(&[
+                ("SNIPER_WS_URL", "wss://env"),
+                ("SNIPER_OWNER_FILE_PATH", "owner.json"),
+                ("SNIPER_WALLETS_DIR", "wallets"),
+                ("SNIPER_DB_PATH", "sniper.db"),
+                ("SNIPER_BLOCK_ENGINE_URL", "https://block-engine"),
+                ("SNIPER_METRICS_LISTEN", "127.0.0.1:9000"),
+            ]))
+            .unwrap();
+
+        assert_eq!(config.endpoints.ws_url.as_deref(), Some("wss://env"));
+        assert_eq!(config.wallet.owner_file_path.as_deref(), Some("owner.json"));
+        assert_eq!(config.wallet.wallets_dir.as_deref(), Some("wallets"));
+        assert_eq!(config.storage.path, "sniper.db");
+        assert_eq!(
+            config.jito.block_engine_url.as_deref(),
+            Some("https://block-engine")
+        );
+        assert_eq!(
+            config.metrics.listen,
+            Some("127.0.0.1:9000".parse().unwrap())
+        );
+
+        let error = Config::default()
+            .apply_env(vars(&[("SNIPER_METRICS_LISTEN", "nowhere")]))
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("SNIPER_METRICS_LISTEN 'nowhere'"));
+    }
+
+    #[test]
+    fn simulates_unless_run_live() {
+        let mut config = Config::default();
+        assert!(config.strategy.simulate_only);
+
+        config
+            .apply_args(&command().get_matches_from(["test", "--live"]))
+            .unwrap();
+
+        assert!(!config.strategy.simulate_only);
+    }
+
+    #[test]
+    fn warns_about_exit_rules_not_acted_on() {
+        let mut config = Config::default();
+        assert!(config.warnings().is_empty());
+
+        config.exit.take_profit_bps = Some(5_000);
+
+        assert_eq!(config.warnings().len(), 1);
+    }
+}
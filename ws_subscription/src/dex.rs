@@ -0,0 +1,141 @@
+//! What the sniper needs from a venue, apart from talking to the cluster.
+//!
+//! A [`Dex`] only decodes: it spots pool creations in transaction logs, reads the new pool from
+//! the creation transaction and from the accounts it asks for, and hands back a [`SwapPool`]
+//! to quote and build swaps with. Fetching is left to the caller, so subscriptions and
+//! execution stay the same whichever venue the pool is on.
+
+use std::fmt;
+
+use anyhow::Context;
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+
+use crate::api::solana_rpc::Transaction;
+
+/// Live reserves of a tracked pool, as of `slot`.
+///
+/// On CPMM pools coin is token 0 and pc token 1, and the pnl not taken yet are the protocol
+/// and fund fees.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PoolReserves {
+    pub coin_vault: u64,
+    pub pc_vault: u64,
+    pub need_take_pnl_coin: u64,
+    pub need_take_pnl_pc: u64,
+    pub slot: u64,
+}
+
+impl PoolReserves {
+    /// The pc and coin reserves that can be swapped against, without the pnl the pool owes
+    /// but has not taken yet.
+    pub fn without_take_pnl(&self) -> anyhow::Result<(u64, u64)> {
+        let total_pc_without_take_pnl = self
+            .pc_vault
+            .checked_sub(self.need_take_pnl_pc)
+            .with_context(|| "Failed to subtract take pnl pc")?;
+
+        let total_coin_without_take_pnl = self
+            .coin_vault
+            .checked_sub(self.need_take_pnl_coin)
+            .with_context(|| "Failed to subtract take pnl coin")?;
+
+        Ok((total_pc_without_take_pnl, total_coin_without_take_pnl))
+    }
+}
+
+/// A pool the sniper can quote and swap on, whichever venue it belongs to.
+pub trait SwapPool: fmt::Debug + Send + Sync {
+    fn id(&self) -> Pubkey;
+
+    /// Program owning `mint`'s token accounts. Only CPMM and CLMM pools may hold Token-2022
+    /// mints.
+    fn token_program(&self, _mint: &Pubkey) -> Pubkey {
+        spl_token::id()
+    }
+
+    /// Whether swaps from SOL debit the owner's lamports rather than a WSOL token account, so
+    /// there is nothing to wrap.
+    fn takes_native_sol(&self) -> bool {
+        false
+    }
+
+    /// What swapping exactly `amount_in` of `input_mint` returns at `reserves`, fees deducted.
+    fn quote(
+        &self,
+        reserves: &PoolReserves,
+        input_mint: &Pubkey,
+        amount_in: u64,
+    ) -> anyhow::Result<u64>;
+
+    /// Instruction swapping exactly `amount_in` of `input_mint` from `input_account` for at
+    /// least `min_amount_out` into `output_account`.
+    fn swap_instruction(
+        &self,
+        owner: &Pubkey,
+        input_mint: &Pubkey,
+        input_account: &Pubkey,
+        output_account: &Pubkey,
+        amount_in: u64,
+        min_amount_out: u64,
+    ) -> anyhow::Result<Instruction>;
+
+    /// Instructions making the swap of [`SwapPool::swap_instruction`], for swaps that take
+    /// more than one, e.g. split across pools.
+    fn swap_instructions(
+        &self,
+        owner: &Pubkey,
+        input_mint: &Pubkey,
+        input_account: &Pubkey,
+        output_account: &Pubkey,
+        amount_in: u64,
+        min_amount_out: u64,
+    ) -> anyhow::Result<Vec<Instruction>> {
+        Ok(vec![self.swap_instruction(
+            owner,
+            input_mint,
+            input_account,
+            output_account,
+            amount_in,
+            min_amount_out,
+        )?])
+    }
+}
+
+/// A venue new pools are detected on, from their creation to a pool ready to swap on.
+pub trait Dex: Send + Sync {
+    /// The new pool as read from its creation transaction.
+    type Creation: fmt::Debug + Send + Sync;
+    /// The pool once decoded, which quotes and builds the swaps.
+    type Pool: SwapPool;
+
+    /// Name of the venue, for logs.
+    fn name(&self) -> &'static str;
+
+    /// Whether the logs of a transaction are those of a pool creation on this venue.
+    fn is_pool_creation(&self, logs: &[String]) -> bool;
+
+    /// The pool created by a confirmed transaction, `None` when it creates none.
+    fn decode_creation(&self, transaction: &Transaction) -> anyhow::Result<Option<Self::Creation>>;
+
+    fn pool_id(&self, creation: &Self::Creation) -> Pubkey;
+
+    /// Both mints of the pool, in the order of its reserves.
+    fn mints(&self, creation: &Self::Creation) -> [Pubkey; 2];
+
+    /// Accounts the pool is decoded from, which change as it trades.
+    fn pool_accounts(&self, creation: &Self::Creation) -> Vec<Pubkey>;
+
+    /// Accounts the pool is decoded from that never change, e.g. its fee config. They are
+    /// fetched once and reused for the next pools.
+    fn immutable_accounts(&self, _creation: &Self::Creation) -> Vec<Pubkey> {
+        Vec::new()
+    }
+
+    /// Decodes the pool and its reserves from the data of `pool_accounts` followed by
+    /// `immutable_accounts`, `None` for those that do not exist.
+    fn decode_pool(
+        &self,
+        creation: &Self::Creation,
+        accounts: &[Option<Vec<u8>>],
+    ) -> anyhow::Result<(Self::Pool, PoolReserves)>;
+}
@@ -0,0 +1,27 @@
+use clap::ArgMatches;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{fmt, EnvFilter};
+
+/// Sets up the global subscriber from `-v`/`-q`, `--log-format` and `RUST_LOG`.
+///
+/// `RUST_LOG`, when set, takes precedence over the verbosity flags. Logs go to stderr so the
+/// output of query commands stays clean on stdout.
+pub fn init(args: &ArgMatches) {
+    let verbosity = i16::from(args.get_count("verbose")) - i16::from(args.get_count("quiet"));
+    let level = match verbosity {
+        i16::MIN..=-2 => LevelFilter::ERROR,
+        -1 => LevelFilter::WARN,
+        0 => LevelFilter::INFO,
+        1 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+    let filter = EnvFilter::builder()
+        .with_default_directive(level.into())
+        .from_env_lossy();
+
+    let subscriber = fmt().with_env_filter(filter).with_writer(std::io::stderr);
+    match args.get_one::<String>("log-format").map(String::as_str) {
+        Some("json") => subscriber.json().init(),
+        _ => subscriber.init(),
+    }
+}
@@ -1,6 +1,24 @@
 mod api;
 mod commands;
+mod config;
+mod dex;
+mod logging;
+mod orca;
+mod pump_fun;
 mod raydium;
+mod router;
+mod senders;
+mod shutdown;
+mod storage;
+mod telemetry;
+mod wallets;
+
+use std::{env, process::ExitCode};
+
+use clap::{Arg, ArgAction};
+use config::Config;
+use shutdown::Shutdown;
+use tracing::warn;
 
 //use raydium::execute_demo;
 
@@ -24,13 +42,53 @@ mod raydium;
 //}
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     let commands = commands::get_commands();
 
     let mut clap_commands = clap::Command::new("solana-raydium-bot")
         .version("0.1.0")
         .subcommand_required(true)
-        .arg_required_else_help(true);
+        .arg_required_else_help(true)
+        .after_help(
+            "Environment: SNIPER_RPC_URL, SNIPER_WS_URL, SNIPER_OWNER_FILE_PATH, \
+             SNIPER_WALLETS_DIR, SNIPER_DB_PATH, SNIPER_BLOCK_ENGINE_URL and \
+             SNIPER_METRICS_LISTEN override the config file, and flags override them.",
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .short('c')
+                .global(true)
+                .env("SNIPER_CONFIG")
+                .action(ArgAction::Set)
+                .help("The path to a TOML or YAML config file"),
+        )
+        .arg(
+            Arg::new("verbose")
+                .long("verbose")
+                .short('v')
+                .global(true)
+                .action(ArgAction::Count)
+                .help("Log more: -v for debug, -vv for trace (full transaction dumps)"),
+        )
+        .arg(
+            Arg::new("quiet")
+                .long("quiet")
+                .short('q')
+                .global(true)
+                .action(ArgAction::Count)
+                .help("Log less: -q for warnings only, -qq for errors only"),
+        )
+        .arg(
+            Arg::new("log-format")
+                .long("log-format")
+                .global(true)
+                .env("SNIPER_LOG_FORMAT")
+                .value_parser(["text", "json"])
+                .default_value("text")
+                .action(ArgAction::Set)
+                .help("The log output format"),
+        );
 
     for command in commands.values() {
         clap_commands = clap_commands.subcommand(command.create());
@@ -41,13 +99,38 @@ async fn main() {
         Some(subcommand) => {
             let (subcommand_name, subcommand_args) = subcommand;
             let command = commands.get(subcommand_name).unwrap();
-            command
-                .execute(subcommand_args)
-                .await
-                .expect("Failed to execute command");
+            logging::init(subcommand_args);
+            let shutdown = Shutdown::listen();
+
+            let result = match load_config(subcommand_args) {
+                Ok(config) => command.execute(&config, subcommand_args, &shutdown).await,
+                Err(e) => Err(e.context("Failed to load config")),
+            };
+
+            match result {
+                Ok(()) => shutdown.exit_code(),
+                Err(e) => {
+                    eprintln!("Error: {:#}", e);
+                    ExitCode::FAILURE
+                }
+            }
         }
         _ => {
             println!("No subcommand provided");
+            ExitCode::FAILURE
         }
     }
 }
+
+fn load_config(args: &clap::ArgMatches) -> anyhow::Result<Config> {
+    let var = |name: &str| env::var(name).ok();
+    let mut config = Config::load(args.get_one::<String>("config").map(String::as_str), var)?;
+    config.apply_env(var)?;
+    config.apply_args(args)?;
+    config.validate()?;
+    for warning in config.warnings() {
+        warn!("{}", warning);
+    }
+
+    Ok(config)
+}
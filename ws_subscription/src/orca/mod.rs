@@ -0,0 +1,253 @@
+//! Orca Whirlpools: concentrated liquidity pools priced like Raydium CLMM pools, used as a price
+//! reference and as another way out when the Raydium pool of a token is thin.
+//!
+//! https://github.com/orca-so/whirlpools
+
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+use crate::{
+    dex::{PoolReserves, SwapPool},
+    raydium::clmm::{
+        math::{swap_base_input, SwapResult, MAX_TICK, MIN_TICK},
+        state::{Tick, TickArray},
+    },
+};
+
+use self::state::{oracle_address, tick_array_address, WhirlpoolState};
+
+pub mod state;
+
+pub const WHIRLPOOL_PROGRAM_ID: Pubkey =
+    solana_sdk::pubkey!("whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc");
+
+/// `sha256("global:swap_v2")`, the swap taking Token-2022 mints.
+const SWAP_V2_DISCRIMINATOR: [u8; 8] = [43, 4, 237, 11, 26, 201, 30, 98];
+pub(crate) const MEMO_PROGRAM_ID: Pubkey =
+    solana_sdk::pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+/// Swaps go through at most this many tick arrays, all passed to the instruction.
+const SWAP_TICK_ARRAYS: i32 = 3;
+
+/// A Whirlpool with the tick arrays around its current price.
+#[derive(Clone, Debug)]
+pub struct WhirlpoolPool {
+    pub program_id: Pubkey,
+    pub address: Pubkey,
+    pub state: WhirlpoolState,
+    pub token_a_program: Pubkey,
+    pub token_b_program: Pubkey,
+    /// Existing tick arrays around the current price, sorted by start index.
+    pub tick_arrays: Vec<TickArray>,
+}
+
+impl WhirlpoolPool {
+    /// Start indexes of the tick arrays a swap may go through, in the order it does. Going up,
+    /// the program starts from the array holding the next tick rather than the current one.
+    fn swap_tick_array_starts(&self, a_to_b: bool) -> Vec<i32> {
+        let shift = if a_to_b {
+            0
+        } else {
+            self.state.tick_spacing as i32
+        };
+        let first = self
+            .state
+            .tick_array_start_index(self.state.tick_current_index + shift);
+        let step = if a_to_b {
+            -self.state.ticks_per_array()
+        } else {
+            self.state.ticks_per_array()
+        };
+
+        (0..SWAP_TICK_ARRAYS)
+            .map(|offset| first + offset * step)
+            .filter(|start| {
+                (MIN_TICK - self.state.ticks_per_array() + 1..=MAX_TICK).contains(start)
+            })
+            .collect()
+    }
+
+    /// Simulates swapping exactly `amount_in` of `input_mint`, within the tick arrays the
+    /// instruction can take.
+    pub fn simulate(&self, input_mint: &Pubkey, amount_in: u64) -> anyhow::Result<SwapResult> {
+        let a_to_b = *input_mint == self.state.token_mint_a;
+        let starts = self.swap_tick_array_starts(a_to_b);
+        let lowest = starts.iter().copied().min().unwrap_or_default();
+        let highest = starts.iter().copied().max().unwrap_or_default();
+
+        let ticks = self
+            .tick_arrays
+            .iter()
+            .filter(|tick_array| starts.contains(&tick_array.start_tick_index))
+            .flat_map(|tick_array| tick_array.ticks.iter().copied())
+            .collect::<Vec<Tick>>();
+
+        swap_base_input(
+            &self.state.price_state(),
+            &ticks,
+            (lowest, highest + self.state.ticks_per_array() - 1),
+            self.state.fee_rate as u32,
+            a_to_b,
+            amount_in,
+        )
+    }
+}
+
+impl SwapPool for WhirlpoolPool {
+    fn id(&self) -> Pubkey {
+        self.address
+    }
+
+    fn token_program(&self, mint: &Pubkey) -> Pubkey {
+        if *mint == self.state.token_mint_b {
+            self.token_b_program
+        } else {
+            self.token_a_program
+        }
+    }
+
+    /// Prices from the pool state and its ticks, like a CLMM pool.
+    fn quote(
+        &self,
+        _reserves: &PoolReserves,
+        input_mint: &Pubkey,
+        amount_in: u64,
+    ) -> anyhow::Result<u64> {
+        Ok(self.simulate(input_mint, amount_in)?.amount_out)
+    }
+
+    fn swap_instruction(
+        &self,
+        owner: &Pubkey,
+        input_mint: &Pubkey,
+        input_account: &Pubkey,
+        output_account: &Pubkey,
+        amount_in: u64,
+        min_amount_out: u64,
+    ) -> anyhow::Result<Instruction> {
+        let a_to_b = *input_mint == self.state.token_mint_a;
+        let (token_account_a, token_account_b) = if a_to_b {
+            (input_account, output_account)
+        } else {
+            (output_account, input_account)
+        };
+
+        // The instruction always takes three arrays: the last one is repeated near the bounds.
+        let mut tick_arrays = self
+            .swap_tick_array_starts(a_to_b)
+            .into_iter()
+            .map(|start| tick_array_address(&self.program_id, &self.address, start))
+            .collect::<Vec<_>>();
+        let last = *tick_arrays
+            .last()
+            .ok_or_else(|| anyhow::anyhow!("No tick array on pool {}", self.address))?;
+        tick_arrays.resize(SWAP_TICK_ARRAYS as usize, last);
+
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.token_a_program, false),
+            AccountMeta::new_readonly(self.token_b_program, false),
+            AccountMeta::new_readonly(MEMO_PROGRAM_ID, false),
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new(self.address, false),
+            AccountMeta::new_readonly(self.state.token_mint_a, false),
+            AccountMeta::new_readonly(self.state.token_mint_b, false),
+            AccountMeta::new(*token_account_a, false),
+            AccountMeta::new(self.state.token_vault_a, false),
+            AccountMeta::new(*token_account_b, false),
+            AccountMeta::new(self.state.token_vault_b, false),
+        ];
+        accounts.extend(
+            tick_arrays
+                .into_iter()
+                .map(|tick_array| AccountMeta::new(tick_array, false)),
+        );
+        accounts.push(AccountMeta::new(
+            oracle_address(&self.program_id, &self.address),
+            false,
+        ));
+
+        let mut data = SWAP_V2_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&amount_in.to_le_bytes());
+        data.extend_from_slice(&min_amount_out.to_le_bytes());
+        // No price limit: the minimum output bounds the swap.
+        data.extend_from_slice(&0u128.to_le_bytes());
+        data.push(true as u8);
+        data.push(a_to_b as u8);
+        // No remaining accounts, e.g. transfer hooks.
+        data.push(0);
+
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts,
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::raydium::clmm::math::sqrt_price_at_tick;
+
+    use super::*;
+
+    fn pool(tick_current_index: i32, tick_arrays: Vec<TickArray>) -> WhirlpoolPool {
+        WhirlpoolPool {
+            program_id: WHIRLPOOL_PROGRAM_ID,
+            address: Pubkey::new_unique(),
+            state: WhirlpoolState {
+                tick_spacing: 64,
+                fee_rate: 3_000,
+                liquidity: 1_000_000_000_000,
+                sqrt_price: sqrt_price_at_tick(tick_current_index).unwrap(),
+                tick_current_index,
+                token_mint_a: Pubkey::new_unique(),
+                token_vault_a: Pubkey::new_unique(),
+                token_mint_b: Pubkey::new_unique(),
+                token_vault_b: Pubkey::new_unique(),
+            },
+            token_a_program: spl_token::id(),
+            token_b_program: spl_token::id(),
+            tick_arrays,
+        }
+    }
+
+    #[test]
+    fn swaps_go_through_three_tick_arrays_in_their_direction() {
+        // 88 ticks of 64 per array: the current tick sits in the array starting at 0, and its
+        // next tick going up in the following one.
+        let pool = pool(5_600, Vec::new());
+
+        assert_eq!(pool.swap_tick_array_starts(true), vec![0, -5_632, -11_264]);
+        assert_eq!(
+            pool.swap_tick_array_starts(false),
+            vec![5_632, 11_264, 16_896]
+        );
+    }
+
+    #[test]
+    fn quotes_stop_at_the_tick_arrays_the_swap_can_take() {
+        // All liquidity ends at tick -64 going down.
+        let pool = pool(
+            0,
+            vec![TickArray {
+                address: Pubkey::new_unique(),
+                start_tick_index: -5_632,
+                ticks: vec![Tick {
+                    index: -64,
+                    liquidity_net: 1_000_000_000_000,
+                }],
+            }],
+        );
+        let token_mint_a = pool.state.token_mint_a;
+        let token_mint_b = pool.state.token_mint_b;
+
+        let small = pool.simulate(&token_mint_a, 1_000_000).unwrap();
+        assert!(small.amount_out > 0 && small.amount_out < 1_000_000);
+        assert!(pool.simulate(&token_mint_a, 1_000_000_000_000).is_err());
+
+        // Going up, nothing is initialized within the three arrays: the swap fails past them.
+        assert!(pool.simulate(&token_mint_b, 1_000_000).is_ok());
+        assert!(pool.simulate(&token_mint_b, u64::MAX / 2).is_err());
+    }
+}
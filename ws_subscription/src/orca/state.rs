@@ -0,0 +1,139 @@
+use anyhow::Context;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::raydium::clmm::{
+    math::PriceState,
+    state::{Tick, TickArray},
+};
+
+/// Anchor discriminators, the first 8 bytes of `sha256("account:<account>")`.
+pub(crate) const WHIRLPOOL_DISCRIMINATOR: [u8; 8] = [63, 149, 209, 12, 225, 128, 99, 9];
+const TICK_ARRAY_DISCRIMINATOR: [u8; 8] = [69, 97, 189, 190, 110, 7, 66, 187];
+
+/// `Whirlpool::token_mint_a` and `token_mint_b`.
+pub(crate) const WHIRLPOOL_MINT_OFFSETS: [usize; 2] = [101, 181];
+
+pub const TICK_ARRAY_SIZE: i32 = 88;
+const TICK_ARRAY_SEED: &[u8] = b"tick_array";
+const ORACLE_SEED: &[u8] = b"oracle";
+/// `TickArray::ticks`, after the discriminator and `start_tick_index`.
+const TICK_ARRAY_TICKS_OFFSET: usize = 8 + 4;
+const TICK_SIZE: usize = 113;
+
+/// The parts of the `Whirlpool` account needed to quote and swap.
+#[derive(Clone, Copy, Debug)]
+pub struct WhirlpoolState {
+    pub tick_spacing: u16,
+    /// Trade fee, in hundredths of a basis point.
+    pub fee_rate: u16,
+    /// Liquidity in range at the current price.
+    pub liquidity: u128,
+    /// Square root of the price of token A in token B, as a Q64.64.
+    pub sqrt_price: u128,
+    pub tick_current_index: i32,
+    pub token_mint_a: Pubkey,
+    pub token_vault_a: Pubkey,
+    pub token_mint_b: Pubkey,
+    pub token_vault_b: Pubkey,
+}
+
+impl WhirlpoolState {
+    pub fn decode(data: &[u8]) -> anyhow::Result<Self> {
+        if data.get(..8) != Some(&WHIRLPOOL_DISCRIMINATOR[..]) {
+            anyhow::bail!("Not a Whirlpool account");
+        }
+
+        Ok(Self {
+            tick_spacing: u16::from_le_bytes(read(data, 41)?),
+            fee_rate: u16::from_le_bytes(read(data, 45)?),
+            liquidity: u128::from_le_bytes(read(data, 49)?),
+            sqrt_price: u128::from_le_bytes(read(data, 65)?),
+            tick_current_index: i32::from_le_bytes(read(data, 81)?),
+            token_mint_a: read_pubkey(data, WHIRLPOOL_MINT_OFFSETS[0])?,
+            token_vault_a: read_pubkey(data, 133)?,
+            token_mint_b: read_pubkey(data, WHIRLPOOL_MINT_OFFSETS[1])?,
+            token_vault_b: read_pubkey(data, 213)?,
+        })
+    }
+
+    pub fn price_state(&self) -> PriceState {
+        PriceState {
+            sqrt_price_x64: self.sqrt_price,
+            tick_current: self.tick_current_index,
+            liquidity: self.liquidity,
+        }
+    }
+
+    /// Ticks covered by each tick array.
+    pub fn ticks_per_array(&self) -> i32 {
+        TICK_ARRAY_SIZE * self.tick_spacing as i32
+    }
+
+    /// Start index of the tick array holding `tick`.
+    pub fn tick_array_start_index(&self, tick: i32) -> i32 {
+        tick.div_euclid(self.ticks_per_array()) * self.ticks_per_array()
+    }
+}
+
+/// The initialized ticks of a Whirlpool `TickArray` account. Ticks do not store their index:
+/// it follows from their slot and the pool's tick spacing.
+pub fn decode_tick_array(
+    address: Pubkey,
+    data: &[u8],
+    tick_spacing: u16,
+) -> anyhow::Result<TickArray> {
+    if data.get(..8) != Some(&TICK_ARRAY_DISCRIMINATOR[..]) {
+        anyhow::bail!("Not a Whirlpool tick array account");
+    }
+
+    let start_tick_index = i32::from_le_bytes(read(data, 8)?);
+    let mut ticks = Vec::new();
+    for index in 0..TICK_ARRAY_SIZE as usize {
+        let offset = TICK_ARRAY_TICKS_OFFSET + index * TICK_SIZE;
+        if read::<1>(data, offset)?[0] != 0 {
+            ticks.push(Tick {
+                index: start_tick_index + index as i32 * tick_spacing as i32,
+                liquidity_net: i128::from_le_bytes(read(data, offset + 1)?),
+            });
+        }
+    }
+
+    Ok(TickArray {
+        address,
+        start_tick_index,
+        ticks,
+    })
+}
+
+/// Whirlpools seed their tick arrays with the start index written out in decimal.
+pub fn tick_array_address(
+    program_id: &Pubkey,
+    whirlpool: &Pubkey,
+    start_tick_index: i32,
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            TICK_ARRAY_SEED,
+            whirlpool.as_ref(),
+            start_tick_index.to_string().as_bytes(),
+        ],
+        program_id,
+    )
+    .0
+}
+
+pub fn oracle_address(program_id: &Pubkey, whirlpool: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[ORACLE_SEED, whirlpool.as_ref()], program_id).0
+}
+
+fn read<const N: usize>(data: &[u8], offset: usize) -> anyhow::Result<[u8; N]> {
+    let bytes = data
+        .get(offset..offset + N)
+        .with_context(|| format!("Account is too short to read offset {}", offset))?;
+
+    Ok(bytes.try_into()?)
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> anyhow::Result<Pubkey> {
+    Ok(Pubkey::new_from_array(read(data, offset)?))
+}
@@ -0,0 +1,204 @@
+//! Events the Pump.fun program emits as `Program data:` logs.
+//!
+//! Layouts from the program IDL. Later versions append fields to some events, which are
+//! ignored here.
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use solana_sdk::pubkey::Pubkey;
+
+const PROGRAM_DATA_PREFIX: &str = "Program data: ";
+
+/// Anchor discriminators, the first 8 bytes of `sha256("event:<event>")`.
+const CREATE_EVENT_DISCRIMINATOR: [u8; 8] = [27, 114, 169, 77, 222, 235, 99, 118];
+const TRADE_EVENT_DISCRIMINATOR: [u8; 8] = [189, 219, 127, 211, 78, 230, 97, 238];
+const COMPLETE_EVENT_DISCRIMINATOR: [u8; 8] = [95, 114, 97, 156, 212, 46, 152, 8];
+
+/// A token launched on its bonding curve.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CreateEvent {
+    pub name: String,
+    pub symbol: String,
+    pub mint: Pubkey,
+    pub bonding_curve: Pubkey,
+    pub user: Pubkey,
+}
+
+/// A buy or sell on a bonding curve, with the virtual reserves after it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TradeEvent {
+    pub mint: Pubkey,
+    pub sol_amount: u64,
+    pub token_amount: u64,
+    pub is_buy: bool,
+    pub user: Pubkey,
+    pub virtual_sol_reserves: u64,
+    pub virtual_token_reserves: u64,
+}
+
+/// A bonding curve sold out: the token is about to migrate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CompleteEvent {
+    pub mint: Pubkey,
+    pub bonding_curve: Pubkey,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PumpFunEvent {
+    Create(CreateEvent),
+    Trade(TradeEvent),
+    Complete(CompleteEvent),
+}
+
+impl PumpFunEvent {
+    /// Decodes every Pump.fun event found in the transaction logs, in order.
+    pub fn from_logs(logs: &[String]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|log| log.strip_prefix(PROGRAM_DATA_PREFIX))
+            .filter_map(|encoded| Self::decode(encoded.trim()))
+            .collect()
+    }
+
+    fn decode(encoded: &str) -> Option<Self> {
+        let bytes = STANDARD.decode(encoded).ok()?;
+        let (discriminator, data) = bytes.split_at_checked(8)?;
+        let mut reader = Reader(data);
+
+        match <[u8; 8]>::try_from(discriminator).ok()? {
+            CREATE_EVENT_DISCRIMINATOR => {
+                let name = reader.string()?;
+                let symbol = reader.string()?;
+                let _uri = reader.string()?;
+                Some(Self::Create(CreateEvent {
+                    name,
+                    symbol,
+                    mint: reader.pubkey()?,
+                    bonding_curve: reader.pubkey()?,
+                    user: reader.pubkey()?,
+                }))
+            }
+            TRADE_EVENT_DISCRIMINATOR => {
+                let mint = reader.pubkey()?;
+                let sol_amount = reader.u64()?;
+                let token_amount = reader.u64()?;
+                let is_buy = reader.bytes::<1>()?[0] != 0;
+                let user = reader.pubkey()?;
+                let _timestamp = reader.u64()?;
+                Some(Self::Trade(TradeEvent {
+                    mint,
+                    sol_amount,
+                    token_amount,
+                    is_buy,
+                    user,
+                    virtual_sol_reserves: reader.u64()?,
+                    virtual_token_reserves: reader.u64()?,
+                }))
+            }
+            COMPLETE_EVENT_DISCRIMINATOR => {
+                let _user = reader.pubkey()?;
+                Some(Self::Complete(CompleteEvent {
+                    mint: reader.pubkey()?,
+                    bonding_curve: reader.pubkey()?,
+                }))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Borsh reader over an event body.
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (bytes, rest) = self.0.split_at_checked(N)?;
+        self.0 = rest;
+        bytes.try_into().ok()
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.bytes().map(u64::from_le_bytes)
+    }
+
+    fn pubkey(&mut self) -> Option<Pubkey> {
+        self.bytes().map(Pubkey::new_from_array)
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = u32::from_le_bytes(self.bytes()?) as usize;
+        let (bytes, rest) = self.0.split_at_checked(len)?;
+        self.0 = rest;
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(discriminator: [u8; 8], body: &[u8]) -> String {
+        let mut data = discriminator.to_vec();
+        data.extend_from_slice(body);
+        format!("{}{}", PROGRAM_DATA_PREFIX, STANDARD.encode(data))
+    }
+
+    fn borsh_string(value: &str) -> Vec<u8> {
+        let mut bytes = (value.len() as u32).to_le_bytes().to_vec();
+        bytes.extend_from_slice(value.as_bytes());
+        bytes
+    }
+
+    #[test]
+    fn decodes_the_events_in_order_and_skips_other_logs() {
+        let mint = Pubkey::new_unique();
+        let bonding_curve = Pubkey::new_unique();
+        let user = Pubkey::new_unique();
+
+        let mut create = [
+            borsh_string("Token"),
+            borsh_string("TKN"),
+            borsh_string("uri"),
+        ]
+        .concat();
+        create.extend_from_slice(mint.as_ref());
+        create.extend_from_slice(bonding_curve.as_ref());
+        create.extend_from_slice(user.as_ref());
+
+        let mut trade = mint.to_bytes().to_vec();
+        trade.extend_from_slice(&1_000_000_000u64.to_le_bytes());
+        trade.extend_from_slice(&34_612_903_225_806u64.to_le_bytes());
+        trade.push(1);
+        trade.extend_from_slice(user.as_ref());
+        trade.extend_from_slice(&1_700_000_000i64.to_le_bytes());
+        trade.extend_from_slice(&31_000_000_000u64.to_le_bytes());
+        trade.extend_from_slice(&1_038_387_096_774_194u64.to_le_bytes());
+
+        let logs = vec![
+            "Program 6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P invoke [1]".to_string(),
+            log(CREATE_EVENT_DISCRIMINATOR, &create),
+            "Program data: not base64".to_string(),
+            log(TRADE_EVENT_DISCRIMINATOR, &trade),
+        ];
+
+        assert_eq!(
+            PumpFunEvent::from_logs(&logs),
+            vec![
+                PumpFunEvent::Create(CreateEvent {
+                    name: "Token".to_string(),
+                    symbol: "TKN".to_string(),
+                    mint,
+                    bonding_curve,
+                    user,
+                }),
+                PumpFunEvent::Trade(TradeEvent {
+                    mint,
+                    sol_amount: 1_000_000_000,
+                    token_amount: 34_612_903_225_806,
+                    is_buy: true,
+                    user,
+                    virtual_sol_reserves: 31_000_000_000,
+                    virtual_token_reserves: 1_038_387_096_774_194,
+                }),
+            ]
+        );
+    }
+}
@@ -0,0 +1,404 @@
+//! Pump.fun: tokens launch on a bonding curve priced off virtual reserves, and migrate to a
+//! Raydium pool once the curve sells out.
+//!
+//! https://github.com/pump-fun/pump-public-docs
+
+use anyhow::Context;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+use spl_associated_token_account::get_associated_token_address;
+
+use crate::{
+    api::solana_rpc::Transaction,
+    dex::{Dex, PoolReserves, SwapPool},
+    raydium::event_processors::WSOL,
+};
+
+use self::events::PumpFunEvent;
+
+pub mod events;
+
+pub const PUMP_FUN_PROGRAM_ID: Pubkey =
+    solana_sdk::pubkey!("6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P");
+
+/// Anchor discriminators: the first 8 bytes of `sha256("global:<instruction>")` and
+/// `sha256("account:<account>")`.
+const BUY_DISCRIMINATOR: [u8; 8] = [102, 6, 61, 18, 1, 218, 235, 234];
+const SELL_DISCRIMINATOR: [u8; 8] = [51, 230, 133, 164, 1, 127, 131, 173];
+const GLOBAL_DISCRIMINATOR: [u8; 8] = [167, 232, 232, 177, 200, 108, 114, 127];
+const BONDING_CURVE_DISCRIMINATOR: [u8; 8] = [23, 183, 248, 55, 96, 216, 172, 96];
+
+pub(crate) const GLOBAL_SEED: &[u8] = b"global";
+const BONDING_CURVE_SEED: &[u8] = b"bonding-curve";
+pub(crate) const EVENT_AUTHORITY_SEED: &[u8] = b"__event_authority";
+
+const FEE_BASIS_POINTS_DENOMINATOR: u64 = 10_000;
+pub const SOLD_OUT_PROGRESS_BPS: u64 = 10_000;
+/// Virtual token reserves every curve starts with, of which `INITIAL_REAL_TOKEN_RESERVES` are
+/// for sale. Both are set in the `Global` account and have never changed.
+const INITIAL_VIRTUAL_TOKEN_RESERVES: u64 = 1_073_000_000_000_000;
+const INITIAL_REAL_TOKEN_RESERVES: u64 = 793_100_000_000_000;
+
+/// Settings shared by every curve, from the `Global` account.
+#[derive(Clone, Copy, Debug)]
+pub struct Global {
+    pub fee_recipient: Pubkey,
+    pub fee_basis_points: u64,
+}
+
+impl Global {
+    /// Offset of `fee_recipient`, after the discriminator, `initialized` and `authority`.
+    const FEE_RECIPIENT_OFFSET: usize = 8 + 1 + 32;
+    /// Offset of `fee_basis_points`, after the four initial reserves and supply words.
+    const FEE_BASIS_POINTS_OFFSET: usize = Self::FEE_RECIPIENT_OFFSET + 32 + 4 * 8;
+
+    pub fn decode(data: &[u8]) -> anyhow::Result<Self> {
+        if data.get(..8) != Some(&GLOBAL_DISCRIMINATOR[..]) {
+            anyhow::bail!("Not a Pump.fun global account");
+        }
+
+        Ok(Self {
+            fee_recipient: Pubkey::new_from_array(read(data, Self::FEE_RECIPIENT_OFFSET)?),
+            fee_basis_points: u64::from_le_bytes(read(data, Self::FEE_BASIS_POINTS_OFFSET)?),
+        })
+    }
+}
+
+/// A `BondingCurve` account.
+#[derive(Clone, Copy, Debug)]
+pub struct BondingCurve {
+    pub virtual_token_reserves: u64,
+    pub virtual_sol_reserves: u64,
+    pub real_token_reserves: u64,
+    /// Set once the curve sold out: it no longer trades and waits for the migration.
+    pub complete: bool,
+}
+
+impl BondingCurve {
+    pub fn decode(data: &[u8]) -> anyhow::Result<Self> {
+        if data.get(..8) != Some(&BONDING_CURVE_DISCRIMINATOR[..]) {
+            anyhow::bail!("Not a Pump.fun bonding curve account");
+        }
+        let word = |index: usize| read(data, 8 + index * 8).map(u64::from_le_bytes);
+
+        Ok(Self {
+            virtual_token_reserves: word(0)?,
+            virtual_sol_reserves: word(1)?,
+            real_token_reserves: word(2)?,
+            // After `real_sol_reserves` and `token_total_supply`.
+            complete: read::<1>(data, 8 + 5 * 8)?[0] != 0,
+        })
+    }
+
+    /// The virtual reserves the curve prices with: the token as coin and SOL as pc.
+    pub fn reserves(&self, slot: u64) -> PoolReserves {
+        PoolReserves {
+            coin_vault: self.virtual_token_reserves,
+            pc_vault: self.virtual_sol_reserves,
+            slot,
+            ..Default::default()
+        }
+    }
+}
+
+/// Share of the curve's tokens sold, in basis points, from its virtual token reserves.
+pub fn curve_progress_bps(virtual_token_reserves: u64) -> u64 {
+    let real_token_reserves = virtual_token_reserves
+        .saturating_sub(INITIAL_VIRTUAL_TOKEN_RESERVES - INITIAL_REAL_TOKEN_RESERVES)
+        .min(INITIAL_REAL_TOKEN_RESERVES);
+    let sold = INITIAL_REAL_TOKEN_RESERVES - real_token_reserves;
+
+    (sold as u128 * SOLD_OUT_PROGRESS_BPS as u128 / INITIAL_REAL_TOKEN_RESERVES as u128) as u64
+}
+
+pub fn bonding_curve_address(program_id: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[BONDING_CURVE_SEED, mint.as_ref()], program_id).0
+}
+
+fn global_address(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[GLOBAL_SEED], program_id).0
+}
+
+fn event_authority_address(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[EVENT_AUTHORITY_SEED], program_id).0
+}
+
+/// A bonding curve that still trades.
+#[derive(Clone, Copy, Debug)]
+pub struct BondingCurvePool {
+    pub program_id: Pubkey,
+    pub mint: Pubkey,
+    pub bonding_curve: Pubkey,
+    pub global: Global,
+    pub curve: BondingCurve,
+}
+
+impl SwapPool for BondingCurvePool {
+    fn id(&self) -> Pubkey {
+        self.bonding_curve
+    }
+
+    fn takes_native_sol(&self) -> bool {
+        true
+    }
+
+    /// Prices off the virtual reserves. The fee is paid on top of the SOL a buy costs, and
+    /// taken out of what a sell returns.
+    fn quote(
+        &self,
+        reserves: &PoolReserves,
+        input_mint: &Pubkey,
+        amount_in: u64,
+    ) -> anyhow::Result<u64> {
+        let (virtual_token_reserves, virtual_sol_reserves) =
+            (reserves.coin_vault as u128, reserves.pc_vault as u128);
+        let fee_basis_points = self.global.fee_basis_points as u128;
+        let denominator = FEE_BASIS_POINTS_DENOMINATOR as u128;
+
+        let amount_out = if *input_mint == self.mint {
+            let sol_out = virtual_sol_reserves * amount_in as u128
+                / (virtual_token_reserves + amount_in as u128);
+            sol_out - (sol_out * fee_basis_points).div_ceil(denominator)
+        } else {
+            let sol_in = amount_in as u128 * denominator / (denominator + fee_basis_points);
+            let tokens_out = virtual_token_reserves * sol_in / (virtual_sol_reserves + sol_in);
+            tokens_out.min(self.curve.real_token_reserves as u128)
+        };
+
+        u64::try_from(amount_out).with_context(|| "Bonding curve quote overflows")
+    }
+
+    /// Buys take the exact token amount and the most SOL to pay for it: this buys
+    /// `min_amount_out` tokens for at most `amount_in` lamports, fee included. Sells are exact
+    /// in.
+    fn swap_instruction(
+        &self,
+        owner: &Pubkey,
+        input_mint: &Pubkey,
+        input_account: &Pubkey,
+        output_account: &Pubkey,
+        amount_in: u64,
+        min_amount_out: u64,
+    ) -> anyhow::Result<Instruction> {
+        let is_buy = *input_mint != self.mint;
+        let user_token_account = if is_buy {
+            *output_account
+        } else {
+            *input_account
+        };
+
+        let mut accounts = vec![
+            AccountMeta::new_readonly(global_address(&self.program_id), false),
+            AccountMeta::new(self.global.fee_recipient, false),
+            AccountMeta::new_readonly(self.mint, false),
+            AccountMeta::new(self.bonding_curve, false),
+            AccountMeta::new(
+                get_associated_token_address(&self.bonding_curve, &self.mint),
+                false,
+            ),
+            AccountMeta::new(user_token_account, false),
+            AccountMeta::new(*owner, true),
+            AccountMeta::new_readonly(solana_system_interface::program::id(), false),
+        ];
+        let mut data;
+        if is_buy {
+            accounts.extend([
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new_readonly(solana_sdk::sysvar::rent::id(), false),
+            ]);
+            data = BUY_DISCRIMINATOR.to_vec();
+            data.extend_from_slice(&min_amount_out.to_le_bytes());
+            data.extend_from_slice(&amount_in.to_le_bytes());
+        } else {
+            accounts.extend([
+                AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+            ]);
+            data = SELL_DISCRIMINATOR.to_vec();
+            data.extend_from_slice(&amount_in.to_le_bytes());
+            data.extend_from_slice(&min_amount_out.to_le_bytes());
+        }
+        accounts.extend([
+            AccountMeta::new_readonly(event_authority_address(&self.program_id), false),
+            AccountMeta::new_readonly(self.program_id, false),
+        ]);
+
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts,
+            data,
+        })
+    }
+}
+
+/// The Pump.fun launchpad, where "pools" are bonding curves paired against SOL.
+#[derive(Clone, Copy, Debug)]
+pub struct PumpFun {
+    pub program_id: Pubkey,
+}
+
+impl PumpFun {
+    /// Whether the program runs in the transaction at all.
+    pub fn is_invoked(&self, logs: &[String]) -> bool {
+        let invoke = format!("Program {} invoke", self.program_id);
+
+        logs.iter().any(|log| log.starts_with(&invoke))
+    }
+}
+
+/// A launch, as read from the `CreateEvent` of its transaction.
+#[derive(Clone, Copy, Debug)]
+pub struct PumpFunCreation {
+    pub mint: Pubkey,
+    pub bonding_curve: Pubkey,
+    pub slot: u64,
+}
+
+impl Dex for PumpFun {
+    type Creation = PumpFunCreation;
+    type Pool = BondingCurvePool;
+
+    fn name(&self) -> &'static str {
+        "pump_fun"
+    }
+
+    /// A top-level call to the program whose first log is the `Create` instruction.
+    fn is_pool_creation(&self, logs: &[String]) -> bool {
+        let invoke = format!("Program {} invoke [1]", self.program_id);
+
+        logs.windows(2)
+            .any(|logs| logs[0] == invoke && logs[1] == "Program log: Instruction: Create")
+    }
+
+    fn decode_creation(
+        &self,
+        transaction: &Transaction,
+    ) -> anyhow::Result<Option<PumpFunCreation>> {
+        let Some(metadata) = &transaction.metadata else {
+            return Ok(None);
+        };
+        if metadata.err.is_some() {
+            return Ok(None);
+        }
+        let logs = Option::<Vec<String>>::from(metadata.log_messages.clone()).unwrap_or_default();
+
+        Ok(PumpFunEvent::from_logs(&logs)
+            .into_iter()
+            .find_map(|event| match event {
+                PumpFunEvent::Create(create) => Some(PumpFunCreation {
+                    mint: create.mint,
+                    bonding_curve: create.bonding_curve,
+                    slot: transaction.slot,
+                }),
+                _ => None,
+            }))
+    }
+
+    fn pool_id(&self, creation: &PumpFunCreation) -> Pubkey {
+        creation.bonding_curve
+    }
+
+    fn mints(&self, creation: &PumpFunCreation) -> [Pubkey; 2] {
+        [creation.mint, WSOL]
+    }
+
+    fn pool_accounts(&self, creation: &PumpFunCreation) -> Vec<Pubkey> {
+        vec![creation.bonding_curve]
+    }
+
+    fn immutable_accounts(&self, _creation: &PumpFunCreation) -> Vec<Pubkey> {
+        vec![global_address(&self.program_id)]
+    }
+
+    fn decode_pool(
+        &self,
+        creation: &PumpFunCreation,
+        accounts: &[Option<Vec<u8>>],
+    ) -> anyhow::Result<(BondingCurvePool, PoolReserves)> {
+        let account = |index: usize| -> anyhow::Result<&[u8]> {
+            accounts
+                .get(index)
+                .and_then(Option::as_deref)
+                .with_context(|| format!("Missing Pump.fun account {}", index))
+        };
+
+        let curve = BondingCurve::decode(account(0)?)?;
+        if curve.complete {
+            anyhow::bail!("Bonding curve {} is complete", creation.bonding_curve);
+        }
+        let pool = BondingCurvePool {
+            program_id: self.program_id,
+            mint: creation.mint,
+            bonding_curve: creation.bonding_curve,
+            global: Global::decode(account(1)?)?,
+            curve,
+        };
+
+        Ok((pool, curve.reserves(creation.slot)))
+    }
+}
+
+fn read<const N: usize>(data: &[u8], offset: usize) -> anyhow::Result<[u8; N]> {
+    let bytes = data
+        .get(offset..offset + N)
+        .with_context(|| format!("Account is too short to read offset {}", offset))?;
+
+    Ok(bytes.try_into()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool() -> BondingCurvePool {
+        BondingCurvePool {
+            program_id: PUMP_FUN_PROGRAM_ID,
+            mint: Pubkey::new_unique(),
+            bonding_curve: Pubkey::new_unique(),
+            global: Global {
+                fee_recipient: Pubkey::new_unique(),
+                fee_basis_points: 100,
+            },
+            curve: BondingCurve {
+                virtual_token_reserves: INITIAL_VIRTUAL_TOKEN_RESERVES,
+                virtual_sol_reserves: 30_000_000_000,
+                real_token_reserves: INITIAL_REAL_TOKEN_RESERVES,
+                complete: false,
+            },
+        }
+    }
+
+    #[test]
+    fn quotes_a_fresh_curve_with_the_fee_on_top() {
+        let pool = pool();
+        let reserves = pool.curve.reserves(0);
+
+        // 1.01 SOL buys what 1 SOL moves along the curve.
+        let tokens = pool.quote(&reserves, &WSOL, 1_010_000_000).unwrap();
+        assert_eq!(tokens, 34_612_903_225_806);
+
+        // Selling them back returns the SOL, less the fee.
+        let after_buy = PoolReserves {
+            coin_vault: reserves.coin_vault - tokens,
+            pc_vault: reserves.pc_vault + 1_000_000_000,
+            ..reserves
+        };
+        let sol = pool.quote(&after_buy, &pool.mint, tokens).unwrap();
+        assert!((989_999_990..=990_000_000).contains(&sol), "{}", sol);
+    }
+
+    #[test]
+    fn progress_goes_from_launch_to_sold_out() {
+        assert_eq!(curve_progress_bps(INITIAL_VIRTUAL_TOKEN_RESERVES), 0);
+        assert_eq!(
+            curve_progress_bps(INITIAL_VIRTUAL_TOKEN_RESERVES - INITIAL_REAL_TOKEN_RESERVES / 2),
+            5_000
+        );
+        assert_eq!(
+            curve_progress_bps(INITIAL_VIRTUAL_TOKEN_RESERVES - INITIAL_REAL_TOKEN_RESERVES),
+            10_000
+        );
+    }
+}
@@ -0,0 +1,356 @@
+//! Off-chain replica of the CLMM swap: Q64.64 square root prices, walking the initialized
+//! ticks one price range at a time.
+//!
+//! Orca Whirlpools run the same math, fee rates included: both take them in millionths.
+
+use anyhow::Context;
+use uint::construct_uint;
+
+use crate::raydium::cpmm::FEE_RATE_DENOMINATOR;
+
+use super::state::Tick;
+
+#[allow(clippy::all)]
+mod uint_types {
+    use super::construct_uint;
+
+    construct_uint! {
+        pub struct U512(8);
+    }
+}
+
+use uint_types::U512;
+
+pub const MIN_TICK: i32 = -443636;
+pub const MAX_TICK: i32 = -MIN_TICK;
+
+/// `1 / sqrt(1.0001)^(2^i)` as Q64.64, for each bit `i` of a tick.
+const SQRT_PRICE_FACTORS: [u128; 19] = [
+    0xfffcb933bd6fb800,
+    0xfff97272373d4000,
+    0xfff2e50f5f657000,
+    0xffe5caca7e10f000,
+    0xffcb9843d60f7000,
+    0xff973b41fa98e800,
+    0xff2ea16466c9b000,
+    0xfe5dee046a9a3800,
+    0xfcbe86c7900bb000,
+    0xf987a7253ac65800,
+    0xf3392b0822bb6000,
+    0xe7159475a2caf000,
+    0xd097f3bdfd2f2000,
+    0xa9f746462d9f8000,
+    0x70d869a156f31c00,
+    0x31be135f97ed3200,
+    0x9aa508b5b85a500,
+    0x5d6af8dedc582c,
+    0x2216e584f5fa,
+];
+
+/// `sqrt(1.0001^tick)` as Q64.64.
+pub fn sqrt_price_at_tick(tick: i32) -> anyhow::Result<u128> {
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        anyhow::bail!("Tick {} is out of bounds", tick);
+    }
+
+    let abs_tick = tick.unsigned_abs();
+    let mut ratio = U512::one() << 64;
+    for (bit, factor) in SQRT_PRICE_FACTORS.iter().enumerate() {
+        if abs_tick & (1 << bit) != 0 {
+            ratio = (ratio * U512::from(*factor)) >> 64;
+        }
+    }
+    if tick > 0 {
+        ratio = U512::from(u128::MAX) / ratio;
+    }
+
+    Ok(ratio.low_u128())
+}
+
+fn to_u128(value: U512) -> Option<u128> {
+    (value.bits() <= 128).then(|| value.low_u128())
+}
+
+fn div_ceil(numerator: U512, denominator: U512) -> U512 {
+    let (quotient, remainder) = numerator.div_mod(denominator);
+    if remainder.is_zero() {
+        quotient
+    } else {
+        quotient + 1
+    }
+}
+
+/// Token 0 between two prices for `liquidity`.
+fn amount_0_delta(sqrt_a: u128, sqrt_b: u128, liquidity: u128, round_up: bool) -> Option<U512> {
+    let (sqrt_a, sqrt_b) = (sqrt_a.min(sqrt_b), sqrt_a.max(sqrt_b));
+    if sqrt_a == 0 {
+        return None;
+    }
+    let numerator = (U512::from(liquidity) << 64) * U512::from(sqrt_b - sqrt_a);
+
+    Some(if round_up {
+        div_ceil(div_ceil(numerator, sqrt_b.into()), sqrt_a.into())
+    } else {
+        numerator / U512::from(sqrt_b) / U512::from(sqrt_a)
+    })
+}
+
+/// Token 1 between two prices for `liquidity`.
+fn amount_1_delta(sqrt_a: u128, sqrt_b: u128, liquidity: u128, round_up: bool) -> U512 {
+    let (sqrt_a, sqrt_b) = (sqrt_a.min(sqrt_b), sqrt_a.max(sqrt_b));
+    let numerator = U512::from(liquidity) * U512::from(sqrt_b - sqrt_a);
+
+    if round_up {
+        div_ceil(numerator, U512::one() << 64)
+    } else {
+        numerator >> 64
+    }
+}
+
+/// Price reached by adding `amount_in` at `sqrt_price`, rounded against the swapper.
+fn next_sqrt_price_from_input(
+    sqrt_price: u128,
+    liquidity: u128,
+    amount_in: u64,
+    zero_for_one: bool,
+) -> Option<u128> {
+    if amount_in == 0 {
+        return Some(sqrt_price);
+    }
+
+    if zero_for_one {
+        let numerator = U512::from(liquidity) << 64;
+        let denominator = numerator + U512::from(amount_in) * U512::from(sqrt_price);
+        to_u128(div_ceil(numerator * U512::from(sqrt_price), denominator))
+    } else {
+        let quotient = (U512::from(amount_in) << 64) / U512::from(liquidity);
+        to_u128(U512::from(sqrt_price) + quotient)
+    }
+}
+
+struct SwapStep {
+    sqrt_price_next: u128,
+    amount_in: u64,
+    amount_out: u64,
+    fee_amount: u64,
+}
+
+/// Swaps as much of `amount_remaining` as fits between the current and target prices.
+fn compute_swap_step(
+    sqrt_price_current: u128,
+    sqrt_price_target: u128,
+    liquidity: u128,
+    amount_remaining: u64,
+    trade_fee_rate: u32,
+    zero_for_one: bool,
+) -> Option<SwapStep> {
+    let fee_rate = trade_fee_rate as u64;
+    let amount_remaining_less_fee = (U512::from(amount_remaining)
+        * U512::from(FEE_RATE_DENOMINATOR - fee_rate)
+        / U512::from(FEE_RATE_DENOMINATOR))
+    .low_u64();
+
+    let amount_in_to_target = if zero_for_one {
+        amount_0_delta(sqrt_price_target, sqrt_price_current, liquidity, true)?
+    } else {
+        amount_1_delta(sqrt_price_current, sqrt_price_target, liquidity, true)
+    };
+    let sqrt_price_next = if U512::from(amount_remaining_less_fee) >= amount_in_to_target {
+        sqrt_price_target
+    } else {
+        next_sqrt_price_from_input(
+            sqrt_price_current,
+            liquidity,
+            amount_remaining_less_fee,
+            zero_for_one,
+        )?
+    };
+    let reached_target = sqrt_price_next == sqrt_price_target;
+
+    let (amount_in, amount_out) = if zero_for_one {
+        (
+            amount_0_delta(sqrt_price_next, sqrt_price_current, liquidity, true)?,
+            amount_1_delta(sqrt_price_next, sqrt_price_current, liquidity, false),
+        )
+    } else {
+        (
+            amount_1_delta(sqrt_price_current, sqrt_price_next, liquidity, true),
+            amount_0_delta(sqrt_price_current, sqrt_price_next, liquidity, false)?,
+        )
+    };
+    let amount_in = u64::try_from(to_u128(amount_in)?).ok()?;
+    let amount_out = u64::try_from(to_u128(amount_out)?).ok()?;
+
+    let fee_amount = if reached_target {
+        to_u128(div_ceil(
+            U512::from(amount_in) * U512::from(fee_rate),
+            U512::from(FEE_RATE_DENOMINATOR - fee_rate),
+        ))? as u64
+    } else {
+        // Whatever is left over the input is the fee.
+        amount_remaining.checked_sub(amount_in)?
+    };
+
+    Some(SwapStep {
+        sqrt_price_next,
+        amount_in,
+        amount_out,
+        fee_amount,
+    })
+}
+
+/// Where the price of a pool stands, and the liquidity in range there.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PriceState {
+    /// Square root of the price of token 0 in token 1, as a Q64.64.
+    pub sqrt_price_x64: u128,
+    pub tick_current: i32,
+    pub liquidity: u128,
+}
+
+/// Outcome of a simulated swap.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SwapResult {
+    pub amount_out: u64,
+    /// Lowest and highest tick the price went through, to know which tick arrays it needs.
+    pub tick_range: (i32, i32),
+}
+
+/// Swaps exactly `amount_in`, going through the initialized `ticks` (sorted ascending) as the
+/// price moves, within `[lowest_tick, highest_tick)`: past it the ticks are not known.
+pub fn swap_base_input(
+    state: &PriceState,
+    ticks: &[Tick],
+    (lowest_tick, highest_tick): (i32, i32),
+    trade_fee_rate: u32,
+    zero_for_one: bool,
+    amount_in: u64,
+) -> anyhow::Result<SwapResult> {
+    let mut amount_remaining = amount_in;
+    let mut amount_out = 0u64;
+    let mut sqrt_price = state.sqrt_price_x64;
+    let mut tick_current = state.tick_current;
+    let mut liquidity = state.liquidity;
+    let mut tick_range = (tick_current, tick_current);
+
+    while amount_remaining > 0 {
+        let next = if zero_for_one {
+            ticks.iter().rev().find(|tick| tick.index <= tick_current)
+        } else {
+            ticks.iter().find(|tick| tick.index > tick_current)
+        };
+        let tick_next = match next {
+            Some(tick) => tick.index,
+            None if zero_for_one => lowest_tick.max(MIN_TICK),
+            None => highest_tick.min(MAX_TICK),
+        };
+        tick_range = (tick_range.0.min(tick_next), tick_range.1.max(tick_next));
+
+        let sqrt_price_target = sqrt_price_at_tick(tick_next)?;
+        let step = compute_swap_step(
+            sqrt_price,
+            sqrt_price_target,
+            liquidity,
+            amount_remaining,
+            trade_fee_rate,
+            zero_for_one,
+        )
+        .with_context(|| format!("Swap step overflowed at tick {}", tick_current))?;
+
+        amount_remaining = amount_remaining
+            .checked_sub(step.amount_in + step.fee_amount)
+            .with_context(|| "Swap step took more than the remaining amount")?;
+        amount_out = amount_out
+            .checked_add(step.amount_out)
+            .with_context(|| "Swap output overflowed")?;
+        sqrt_price = step.sqrt_price_next;
+
+        if sqrt_price != sqrt_price_target {
+            break;
+        }
+        let Some(tick) = next else {
+            if amount_remaining > 0 {
+                anyhow::bail!(
+                    "Swap of {} goes past the known ticks, {} left",
+                    amount_in,
+                    amount_remaining
+                );
+            }
+            break;
+        };
+        liquidity = if zero_for_one {
+            liquidity.checked_add_signed(-tick.liquidity_net)
+        } else {
+            liquidity.checked_add_signed(tick.liquidity_net)
+        }
+        .with_context(|| format!("Liquidity overflowed crossing tick {}", tick.index))?;
+        tick_current = if zero_for_one {
+            tick.index - 1
+        } else {
+            tick.index
+        };
+    }
+
+    Ok(SwapResult {
+        amount_out,
+        tick_range,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const Q64: u128 = 1 << 64;
+
+    fn pool(liquidity: u128, tick_current: i32) -> PriceState {
+        PriceState {
+            sqrt_price_x64: sqrt_price_at_tick(tick_current).unwrap(),
+            tick_current,
+            liquidity,
+        }
+    }
+
+    #[test]
+    fn sqrt_price_matches_the_tick_bounds() {
+        assert_eq!(sqrt_price_at_tick(0).unwrap(), Q64);
+        assert_eq!(sqrt_price_at_tick(MIN_TICK).unwrap(), 4295048016);
+        assert_eq!(
+            sqrt_price_at_tick(MAX_TICK).unwrap(),
+            79226673521066979257578248091
+        );
+        assert!(sqrt_price_at_tick(MAX_TICK + 1).is_err());
+
+        // 1.0001^(100/2) = 1.00501...
+        let price = sqrt_price_at_tick(100).unwrap() as f64 / Q64 as f64;
+        assert!((price - 1.0001f64.powf(50.0)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn swap_within_one_range_matches_constant_product() {
+        // Around price 1, liquidity L behaves like reserves of L on each side.
+        let liquidity = 1_000_000_000_000u128;
+        let state = pool(liquidity, 0);
+        let result = swap_base_input(&state, &[], (-10_000, 10_000), 0, true, 1_000_000).unwrap();
+
+        let expected = 1_000_000u128 * liquidity / (liquidity + 1_000_000);
+        assert!(expected as u64 - result.amount_out <= 1);
+    }
+
+    #[test]
+    fn swap_crosses_ticks_and_their_liquidity() {
+        let state = pool(1_000_000_000, 0);
+        // All liquidity ends at tick -10 going down: selling more than what sits in the range
+        // must fail rather than quote beyond.
+        let ticks = [Tick {
+            index: -10,
+            liquidity_net: 1_000_000_000,
+        }];
+
+        let small = swap_base_input(&state, &ticks, (-600, 600), 2_500, true, 10_000).unwrap();
+        assert!(small.amount_out > 0 && small.amount_out < 10_000);
+        assert_eq!(small.tick_range, (-10, 0));
+
+        assert!(swap_base_input(&state, &ticks, (-600, 600), 2_500, true, 10_000_000).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+//! Raydium CLMM: concentrated liquidity pools, where liquidity sits in tick ranges instead of
+//! along the whole curve.
+//!
+//! https://github.com/raydium-io/raydium-clmm
+
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+use crate::dex::{PoolReserves, SwapPool};
+
+use self::{
+    math::{swap_base_input, SwapResult},
+    state::{tick_array_bitmap_extension_address, ClmmPoolState, Tick, TickArray},
+};
+
+pub mod math;
+pub mod state;
+
+/// `sha256("global:swap_v2")`, the swap taking Token-2022 mints.
+const SWAP_V2_DISCRIMINATOR: [u8; 8] = [43, 4, 237, 11, 26, 201, 30, 98];
+const MEMO_PROGRAM_ID: Pubkey = solana_sdk::pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+pub const TOKEN_2022_PROGRAM_ID: Pubkey =
+    solana_sdk::pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+/// A CLMM pool with the tick arrays around its current price.
+#[derive(Clone, Debug)]
+pub struct ClmmPool {
+    pub program_id: Pubkey,
+    pub address: Pubkey,
+    pub state: ClmmPoolState,
+    pub trade_fee_rate: u32,
+    pub token_0_program: Pubkey,
+    pub token_1_program: Pubkey,
+    /// Existing tick arrays within `tick_range`, sorted by start index.
+    pub tick_arrays: Vec<TickArray>,
+    /// Ticks whose arrays were looked up: those missing have no liquidity.
+    pub tick_range: (i32, i32),
+}
+
+impl ClmmPool {
+    fn initialized_ticks(&self) -> Vec<Tick> {
+        let mut ticks = self
+            .tick_arrays
+            .iter()
+            .flat_map(|tick_array| tick_array.ticks.iter().copied())
+            .collect::<Vec<_>>();
+        ticks.sort_by_key(|tick| tick.index);
+
+        ticks
+    }
+
+    /// Simulates swapping exactly `amount_in` of `input_mint`.
+    pub fn simulate(&self, input_mint: &Pubkey, amount_in: u64) -> anyhow::Result<SwapResult> {
+        if !self.state.swap_enabled() {
+            anyhow::bail!("Swaps are disabled on pool {}", self.address);
+        }
+
+        swap_base_input(
+            &self.state.price_state(),
+            &self.initialized_ticks(),
+            self.tick_range,
+            self.trade_fee_rate,
+            *input_mint == self.state.token_mint_0,
+            amount_in,
+        )
+    }
+
+    /// Tick arrays the swap goes through, in the order it does. The program needs at least the
+    /// first existing one in the swap direction, even when the price does not leave it.
+    fn tick_arrays_for(&self, result: &SwapResult, zero_for_one: bool) -> Vec<Pubkey> {
+        let (lowest, highest) = result.tick_range;
+        let first = self.state.tick_array_start_index(lowest);
+        let last = self.state.tick_array_start_index(highest);
+        let mut tick_arrays = self
+            .tick_arrays
+            .iter()
+            .filter(|tick_array| (first..=last).contains(&tick_array.start_tick_index))
+            .map(|tick_array| tick_array.address)
+            .collect::<Vec<_>>();
+        if zero_for_one {
+            tick_arrays.reverse();
+        }
+
+        if tick_arrays.is_empty() {
+            let current = self.state.tick_array_start_index(self.state.tick_current);
+            let next = if zero_for_one {
+                self.tick_arrays
+                    .iter()
+                    .rev()
+                    .find(|tick_array| tick_array.start_tick_index <= current)
+            } else {
+                self.tick_arrays
+                    .iter()
+                    .find(|tick_array| tick_array.start_tick_index >= current)
+            };
+            tick_arrays.extend(next.map(|tick_array| tick_array.address));
+        }
+
+        tick_arrays
+    }
+}
+
+impl SwapPool for ClmmPool {
+    fn id(&self) -> Pubkey {
+        self.address
+    }
+
+    fn token_program(&self, mint: &Pubkey) -> Pubkey {
+        if *mint == self.state.token_mint_1 {
+            self.token_1_program
+        } else {
+            self.token_0_program
+        }
+    }
+
+    /// Prices from the pool state and its ticks: vault reserves mean nothing for a CLMM pool.
+    fn quote(
+        &self,
+        _reserves: &PoolReserves,
+        input_mint: &Pubkey,
+        amount_in: u64,
+    ) -> anyhow::Result<u64> {
+        Ok(self.simulate(input_mint, amount_in)?.amount_out)
+    }
+
+    fn swap_instruction(
+        &self,
+        owner: &Pubkey,
+        input_mint: &Pubkey,
+        input_account: &Pubkey,
+        output_account: &Pubkey,
+        amount_in: u64,
+        min_amount_out: u64,
+    ) -> anyhow::Result<Instruction> {
+        let zero_for_one = *input_mint == self.state.token_mint_0;
+        let result = self.simulate(input_mint, amount_in)?;
+        let tick_arrays = self.tick_arrays_for(&result, zero_for_one);
+        if tick_arrays.is_empty() {
+            return Err(anyhow::anyhow!(
+                "No initialized tick array on pool {}",
+                self.address
+            ));
+        }
+
+        let (input_vault, output_vault, output_mint) = if zero_for_one {
+            (
+                self.state.token_vault_0,
+                self.state.token_vault_1,
+                self.state.token_mint_1,
+            )
+        } else {
+            (
+                self.state.token_vault_1,
+                self.state.token_vault_0,
+                self.state.token_mint_0,
+            )
+        };
+
+        let mut accounts = vec![
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new_readonly(self.state.amm_config, false),
+            AccountMeta::new(self.address, false),
+            AccountMeta::new(*input_account, false),
+            AccountMeta::new(*output_account, false),
+            AccountMeta::new(input_vault, false),
+            AccountMeta::new(output_vault, false),
+            AccountMeta::new(self.state.observation, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(TOKEN_2022_PROGRAM_ID, false),
+            AccountMeta::new_readonly(MEMO_PROGRAM_ID, false),
+            AccountMeta::new_readonly(*input_mint, false),
+            AccountMeta::new_readonly(output_mint, false),
+            AccountMeta::new(
+                tick_array_bitmap_extension_address(&self.program_id, &self.address),
+                false,
+            ),
+        ];
+        accounts.extend(
+            tick_arrays
+                .into_iter()
+                .map(|tick_array| AccountMeta::new(tick_array, false)),
+        );
+
+        let mut data = SWAP_V2_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&amount_in.to_le_bytes());
+        data.extend_from_slice(&min_amount_out.to_le_bytes());
+        // No price limit: the minimum output bounds the swap.
+        data.extend_from_slice(&0u128.to_le_bytes());
+        data.push(true as u8);
+
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts,
+            data,
+        })
+    }
+}
+
+/// Token program of a mint, from the owner of its account.
+pub fn token_program_of(mint: &Pubkey, owner: &Pubkey) -> anyhow::Result<Pubkey> {
+    if *owner == spl_token::id() || *owner == TOKEN_2022_PROGRAM_ID {
+        Ok(*owner)
+    } else {
+        Err(anyhow::anyhow!(
+            "Mint {} is owned by {}, not a token program",
+            mint,
+            owner
+        ))
+    }
+}
@@ -0,0 +1,174 @@
+use anyhow::Context;
+use solana_sdk::pubkey::Pubkey;
+
+use super::math::PriceState;
+
+/// Anchor discriminators, the first 8 bytes of `sha256("account:<account>")`.
+pub(crate) const POOL_STATE_DISCRIMINATOR: [u8; 8] = [247, 237, 227, 245, 215, 195, 222, 70];
+const AMM_CONFIG_DISCRIMINATOR: [u8; 8] = [218, 244, 33, 104, 203, 203, 43, 111];
+const TICK_ARRAY_DISCRIMINATOR: [u8; 8] = [192, 155, 85, 205, 49, 249, 129, 42];
+
+/// `PoolState::token_mint_0` and `token_mint_1`.
+pub(crate) const POOL_STATE_MINT_OFFSETS: [usize; 2] = [73, 105];
+/// `AmmConfig::trade_fee_rate`, after the bump, `index`, `owner` and `protocol_fee_rate`.
+const AMM_CONFIG_TRADE_FEE_RATE_OFFSET: usize = 47;
+/// `PoolState::status` bit set when swaps are disabled.
+const POOL_STATUS_SWAP_DISABLED: u8 = 1 << 4;
+
+pub const TICK_ARRAY_SIZE: i32 = 60;
+const TICK_ARRAY_SEED: &[u8] = b"tick_array";
+const TICK_ARRAY_BITMAP_EXTENSION_SEED: &[u8] = b"pool_tick_array_bitmap_extension";
+/// `TickArrayState::ticks`, after the pool id and `start_tick_index`.
+const TICK_ARRAY_TICKS_OFFSET: usize = 8 + 32 + 4;
+const TICK_STATE_SIZE: usize = 168;
+
+/// The parts of the CLMM `PoolState` account needed to quote and swap.
+#[derive(Clone, Copy, Debug)]
+pub struct ClmmPoolState {
+    pub amm_config: Pubkey,
+    pub token_mint_0: Pubkey,
+    pub token_mint_1: Pubkey,
+    pub token_vault_0: Pubkey,
+    pub token_vault_1: Pubkey,
+    pub observation: Pubkey,
+    pub tick_spacing: u16,
+    /// Liquidity in range at the current price.
+    pub liquidity: u128,
+    /// Square root of the price of token 0 in token 1, as a Q64.64.
+    pub sqrt_price_x64: u128,
+    pub tick_current: i32,
+    pub status: u8,
+}
+
+impl ClmmPoolState {
+    pub fn decode(data: &[u8]) -> anyhow::Result<Self> {
+        if data.get(..8) != Some(&POOL_STATE_DISCRIMINATOR[..]) {
+            anyhow::bail!("Not a CLMM pool state account");
+        }
+
+        // Pubkeys start after the discriminator and the bump; `owner` comes second.
+        let pubkey = |index: usize| read_pubkey(data, 9 + index * 32);
+        Ok(Self {
+            amm_config: pubkey(0)?,
+            token_mint_0: pubkey(2)?,
+            token_mint_1: pubkey(3)?,
+            token_vault_0: pubkey(4)?,
+            token_vault_1: pubkey(5)?,
+            observation: pubkey(6)?,
+            tick_spacing: u16::from_le_bytes(read(data, 235)?),
+            liquidity: u128::from_le_bytes(read(data, 237)?),
+            sqrt_price_x64: u128::from_le_bytes(read(data, 253)?),
+            tick_current: i32::from_le_bytes(read(data, 269)?),
+            status: u8::from_le_bytes(read(data, 389)?),
+        })
+    }
+
+    pub fn price_state(&self) -> PriceState {
+        PriceState {
+            sqrt_price_x64: self.sqrt_price_x64,
+            tick_current: self.tick_current,
+            liquidity: self.liquidity,
+        }
+    }
+
+    pub fn swap_enabled(&self) -> bool {
+        self.status & POOL_STATUS_SWAP_DISABLED == 0
+    }
+
+    /// Ticks covered by each tick array.
+    pub fn ticks_per_array(&self) -> i32 {
+        TICK_ARRAY_SIZE * self.tick_spacing as i32
+    }
+
+    /// Start index of the tick array holding `tick`.
+    pub fn tick_array_start_index(&self, tick: i32) -> i32 {
+        tick.div_euclid(self.ticks_per_array()) * self.ticks_per_array()
+    }
+}
+
+/// `AmmConfig::trade_fee_rate`, in millionths.
+pub fn decode_trade_fee_rate(amm_config: &[u8]) -> anyhow::Result<u32> {
+    if amm_config.get(..8) != Some(&AMM_CONFIG_DISCRIMINATOR[..]) {
+        anyhow::bail!("Not a CLMM config account");
+    }
+
+    Ok(u32::from_le_bytes(read(
+        amm_config,
+        AMM_CONFIG_TRADE_FEE_RATE_OFFSET,
+    )?))
+}
+
+/// An initialized tick: crossing it adds `liquidity_net` going up and removes it going down.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Tick {
+    pub index: i32,
+    pub liquidity_net: i128,
+}
+
+/// The initialized ticks of a `TickArrayState` account.
+#[derive(Clone, Debug)]
+pub struct TickArray {
+    pub address: Pubkey,
+    pub start_tick_index: i32,
+    pub ticks: Vec<Tick>,
+}
+
+impl TickArray {
+    pub fn decode(address: Pubkey, data: &[u8]) -> anyhow::Result<Self> {
+        if data.get(..8) != Some(&TICK_ARRAY_DISCRIMINATOR[..]) {
+            anyhow::bail!("Not a CLMM tick array account");
+        }
+
+        let mut ticks = Vec::new();
+        for index in 0..TICK_ARRAY_SIZE as usize {
+            let offset = TICK_ARRAY_TICKS_OFFSET + index * TICK_STATE_SIZE;
+            let liquidity_gross = u128::from_le_bytes(read(data, offset + 20)?);
+            if liquidity_gross > 0 {
+                ticks.push(Tick {
+                    index: i32::from_le_bytes(read(data, offset)?),
+                    liquidity_net: i128::from_le_bytes(read(data, offset + 4)?),
+                });
+            }
+        }
+
+        Ok(Self {
+            address,
+            start_tick_index: i32::from_le_bytes(read(data, 40)?),
+            ticks,
+        })
+    }
+}
+
+pub fn tick_array_address(program_id: &Pubkey, pool: &Pubkey, start_tick_index: i32) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            TICK_ARRAY_SEED,
+            pool.as_ref(),
+            &start_tick_index.to_be_bytes(),
+        ],
+        program_id,
+    )
+    .0
+}
+
+/// Bitmap of the tick arrays too far from the current price for the pool's own bitmap. Swaps
+/// take it whether or not they go that far.
+pub fn tick_array_bitmap_extension_address(program_id: &Pubkey, pool: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[TICK_ARRAY_BITMAP_EXTENSION_SEED, pool.as_ref()],
+        program_id,
+    )
+    .0
+}
+
+fn read<const N: usize>(data: &[u8], offset: usize) -> anyhow::Result<[u8; N]> {
+    let bytes = data
+        .get(offset..offset + N)
+        .with_context(|| format!("Account is too short to read offset {}", offset))?;
+
+    Ok(bytes.try_into()?)
+}
+
+fn read_pubkey(data: &[u8], offset: usize) -> anyhow::Result<Pubkey> {
+    Ok(Pubkey::new_from_array(read(data, offset)?))
+}
@@ -0,0 +1,631 @@
+//! Raydium CPMM: the constant product AMM without an order book, which also takes Token-2022
+//! mints.
+//!
+//! https://github.com/raydium-io/raydium-cp-swap
+
+use std::str::FromStr;
+
+use anyhow::Context;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::Signature,
+};
+use solana_transaction_status_client_types::{
+    EncodedTransaction, UiInstruction, UiMessage, UiParsedInstruction,
+    UiPartiallyDecodedInstruction,
+};
+use spl_token_2022::{
+    extension::{transfer_fee::TransferFeeConfig, BaseStateWithExtensions, StateWithExtensions},
+    state::Mint,
+};
+
+use crate::dex::{PoolReserves, SwapPool};
+
+/// Fee rates are in millionths.
+pub const FEE_RATE_DENOMINATOR: u64 = 1_000_000;
+
+/// Anchor discriminators: the first 8 bytes of `sha256("global:<instruction>")` and
+/// `sha256("account:<account>")`.
+const SWAP_BASE_INPUT_DISCRIMINATOR: [u8; 8] = [143, 190, 90, 218, 196, 30, 51, 222];
+const SWAP_BASE_OUTPUT_DISCRIMINATOR: [u8; 8] = [55, 217, 98, 86, 163, 74, 180, 173];
+pub(crate) const POOL_STATE_DISCRIMINATOR: [u8; 8] = [247, 237, 227, 245, 215, 195, 222, 70];
+const AMM_CONFIG_DISCRIMINATOR: [u8; 8] = [218, 244, 33, 104, 203, 203, 43, 111];
+
+/// `PoolState::token_0_mint` and `token_1_mint`.
+pub(crate) const POOL_STATE_MINT_OFFSETS: [usize; 2] = [168, 200];
+pub(crate) const AUTH_SEED: &[u8] = b"vault_and_lp_mint_auth_seed";
+/// `AmmConfig::trade_fee_rate`, after the bump, `disable_create_pool` and `index`.
+const AMM_CONFIG_TRADE_FEE_RATE_OFFSET: usize = 12;
+/// `PoolState::status` bit set when swaps are disabled.
+const POOL_STATUS_SWAP_DISABLED: u8 = 1 << 2;
+/// SPL token (and Token-2022) account `amount`.
+const TOKEN_ACCOUNT_AMOUNT_OFFSET: usize = 64;
+
+/// Accounts of a CPMM pool, as listed by its `initialize` instruction.
+#[derive(Clone, Copy, Debug)]
+pub struct CpmmKeys {
+    pub pool: Pubkey,
+    pub amm_config: Pubkey,
+    pub authority: Pubkey,
+    pub token_0_mint: Pubkey,
+    pub token_1_mint: Pubkey,
+    pub token_0_vault: Pubkey,
+    pub token_1_vault: Pubkey,
+    pub token_0_program: Pubkey,
+    pub token_1_program: Pubkey,
+    pub lp_mint: Pubkey,
+    pub observation: Pubkey,
+}
+
+impl CpmmKeys {
+    /// Maps the accounts of a CPMM `initialize` instruction to the pool keys.
+    pub fn from_initialize(instruction: &UiPartiallyDecodedInstruction) -> anyhow::Result<Self> {
+        let account = |index: usize| -> anyhow::Result<Pubkey> {
+            let account = instruction
+                .accounts
+                .get(index)
+                .with_context(|| format!("Missing initialize account {}", index))?;
+
+            Pubkey::from_str(account)
+                .with_context(|| format!("Invalid initialize account {}", account))
+        };
+
+        Ok(Self {
+            amm_config: account(1)?,
+            authority: account(2)?,
+            pool: account(3)?,
+            token_0_mint: account(4)?,
+            token_1_mint: account(5)?,
+            lp_mint: account(6)?,
+            token_0_vault: account(10)?,
+            token_1_vault: account(11)?,
+            observation: account(13)?,
+            token_0_program: account(15)?,
+            token_1_program: account(16)?,
+        })
+    }
+
+    /// Reads the keys of an existing pool from its `PoolState` account.
+    pub fn from_pool_state(
+        program_id: &Pubkey,
+        pool: &Pubkey,
+        data: &[u8],
+    ) -> anyhow::Result<Self> {
+        if data.get(..8) != Some(&POOL_STATE_DISCRIMINATOR[..]) {
+            anyhow::bail!("Not a CPMM pool state account");
+        }
+        // The pubkeys follow the discriminator, the pool creator coming second.
+        let pubkey = |index: usize| -> anyhow::Result<Pubkey> {
+            let offset = 8 + index * 32;
+            let bytes = data
+                .get(offset..offset + 32)
+                .with_context(|| "Pool state account is too short")?;
+            Ok(Pubkey::new_from_array(bytes.try_into()?))
+        };
+
+        Ok(Self {
+            pool: *pool,
+            amm_config: pubkey(0)?,
+            authority: Pubkey::find_program_address(&[AUTH_SEED], program_id).0,
+            token_0_vault: pubkey(2)?,
+            token_1_vault: pubkey(3)?,
+            lp_mint: pubkey(4)?,
+            token_0_mint: pubkey(5)?,
+            token_1_mint: pubkey(6)?,
+            token_0_program: pubkey(7)?,
+            token_1_program: pubkey(8)?,
+            observation: pubkey(9)?,
+        })
+    }
+
+    /// Finds the top-level `initialize` sent to the CPMM program and maps its accounts.
+    pub fn find_in_transaction(
+        program_id: &Pubkey,
+        transaction: &EncodedTransaction,
+    ) -> anyhow::Result<Option<Self>> {
+        let program_id = program_id.to_string();
+
+        if let EncodedTransaction::Json(ui_transaction) = transaction
+            && let UiMessage::Parsed(ui_parsed_message) = &ui_transaction.message
+        {
+            for instruction in &ui_parsed_message.instructions {
+                if let UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(
+                    parsed_instruction,
+                )) = instruction
+                    && parsed_instruction.program_id == program_id
+                {
+                    return Self::from_initialize(parsed_instruction).map(Some);
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Whether the logs are those of a CPMM pool creation: a top-level call to the program whose
+/// first log is the `Initialize` instruction.
+pub fn is_pool_creation(program_id: &Pubkey, logs: &[String]) -> bool {
+    let invoke = format!("Program {} invoke [1]", program_id);
+
+    logs.windows(2)
+        .any(|logs| logs[0] == invoke && logs[1] == "Program log: Instruction: Initialize")
+}
+
+/// The parts of the `PoolState` account the sniper needs.
+#[derive(Clone, Copy, Debug)]
+pub struct CpmmPoolState {
+    pub status: u8,
+    pub protocol_fees_token_0: u64,
+    pub protocol_fees_token_1: u64,
+    pub fund_fees_token_0: u64,
+    pub fund_fees_token_1: u64,
+    /// Unix time swaps are allowed from.
+    pub open_time: u64,
+}
+
+impl CpmmPoolState {
+    /// Offset of `status`: after the discriminator and the 10 pubkeys, then `auth_bump`.
+    const STATUS_OFFSET: usize = 8 + 10 * 32 + 1;
+    /// Offset of `protocol_fees_token_0`, after the decimals and `lp_supply` that follow
+    /// `status`.
+    const FEES_OFFSET: usize = Self::STATUS_OFFSET + 4 + 8;
+
+    pub fn decode(data: &[u8]) -> anyhow::Result<Self> {
+        if data.get(..8) != Some(&POOL_STATE_DISCRIMINATOR[..]) {
+            anyhow::bail!("Not a CPMM pool state account");
+        }
+        let status = *data
+            .get(Self::STATUS_OFFSET)
+            .with_context(|| "Pool state account is too short")?;
+
+        Ok(Self {
+            status,
+            protocol_fees_token_0: read_u64(data, Self::FEES_OFFSET)?,
+            protocol_fees_token_1: read_u64(data, Self::FEES_OFFSET + 8)?,
+            fund_fees_token_0: read_u64(data, Self::FEES_OFFSET + 16)?,
+            fund_fees_token_1: read_u64(data, Self::FEES_OFFSET + 24)?,
+            open_time: read_u64(data, Self::FEES_OFFSET + 32)?,
+        })
+    }
+
+    pub fn swap_enabled(&self) -> bool {
+        self.status & POOL_STATUS_SWAP_DISABLED == 0
+    }
+
+    /// Reserves the curve trades against, given the vault balances: the fees owed to the
+    /// protocol and the fund sit in the vaults but are not part of them.
+    pub fn reserves(&self, token_0_vault: u64, token_1_vault: u64, slot: u64) -> PoolReserves {
+        PoolReserves {
+            coin_vault: token_0_vault,
+            pc_vault: token_1_vault,
+            need_take_pnl_coin: self.protocol_fees_token_0 + self.fund_fees_token_0,
+            need_take_pnl_pc: self.protocol_fees_token_1 + self.fund_fees_token_1,
+            slot,
+        }
+    }
+}
+
+/// `AmmConfig::trade_fee_rate`, the fee tier of the pools created with this config.
+pub fn decode_trade_fee_rate(amm_config: &[u8]) -> anyhow::Result<u64> {
+    if amm_config.get(..8) != Some(&AMM_CONFIG_DISCRIMINATOR[..]) {
+        anyhow::bail!("Not a CPMM config account");
+    }
+
+    read_u64(amm_config, AMM_CONFIG_TRADE_FEE_RATE_OFFSET)
+}
+
+/// Balance of an SPL token or Token-2022 account.
+pub fn decode_token_amount(token_account: &[u8]) -> anyhow::Result<u64> {
+    read_u64(token_account, TOKEN_ACCOUNT_AMOUNT_OFFSET)
+}
+
+/// Transfer fee config of an SPL token or Token-2022 mint, `None` when transfers are free.
+pub fn decode_transfer_fee(mint: &[u8]) -> anyhow::Result<Option<TransferFeeConfig>> {
+    let mint = StateWithExtensions::<Mint>::unpack(mint).context("Failed to decode mint")?;
+
+    Ok(mint.get_extension::<TransferFeeConfig>().ok().copied())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> anyhow::Result<u64> {
+    let bytes = data
+        .get(offset..offset + 8)
+        .with_context(|| format!("Account is too short to read offset {}", offset))?;
+
+    Ok(u64::from_le_bytes(bytes.try_into()?))
+}
+
+/// Output of swapping exactly `amount_in`, the trade fee being taken from the input.
+pub fn swap_base_input(
+    amount_in: u64,
+    input_reserve: u64,
+    output_reserve: u64,
+    trade_fee_rate: u64,
+) -> Option<u64> {
+    let amount_in = amount_in as u128;
+    let trade_fee = ceil_div(
+        amount_in.checked_mul(trade_fee_rate as u128)?,
+        FEE_RATE_DENOMINATOR as u128,
+    )?;
+    let amount_in_less_fees = amount_in.checked_sub(trade_fee)?;
+
+    let amount_out = amount_in_less_fees.checked_mul(output_reserve as u128)?
+        / (input_reserve as u128).checked_add(amount_in_less_fees)?;
+
+    u64::try_from(amount_out).ok()
+}
+
+/// Input needed to get exactly `amount_out`, trade fee included.
+#[allow(dead_code)]
+pub fn swap_base_output(
+    amount_out: u64,
+    input_reserve: u64,
+    output_reserve: u64,
+    trade_fee_rate: u64,
+) -> Option<u64> {
+    let amount_out = amount_out as u128;
+    let amount_in_less_fees = ceil_div(
+        amount_out.checked_mul(input_reserve as u128)?,
+        (output_reserve as u128).checked_sub(amount_out)?,
+    )?;
+    let amount_in = if trade_fee_rate == 0 {
+        amount_in_less_fees
+    } else {
+        ceil_div(
+            amount_in_less_fees.checked_mul(FEE_RATE_DENOMINATOR as u128)?,
+            (FEE_RATE_DENOMINATOR as u128).checked_sub(trade_fee_rate as u128)?,
+        )?
+    };
+
+    u64::try_from(amount_in).ok()
+}
+
+fn ceil_div(numerator: u128, denominator: u128) -> Option<u128> {
+    if denominator == 0 {
+        return None;
+    }
+
+    numerator
+        .checked_add(denominator - 1)
+        .map(|n| n / denominator)
+}
+
+/// A CPMM pool, as created.
+#[derive(Clone, Copy, Debug)]
+pub struct CpmmPool {
+    pub program_id: Pubkey,
+    pub keys: CpmmKeys,
+    pub trade_fee_rate: u64,
+    /// Token-2022 transfer fees of `token_0_mint` and `token_1_mint`.
+    pub transfer_fees: [Option<TransferFeeConfig>; 2],
+    /// Epoch when the pool was decoded, which picks the transfer fee in force.
+    pub epoch: u64,
+    pub initial_reserves: PoolReserves,
+    pub creation_signature: Signature,
+    pub slot: u64,
+}
+
+impl CpmmPool {
+    /// Transfer fee taken from `amount` of a mint with `config`, at the pool's epoch.
+    fn transfer_fee(&self, config: Option<TransferFeeConfig>, amount: u64) -> Option<u64> {
+        config.map_or(Some(0), |config| {
+            config.calculate_epoch_fee(self.epoch, amount)
+        })
+    }
+
+    /// Vaults and token programs on the input then output side of a swap from `input_mint`.
+    fn sides(&self, input_mint: &Pubkey) -> ([Pubkey; 3], [Pubkey; 3]) {
+        let token_0 = [
+            self.keys.token_0_mint,
+            self.keys.token_0_vault,
+            self.keys.token_0_program,
+        ];
+        let token_1 = [
+            self.keys.token_1_mint,
+            self.keys.token_1_vault,
+            self.keys.token_1_program,
+        ];
+
+        if *input_mint == self.keys.token_0_mint {
+            (token_0, token_1)
+        } else {
+            (token_1, token_0)
+        }
+    }
+
+    fn swap_accounts(
+        &self,
+        owner: &Pubkey,
+        input_mint: &Pubkey,
+        input_account: &Pubkey,
+        output_account: &Pubkey,
+    ) -> Vec<AccountMeta> {
+        let ([input_mint, input_vault, input_program], [output_mint, output_vault, output_program]) =
+            self.sides(input_mint);
+
+        vec![
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new_readonly(self.keys.authority, false),
+            AccountMeta::new_readonly(self.keys.amm_config, false),
+            AccountMeta::new(self.keys.pool, false),
+            AccountMeta::new(*input_account, false),
+            AccountMeta::new(*output_account, false),
+            AccountMeta::new(input_vault, false),
+            AccountMeta::new(output_vault, false),
+            AccountMeta::new_readonly(input_program, false),
+            AccountMeta::new_readonly(output_program, false),
+            AccountMeta::new_readonly(input_mint, false),
+            AccountMeta::new_readonly(output_mint, false),
+            AccountMeta::new(self.keys.observation, false),
+        ]
+    }
+
+    /// `swap_base_output` instruction: at most `max_amount_in` of `input_mint` for exactly
+    /// `amount_out`.
+    #[allow(dead_code)]
+    pub fn swap_base_output_instruction(
+        &self,
+        owner: &Pubkey,
+        input_mint: &Pubkey,
+        input_account: &Pubkey,
+        output_account: &Pubkey,
+        max_amount_in: u64,
+        amount_out: u64,
+    ) -> Instruction {
+        let mut data = SWAP_BASE_OUTPUT_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&max_amount_in.to_le_bytes());
+        data.extend_from_slice(&amount_out.to_le_bytes());
+
+        Instruction {
+            program_id: self.program_id,
+            accounts: self.swap_accounts(owner, input_mint, input_account, output_account),
+            data,
+        }
+    }
+}
+
+impl SwapPool for CpmmPool {
+    fn id(&self) -> Pubkey {
+        self.keys.pool
+    }
+
+    fn token_program(&self, mint: &Pubkey) -> Pubkey {
+        if *mint == self.keys.token_1_mint {
+            self.keys.token_1_program
+        } else {
+            self.keys.token_0_program
+        }
+    }
+
+    /// Like the program, takes the Token-2022 transfer fee of the input mint before the swap
+    /// and that of the output mint from what it returns.
+    fn quote(
+        &self,
+        reserves: &PoolReserves,
+        input_mint: &Pubkey,
+        amount_in: u64,
+    ) -> anyhow::Result<u64> {
+        let (token_1, token_0) = reserves.without_take_pnl()?;
+        let [fee_0, fee_1] = self.transfer_fees;
+        let ((input_reserve, input_fee), (output_reserve, output_fee)) =
+            if *input_mint == self.keys.token_0_mint {
+                ((token_0, fee_0), (token_1, fee_1))
+            } else {
+                ((token_1, fee_1), (token_0, fee_0))
+            };
+        let quote = || -> Option<u64> {
+            let amount_in = amount_in.checked_sub(self.transfer_fee(input_fee, amount_in)?)?;
+            let amount_out = swap_base_input(
+                amount_in,
+                input_reserve,
+                output_reserve,
+                self.trade_fee_rate,
+            )?;
+
+            amount_out.checked_sub(self.transfer_fee(output_fee, amount_out)?)
+        };
+
+        quote().with_context(|| format!("Failed to quote {} on pool {}", amount_in, self.keys.pool))
+    }
+
+    fn swap_instruction(
+        &self,
+        owner: &Pubkey,
+        input_mint: &Pubkey,
+        input_account: &Pubkey,
+        output_account: &Pubkey,
+        amount_in: u64,
+        min_amount_out: u64,
+    ) -> anyhow::Result<Instruction> {
+        let mut data = SWAP_BASE_INPUT_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&amount_in.to_le_bytes());
+        data.extend_from_slice(&min_amount_out.to_le_bytes());
+
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts: self.swap_accounts(owner, input_mint, input_account, output_account),
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::{hash::hash, program_option::COption, program_pack::Pack};
+    use spl_token_2022::extension::{
+        BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut,
+    };
+
+    use super::*;
+
+    fn discriminator(preimage: &str) -> [u8; 8] {
+        hash(preimage.as_bytes()).to_bytes()[..8]
+            .try_into()
+            .unwrap()
+    }
+
+    #[test]
+    fn discriminators_match_anchor() {
+        assert_eq!(
+            SWAP_BASE_INPUT_DISCRIMINATOR,
+            discriminator("global:swap_base_input")
+        );
+        assert_eq!(
+            SWAP_BASE_OUTPUT_DISCRIMINATOR,
+            discriminator("global:swap_base_output")
+        );
+        assert_eq!(POOL_STATE_DISCRIMINATOR, discriminator("account:PoolState"));
+        assert_eq!(AMM_CONFIG_DISCRIMINATOR, discriminator("account:AmmConfig"));
+    }
+
+    #[test]
+    fn swap_base_input_takes_the_fee_from_the_input() {
+        // 0.25% of 1_000_000 is 2_500, the 997_500 left against 10x deeper reserves.
+        assert_eq!(
+            swap_base_input(1_000_000, 10_000_000, 100_000_000, 2_500),
+            Some(997_500 * 100_000_000 / 10_997_500)
+        );
+        assert_eq!(
+            swap_base_input(1_000_000, 10_000_000, 100_000_000, 0),
+            Some(9_090_909)
+        );
+    }
+
+    #[test]
+    fn swap_base_output_needs_at_least_what_base_input_gives() {
+        for trade_fee_rate in [0, 2_500, 10_000, 40_000] {
+            let amount_out = 5_000_000;
+            let amount_in =
+                swap_base_output(amount_out, 10_000_000, 100_000_000, trade_fee_rate).unwrap();
+
+            assert!(
+                swap_base_input(amount_in, 10_000_000, 100_000_000, trade_fee_rate).unwrap()
+                    >= amount_out
+            );
+            assert!(
+                swap_base_input(amount_in - 1, 10_000_000, 100_000_000, trade_fee_rate).unwrap()
+                    < amount_out
+            );
+        }
+    }
+
+    #[test]
+    fn detects_top_level_initialize_only() {
+        let program_id = Pubkey::new_unique();
+        let logs = |invoke: &str| {
+            vec![
+                format!("Program {} invoke {}", program_id, invoke),
+                "Program log: Instruction: Initialize".to_string(),
+            ]
+        };
+
+        assert!(is_pool_creation(&program_id, &logs("[1]")));
+        assert!(!is_pool_creation(&program_id, &logs("[2]")));
+        assert!(!is_pool_creation(&Pubkey::new_unique(), &logs("[1]")));
+    }
+
+    /// A Token-2022 mint whose fee goes from nothing to `basis_points` at `epoch`.
+    fn mint_with_transfer_fee(epoch: u64, basis_points: u16) -> Vec<u8> {
+        let len =
+            ExtensionType::try_calculate_account_len::<Mint>(&[ExtensionType::TransferFeeConfig])
+                .unwrap();
+        let mut data = vec![0; len];
+        let mut mint = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        let config = mint.init_extension::<TransferFeeConfig>(true).unwrap();
+        config.newer_transfer_fee.epoch = epoch.into();
+        config.newer_transfer_fee.maximum_fee = u64::MAX.into();
+        config.newer_transfer_fee.transfer_fee_basis_points = basis_points.into();
+        mint.base = Mint {
+            mint_authority: COption::None,
+            supply: 0,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        };
+        mint.pack_base();
+        mint.init_account_type().unwrap();
+
+        data
+    }
+
+    fn pool(transfer_fees: [Option<TransferFeeConfig>; 2], epoch: u64) -> CpmmPool {
+        let reserves = PoolReserves {
+            coin_vault: 10_000_000,
+            pc_vault: 100_000_000,
+            need_take_pnl_coin: 0,
+            need_take_pnl_pc: 0,
+            slot: 0,
+        };
+
+        CpmmPool {
+            program_id: Pubkey::new_unique(),
+            keys: CpmmKeys {
+                pool: Pubkey::new_unique(),
+                amm_config: Pubkey::new_unique(),
+                authority: Pubkey::new_unique(),
+                token_0_mint: Pubkey::new_unique(),
+                token_1_mint: Pubkey::new_unique(),
+                token_0_vault: Pubkey::new_unique(),
+                token_1_vault: Pubkey::new_unique(),
+                token_0_program: spl_token_2022::id(),
+                token_1_program: spl_token::id(),
+                lp_mint: Pubkey::new_unique(),
+                observation: Pubkey::new_unique(),
+            },
+            trade_fee_rate: 2_500,
+            transfer_fees,
+            epoch,
+            initial_reserves: reserves,
+            creation_signature: Signature::default(),
+            slot: 0,
+        }
+    }
+
+    #[test]
+    fn decodes_transfer_fees_of_token_2022_mints_only() {
+        let config = decode_transfer_fee(&mint_with_transfer_fee(5, 100))
+            .unwrap()
+            .unwrap();
+        assert_eq!(config.calculate_epoch_fee(4, 1_000_000), Some(0));
+        assert_eq!(config.calculate_epoch_fee(5, 1_000_000), Some(10_000));
+
+        let mut legacy = vec![0; Mint::LEN];
+        Mint {
+            is_initialized: true,
+            ..Mint::default()
+        }
+        .pack_into_slice(&mut legacy);
+        assert_eq!(decode_transfer_fee(&legacy).unwrap(), None);
+    }
+
+    #[test]
+    fn quote_takes_transfer_fees_in_force_at_the_epoch() {
+        let config = decode_transfer_fee(&mint_with_transfer_fee(5, 100)).unwrap();
+        let reserves = pool([None; 2], 0).initial_reserves;
+
+        // 1% of the 1_000_000 sent is withheld before reaching the vault.
+        let pool = pool([config, None], 5);
+        assert_eq!(
+            pool.quote(&reserves, &pool.keys.token_0_mint, 1_000_000)
+                .unwrap(),
+            swap_base_input(990_000, 10_000_000, 100_000_000, 2_500).unwrap()
+        );
+
+        // And 1% of what the vault sends back.
+        let amount_out = swap_base_input(1_000_000, 100_000_000, 10_000_000, 2_500).unwrap();
+        assert_eq!(
+            pool.quote(&reserves, &pool.keys.token_1_mint, 1_000_000)
+                .unwrap(),
+            amount_out - amount_out.div_ceil(100)
+        );
+
+        // The fee is only scheduled before epoch 5.
+        let pool = CpmmPool { epoch: 4, ..pool };
+        assert_eq!(
+            pool.quote(&reserves, &pool.keys.token_0_mint, 1_000_000)
+                .unwrap(),
+            swap_base_input(1_000_000, 10_000_000, 100_000_000, 2_500).unwrap()
+        );
+    }
+}
@@ -0,0 +1,436 @@
+//! Raydium AMM V4 and CPMM as [`Dex`] venues.
+
+use std::str::FromStr;
+
+use anyhow::Context;
+use solana_sdk::{clock::Clock, pubkey::Pubkey, signature::Signature, sysvar};
+use solana_transaction_status_client_types::{
+    EncodedTransaction, UiInstruction, UiMessage, UiParsedInstruction,
+    UiPartiallyDecodedInstruction, UiTransactionTokenBalance,
+};
+
+use crate::{
+    api::solana_rpc::Transaction,
+    dex::{Dex, PoolReserves},
+    storage::sqlite::now,
+};
+
+use super::{
+    cpmm::{
+        self, decode_token_amount, decode_trade_fee_rate, decode_transfer_fee, CpmmKeys, CpmmPool,
+        CpmmPoolState,
+    },
+    event_processors::EventProcessor,
+    models::{AmmKeys, Pool},
+    pool::AmmV4Pool,
+};
+
+/// The legacy AMM V4, whose pools trade against an OpenBook market.
+///
+/// Snipes take the market keys from the market cache rather than from `decode_pool`: the
+/// market watcher fills it before the pool exists.
+#[derive(Clone, Copy, Debug)]
+pub struct RaydiumV4 {
+    pub program_id: Pubkey,
+}
+
+impl RaydiumV4 {
+    /// Finds the top-level instruction sent to the AMM program and maps its accounts.
+    fn find_initialize2_accounts(
+        &self,
+        transaction: &EncodedTransaction,
+    ) -> anyhow::Result<Option<AmmKeys>> {
+        let program_id = self.program_id.to_string();
+
+        if let EncodedTransaction::Json(ui_transaction) = transaction
+            && let UiMessage::Parsed(ui_parsed_message) = &ui_transaction.message
+        {
+            for instruction in &ui_parsed_message.instructions {
+                if let UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(
+                    parsed_instruction,
+                )) = instruction
+                    && parsed_instruction.program_id == program_id
+                {
+                    return Self::parse_initialize2_accounts(parsed_instruction).map(Some);
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Maps the accounts of a Raydium `initialize2` instruction to the pool keys.
+    fn parse_initialize2_accounts(
+        instruction: &UiPartiallyDecodedInstruction,
+    ) -> anyhow::Result<AmmKeys> {
+        let account = |index: usize| -> anyhow::Result<Pubkey> {
+            let account = instruction
+                .accounts
+                .get(index)
+                .with_context(|| format!("Missing initialize2 account {}", index))?;
+
+            Pubkey::from_str(account)
+                .with_context(|| format!("Invalid initialize2 account {}", account))
+        };
+
+        Ok(AmmKeys {
+            amm_pool: account(4)?,
+            amm_authority: account(5)?,
+            amm_open_order: account(6)?,
+            amm_lp_mint: account(7)?,
+            amm_coin_mint: account(8)?,
+            amm_pc_mint: account(9)?,
+            amm_coin_vault: account(10)?,
+            amm_pc_vault: account(11)?,
+            amm_target: account(13)?,
+            market_program: account(15)?,
+            market: account(16)?,
+            //fees: None,
+            //state_data: None,
+            nonce: 0,
+        })
+    }
+}
+
+impl Dex for RaydiumV4 {
+    /// The pool keys and its reserves right after the creation.
+    type Creation = Pool;
+    type Pool = AmmV4Pool;
+
+    fn name(&self) -> &'static str {
+        "raydium_v4"
+    }
+
+    fn is_pool_creation(&self, logs: &[String]) -> bool {
+        logs.iter()
+            .any(|log| log.to_lowercase().contains("initialize2"))
+    }
+
+    /// Rebuilds the pool without further RPC calls: the initial reserves are the vault
+    /// balances right after the transaction.
+    ///
+    /// Returns `None` when the transaction failed or is not a Raydium `initialize2`.
+    fn decode_creation(&self, transaction: &Transaction) -> anyhow::Result<Option<Pool>> {
+        let Some(metadata) = &transaction.metadata else {
+            return Ok(None);
+        };
+        let logs = Option::<Vec<String>>::from(metadata.log_messages.clone()).unwrap_or_default();
+        if metadata.err.is_some() || !self.is_pool_creation(&logs) {
+            return Ok(None);
+        }
+
+        let Some(amm_keys) = self.find_initialize2_accounts(&transaction.transaction)? else {
+            return Ok(None);
+        };
+
+        let account_keys = match &transaction.transaction {
+            EncodedTransaction::Json(ui_transaction) => match &ui_transaction.message {
+                UiMessage::Parsed(message) => message
+                    .account_keys
+                    .iter()
+                    .map(|account| account.pubkey.clone())
+                    .collect::<Vec<_>>(),
+                UiMessage::Raw(message) => message.account_keys.clone(),
+            },
+            _ => Vec::new(),
+        };
+        let post_token_balances =
+            Option::<Vec<UiTransactionTokenBalance>>::from(metadata.post_token_balances.clone())
+                .unwrap_or_default();
+        let post_balance = |vault: &Pubkey| -> anyhow::Result<u64> {
+            let vault = vault.to_string();
+            let balance = account_keys
+                .iter()
+                .position(|account| *account == vault)
+                .and_then(|index| {
+                    post_token_balances
+                        .iter()
+                        .find(|balance| balance.account_index as usize == index)
+                })
+                .with_context(|| format!("Missing post balance of vault {}", vault))?;
+
+            balance
+                .ui_token_amount
+                .amount
+                .parse()
+                .with_context(|| format!("Failed to parse post balance of vault {}", vault))
+        };
+
+        Ok(Some(Pool {
+            initial_coin_balance: post_balance(&amm_keys.amm_coin_vault)?,
+            initial_pc_balance: post_balance(&amm_keys.amm_pc_vault)?,
+            amm: amm_keys,
+            creation_signature: transaction.signature,
+            slot: transaction.slot,
+        }))
+    }
+
+    fn pool_id(&self, creation: &Pool) -> Pubkey {
+        creation.amm.amm_pool
+    }
+
+    fn mints(&self, creation: &Pool) -> [Pubkey; 2] {
+        [creation.amm.amm_coin_mint, creation.amm.amm_pc_mint]
+    }
+
+    /// Reserves come from the creation: only the market is left to decode.
+    fn pool_accounts(&self, _creation: &Pool) -> Vec<Pubkey> {
+        Vec::new()
+    }
+
+    fn immutable_accounts(&self, creation: &Pool) -> Vec<Pubkey> {
+        vec![creation.amm.market]
+    }
+
+    fn decode_pool(
+        &self,
+        creation: &Pool,
+        accounts: &[Option<Vec<u8>>],
+    ) -> anyhow::Result<(AmmV4Pool, PoolReserves)> {
+        let market = accounts
+            .first()
+            .and_then(Option::as_deref)
+            .with_context(|| format!("Missing market {}", creation.amm.market))?;
+        let market_keys = EventProcessor::decode_market_keys(
+            &creation.amm.market,
+            &creation.amm.market_program,
+            market,
+        )?;
+
+        Ok((
+            AmmV4Pool {
+                program_id: self.program_id,
+                amm: creation.amm,
+                market_keys,
+            },
+            creation.initial_reserves(),
+        ))
+    }
+}
+
+/// The constant product AMM without an order book.
+#[derive(Clone, Copy, Debug)]
+pub struct RaydiumCpmm {
+    pub program_id: Pubkey,
+}
+
+/// A CPMM pool as read from its `initialize` transaction.
+#[derive(Clone, Copy, Debug)]
+pub struct CpmmCreation {
+    pub keys: CpmmKeys,
+    pub signature: Signature,
+    pub slot: u64,
+}
+
+impl Dex for RaydiumCpmm {
+    type Creation = CpmmCreation;
+    type Pool = CpmmPool;
+
+    fn name(&self) -> &'static str {
+        "raydium_cpmm"
+    }
+
+    fn is_pool_creation(&self, logs: &[String]) -> bool {
+        cpmm::is_pool_creation(&self.program_id, logs)
+    }
+
+    fn decode_creation(&self, transaction: &Transaction) -> anyhow::Result<Option<CpmmCreation>> {
+        if transaction
+            .metadata
+            .as_ref()
+            .is_some_and(|metadata| metadata.err.is_some())
+        {
+            return Ok(None);
+        }
+
+        Ok(
+            CpmmKeys::find_in_transaction(&self.program_id, &transaction.transaction)?.map(
+                |keys| CpmmCreation {
+                    keys,
+                    signature: transaction.signature,
+                    slot: transaction.slot,
+                },
+            ),
+        )
+    }
+
+    fn pool_id(&self, creation: &CpmmCreation) -> Pubkey {
+        creation.keys.pool
+    }
+
+    fn mints(&self, creation: &CpmmCreation) -> [Pubkey; 2] {
+        [creation.keys.token_0_mint, creation.keys.token_1_mint]
+    }
+
+    /// The clock gives the epoch that picks the transfer fees of the mints.
+    fn pool_accounts(&self, creation: &CpmmCreation) -> Vec<Pubkey> {
+        vec![
+            creation.keys.pool,
+            creation.keys.token_0_vault,
+            creation.keys.token_1_vault,
+            creation.keys.token_0_mint,
+            creation.keys.token_1_mint,
+            sysvar::clock::id(),
+        ]
+    }
+
+    /// The fee tier only depends on the config, shared by many pools.
+    fn immutable_accounts(&self, creation: &CpmmCreation) -> Vec<Pubkey> {
+        vec![creation.keys.amm_config]
+    }
+
+    fn decode_pool(
+        &self,
+        creation: &CpmmCreation,
+        accounts: &[Option<Vec<u8>>],
+    ) -> anyhow::Result<(CpmmPool, PoolReserves)> {
+        let account = |index: usize| -> anyhow::Result<&[u8]> {
+            accounts
+                .get(index)
+                .and_then(Option::as_deref)
+                .with_context(|| format!("Missing CPMM pool account {}", index))
+        };
+        let pool = creation.keys.pool;
+
+        let state = CpmmPoolState::decode(account(0)?)?;
+        if !state.swap_enabled() {
+            return Err(anyhow::anyhow!("Swaps are disabled on pool {}", pool));
+        }
+        if state.open_time > now() as u64 {
+            return Err(anyhow::anyhow!(
+                "Pool {} only opens at {}",
+                pool,
+                state.open_time
+            ));
+        }
+
+        let initial_reserves = state.reserves(
+            decode_token_amount(account(1)?)?,
+            decode_token_amount(account(2)?)?,
+            creation.slot,
+        );
+        let clock: Clock = bincode::deserialize(account(5)?).context("Failed to decode clock")?;
+        let pool = CpmmPool {
+            program_id: self.program_id,
+            keys: creation.keys,
+            trade_fee_rate: decode_trade_fee_rate(account(6)?)?,
+            transfer_fees: [
+                decode_transfer_fee(account(3)?)?,
+                decode_transfer_fee(account(4)?)?,
+            ],
+            epoch: clock.epoch,
+            initial_reserves,
+            creation_signature: creation.signature,
+            slot: creation.slot,
+        };
+
+        Ok((pool, initial_reserves))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn token_balance(account_index: usize, mint: &Pubkey, amount: u64) -> serde_json::Value {
+        json!({
+            "accountIndex": account_index,
+            "mint": mint.to_string(),
+            "uiTokenAmount": {
+                "uiAmount": null,
+                "decimals": 9,
+                "amount": amount.to_string(),
+                "uiAmountString": "0",
+            },
+        })
+    }
+
+    /// An `initialize2` with its 21 accounts, of which the message only lists the vaults.
+    fn creation(program_id: &Pubkey, accounts: &[Pubkey], err: Option<&str>) -> Transaction {
+        let (coin_vault, pc_vault) = (accounts[10], accounts[11]);
+        let account_key = |pubkey: &Pubkey| json!({ "pubkey": pubkey.to_string(), "writable": true, "signer": false });
+
+        Transaction {
+            slot: 7,
+            block_time: None,
+            transaction: serde_json::from_value(json!({
+                "signatures": [],
+                "message": {
+                    "accountKeys": [account_key(&pc_vault), account_key(&coin_vault)],
+                    "recentBlockhash": Pubkey::default().to_string(),
+                    "instructions": [{
+                        "programId": program_id.to_string(),
+                        "accounts": accounts.iter().map(Pubkey::to_string).collect::<Vec<_>>(),
+                        "data": "",
+                        "stackHeight": null,
+                    }],
+                },
+            }))
+            .unwrap(),
+            metadata: Some(
+                serde_json::from_value(json!({
+                    "err": err,
+                    "status": { "Ok": null },
+                    "fee": 5000,
+                    "preBalances": [],
+                    "postBalances": [],
+                    "logMessages": ["Program log: initialize2: InitializeInstruction2"],
+                    "postTokenBalances": [
+                        token_balance(0, &accounts[9], 2_000_000),
+                        token_balance(1, &accounts[8], 500_000),
+                    ],
+                }))
+                .unwrap(),
+            ),
+            version: None,
+            signature: Signature::default(),
+        }
+    }
+
+    #[test]
+    fn decodes_initialize2_accounts_and_vault_balances() {
+        let dex = RaydiumV4 {
+            program_id: Pubkey::new_unique(),
+        };
+        let accounts = (0..21).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+
+        let pool = dex
+            .decode_creation(&creation(&dex.program_id, &accounts, None))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(pool.amm.amm_pool, accounts[4]);
+        assert_eq!(pool.amm.amm_authority, accounts[5]);
+        assert_eq!(pool.amm.amm_open_order, accounts[6]);
+        assert_eq!(pool.amm.amm_lp_mint, accounts[7]);
+        assert_eq!(pool.amm.amm_coin_mint, accounts[8]);
+        assert_eq!(pool.amm.amm_pc_mint, accounts[9]);
+        assert_eq!(pool.amm.amm_coin_vault, accounts[10]);
+        assert_eq!(pool.amm.amm_pc_vault, accounts[11]);
+        assert_eq!(pool.amm.amm_target, accounts[13]);
+        assert_eq!(pool.amm.market_program, accounts[15]);
+        assert_eq!(pool.amm.market, accounts[16]);
+        assert_eq!(pool.initial_coin_balance, 500_000);
+        assert_eq!(pool.initial_pc_balance, 2_000_000);
+        assert_eq!(pool.slot, 7);
+    }
+
+    #[test]
+    fn skips_failed_and_foreign_creations() {
+        let dex = RaydiumV4 {
+            program_id: Pubkey::new_unique(),
+        };
+        let accounts = (0..21).map(|_| Pubkey::new_unique()).collect::<Vec<_>>();
+
+        let failed = creation(&dex.program_id, &accounts, Some("AccountInUse"));
+        assert!(dex.decode_creation(&failed).unwrap().is_none());
+
+        let foreign = creation(&Pubkey::new_unique(), &accounts, None);
+        assert!(dex.decode_creation(&foreign).unwrap().is_none());
+
+        let truncated = creation(&dex.program_id, &accounts[..16], None);
+        assert!(dex.decode_creation(&truncated).is_err());
+    }
+}
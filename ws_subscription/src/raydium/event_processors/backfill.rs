@@ -0,0 +1,131 @@
+use std::{str::FromStr, sync::Arc};
+
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use tracing::{info, instrument, warn};
+
+use crate::storage::{models::SwapRecord, sqlite::now};
+
+use super::{new_swap::RaySwapLog, EventProcessor};
+
+/// Maximum page size accepted by `getSignaturesForAddress`.
+pub(crate) const SIGNATURES_PAGE_LIMIT: usize = 1000;
+
+impl EventProcessor {
+    /// Resubscribes to every pool we still care about after a restart: pools with an open
+    /// position and pools flagged for watching within `storage.watch_retention_secs`.
+    ///
+    /// Each subscription backfills the swaps missed while we were down before streaming.
+    pub async fn resume_subscriptions(self: &Arc<Self>) -> anyhow::Result<()> {
+        let tracked_since = now() - self.config.storage.watch_retention_secs as i64;
+        let pools = self.store.pools_to_resume(tracked_since)?;
+        info!(pools = pools.len(), "Resuming pool subscriptions");
+
+        let amm_pools = {
+            let mut tracked = self.pools.lock().await;
+            pools
+                .into_iter()
+                .map(|pool| {
+                    let amm_pool = pool.amm.amm_pool;
+                    tracked.entry(amm_pool).or_insert(pool);
+                    amm_pool
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for amm_pool in amm_pools {
+            if let Err(e) = self.subscribe_to_new_pool(amm_pool).await {
+                warn!(pool = %amm_pool, "Failed to resume pool: {:#}", e);
+            }
+        }
+        let subscriptions = self.subscriptions.len().await;
+        info!(subscriptions, "Pool subscriptions running");
+
+        Ok(())
+    }
+
+    /// Records every swap on the pool since the last one we stored (or since its creation), by
+    /// paging `getSignaturesForAddress` back to that slot.
+    ///
+    /// Swaps already recorded are ignored by the store, so overlapping with a live subscription
+    /// is harmless.
+    #[instrument(skip(self), fields(pool = %amm_pool))]
+    pub(crate) async fn backfill_pool_swaps(&self, amm_pool: &Pubkey) -> anyhow::Result<usize> {
+        let from_slot = match self.store.last_swap_slot(amm_pool)? {
+            Some(slot) => slot,
+            None => match self.pools.lock().await.get(amm_pool) {
+                Some(pool) => pool.slot,
+                None => return Ok(0),
+            },
+        };
+
+        let mut signatures = Vec::new();
+        let mut before = None;
+        loop {
+            let page = self
+                .solana_api
+                .get_signatures_for_address(amm_pool, before, SIGNATURES_PAGE_LIMIT)
+                .await?;
+            let reached_start = page.len() < SIGNATURES_PAGE_LIMIT
+                || page.last().is_some_and(|status| status.slot < from_slot);
+            before = match page.last() {
+                Some(status) => Some(Signature::from_str(&status.signature)?),
+                None => None,
+            };
+
+            signatures.extend(
+                page.into_iter()
+                    .filter(|status| status.slot >= from_slot && status.err.is_none()),
+            );
+
+            if reached_start || before.is_none() {
+                break;
+            }
+        }
+
+        let mut backfilled = 0;
+        for status in signatures.iter().rev() {
+            let transaction = self.solana_api.get_transaction(&status.signature).await?;
+            let logs = transaction
+                .metadata
+                .and_then(|metadata| Option::<Vec<String>>::from(metadata.log_messages))
+                .unwrap_or_default();
+
+            if let Some(swap) = RaySwapLog::from_logs(&logs) {
+                self.record_swap(
+                    *amm_pool,
+                    transaction.signature,
+                    transaction.slot,
+                    swap,
+                    transaction.block_time.unwrap_or_else(now),
+                )?;
+                backfilled += 1;
+            }
+        }
+
+        info!(backfilled, from_slot, "Backfilled missed swaps");
+
+        Ok(backfilled)
+    }
+
+    pub(crate) fn record_swap(
+        &self,
+        amm_pool: Pubkey,
+        signature: Signature,
+        slot: u64,
+        swap: RaySwapLog,
+        observed_at: i64,
+    ) -> anyhow::Result<()> {
+        self.store.save_swap(&SwapRecord {
+            signature,
+            amm_pool,
+            slot,
+            base_in: swap.base_in,
+            direction: swap.direction,
+            amount_in: swap.amount_in,
+            amount_out: swap.amount_out,
+            pool_coin: swap.pool_coin,
+            pool_pc: swap.pool_pc,
+            observed_at,
+        })
+    }
+}
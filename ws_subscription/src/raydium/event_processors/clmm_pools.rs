@@ -0,0 +1,104 @@
+use anyhow::Context;
+use solana_sdk::pubkey::Pubkey;
+use tracing::debug;
+
+use crate::{
+    dex::PoolReserves,
+    raydium::{
+        clmm::{
+            state::{decode_trade_fee_rate, tick_array_address, ClmmPoolState, TickArray},
+            token_program_of, ClmmPool,
+        },
+        cpmm::decode_token_amount,
+    },
+};
+
+use super::EventProcessor;
+
+/// Tick arrays fetched on each side of the current one. Swaps going further are refused rather
+/// than quoted against ticks we have not seen.
+const TICK_ARRAYS_AROUND_CURRENT: i32 = 5;
+
+impl EventProcessor {
+    /// Decodes the pool state from its account `data`, then fetches its config, mints, vaults
+    /// and the tick arrays around the current price in a single request.
+    ///
+    /// The vault balances come along as reserves, for the liquidity filter only.
+    pub async fn get_clmm_pool(
+        &self,
+        pool: &Pubkey,
+        data: &[u8],
+    ) -> anyhow::Result<(ClmmPool, PoolReserves)> {
+        let program_id = self.config.programs.raydium_clmm;
+        let state = ClmmPoolState::decode(data)
+            .with_context(|| format!("Failed to decode CLMM pool {}", pool))?;
+        debug!(?state, "CLMM pool state");
+
+        let ticks_per_array = state.ticks_per_array();
+        let current = state.tick_array_start_index(state.tick_current);
+        let starts = (-TICK_ARRAYS_AROUND_CURRENT..=TICK_ARRAYS_AROUND_CURRENT)
+            .map(|offset| current + offset * ticks_per_array)
+            .collect::<Vec<_>>();
+
+        let mut accounts = vec![
+            state.amm_config,
+            state.token_mint_0,
+            state.token_mint_1,
+            state.token_vault_0,
+            state.token_vault_1,
+        ];
+        let header = accounts.len();
+        accounts.extend(
+            starts
+                .iter()
+                .map(|start| tick_array_address(&program_id, pool, *start)),
+        );
+        let fetched = self.solana_api.get_multiple_accounts(&accounts).await?;
+        let account = |index: usize| {
+            fetched
+                .get(index)
+                .and_then(Option::as_ref)
+                .with_context(|| format!("Missing CLMM pool account {}", accounts[index]))
+        };
+
+        let trade_fee_rate = decode_trade_fee_rate(&account(0)?.data)?;
+        let token_0_program = token_program_of(&state.token_mint_0, &account(1)?.owner)?;
+        let token_1_program = token_program_of(&state.token_mint_1, &account(2)?.owner)?;
+        let reserves = PoolReserves {
+            coin_vault: decode_token_amount(&account(3)?.data)?,
+            pc_vault: decode_token_amount(&account(4)?.data)?,
+            ..Default::default()
+        };
+
+        // Arrays that do not exist hold no liquidity: they are left out but stay in range.
+        let tick_arrays = accounts[header..]
+            .iter()
+            .zip(&fetched[header..])
+            .filter_map(|(address, account)| {
+                account
+                    .as_ref()
+                    .map(|account| TickArray::decode(*address, &account.data))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let tick_range = (starts[0], starts[starts.len() - 1] + ticks_per_array - 1);
+        debug!(
+            tick_arrays = tick_arrays.len(),
+            ?tick_range,
+            "CLMM tick arrays fetched"
+        );
+
+        Ok((
+            ClmmPool {
+                program_id,
+                address: *pool,
+                state,
+                trade_fee_rate,
+                token_0_program,
+                token_1_program,
+                tick_arrays,
+                tick_range,
+            },
+            reserves,
+        ))
+    }
+}
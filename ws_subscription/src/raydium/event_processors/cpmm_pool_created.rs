@@ -0,0 +1,52 @@
+use metrics::counter;
+use solana_sdk::pubkey::Pubkey;
+use tracing::{info, instrument};
+
+use crate::{dex::Dex, telemetry, wallets::Wallet};
+
+use super::{pool_created::PoolRejected, timings::TradeTimings, EventProcessor};
+
+impl EventProcessor {
+    /// Buys the target on a new CPMM pool with every wallet.
+    ///
+    /// The pool is not tracked afterwards: swap logs and reserve streams only decode AMM V4
+    /// pools for now.
+    #[instrument(skip(self, wallets, simulate_only, timings))]
+    pub async fn buy_new_cpmm_pool(
+        &self,
+        wallets: &[Wallet],
+        target: Pubkey,
+        signature: &str,
+        simulate_only: bool,
+        mut timings: TradeTimings,
+    ) -> anyhow::Result<()> {
+        let (creation, pool, initial_reserves) = self
+            .decode_new_pool(&self.raydium_cpmm, signature, &mut timings)
+            .await?;
+
+        info!(
+            pool = %pool.keys.pool,
+            token_0_mint = %pool.keys.token_0_mint,
+            token_1_mint = %pool.keys.token_1_mint,
+            initial_token_0_balance = initial_reserves.coin_vault,
+            initial_token_1_balance = initial_reserves.pc_vault,
+            trade_fee_rate = pool.trade_fee_rate,
+            slot = pool.slot,
+            "New CPMM pool detected"
+        );
+        self.store.save_cpmm_pool(&pool)?;
+        counter!(telemetry::POOLS_DETECTED).increment(1);
+
+        let mints = self.raydium_cpmm.mints(&creation);
+        if self.raydium_cpmm.pool_id(&creation) != target && !mints.contains(&target) {
+            return Err(PoolRejected("Target not found in pool creation".to_string()).into());
+        }
+
+        let token_mint_output =
+            self.check_pool_filters(pool.keys.pool, mints, &initial_reserves)?;
+        let plans = self.plan_per_wallet(wallets, &pool, token_mint_output);
+
+        self.buy_with_wallets(plans, initial_reserves, simulate_only, timings)
+            .await
+    }
+}
@@ -0,0 +1,408 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use anyhow::Context;
+use metrics::{counter, gauge};
+use solana_sdk::{
+    commitment_config::{CommitmentConfig, CommitmentLevel},
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    transaction::VersionedTransaction,
+};
+use solana_transaction_status_client_types::{
+    option_serializer::OptionSerializer, UiTransactionStatusMeta, UiTransactionTokenBalance,
+};
+use tokio::{
+    sync::oneshot,
+    time::{sleep, Instant},
+};
+use tracing::{info, warn};
+
+use crate::{
+    senders::Outcome,
+    storage::models::{Position, TradeStatus},
+    telemetry,
+};
+
+use super::{
+    nonces::DurableNonce,
+    timings::{Stage, TradeTimings},
+    EventProcessor,
+};
+
+/// Upper bound on how long shutdown waits for sent transactions. A blockhash expires after
+/// ~150 blocks, so anything still unknown by then is reported as expired anyway.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(90);
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A sent transaction whose outcome we do not know yet.
+#[derive(Clone, Debug)]
+pub struct InFlightTrade {
+    /// Row of the trade in the store, if saving it worked.
+    pub trade_id: Option<i64>,
+    pub signature: Signature,
+    /// Past this block height the transaction can no longer land.
+    pub last_valid_block_height: u64,
+    /// Durable nonce the transaction was signed on, refreshed once it settles.
+    pub nonce: Option<DurableNonce>,
+    pub timings: TradeTimings,
+    /// Senders that accepted the transaction, credited with its outcome.
+    pub senders: Vec<String>,
+    /// Kept to send the transaction some other way if a sender gives up on it.
+    pub transaction: VersionedTransaction,
+    pub payer: Arc<Keypair>,
+    /// Position the buy adds to, opened only once it lands.
+    pub position: Option<Position>,
+}
+
+/// Outcome of the in-flight transactions waited for on shutdown.
+#[derive(Debug, Default)]
+pub struct DrainSummary {
+    pub landed: usize,
+    pub failed: usize,
+    pub expired: usize,
+    pub unknown: usize,
+}
+
+impl EventProcessor {
+    pub(crate) async fn add_in_flight(&self, trade: InFlightTrade) {
+        self.in_flight.lock().await.push(trade);
+    }
+
+    /// Settles sent transactions in the background until `stop` fires, so trade statuses and
+    /// metrics follow what lands while the bot runs.
+    pub(crate) async fn track_in_flight(self: Arc<Self>, mut stop: oneshot::Receiver<()>) {
+        loop {
+            tokio::select! {
+                _ = &mut stop => break,
+                _ = sleep(POLL_INTERVAL) => {}
+            }
+
+            if let Err(e) = self.poll_in_flight(&mut DrainSummary::default()).await {
+                warn!("Failed to poll in-flight transactions: {:#}", e);
+            }
+        }
+    }
+
+    /// Waits for every in-flight transaction to land, fail or expire, recording the outcome of
+    /// each trade in the store.
+    pub async fn drain_in_flight(&self) -> DrainSummary {
+        let mut summary = DrainSummary::default();
+        let pending = self.in_flight.lock().await.len();
+        if pending == 0 {
+            return summary;
+        }
+
+        info!(pending, "Waiting for in-flight transactions");
+        let deadline = Instant::now() + DRAIN_TIMEOUT;
+
+        loop {
+            if let Err(e) = self.poll_in_flight(&mut summary).await {
+                warn!("Failed to poll in-flight transactions: {:#}", e);
+            }
+            if self.in_flight.lock().await.is_empty() || Instant::now() >= deadline {
+                break;
+            }
+            sleep(POLL_INTERVAL).await;
+        }
+
+        let pending = std::mem::take(&mut *self.in_flight.lock().await);
+        for trade in &pending {
+            warn!(signature = %trade.signature, "Gave up waiting for transaction");
+        }
+        summary.unknown = pending.len();
+
+        summary
+    }
+
+    /// Logs what each sender got to land so far.
+    pub fn log_sender_stats(&self) {
+        for (sender, stats) in self.sender.stats() {
+            info!(
+                sender,
+                sent = stats.sent,
+                rejected = stats.rejected,
+                landed = stats.landed,
+                failed = stats.failed,
+                expired = stats.expired,
+                landing_rate = stats.landing_rate(),
+                mean_send_time = ?stats.mean_send_time(),
+                "Sender stats"
+            );
+        }
+    }
+
+    /// Opens (or adds to) the position of a landed buy, with the tokens it actually got rather
+    /// than the quoted ones whenever the transaction can be read back.
+    async fn open_position(&self, mut position: Position) {
+        let bought = match position.entry_signature {
+            Some(signature) => self.tokens_received(&signature, &position).await,
+            None => Err(anyhow::anyhow!("No entry signature")),
+        };
+        match bought {
+            Ok(bought) => position.token_amount = bought,
+            Err(e) => warn!(
+                mint = %position.mint,
+                quoted = position.token_amount,
+                "Failed to read the tokens bought, keeping the quote: {:#}",
+                e
+            ),
+        }
+
+        if let Err(e) = self.store.open_position(&position) {
+            warn!(mint = %position.mint, "Failed to save position: {:#}", e);
+        }
+
+        let mut positions = self.positions.lock().await;
+        positions
+            .entry((position.owner, position.mint))
+            .and_modify(|open| {
+                open.quote_amount += position.quote_amount;
+                open.token_amount += position.token_amount;
+            })
+            .or_insert(position);
+        gauge!(telemetry::OPEN_POSITIONS).set(positions.len() as f64);
+        drop(positions);
+        self.refresh_unrealised_pnl().await;
+    }
+
+    /// Tokens of the position's mint the owner gained in the landed transaction.
+    async fn tokens_received(
+        &self,
+        signature: &Signature,
+        position: &Position,
+    ) -> anyhow::Result<u64> {
+        let transaction = self
+            .solana_api
+            .get_transaction(&signature.to_string())
+            .await?;
+        let metadata = transaction
+            .metadata
+            .with_context(|| format!("Transaction {} has no metadata", signature))?;
+
+        token_balance_change(&metadata, &position.owner, &position.mint)
+    }
+
+    async fn poll_in_flight(&self, summary: &mut DrainSummary) -> anyhow::Result<()> {
+        let pending = std::mem::take(&mut *self.in_flight.lock().await);
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let signatures = pending
+            .iter()
+            .map(|trade| trade.signature)
+            .collect::<Vec<_>>();
+        // A transaction signed on a nonce can no longer land once the nonce moved on.
+        let nonces = pending
+            .iter()
+            .filter_map(|trade| trade.nonce)
+            .collect::<Vec<_>>();
+        let polled = async {
+            // Read before the statuses, so a nonce advanced by our own transaction comes with
+            // its status.
+            let mut current_nonces = HashMap::new();
+            for nonce in &nonces {
+                current_nonces.insert(nonce.account, self.fetch_nonce(&nonce.authority).await?);
+            }
+            let statuses = self.solana_api.get_signature_statuses(&signatures).await?;
+            let block_height = self.solana_api.get_block_height().await?;
+            anyhow::Ok((statuses, block_height, current_nonces))
+        }
+        .await;
+        let (statuses, block_height, current_nonces) = match polled {
+            Ok(polled) => polled,
+            Err(e) => {
+                self.in_flight.lock().await.extend(pending);
+                return Err(e);
+            }
+        };
+
+        // A processed transaction can still be dropped on a fork, so landed means confirmed.
+        let required = match self.config.endpoints.commitment {
+            CommitmentLevel::Finalized => CommitmentConfig::finalized(),
+            _ => CommitmentConfig::confirmed(),
+        };
+
+        let mut still_pending = Vec::new();
+        for (mut trade, status) in pending.into_iter().zip(statuses) {
+            let nonce_advanced = trade.nonce.is_some_and(|nonce| {
+                current_nonces
+                    .get(&nonce.account)
+                    .is_some_and(|current| current.blockhash != nonce.blockhash)
+            });
+            let outcome = match status {
+                Some(status) if status.err.is_some() => Some((
+                    TradeStatus::Failed,
+                    status.err.map(|e| e.to_string()),
+                    Outcome::Failed,
+                )),
+                Some(status) if status.satisfies_commitment(required) => {
+                    // Only as precise as the polling interval.
+                    trade.timings.mark(Stage::Landed);
+                    trade.timings.landed_slot = Some(status.slot);
+                    Some((TradeStatus::Landed, None, Outcome::Landed))
+                }
+                None if block_height > trade.last_valid_block_height => Some((
+                    TradeStatus::Failed,
+                    Some("Blockhash expired before the transaction landed".to_string()),
+                    Outcome::Expired,
+                )),
+                None if nonce_advanced => Some((
+                    TradeStatus::Failed,
+                    Some("Nonce advanced before the transaction landed".to_string()),
+                    Outcome::Expired,
+                )),
+                _ => None,
+            };
+
+            let Some((status, error, sender_outcome)) = outcome else {
+                if let Some(senders) = self
+                    .sender
+                    .follow_up(&trade.senders, &trade.transaction, &trade.payer)
+                    .await
+                {
+                    info!(signature = %trade.signature, ?senders, "Transaction sent again");
+                    trade.senders = senders;
+                }
+                still_pending.push(trade);
+                continue;
+            };
+
+            match sender_outcome {
+                Outcome::Landed => summary.landed += 1,
+                Outcome::Failed => summary.failed += 1,
+                Outcome::Expired => summary.expired += 1,
+            }
+            for sender in &trade.senders {
+                self.sender.record_outcome(sender, sender_outcome);
+            }
+            counter!(telemetry::TRANSACTIONS, "status" => status.as_str()).increment(1);
+            info!(
+                signature = %trade.signature,
+                trade_id = ?trade.trade_id,
+                %status,
+                error,
+                "Transaction settled"
+            );
+            if status == TradeStatus::Landed {
+                info!(
+                    signature = %trade.signature,
+                    notification_slot = trade.timings.notification_slot,
+                    landed_slot = trade.timings.landed_slot,
+                    slots_to_land = trade.timings.slots_to_land(),
+                    timings = %trade.timings,
+                    "Trade latency"
+                );
+            }
+            if let Some(trade_id) = trade.trade_id
+                && let Err(e) = self
+                    .store
+                    .update_trade_status(trade_id, status, error.as_deref())
+                    .and_then(|()| self.store.save_trade_timings(trade_id, &trade.timings))
+            {
+                warn!(trade_id, "Failed to update trade: {:#}", e);
+            }
+            if let Some(nonce) = trade.nonce
+                && let Some(current) = current_nonces.get(&nonce.account)
+            {
+                self.release_nonce(*current).await;
+            }
+            if status == TradeStatus::Landed
+                && let Some(position) = trade.position
+            {
+                self.open_position(position).await;
+            }
+        }
+        self.in_flight.lock().await.extend(still_pending);
+
+        Ok(())
+    }
+}
+
+/// Net change of the `mint` balances owned by `owner` over a transaction.
+fn token_balance_change(
+    metadata: &UiTransactionStatusMeta,
+    owner: &Pubkey,
+    mint: &Pubkey,
+) -> anyhow::Result<u64> {
+    let (owner, mint) = (owner.to_string(), mint.to_string());
+    let total = |balances: &OptionSerializer<Vec<UiTransactionTokenBalance>>| {
+        Option::<Vec<UiTransactionTokenBalance>>::from(balances.clone())
+            .unwrap_or_default()
+            .iter()
+            .filter(|balance| {
+                balance.mint == mint
+                    && Option::<String>::from(balance.owner.clone()).as_ref() == Some(&owner)
+            })
+            .map(|balance| {
+                balance
+                    .ui_token_amount
+                    .amount
+                    .parse::<u64>()
+                    .with_context(|| {
+                        format!("Invalid token amount {}", balance.ui_token_amount.amount)
+                    })
+            })
+            .try_fold(0u64, |total, amount| {
+                anyhow::Ok(total.saturating_add(amount?))
+            })
+    };
+    let pre = total(&metadata.pre_token_balances)?;
+    let post = total(&metadata.post_token_balances)?;
+
+    post.checked_sub(pre)
+        .with_context(|| format!("The balance of {} went down from {} to {}", mint, pre, post))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn token_balance(owner: &Pubkey, mint: &Pubkey, amount: u64) -> serde_json::Value {
+        json!({
+            "accountIndex": 1,
+            "mint": mint.to_string(),
+            "owner": owner.to_string(),
+            "uiTokenAmount": {
+                "uiAmount": null,
+                "decimals": 6,
+                "amount": amount.to_string(),
+                "uiAmountString": "0",
+            },
+        })
+    }
+
+    #[test]
+    fn counts_the_tokens_the_owner_received() {
+        let (owner, mint, pool) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let metadata: UiTransactionStatusMeta = serde_json::from_value(json!({
+            "err": null,
+            "status": { "Ok": null },
+            "fee": 5000,
+            "preBalances": [],
+            "postBalances": [],
+            "preTokenBalances": [token_balance(&pool, &mint, 1_000_000)],
+            "postTokenBalances": [
+                token_balance(&pool, &mint, 750_000),
+                token_balance(&owner, &mint, 250_000),
+            ],
+        }))
+        .unwrap();
+
+        assert_eq!(
+            token_balance_change(&metadata, &owner, &mint).unwrap(),
+            250_000
+        );
+        assert_eq!(
+            token_balance_change(&metadata, &owner, &Pubkey::new_unique()).unwrap(),
+            0
+        );
+        assert!(token_balance_change(&metadata, &pool, &mint).is_err());
+    }
+}
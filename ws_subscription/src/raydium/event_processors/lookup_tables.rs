@@ -0,0 +1,228 @@
+//! Address lookup tables, listing accounts that v0 transactions then reference by a one byte
+//! index instead of their 32 byte address.
+//!
+//! A Raydium V4 swap alone lists 18 accounts: with the compute budget, ATA creation and WSOL
+//! wrapping around it, a buy gets close to the size limit of a legacy transaction. The static
+//! accounts every buy uses go in the owner's own tables, and swaps on listed pools also use
+//! the table Raydium made for the pool.
+
+use anyhow::Context;
+use solana_address_lookup_table_interface::{program, state::AddressLookupTable};
+use solana_sdk::{
+    account::Account,
+    hash::Hash,
+    instruction::Instruction,
+    message::{v0, AddressLookupTableAccount, Message, VersionedMessage},
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+    transaction::{Transaction, VersionedTransaction},
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    api::solana_rpc::SolanaApi,
+    config::Programs,
+    pump_fun,
+    raydium::{cpmm, utils::AUTHORITY_AMM},
+};
+
+use super::{EventProcessor, WSOL};
+
+/// Decodes the lookup table at `address`, which must still be active.
+pub(crate) fn decode_lookup_table(
+    address: &Pubkey,
+    account: &Account,
+) -> anyhow::Result<AddressLookupTableAccount> {
+    anyhow::ensure!(
+        account.owner == program::id(),
+        "{} is not an address lookup table",
+        address
+    );
+    let table = AddressLookupTable::deserialize(&account.data)
+        .map_err(|e| anyhow::anyhow!("Invalid address lookup table {}: {}", address, e))?;
+    anyhow::ensure!(
+        table.meta.deactivation_slot == u64::MAX,
+        "Address lookup table {} is deactivated",
+        address
+    );
+
+    Ok(AddressLookupTableAccount {
+        key: *address,
+        addresses: table.addresses.to_vec(),
+    })
+}
+
+/// Signs `instructions` as a v0 transaction looking its accounts up in `lookup_tables`, or as
+/// a legacy one when there is none.
+pub(crate) fn sign_transaction(
+    owner: &Keypair,
+    instructions: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+    recent_blockhash: Hash,
+) -> anyhow::Result<VersionedTransaction> {
+    if lookup_tables.is_empty() {
+        let message =
+            Message::new_with_blockhash(instructions, Some(&owner.pubkey()), &recent_blockhash);
+        return Ok(Transaction::new(&[owner], message, recent_blockhash).into());
+    }
+
+    let message = v0::Message::try_compile(
+        &owner.pubkey(),
+        instructions,
+        lookup_tables,
+        recent_blockhash,
+    )?;
+
+    Ok(VersionedTransaction::try_new(
+        VersionedMessage::V0(message),
+        &[owner],
+    )?)
+}
+
+/// Accounts every buy may list whatever the pool: the programs and their authorities, and
+/// the WSOL mint. Programs that are invoked stay in the transaction, but most are also passed
+/// as accounts.
+pub fn static_accounts(programs: &Programs) -> Vec<Pubkey> {
+    vec![
+        solana_system_interface::program::id(),
+        spl_token::id(),
+        crate::raydium::clmm::TOKEN_2022_PROGRAM_ID,
+        spl_associated_token_account::id(),
+        solana_sdk::compute_budget::id(),
+        WSOL,
+        programs.raydium_liquidity_pool_v4,
+        Pubkey::find_program_address(&[AUTHORITY_AMM], &programs.raydium_liquidity_pool_v4).0,
+        programs.openbook_market,
+        programs.raydium_cpmm,
+        Pubkey::find_program_address(&[cpmm::AUTH_SEED], &programs.raydium_cpmm).0,
+        programs.raydium_clmm,
+        programs.orca_whirlpool,
+        crate::orca::MEMO_PROGRAM_ID,
+        programs.pump_fun,
+        Pubkey::find_program_address(&[pump_fun::GLOBAL_SEED], &programs.pump_fun).0,
+        Pubkey::find_program_address(&[pump_fun::EVENT_AUTHORITY_SEED], &programs.pump_fun).0,
+    ]
+}
+
+/// Fetches the tables at `addresses`, which must all exist and be active.
+pub(crate) async fn load_lookup_tables(
+    solana_api: &SolanaApi,
+    addresses: &[Pubkey],
+) -> anyhow::Result<Vec<AddressLookupTableAccount>> {
+    if addresses.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let accounts = solana_api.get_multiple_accounts(addresses).await?;
+    let mut tables = Vec::with_capacity(addresses.len());
+    for (address, account) in addresses.iter().zip(accounts) {
+        let account = account.with_context(|| {
+            format!(
+                "No address lookup table {}, create it with create-lookup-table",
+                address
+            )
+        })?;
+        tables.push(decode_lookup_table(address, &account)?);
+    }
+    info!(
+        tables = tables.len(),
+        addresses = tables
+            .iter()
+            .map(|table| table.addresses.len())
+            .sum::<usize>(),
+        "Loaded address lookup tables"
+    );
+
+    Ok(tables)
+}
+
+impl EventProcessor {
+    /// Looks up the tables Raydium made for `pools` and caches them under `pool`, the pool or
+    /// route they are bought through, so buys on it use them. Pools not listed yet are looked
+    /// up again next time.
+    pub(crate) async fn resolve_pool_lookup_tables(&self, pool: Pubkey, pools: &[Pubkey]) {
+        let Some(raydium_api) = &self.raydium_api else {
+            return;
+        };
+        if self.pool_lookup_tables.lock().await.contains_key(&pool) {
+            return;
+        }
+
+        let mut tables = Vec::new();
+        for leg in pools {
+            let resolved = async {
+                let Some(address) = raydium_api.get_pool_lookup_table(leg).await? else {
+                    return anyhow::Ok(None);
+                };
+                let account = self.solana_api.get_account_info(&address).await?;
+
+                Ok(Some(decode_lookup_table(&address, &account)?))
+            };
+            match resolved.await {
+                Ok(Some(table)) => {
+                    debug!(pool = %leg, lookup_table = %table.key, "Pool lookup table resolved");
+                    tables.push(table);
+                }
+                Ok(None) => debug!(pool = %leg, "No lookup table listed for the pool"),
+                Err(e) => warn!(pool = %leg, "Failed to resolve the pool lookup table: {:#}", e),
+            }
+        }
+        if !tables.is_empty() {
+            self.pool_lookup_tables.lock().await.insert(pool, tables);
+        }
+    }
+
+    /// The tables a buy on `pool` is compiled with.
+    pub(crate) async fn lookup_tables_for(&self, pool: &Pubkey) -> Vec<AddressLookupTableAccount> {
+        let mut tables = self.lookup_tables.clone();
+        if let Some(pool_tables) = self.pool_lookup_tables.lock().await.get(pool) {
+            tables.extend_from_slice(pool_tables);
+        }
+
+        tables
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::{message::VersionedMessage, pubkey::Pubkey};
+
+    use super::*;
+
+    #[test]
+    fn looks_up_the_accounts_listed_in_the_tables() {
+        let owner = Keypair::new();
+        let programs = Programs::default();
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: static_accounts(&programs),
+        };
+        let ata =
+            spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+                &owner.pubkey(),
+                &owner.pubkey(),
+                &WSOL,
+                &spl_token::id(),
+            );
+
+        let legacy =
+            sign_transaction(&owner, std::slice::from_ref(&ata), &[], Hash::default()).unwrap();
+        let v0 = sign_transaction(&owner, &[ata], &[table], Hash::default()).unwrap();
+
+        assert!(matches!(legacy.message, VersionedMessage::Legacy(_)));
+        let VersionedMessage::V0(message) = &v0.message else {
+            panic!("Expected a v0 message");
+        };
+        // The mint and the token and system programs passed as accounts are looked up, the
+        // invoked ATA program stays.
+        assert_eq!(message.address_table_lookups[0].readonly_indexes.len(), 3);
+        assert!(message
+            .account_keys
+            .contains(&spl_associated_token_account::id()));
+        assert!(v0.verify_with_results().iter().all(|verified| *verified));
+        assert!(
+            bincode::serialize(&v0).unwrap().len() < bincode::serialize(&legacy).unwrap().len()
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use std::{mem::offset_of, str::FromStr, sync::Arc};
+
+use futures::StreamExt;
+use safe_transmute::{transmute_one_pedantic, transmute_to_bytes};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    nonblocking::pubsub_client::PubsubClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+    rpc_response::RpcKeyedAccount,
+};
+use solana_sdk::pubkey::Pubkey;
+use tokio::{sync::oneshot, time::sleep};
+use tracing::{debug, info, trace, warn};
+
+use crate::{
+    raydium::{
+        models::{AccountFlag, Market, MarketKeys, MarketState, MarketStateV2, Pool},
+        utils::{
+            gen_vault_signer_key, remove_dex_account_padding, ACCOUNT_HEAD_PADDING,
+            ACCOUNT_TAIL_PADDING,
+        },
+    },
+    wallets::Wallet,
+};
+
+use super::{subscriptions::RESUBSCRIBE_BACKOFF, EventProcessor};
+
+/// Size of a (non permissioned) market account: the state between the dex paddings.
+const MARKET_ACCOUNT_SIZE: usize =
+    ACCOUNT_HEAD_PADDING.len() + size_of::<MarketState>() + ACCOUNT_TAIL_PADDING.len();
+const MARKET_PC_MINT_OFFSET: usize = ACCOUNT_HEAD_PADDING.len() + offset_of!(MarketState, pc_mint);
+
+impl EventProcessor {
+    /// Keys of the pool's market, from the cache when it was already seen (or prefetched),
+    /// otherwise fetched and cached.
+    pub(crate) async fn get_market_keys(&self, pool: &Pool) -> anyhow::Result<MarketKeys> {
+        if let Some(market_keys) = self.market_keys.lock().await.get(&pool.amm.market) {
+            debug!(market = %pool.amm.market, "Market keys cached");
+            return Ok(*market_keys);
+        }
+
+        let account_data = self.solana_api.get_account_data(&pool.amm.market).await?;
+        let market_keys =
+            Self::decode_market_keys(&pool.amm.market, &pool.amm.market_program, &account_data)?;
+        self.cache_market_keys(pool.amm.market, market_keys).await;
+
+        Ok(market_keys)
+    }
+
+    /// Prefetches the keys of every market quoted in `filters.quote_mint` until `stop` fires,
+    /// and stages a snipe of each of `wallets` on the pool of each new market of the `target`
+    /// mint.
+    ///
+    /// OpenBook markets are created before the Raydium pool listing them, so by the time the
+    /// pool shows up its market keys are usually cached. The subscription also reports updates
+    /// of existing markets; those are cached the first time they are seen and ignored after.
+    pub(crate) async fn watch_markets(
+        self: Arc<Self>,
+        wallets: Arc<Vec<Wallet>>,
+        target: Pubkey,
+        mut stop: oneshot::Receiver<()>,
+    ) {
+        let program_id = self.config.programs.openbook_market;
+        info!(%program_id, "Prefetching new markets");
+
+        loop {
+            let ws_client = self.ws_client().await;
+            match self
+                .stream_markets(&ws_client, &wallets, &target, &mut stop)
+                .await
+            {
+                Ok(true) => break,
+                Ok(false) => warn!("Market stream closed, resubscribing"),
+                Err(e) => warn!("Market subscription failed: {:#}", e),
+            }
+
+            if let Err(e) = self.reconnect_ws(&ws_client).await {
+                warn!("Failed to reconnect the websocket: {:#}", e);
+            }
+            tokio::select! {
+                _ = &mut stop => break,
+                _ = sleep(RESUBSCRIBE_BACKOFF) => {}
+            }
+        }
+    }
+
+    /// Streams market accounts until `stop` fires (`true`) or the websocket closes (`false`).
+    async fn stream_markets(
+        &self,
+        ws_client: &PubsubClient,
+        wallets: &[Wallet],
+        target: &Pubkey,
+        stop: &mut oneshot::Receiver<()>,
+    ) -> anyhow::Result<bool> {
+        let (mut accounts, unsubscriber) = ws_client
+            .program_subscribe(
+                &self.config.programs.openbook_market,
+                Some(RpcProgramAccountsConfig {
+                    filters: Some(vec![
+                        RpcFilterType::DataSize(MARKET_ACCOUNT_SIZE as u64),
+                        RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                            MARKET_PC_MINT_OFFSET,
+                            self.config.filters.quote_mint.as_ref(),
+                        )),
+                    ]),
+                    account_config: RpcAccountInfoConfig {
+                        encoding: Some(UiAccountEncoding::Base64),
+                        commitment: Some(self.commitment()),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                }),
+            )
+            .await?;
+
+        let stopped = loop {
+            tokio::select! {
+                _ = &mut *stop => break true,
+                response = accounts.next() => match response {
+                    Some(response) => {
+                        if let Err(e) = self.prefetch_market(&response.value, wallets, target).await {
+                            warn!(market = response.value.pubkey, "Failed to prefetch market: {:#}", e);
+                        }
+                    }
+                    None => break false,
+                },
+            }
+        };
+
+        drop(accounts);
+        unsubscriber().await;
+
+        Ok(stopped)
+    }
+
+    async fn prefetch_market(
+        &self,
+        account: &RpcKeyedAccount,
+        wallets: &[Wallet],
+        target: &Pubkey,
+    ) -> anyhow::Result<()> {
+        let market = Pubkey::from_str(&account.pubkey)?;
+        let account_data = account
+            .account
+            .data
+            .decode()
+            .ok_or_else(|| anyhow::anyhow!("Undecodable account data"))?;
+        let market_state = Self::decode_market_state(&account_data)?;
+
+        let cached = self.market_keys.lock().await.get(&market).copied();
+        let market_keys = match cached {
+            Some(market_keys) => market_keys,
+            None => {
+                let market_keys = Self::market_keys_from_state(
+                    &market,
+                    &self.config.programs.openbook_market,
+                    &market_state,
+                )?;
+                debug!(%market, "Market keys prefetched");
+                self.cache_market_keys(market, market_keys).await;
+                market_keys
+            }
+        };
+
+        let coin_mint = pubkey_from_words(market_state.coin_mint);
+        let pc_mint = pubkey_from_words(market_state.pc_mint);
+        if coin_mint == *target || pc_mint == *target {
+            self.stage_snipe(wallets, market, coin_mint, pc_mint, market_keys)
+                .await;
+        }
+
+        Ok(())
+    }
+
+    /// Keeps the keys in memory and in the store, so they survive a restart.
+    async fn cache_market_keys(&self, market: Pubkey, market_keys: MarketKeys) {
+        let cached = self
+            .market_keys
+            .lock()
+            .await
+            .insert(market, market_keys)
+            .is_some();
+
+        if !cached && let Err(e) = self.store.save_market_keys(&market, &market_keys) {
+            warn!(%market, "Failed to save market keys: {:#}", e);
+        }
+    }
+
+    pub(crate) fn decode_market_keys(
+        market: &Pubkey,
+        market_program: &Pubkey,
+        account_data: &[u8],
+    ) -> anyhow::Result<MarketKeys> {
+        let market_state = Self::decode_market_state(account_data)?;
+        Self::market_keys_from_state(market, market_program, &market_state)
+    }
+
+    fn decode_market_state(account_data: &[u8]) -> anyhow::Result<MarketState> {
+        let words = remove_dex_account_padding(account_data).map_err(anyhow::Error::msg)?;
+
+        let market_state: MarketState = {
+            let account_flags = Market::account_flags(account_data)?;
+            if account_flags.intersects(AccountFlag::Permissioned) {
+                let state = transmute_one_pedantic::<MarketStateV2>(transmute_to_bytes(&words))
+                    .map_err(|e| e.without_src())?;
+                //state.check_flags(true)?;
+                state.inner
+            } else {
+                let state = transmute_one_pedantic::<MarketState>(transmute_to_bytes(&words))
+                    .map_err(|e| e.without_src())?;
+                //state.check_flags(true)?;
+                state
+            }
+        };
+
+        trace!(?market_state, "Market state");
+
+        Ok(market_state)
+    }
+
+    fn market_keys_from_state(
+        market: &Pubkey,
+        market_program: &Pubkey,
+        market_state: &MarketState,
+    ) -> anyhow::Result<MarketKeys> {
+        let vault_signer_key =
+            gen_vault_signer_key(market_state.vault_signer_nonce, market, market_program)?;
+
+        Ok(MarketKeys {
+            event_queue: pubkey_from_words(market_state.event_q),
+            bids: pubkey_from_words(market_state.bids),
+            asks: pubkey_from_words(market_state.asks),
+            coin_vault: pubkey_from_words(market_state.coin_vault),
+            pc_vault: pubkey_from_words(market_state.pc_vault),
+            vault_signer_key,
+        })
+    }
+}
+
+/// Market state fields hold pubkeys as little-endian words.
+fn pubkey_from_words(words: [u64; 4]) -> Pubkey {
+    Pubkey::new_from_array(bytemuck::cast(words))
+}
@@ -1,103 +1,399 @@
-use std::{collections::HashMap, str::FromStr};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use anyhow::Context;
-use futures::{future::BoxFuture, StreamExt};
+use futures::{stream::BoxStream, StreamExt};
+use metrics::gauge;
 use solana_client::{
-    nonblocking::{pubsub_client::PubsubClient, rpc_client::RpcClient},
+    nonblocking::pubsub_client::PubsubClient,
     rpc_config::{RpcTransactionLogsConfig, RpcTransactionLogsFilter},
+    rpc_response::{Response, RpcLogsResponse},
 };
 use solana_sdk::{
-    commitment_config::{CommitmentConfig, CommitmentLevel},
-    pubkey::Pubkey,
-    signature::{Keypair, Signature},
+    commitment_config::CommitmentConfig, message::AddressLookupTableAccount, pubkey::Pubkey,
+};
+use tokio::{
+    sync::{oneshot, Mutex},
+    time::sleep,
+};
+use tracing::{info, warn};
+
+use crate::{
+    api::{raydium::RaydiumApi, solana_rpc::SolanaApi},
+    config::Config,
+    dex::{Dex, PoolReserves},
+    pump_fun::PumpFun,
+    senders::{self, TransactionSender},
+    shutdown::Shutdown,
+    storage::{
+        models::Position,
+        sqlite::{now, Store},
+    },
+    telemetry,
+    wallets::Wallet,
 };
-use tokio::sync::Mutex;
 
-use crate::api::solana_rpc::SolanaApi;
+use self::{
+    in_flight::InFlightTrade,
+    new_swap::RayInitLog,
+    nonces::DurableNonce,
+    pool_created::PoolRejected,
+    pump_fun_curves::CurveProgress,
+    snipes::SnipePlan,
+    subscriptions::{SubscriptionManager, RESUBSCRIBE_BACKOFF},
+    timings::TradeTimings,
+};
 
-use super::models::Pool;
+use super::{
+    dex::{RaydiumCpmm, RaydiumV4},
+    models::{MarketKeys, Pool},
+    pool::AmmV4Pool,
+};
 
+pub mod backfill;
+pub mod clmm_pools;
+pub mod cpmm_pool_created;
+pub mod in_flight;
+pub mod lookup_tables;
+pub mod markets;
+pub mod new_pools;
 pub mod new_swap;
+pub mod nonces;
 pub mod pool_created;
+pub mod pump_fun_curves;
+pub mod reserves;
+pub mod routes;
+pub mod snipes;
+pub mod subscriptions;
+pub mod swaps;
+pub mod timings;
+pub mod wallet_buys;
+pub mod whirlpools;
 
-const RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID: Pubkey =
+pub(crate) const RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID: Pubkey =
     solana_sdk::pubkey!("675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8");
-const WSOL: Pubkey = solana_sdk::pubkey!("So11111111111111111111111111111111111111112");
+pub(crate) const OPENBOOK_MARKET_PROGRAM_ID: Pubkey =
+    solana_sdk::pubkey!("srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX");
+pub(crate) const RAYDIUM_CPMM_PROGRAM_ID: Pubkey =
+    solana_sdk::pubkey!("CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C");
+pub(crate) const RAYDIUM_CLMM_PROGRAM_ID: Pubkey =
+    solana_sdk::pubkey!("CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK");
+pub(crate) const WSOL: Pubkey = solana_sdk::pubkey!("So11111111111111111111111111111111111111112");
 //const TOKEN_PROGRAM: Pubkey = solana_sdk::pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
-//const SERUM_PROGRAM: Pubkey = solana_sdk::pubkey!("srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX");
-
-type Unsubscriber = Box<dyn FnOnce() -> BoxFuture<'static, ()> + Send>;
 
 pub struct EventProcessor {
-    ws_client: PubsubClient,
-    solana_api: SolanaApi,
+    /// Replaced by a new connection once its socket dies, see `reconnect_ws`.
+    ws_client: Mutex<Arc<PubsubClient>>,
+    solana_api: Arc<SolanaApi>,
+    /// Where buys are sent through.
+    sender: Box<dyn TransactionSender>,
+    config: Config,
+    store: Store,
     pools: Mutex<HashMap<Pubkey, Pool>>,
-    subscriptions: Mutex<HashMap<Pubkey, Unsubscriber>>,
+    /// Open positions, by wallet and mint.
+    positions: Mutex<HashMap<(Pubkey, Pubkey), Position>>,
+    market_keys: Mutex<HashMap<Pubkey, MarketKeys>>,
+    /// Live reserves of the subscribed pools, by pool.
+    reserves: Mutex<HashMap<Pubkey, PoolReserves>>,
+    /// Buys worked out ahead of their pool, by market and wallet.
+    staged_snipes: Mutex<HashMap<Pubkey, HashMap<Pubkey, SnipePlan<AmmV4Pool>>>>,
+    raydium_v4: RaydiumV4,
+    raydium_cpmm: RaydiumCpmm,
+    /// Data of the accounts pools are decoded from that never change, e.g. fee configs.
+    immutable_accounts: Mutex<HashMap<Pubkey, Vec<u8>>>,
+    pump_fun: PumpFun,
+    /// Bonding curves of the targets launched on Pump.fun, by mint, until they migrate.
+    pump_fun_curves: Mutex<HashMap<Pubkey, CurveProgress>>,
+    subscriptions: SubscriptionManager,
+    in_flight: Mutex<Vec<InFlightTrade>>,
+    /// Durable nonce of each owner, while it is free to sign with.
+    nonces: Mutex<HashMap<Pubkey, DurableNonce>>,
+    /// Tables of `lookup_tables.addresses`, used by every buy.
+    lookup_tables: Vec<AddressLookupTableAccount>,
+    /// Where pool tables are looked up, unless disabled.
+    raydium_api: Option<RaydiumApi>,
+    /// Tables Raydium made for the pools swapped on, by pool or route.
+    pool_lookup_tables: Mutex<HashMap<Pubkey, Vec<AddressLookupTableAccount>>>,
+    /// SOL balance of each wallet buying, as last fetched less what it spent since.
+    wallet_balances: Mutex<HashMap<Pubkey, u64>>,
 }
 
 impl EventProcessor {
-    pub async fn new(rpc_url: &str, ws_url: &str) -> anyhow::Result<Self> {
-        let ws_client = PubsubClient::new(ws_url)
+    pub async fn new(config: &Config) -> anyhow::Result<Self> {
+        let ws_client = PubsubClient::new(config.ws_url()?)
             .await
             .with_context(|| "Failed to create WS client")?;
-        let solana_api = SolanaApi::new(
-            rpc_url,
+        let solana_api = Arc::new(SolanaApi::new(
+            config.rpc_url()?,
             None,
             Some(CommitmentConfig {
-                commitment: CommitmentLevel::Processed,
+                commitment: config.endpoints.commitment,
             }),
+        ));
+        solana_api.spawn_blockhash_refresh(
+            Duration::from_millis(config.blockhash.refresh_interval_ms),
+            CommitmentConfig {
+                commitment: config.blockhash.commitment,
+            },
+            config.blockhash.min_remaining_blocks,
         );
+        let sender = senders::from_config(config, Arc::clone(&solana_api)).await?;
+        let lookup_tables =
+            lookup_tables::load_lookup_tables(&solana_api, &config.lookup_tables.addresses).await?;
 
-        let pools = Mutex::new(HashMap::new());
-        let subscriptions = Mutex::new(HashMap::new());
+        let store = Store::open(&config.storage.path)?;
+
+        let pools = store
+            .tracked_pools()?
+            .into_iter()
+            .map(|pool| (pool.amm.amm_pool, pool))
+            .collect::<HashMap<_, _>>();
+        let positions = store
+            .open_positions()?
+            .into_iter()
+            .map(|position| ((position.owner, position.mint), position))
+            .collect::<HashMap<_, _>>();
+        let market_keys = store
+            .market_keys(now() - config.markets.cache_retention_secs as i64)?
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        info!(
+            tracked_pools = pools.len(),
+            open_positions = positions.len(),
+            cached_markets = market_keys.len(),
+            store = %config.storage.path,
+            "Loaded state from the store"
+        );
+
+        gauge!(telemetry::OPEN_POSITIONS).set(positions.len() as f64);
+        gauge!(telemetry::REALISED_PNL).set(store.realised_pnl()? as f64);
+
+        let pools = Mutex::new(pools);
+        let positions = Mutex::new(positions);
+        let subscriptions = SubscriptionManager::new(
+            config.subscriptions.max_concurrent,
+            Duration::from_secs(config.subscriptions.inactivity_timeout_secs),
+        );
 
         Ok(Self {
             solana_api,
-            ws_client,
+            sender,
+            ws_client: Mutex::new(Arc::new(ws_client)),
+            config: config.clone(),
+            store,
             pools,
+            positions,
+            market_keys: Mutex::new(market_keys),
+            reserves: Mutex::new(HashMap::new()),
+            staged_snipes: Mutex::new(HashMap::new()),
+            raydium_v4: RaydiumV4 {
+                program_id: config.programs.raydium_liquidity_pool_v4,
+            },
+            raydium_cpmm: RaydiumCpmm {
+                program_id: config.programs.raydium_cpmm,
+            },
+            immutable_accounts: Mutex::new(HashMap::new()),
+            pump_fun: PumpFun {
+                program_id: config.programs.pump_fun,
+            },
+            pump_fun_curves: Mutex::new(HashMap::new()),
             subscriptions,
+            in_flight: Mutex::new(Vec::new()),
+            nonces: Mutex::new(HashMap::new()),
+            lookup_tables,
+            raydium_api: config
+                .lookup_tables
+                .pool_tables
+                .then(|| RaydiumApi::new(&config.lookup_tables.raydium_api_url)),
+            pool_lookup_tables: Mutex::new(HashMap::new()),
+            wallet_balances: Mutex::new(HashMap::new()),
         })
     }
 
+    /// Buys `target` with every one of `wallets` as soon as it is created.
     pub async fn execute_on_creation(
+        self: &Arc<Self>,
+        wallets: Vec<Wallet>,
+        target: Pubkey,
+        simulate_only: bool,
+        shutdown: &Shutdown,
+    ) -> anyhow::Result<()> {
+        info!(%target, "Starting event processor");
+        let wallets = Arc::new(self.check_wallet_balances(wallets).await?);
+
+        for wallet in wallets.iter() {
+            self.refresh_nonce(&wallet.pubkey()).await?;
+        }
+        self.resume_subscriptions().await?;
+        let (stop_tracker, tracker_stopped) = oneshot::channel();
+        let tracker = tokio::spawn(Arc::clone(self).track_in_flight(tracker_stopped));
+        let (stop_market_watcher, market_watcher_stopped) = oneshot::channel();
+        let market_watcher = self.config.markets.prefetch.then(|| {
+            tokio::spawn(Arc::clone(self).watch_markets(
+                Arc::clone(&wallets),
+                target,
+                market_watcher_stopped,
+            ))
+        });
+
+        let result = self
+            .buy_on_creation(&wallets, target, simulate_only, shutdown)
+            .await;
+
+        // No new pool events are taken from here on: let what was sent settle, then close
+        // the pool streams. Everything is already persisted as it happens.
+        let _ = stop_market_watcher.send(());
+        if let Some(market_watcher) = market_watcher {
+            let _ = market_watcher.await;
+        }
+        let _ = stop_tracker.send(());
+        let _ = tracker.await;
+        let drained = self.drain_in_flight().await;
+        self.log_sender_stats();
+        self.log_positions().await;
+        let subscriptions = self.subscriptions.len().await;
+        self.unsubscribe_all().await;
+
+        info!(
+            subscriptions,
+            landed = drained.landed,
+            failed = drained.failed,
+            expired = drained.expired,
+            unknown = drained.unknown,
+            "Event processor stopped"
+        );
+
+        result
+    }
+
+    async fn buy_on_creation(
         &self,
-        owner: Keypair,
+        wallets: &[Wallet],
         target: Pubkey,
-        amount: u64,
         simulate_only: bool,
+        shutdown: &Shutdown,
     ) -> anyhow::Result<()> {
-        println!("RAYDIUM - Starting event processor for target: {}", target);
-
-        let (mut accounts, unsubscriber) = self
-            .ws_client
-            .logs_subscribe(
-                RpcTransactionLogsFilter::Mentions(vec![
-                    //RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID.to_string()
-                    target.to_string(),
-                ]),
-                RpcTransactionLogsConfig {
-                    commitment: Some(CommitmentConfig {
-                        commitment: CommitmentLevel::Processed,
-                    }),
-                },
-            )
-            .await?;
+        loop {
+            let ws_client = self.ws_client().await;
+            match ws_client
+                .logs_subscribe(
+                    RpcTransactionLogsFilter::Mentions(vec![
+                        //RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID.to_string()
+                        target.to_string(),
+                    ]),
+                    RpcTransactionLogsConfig {
+                        commitment: Some(self.commitment()),
+                    },
+                )
+                .await
+            {
+                Ok((mut accounts, unsubscriber)) => {
+                    let stopped = self
+                        .stream_creations(&mut accounts, wallets, target, simulate_only, shutdown)
+                        .await;
+                    drop(accounts);
+                    unsubscriber().await;
 
-        while let Some(response) = accounts.next().await {
+                    if stopped? {
+                        return Ok(());
+                    }
+                    warn!(%target, "Pool creation stream closed, resubscribing");
+                }
+                Err(e) => warn!(%target, "Pool creation subscription failed: {:#}", e),
+            }
+
+            if let Err(e) = self.reconnect_ws(&ws_client).await {
+                warn!("Failed to reconnect the websocket: {:#}", e);
+            }
+            tokio::select! {
+                _ = shutdown.requested() => return Ok(()),
+                _ = sleep(RESUBSCRIBE_BACKOFF) => {}
+            }
+        }
+    }
+
+    /// Buys from the creation logs of `target` until shutdown (`true`) or the stream closes
+    /// (`false`).
+    async fn stream_creations(
+        &self,
+        accounts: &mut BoxStream<'_, Response<RpcLogsResponse>>,
+        wallets: &[Wallet],
+        target: Pubkey,
+        simulate_only: bool,
+        shutdown: &Shutdown,
+    ) -> anyhow::Result<bool> {
+        loop {
+            let response = tokio::select! {
+                _ = shutdown.requested() => return Ok(true),
+                response = accounts.next() => match response {
+                    Some(response) => response,
+                    None => return Ok(false),
+                },
+            };
+            let slot = response.context.slot;
+            let timings = TradeTimings::start(slot);
             let logs = response.value.logs;
             let signature = response.value.signature;
 
-            for log in &logs {
-                if log.to_lowercase().contains("initialize2") {
-                    println!("RAYDIUM - Pool creation detected for key {:#?}", target);
-                    self.buy_new_pool(&owner, target, amount, &signature, simulate_only)
-                        .await?;
+            let bought = if self.raydium_v4.is_pool_creation(&logs) {
+                info!(%target, %signature, "Pool creation detected");
+                let migration = self.take_pump_fun_migration(&target).await;
+                if migration.is_some_and(|curve| curve.bought) {
+                    info!(%target, "Already bought on the bonding curve, skipping the migration");
+                    continue;
+                }
+                let staged = match RayInitLog::from_logs(&logs) {
+                    Some(init) => self
+                        .take_staged_snipe(&init)
+                        .await
+                        .map(|plans| (plans, init)),
+                    None => None,
+                };
+                match staged {
+                    Some((plans, init)) => {
+                        self.buy_staged_pool(
+                            wallets,
+                            plans,
+                            init,
+                            &signature,
+                            slot,
+                            simulate_only,
+                            timings,
+                        )
+                        .await
+                    }
+                    None => {
+                        self.buy_new_pool(wallets, target, &signature, simulate_only, timings)
+                            .await
+                    }
                 }
+            } else if self.raydium_cpmm.is_pool_creation(&logs) {
+                info!(%target, %signature, "CPMM pool creation detected");
+                self.buy_new_cpmm_pool(wallets, target, &signature, simulate_only, timings)
+                    .await
+            } else if self.pump_fun.is_invoked(&logs) {
+                self.handle_pump_fun_events(
+                    wallets,
+                    target,
+                    &logs,
+                    &signature,
+                    simulate_only,
+                    timings,
+                )
+                .await
+            } else {
+                Ok(())
+            };
+            match bought {
+                Ok(()) => {}
+                Err(e) if e.is::<PoolRejected>() => info!(%signature, "Pool skipped: {:#}", e),
+                Err(e) => return Err(e),
             }
         }
+    }
 
-        unsubscriber().await;
-
-        Ok(())
+    fn commitment(&self) -> CommitmentConfig {
+        CommitmentConfig {
+            commitment: self.config.endpoints.commitment,
+        }
     }
 }
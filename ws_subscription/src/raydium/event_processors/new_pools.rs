@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use tracing::{debug, instrument, trace};
+
+use crate::dex::{Dex, PoolReserves};
+
+use super::{
+    timings::{Stage, TradeTimings},
+    EventProcessor,
+};
+
+impl EventProcessor {
+    /// Fetches the pool creation, then every account `dex` decodes the pool from in a single
+    /// request. Immutable accounts are only fetched the first time.
+    #[instrument(skip(self, dex, timings), fields(dex = dex.name()))]
+    pub(crate) async fn decode_new_pool<D: Dex>(
+        &self,
+        dex: &D,
+        signature: &str,
+        timings: &mut TradeTimings,
+    ) -> anyhow::Result<(D::Creation, D::Pool, PoolReserves)> {
+        let transaction = self.solana_api.get_transaction(signature).await?;
+        timings.mark(Stage::TransactionFetched);
+        trace!(?transaction, "Pool creation transaction");
+
+        let creation = dex
+            .decode_creation(&transaction)?
+            .with_context(|| format!("No {} pool created by {}", dex.name(), signature))?;
+        let accounts = self.fetch_pool_accounts(dex, &creation).await?;
+        let (pool, reserves) = dex.decode_pool(&creation, &accounts)?;
+        debug!(?pool, ?reserves, "Pool decoded");
+        timings.mark(Stage::PoolDecoded);
+        timings.pool_slot = Some(transaction.slot);
+
+        Ok((creation, pool, reserves))
+    }
+
+    /// Data of the `pool_accounts` then `immutable_accounts` of `dex`, the immutable ones from
+    /// the cache when already seen.
+    pub(super) async fn fetch_pool_accounts<D: Dex>(
+        &self,
+        dex: &D,
+        creation: &D::Creation,
+    ) -> anyhow::Result<Vec<Option<Vec<u8>>>> {
+        let pool_accounts = dex.pool_accounts(creation);
+        let immutable_accounts = dex.immutable_accounts(creation);
+        let cached = {
+            let cache = self.immutable_accounts.lock().await;
+            immutable_accounts
+                .iter()
+                .filter_map(|account| Some((*account, cache.get(account)?.clone())))
+                .collect::<HashMap<_, _>>()
+        };
+
+        let mut to_fetch = pool_accounts.clone();
+        to_fetch.extend(
+            immutable_accounts
+                .iter()
+                .filter(|account| !cached.contains_key(account)),
+        );
+        let mut fetched = if to_fetch.is_empty() {
+            Vec::new()
+        } else {
+            self.solana_api
+                .get_multiple_accounts_data(&to_fetch)
+                .await?
+        }
+        .into_iter();
+
+        let mut accounts = fetched
+            .by_ref()
+            .take(pool_accounts.len())
+            .collect::<Vec<_>>();
+        let mut cache = self.immutable_accounts.lock().await;
+        for account in &immutable_accounts {
+            let data = match cached.get(account) {
+                Some(data) => Some(data.clone()),
+                None => {
+                    let data = fetched.next().flatten();
+                    if let Some(data) = &data {
+                        cache.insert(*account, data.clone());
+                    }
+                    data
+                }
+            };
+            accounts.push(data);
+        }
+
+        Ok(accounts)
+    }
+}
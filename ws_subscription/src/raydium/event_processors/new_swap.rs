@@ -1 +1,105 @@
-pub fn execute() {}
+use base64::{engine::general_purpose::STANDARD, Engine};
+use solana_sdk::pubkey::Pubkey;
+
+const RAY_LOG_PREFIX: &str = "Program log: ray_log: ";
+const INIT_LOG_TYPE: u8 = 0;
+const SWAP_BASE_IN_LOG_TYPE: u8 = 3;
+const SWAP_BASE_OUT_LOG_TYPE: u8 = 4;
+
+/// Swap summary Raydium AMM V4 emits as a `ray_log` on every swap.
+///
+/// Layout from https://github.com/raydium-io/raydium-amm/blob/master/program/src/log.rs
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RaySwapLog {
+    pub base_in: bool,
+    pub direction: u64,
+    /// Amount actually taken from the user.
+    pub amount_in: u64,
+    /// Amount actually sent to the user.
+    pub amount_out: u64,
+    /// Coin vault balance before the swap.
+    pub pool_coin: u64,
+    /// Pc vault balance before the swap.
+    pub pool_pc: u64,
+}
+
+impl RaySwapLog {
+    /// Decodes the first swap `ray_log` found in the transaction logs.
+    pub fn from_logs(logs: &[String]) -> Option<Self> {
+        logs.iter()
+            .filter_map(|log| log.strip_prefix(RAY_LOG_PREFIX))
+            .find_map(|encoded| Self::decode(encoded.trim()))
+    }
+
+    fn decode(encoded: &str) -> Option<Self> {
+        let bytes = STANDARD.decode(encoded).ok()?;
+        let log_type = *bytes.first()?;
+        if log_type != SWAP_BASE_IN_LOG_TYPE && log_type != SWAP_BASE_OUT_LOG_TYPE {
+            return None;
+        }
+
+        let word = |index: usize| -> Option<u64> {
+            let start = 1 + index * 8;
+            let chunk = bytes.get(start..start + 8)?;
+            Some(u64::from_le_bytes(chunk.try_into().ok()?))
+        };
+
+        // base in:  amount_in, minimum_out, direction, user_source, pool_coin, pool_pc, out_amount
+        // base out: max_in, amount_out, direction, user_source, pool_coin, pool_pc, deduct_in
+        let base_in = log_type == SWAP_BASE_IN_LOG_TYPE;
+        let (amount_in, amount_out) = if base_in {
+            (word(0)?, word(6)?)
+        } else {
+            (word(6)?, word(1)?)
+        };
+
+        Some(Self {
+            base_in,
+            direction: word(2)?,
+            amount_in,
+            amount_out,
+            pool_coin: word(4)?,
+            pool_pc: word(5)?,
+        })
+    }
+}
+
+/// Pool creation summary Raydium AMM V4 emits as a `ray_log` from `initialize2`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayInitLog {
+    /// Coin deposited at creation, i.e. the initial coin reserve.
+    pub coin_amount: u64,
+    /// Pc deposited at creation, i.e. the initial pc reserve.
+    pub pc_amount: u64,
+    pub market: Pubkey,
+}
+
+impl RayInitLog {
+    /// Decodes the first init `ray_log` found in the transaction logs.
+    pub fn from_logs(logs: &[String]) -> Option<Self> {
+        logs.iter()
+            .filter_map(|log| log.strip_prefix(RAY_LOG_PREFIX))
+            .find_map(|encoded| Self::decode(encoded.trim()))
+    }
+
+    fn decode(encoded: &str) -> Option<Self> {
+        let bytes = STANDARD.decode(encoded).ok()?;
+        if *bytes.first()? != INIT_LOG_TYPE {
+            return None;
+        }
+
+        // log_type: u8, time: u64, pc_decimals: u8, coin_decimals: u8, pc_lot_size: u64,
+        // coin_lot_size: u64, pc_amount: u64, coin_amount: u64, market: Pubkey
+        let word = |start: usize| -> Option<u64> {
+            Some(u64::from_le_bytes(
+                bytes.get(start..start + 8)?.try_into().ok()?,
+            ))
+        };
+
+        Some(Self {
+            pc_amount: word(27)?,
+            coin_amount: word(35)?,
+            market: Pubkey::try_from(bytes.get(43..75)?).ok()?,
+        })
+    }
+}
@@ -0,0 +1,210 @@
+//! Durable nonces, standing in for a recent blockhash so buys can be signed ahead of time and
+//! sent without fetching one.
+//!
+//! A nonce only signs one transaction that lands: the transaction advances it in its first
+//! instruction. So it is handed out once, then refreshed when that transaction settles, and
+//! buys fall back to the latest blockhash in between.
+
+use solana_client::nonce_utils;
+use solana_sdk::{
+    hash::Hash, instruction::Instruction, message::AddressLookupTableAccount, pubkey::Pubkey,
+    signature::Keypair, signer::Signer, transaction::VersionedTransaction,
+};
+use tracing::{debug, info};
+
+use super::{lookup_tables::sign_transaction, EventProcessor};
+
+/// Size of a nonce account, `solana_nonce::state::State::size()`.
+pub(crate) const NONCE_ACCOUNT_SIZE: usize = 80;
+
+/// A durable nonce value of an owner's nonce account.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DurableNonce {
+    pub account: Pubkey,
+    /// The owner, allowed to advance it.
+    pub authority: Pubkey,
+    /// Stored nonce, used as the blockhash of the transaction.
+    pub blockhash: Hash,
+}
+
+impl DurableNonce {
+    /// `instructions`, advancing the nonce first as the runtime requires.
+    pub fn with_advance(&self, instructions: &[Instruction]) -> Vec<Instruction> {
+        let mut nonced = vec![solana_system_interface::instruction::advance_nonce_account(
+            &self.account,
+            &self.authority,
+        )];
+        nonced.extend_from_slice(instructions);
+
+        nonced
+    }
+}
+
+/// A transaction ready to send, and what it was signed on.
+#[derive(Clone, Debug)]
+pub struct SignedBuy {
+    pub transaction: VersionedTransaction,
+    /// Past this block height the transaction can no longer land. Transactions signed on a
+    /// durable nonce do not expire: they are dropped once the nonce moves on instead.
+    pub last_valid_block_height: u64,
+    pub nonce: Option<DurableNonce>,
+}
+
+/// Nonce account of `owner` for `seed`, which `owner` funds and advances.
+pub fn nonce_account_address(owner: &Pubkey, seed: &str) -> anyhow::Result<Pubkey> {
+    Ok(Pubkey::create_with_seed(
+        owner,
+        seed,
+        &solana_system_interface::program::id(),
+    )?)
+}
+
+impl EventProcessor {
+    /// Fetches the current value of the nonce of `owner`, when nonces are enabled, so the next
+    /// buy can be signed on it.
+    pub(crate) async fn refresh_nonce(&self, owner: &Pubkey) -> anyhow::Result<()> {
+        if !self.config.nonce.enabled {
+            return Ok(());
+        }
+
+        let nonce = self.fetch_nonce(owner).await?;
+        debug!(account = %nonce.account, blockhash = %nonce.blockhash, "Nonce refreshed");
+        self.nonces.lock().await.insert(*owner, nonce);
+
+        Ok(())
+    }
+
+    /// The value the nonce account of `owner` holds on chain.
+    pub(crate) async fn fetch_nonce(&self, owner: &Pubkey) -> anyhow::Result<DurableNonce> {
+        let account = nonce_account_address(owner, &self.config.nonce.seed)?;
+        let data = self
+            .solana_api
+            .get_account_info(&account)
+            .await
+            .map_err(|e| {
+                e.context(format!(
+                    "No nonce account {} for {}, create it with create-nonce-account",
+                    account, owner
+                ))
+            })
+            .and_then(|account| Ok(nonce_utils::data_from_account(&account)?))?;
+
+        Ok(DurableNonce {
+            account,
+            authority: *owner,
+            blockhash: data.blockhash(),
+        })
+    }
+
+    /// The nonce of `owner` when it is ready to sign with, without handing it out.
+    pub(crate) async fn peek_nonce(&self, owner: &Pubkey) -> Option<DurableNonce> {
+        self.nonces.lock().await.get(owner).copied()
+    }
+
+    /// Hands out `nonce` for a transaction signed on it, if it is still the current one.
+    async fn take_nonce(&self, nonce: &DurableNonce) -> bool {
+        let mut nonces = self.nonces.lock().await;
+        if nonces.get(&nonce.authority) != Some(nonce) {
+            return false;
+        }
+        nonces.remove(&nonce.authority);
+
+        true
+    }
+
+    /// Gives back a nonce handed out for a transaction that was never sent.
+    pub(crate) async fn release_nonce(&self, nonce: DurableNonce) {
+        self.nonces
+            .lock()
+            .await
+            .entry(nonce.authority)
+            .or_insert(nonce);
+    }
+
+    /// Signs `instructions` on the nonce of `owner` when it is ready, and on the cached
+    /// blockhash otherwise, so nothing is fetched unless it went stale.
+    pub(crate) async fn sign(
+        &self,
+        owner: &Keypair,
+        instructions: &[Instruction],
+        lookup_tables: &[AddressLookupTableAccount],
+    ) -> anyhow::Result<SignedBuy> {
+        let nonce = self.nonces.lock().await.remove(&owner.pubkey());
+        if let Some(nonce) = nonce {
+            let transaction = sign_transaction(
+                owner,
+                &nonce.with_advance(instructions),
+                lookup_tables,
+                nonce.blockhash,
+            );
+            if transaction.is_err() {
+                self.release_nonce(nonce).await;
+            }
+
+            return Ok(SignedBuy {
+                transaction: transaction?,
+                last_valid_block_height: u64::MAX,
+                nonce: Some(nonce),
+            });
+        }
+
+        if self.config.nonce.enabled {
+            info!("Nonce in use by a pending transaction, signing on the latest blockhash");
+        }
+        let (recent_blockhash, last_valid_block_height) = match self.solana_api.recent_blockhash() {
+            Some(cached) if !cached.stale => (cached.blockhash, cached.last_valid_block_height),
+            cached => {
+                debug!(
+                    stale = cached.is_some(),
+                    "No fresh cached blockhash, fetching the latest"
+                );
+                self.solana_api.get_latest_blockhash().await?
+            }
+        };
+
+        Ok(SignedBuy {
+            transaction: sign_transaction(owner, instructions, lookup_tables, recent_blockhash)?,
+            last_valid_block_height,
+            nonce: None,
+        })
+    }
+
+    /// Takes a buy signed ahead of time, if its nonce was not used since.
+    pub(crate) async fn take_presigned(&self, presigned: &SignedBuy) -> bool {
+        match &presigned.nonce {
+            Some(nonce) => self.take_nonce(nonce).await,
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nonced_transactions_advance_the_owner_nonce_first() {
+        let owner = Keypair::new();
+        let nonce = DurableNonce {
+            account: nonce_account_address(&owner.pubkey(), "sniper-nonce").unwrap(),
+            authority: owner.pubkey(),
+            blockhash: Hash::new_unique(),
+        };
+        let transfer = solana_system_interface::instruction::transfer(
+            &owner.pubkey(),
+            &Pubkey::new_unique(),
+            1,
+        );
+
+        let transaction = sign_transaction(
+            &owner,
+            &nonce.with_advance(&[transfer]),
+            &[],
+            nonce.blockhash,
+        )
+        .unwrap();
+
+        assert!(transaction.uses_durable_nonce());
+        assert_eq!(transaction.message.instructions().len(), 2);
+    }
+}
@@ -1,826 +1,629 @@
-use anyhow::{anyhow, Context};
-use bytemuck::bytes_of;
-use futures::StreamExt;
-use safe_transmute::{
-    transmute_many_pedantic, transmute_one_pedantic, transmute_one_to_bytes, transmute_to_bytes,
-};
-use solana_client::rpc_config::{RpcTransactionLogsConfig, RpcTransactionLogsFilter};
-use solana_sdk::{
-    commitment_config::{CommitmentConfig, CommitmentLevel},
-    program_error::ProgramError,
-    pubkey::Pubkey,
-    signature::{Keypair, Signature},
-};
-use solana_transaction_status_client_types::{
-    EncodedTransaction, UiInstruction, UiMessage, UiParsedInstruction,
-};
-use std::{borrow::Cow, convert::identity, str::FromStr};
+use anyhow::Context;
+use metrics::{counter, histogram};
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use std::{fmt, sync::Arc};
+use tracing::{debug, info, instrument, trace, warn};
 use uint::construct_uint;
 
 use crate::{
-    api::solana_rpc::Transaction,
-    raydium::{
-        event_processors::RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID,
-        models::{
-            AccountFlag, AmmInfo, AmmKeys, Market, MarketKeys, MarketState, MarketStateV2, Pool,
-        },
+    dex::{Dex, PoolReserves, SwapPool},
+    raydium::{models::Pool, pool::AmmV4Pool},
+    storage::{
+        models::{Position, TradeRecord, TradeSide, TradeStatus},
+        sqlite::now,
     },
+    telemetry,
+    wallets::Wallet,
 };
 
-pub const AUTHORITY_AMM: &[u8] = b"amm authority";
-pub const ACCOUNT_HEAD_PADDING: &[u8; 5] = b"serum";
-pub const ACCOUNT_TAIL_PADDING: &[u8; 7] = b"padding";
 pub const TEN_THOUSAND: u64 = 10000;
+/// Raydium AMM V4 charges a fixed 0.25% swap fee.
+pub(crate) const SWAP_FEE_NUMERATOR: u64 = 25;
+pub(crate) const SWAP_FEE_DENOMINATOR: u64 = 10000;
+
+use super::{
+    in_flight::InFlightTrade,
+    snipes::SnipePlan,
+    timings::{Stage, TradeTimings},
+    EventProcessor,
+};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[repr(u64)]
+pub enum SwapDirection {
+    /// Input token pc, output token coin
+    PC2Coin = 1u64,
+    /// Input token coin, output token pc
+    Coin2PC = 2u64,
+}
+
+#[allow(clippy::all)]
+mod uint_types {
+    use super::construct_uint;
 
-use super::{EventProcessor, WSOL};
-
-//#[derive(Copy, Clone, Debug, Eq, PartialEq)]
-//#[repr(u64)]
-//pub enum SwapDirection {
-//    /// Input token pc, output token coin
-//    PC2Coin = 1u64,
-//    /// Input token coin, output token pc
-//    Coin2PC = 2u64,
-//}
-//
-//construct_uint! {
-//    pub struct U128(2);
-//}
+    construct_uint! {
+        pub struct U128(2);
+    }
+}
+
+pub use uint_types::U128;
+
+/// A new pool passed over: the target is not on it, or `filters` rule it out. Not worth
+/// stopping for, unlike the errors met while buying.
+#[derive(Debug)]
+pub(crate) struct PoolRejected(pub String);
+
+impl fmt::Display for PoolRejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for PoolRejected {}
 
 impl EventProcessor {
     pub async fn get_pool_from_create_transaction(&self, signature: &str) -> anyhow::Result<Pool> {
-        let transaction = self.solana_api.get_transaction(signature).await?;
+        self.decode_pool_creation(signature, None).await
+    }
 
-        let Transaction {
-            transaction,
-            metadata,
-            signature,
-            ..
-        } = transaction;
-
-        println!("\nMetadata: {:#?}\n", &metadata);
-        println!("\nTransaction: {:#?}\n", &transaction);
-
-        if let EncodedTransaction::Json(ui_transaction) = transaction {
-            if let UiMessage::Parsed(ui_parsed_message) = ui_transaction.message {
-                for instruction in ui_parsed_message.instructions {
-                    if let UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(
-                        parsed_instruction,
-                    )) = instruction
-                    {
-                        if parsed_instruction.program_id
-                            == RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID.to_string()
-                        {
-                            println!("------------ New Pool Detected ------------");
-                            println!("    Tx Signature: {:#?}", &signature);
-                            println!("    Instruction: {:#?}", &parsed_instruction);
-
-                            let amm = &parsed_instruction.accounts[4];
-                            let amm_authority = &parsed_instruction.accounts[5];
-                            let amm_open_orders = &parsed_instruction.accounts[6];
-                            let amm_lp_mint = &parsed_instruction.accounts[7];
-                            let amm_coin_mint = &parsed_instruction.accounts[8];
-                            let amm_coin_vault = &parsed_instruction.accounts[10];
-                            let amm_pc_mint = &parsed_instruction.accounts[9];
-                            let amm_pc_vault = &parsed_instruction.accounts[11];
-                            let amm_target = &parsed_instruction.accounts[13];
-                            let market_program = &parsed_instruction.accounts[15];
-                            let market = &parsed_instruction.accounts[16];
-
-                            let amm_keys = AmmKeys {
-                                amm_pool: Pubkey::from_str(amm).unwrap(),
-                                amm_coin_mint: Pubkey::from_str(amm_coin_mint).unwrap(),
-                                amm_pc_mint: Pubkey::from_str(amm_pc_mint).unwrap(),
-                                amm_authority: Pubkey::from_str(amm_authority).unwrap(),
-                                amm_target: Pubkey::from_str(amm_target).unwrap(),
-                                amm_coin_vault: Pubkey::from_str(amm_coin_vault).unwrap(),
-                                amm_pc_vault: Pubkey::from_str(amm_pc_vault).unwrap(),
-                                amm_lp_mint: Pubkey::from_str(amm_lp_mint).unwrap(),
-                                amm_open_order: Pubkey::from_str(amm_open_orders).unwrap(),
-                                market_program: Pubkey::from_str(market_program).unwrap(),
-                                market: Pubkey::from_str(market).unwrap(),
-                                //fees: None,
-                                //state_data: None,
-                                nonce: 0,
-                            };
-
-                            let amm_coin_initial_balance =
-                                self.solana_api.get_token_balance(amm_coin_vault).await?;
-
-                            println!(
-                                "    AMM Coin Initial Balance: {:#?}",
-                                amm_coin_initial_balance
-                            );
-
-                            let amm_pc_initial_balance =
-                                self.solana_api.get_token_balance(amm_pc_vault).await?;
-                            println!("    AMM PC Initial Balance: {:#?}", amm_pc_initial_balance);
-
-                            let pool = Pool {
-                                amm: amm_keys,
-                                initial_coin_balance: amm_coin_initial_balance
-                                    .amount
-                                    .parse()
-                                    .with_context(|| "Failed to parse initial coin balance")?,
-                                initial_pc_balance: amm_pc_initial_balance
-                                    .amount
-                                    .parse()
-                                    .with_context(|| "Failed to parse initial pc balance")?,
-                            };
-
-                            println!("    Pool: {:#?}", &pool);
-                            println!("-------------------------------------------");
-
-                            return Ok(pool);
-                        }
-                    }
-                }
-            }
+    /// Fetches and decodes the pool creation, marking the stages on `timings` when the pool
+    /// is being sniped.
+    #[instrument(skip(self, timings))]
+    async fn decode_pool_creation(
+        &self,
+        signature: &str,
+        mut timings: Option<&mut TradeTimings>,
+    ) -> anyhow::Result<Pool> {
+        let transaction = self.solana_api.get_transaction(signature).await?;
+        if let Some(timings) = timings.as_deref_mut() {
+            timings.mark(Stage::TransactionFetched);
+        }
+        trace!(?transaction, "Pool creation transaction");
+
+        let pool = self
+            .raydium_v4
+            .decode_creation(&transaction)?
+            .with_context(|| "Pool not found")?;
+        if let Some(timings) = timings {
+            timings.mark(Stage::PoolDecoded);
+            timings.pool_slot = Some(pool.slot);
         }
 
-        Err(anyhow::anyhow!("Pool not found"))
+        self.record_new_pool(&pool)?;
+
+        Ok(pool)
     }
 
+    pub(crate) fn record_new_pool(&self, pool: &Pool) -> anyhow::Result<()> {
+        info!(
+            pool = %pool.amm.amm_pool,
+            coin_mint = %pool.amm.amm_coin_mint,
+            pc_mint = %pool.amm.amm_pc_mint,
+            initial_coin_balance = pool.initial_coin_balance,
+            initial_pc_balance = pool.initial_pc_balance,
+            slot = pool.slot,
+            "New pool detected"
+        );
+        debug!(?pool);
+
+        self.store.save_pool(pool)?;
+        counter!(telemetry::POOLS_DETECTED).increment(1);
+
+        Ok(())
+    }
+
+    #[instrument(skip(self, wallets, simulate_only, timings))]
     pub async fn buy_new_pool(
         &self,
-        owner: &Keypair,
+        wallets: &[Wallet],
         target: Pubkey,
-        amount: u64,
         signature: &str,
         simulate_only: bool,
+        mut timings: TradeTimings,
     ) -> anyhow::Result<()> {
-        println!("RAYDIUM - Signature: {:#?}", &signature);
-
-        let pool = self.get_pool_from_create_transaction(signature).await?;
+        let pool = self
+            .decode_pool_creation(signature, Some(&mut timings))
+            .await?;
 
-        if pool.amm.amm_coin_mint != target || pool.amm.amm_pc_mint != target {
-            return anyhow::Result::Err(anyhow::anyhow!("Target not found in pool creation"));
+        let mints = self.raydium_v4.mints(&pool);
+        if self.raydium_v4.pool_id(&pool) != target && !mints.contains(&target) {
+            return Err(PoolRejected("Target not found in pool creation".to_string()).into());
         }
 
-        self.buy(owner, target, pool, amount, simulate_only).await?;
+        let initial_reserves = pool.initial_reserves();
+        let token_mint_output =
+            self.check_pool_filters(pool.amm.amm_pool, mints, &initial_reserves)?;
+        let market_keys = self.get_market_keys(&pool).await?;
+        timings.mark(Stage::MarketKeysFetched);
+        let plans = self.plan_per_wallet(
+            wallets,
+            &AmmV4Pool {
+                program_id: self.config.programs.raydium_liquidity_pool_v4,
+                amm: pool.amm,
+                market_keys,
+            },
+            token_mint_output,
+        );
+
+        self.buy_with_wallets(plans, initial_reserves, simulate_only, timings)
+            .await?;
+
+        self.track_pool(pool).await?;
 
         Ok(())
     }
 
-    async fn buy(
+    /// Checks the pool, given its mints and initial reserves in the same order, against
+    /// `filters` and returns the mint bought on it, or [`PoolRejected`].
+    pub(crate) fn check_pool_filters(
+        &self,
+        pool: Pubkey,
+        [mint_0, mint_1]: [Pubkey; 2],
+        reserves: &PoolReserves,
+    ) -> anyhow::Result<Pubkey> {
+        let quote_mint = self.config.filters.quote_mint;
+        let (token_mint_output, quote_liquidity) = if mint_0 == quote_mint {
+            (mint_1, reserves.coin_vault)
+        } else if mint_1 == quote_mint {
+            (mint_0, reserves.pc_vault)
+        } else {
+            return Err(PoolRejected(format!(
+                "Pool {} is not paired against quote mint {}",
+                pool, quote_mint
+            ))
+            .into());
+        };
+
+        if quote_liquidity < self.config.filters.min_quote_liquidity {
+            return Err(PoolRejected(format!(
+                "Pool {} quote liquidity {} is below the minimum {}",
+                pool, quote_liquidity, self.config.filters.min_quote_liquidity
+            ))
+            .into());
+        }
+
+        Ok(token_mint_output)
+    }
+
+    #[instrument(
+        name = "trade",
+        skip_all,
+        fields(pool = %plan.pool.id(), output_mint = %plan.output_mint, amount, simulate_only)
+    )]
+    pub(crate) async fn buy<P: SwapPool>(
         &self,
         owner: &Keypair,
-        target: Pubkey,
-        pool: Pool,
+        plan: SnipePlan<P>,
+        initial_reserves: PoolReserves,
         amount: u64,
         simulate_only: bool,
+        mut timings: TradeTimings,
     ) -> anyhow::Result<()> {
-        let token_mint_input = WSOL;
-        let token_mint_output = target;
-        let slippage_bps = 1000_u64;
-        let amount_specified_is_input = true;
-        //    let owner = Keypair::read_from_file(keypair_file_path).expect("Error parsing private key");
-        //
-        //    let token_account_input =
-        //        Pubkey::from_str("GKNaPCWkQfg8KK8gxtnCrVWsgLPUj9AhoywTQ7yX9GBE").unwrap();
-        //    let token_account_output =
-        //        Pubkey::from_str("4oSZPX4QJkpy12VaGuJjRiMaFxAMFdKZpJznSbEFm61h").unwrap();
-        //
-        //    let (amm_keys, market_keys) = get_serum_quote_via_raydium_api(
-        //        &token_mint_output.to_string(),
-        //        &token_mint_input.to_string(),
-        //    )
-        //    .await
-        //    .unwrap();
-        //
-        //    //println!("AMM Keys: {:#?}", &amm_keys);
-        //    //println!("Market Keys: {:#?}", &market_keys);
-        //
+        let pool = plan.pool.id();
+        let token_mint_input = plan.input_mint;
+        let token_mint_output = plan.output_mint;
+
+        // Live when the pool is streamed, e.g. when buying more of a pool already held.
+        let reserves = self
+            .reserves
+            .lock()
+            .await
+            .get(&pool)
+            .copied()
+            .unwrap_or(initial_reserves);
+        let quote = plan.pool.quote(&reserves, &token_mint_input, amount)?;
+        let min_output_amount = match self.config.strategy.min_output_amount {
+            Some(min_output_amount) => min_output_amount,
+            None => Self::min_amount_with_slippage(quote, self.config.strategy.slippage_bps)?,
+        };
 
-        let market_keys = self.get_market_keys(&pool).await?;
-        //let (direction, coin_to_pc) = if token_mint_input == pool.amm.amm_coin_mint
-        //    && token_mint_output == pool.amm.amm_pc_mint
-        //{
-        //    (SwapDirection::Coin2PC, true)
-        //} else {
-        //    (SwapDirection::PC2Coin, false)
-        //};
-        //
-        //let (amm_pool_pc_vault_amount, amm_pool_coin_vault_amount) =
-        //    Self::calc_total_without_take_pnl_no_orderbook(
-        //        pool.initial_pc_balance,
-        //        pool.initial_coin_balance,
-        //        &pool.amm,
-        //    )?;
-        //
-        //let (min_output_amount, other_min_output_amount) = Self::swap_with_slippage(
-        //    amm_pool_pc_vault_amount,
-        //    amm_pool_coin_vault_amount,
-        //    //pool.amm.fees.swap_fee_numerator,
-        //    0,
-        //    //pool.amm.fees.swap_fee_denominator,
-        //    0,
-        //    direction,
-        //    amount,
-        //    amount_specified_is_input,
-        //    slippage_bps,
-        //)?;
-        //
-        //println!("Min Output Amount: {:#?}", &min_output_amount);
-        //println!("Other Min Output Amount: {:#?}", &other_min_output_amount);
-        //let (amm_keys, market_keys) = self.get_serum_quote(&pool).await?;
-        //
-        //    //println!("AMM Keys: {:#?}", &amm_keys);
-        //    //println!("Market Keys: {:#?}", &market_keys);
-        //
-        //    let amount_in: u64 = 143594511; //1_000_000;
-
-        let min_output_amount = 900_000_u64;
-        let instruction_tag = 9u8; // "Swap" tag, https://github.com/reactive-biscuit/raydium-amm/blob/ae039d21cd49ef670d76b3a1cf5485ae0213dc5e/program/src/instruction.rs#L487
-        let mut swap_data = vec![instruction_tag];
-        swap_data.extend_from_slice(&amount.to_le_bytes());
-        swap_data.extend_from_slice(&min_output_amount.to_le_bytes());
-        //
-        //    let swap_accounts = vec![
-        //        AccountMeta::new(TOKEN_PROGRAM, false),
-        //        AccountMeta::new(amm_keys.amm_pool, false),
-        //        AccountMeta::new(amm_keys.amm_authority, false),
-        //        AccountMeta::new(amm_keys.amm_open_order, false),
-        //        AccountMeta::new(amm_keys.amm_coin_vault, false),
-        //        AccountMeta::new(amm_keys.amm_pc_vault, false),
-        //        AccountMeta::new(amm_keys.market_program, false),
-        //        AccountMeta::new(amm_keys.market, false),
-        //        AccountMeta::new(market_keys.bids, false),
-        //        AccountMeta::new(market_keys.asks, false),
-        //        AccountMeta::new(market_keys.event_queue, false),
-        //        AccountMeta::new(market_keys.coin_vault, false),
-        //        AccountMeta::new(market_keys.pc_vault, false),
-        //        AccountMeta::new(market_keys.vault_signer_key, false),
-        //        AccountMeta::new(token_account_output, false),
-        //        AccountMeta::new(token_account_input, false),
-        //        AccountMeta::new(owner.pubkey(), true),
-        //    ];
-        //    let compute_units_price = ComputeBudgetInstruction::set_compute_unit_price(3_000_000);
-        //
-        //    let swap_ix = Instruction {
-        //        program_id: RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID,
-        //        accounts: swap_accounts,
-        //        data: swap_data,
-        //    };
-        //
-        //    let recent_blockhash = rpc_client.get_latest_blockhash().await.unwrap();
-        //    let mut message = VersionedMessage::Legacy(Message::new(&[compute_units_price, swap_ix], None));
-        //    message.set_recent_blockhash(recent_blockhash);
-        //
-        //    let signers: [&dyn Signer; 1] = [&owner];
-        //
-        //    let transaction = VersionedTransaction::try_new(message.clone(), &signers).unwrap();
-        //    let simulation_result = rpc_client
-        //        .simulate_transaction(&transaction)
-        //        .await
-        //        .expect("Simulation failed");
-        //
-        //    println!("Simulation result: {:#?}", simulation_result);
-        //
-        //    let recent_blockhash = rpc_client.get_latest_blockhash().await.unwrap();
-        //    message.set_recent_blockhash(recent_blockhash);
-        //    let transaction = VersionedTransaction::try_new(message.clone(), &signers).unwrap();
-        //
-        //    let result = rpc_client.send_and_confirm_transaction(&transaction).await;
-        //    println!("Transaction result {:#?}", result);
-        //
-        //    Ok(())
-        Ok(())
-    }
+        timings.mark(Stage::QuoteComputed);
+        debug!(quote, min_output_amount, "Quote computed");
 
-    //async fn get_serum_quote(&self, pool: &Pool) -> anyhow::Result<MarketKeys> {
-    //let amm_info = self
-    //    .solana_api
-    //    .get_account::<AmmInfo>(&pool.amm.amm_pool)
-    //    .await
-    //    .unwrap()
-    //    .unwrap();
-    //
-    //let amm_keys = AmmKeys {
-    //    amm_pool: *amm_pool,
-    //    amm_target: amm_info.target_orders,
-    //    amm_coin_vault: amm_info.coin_vault,
-    //    amm_pc_vault: amm_info.pc_vault,
-    //    amm_lp_mint: amm_info.lp_mint,
-    //    amm_open_order: amm_info.open_orders,
-    //    amm_coin_mint: amm_info.coin_vault_mint,
-    //    amm_pc_mint: amm_info.pc_vault_mint,
-    //    amm_authority: compute_amm_authority_id(
-    //        &RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID,
-    //        amm_info.nonce as u8,
-    //    )?,
-    //    market: amm_info.market,
-    //    market_program: amm_info.market_program,
-    //    nonce: amm_info.nonce as u8,
-    //};
-
-    //let market_keys = self.get_market_keys(pool).await?;
-    //Ok(market_keys)
-    //}
-
-    pub async fn process_new_pool(&self, signature: &str) -> anyhow::Result<()> {
-        println!("Signature: {:#?}", &signature);
+        let presigned = plan.presigned.as_ref().filter(|presigned| {
+            presigned.amount == amount && presigned.min_output_amount == min_output_amount
+        });
+        let signed = match presigned {
+            Some(presigned) if self.take_presigned(&presigned.signed).await => {
+                debug!("Sending the presigned buy");
+                presigned.signed.clone()
+            }
+            _ => {
+                let instructions =
+                    plan.instructions(&self.config, &owner.pubkey(), amount, min_output_amount)?;
+                self.sign(owner, &instructions, &self.lookup_tables_for(&pool).await)
+                    .await?
+            }
+        };
+        let transaction = &signed.transaction;
+        timings.mark(Stage::Signed);
+
+        let mut trade = TradeRecord {
+            id: None,
+            amm_pool: pool,
+            owner: owner.pubkey(),
+            side: TradeSide::Buy,
+            input_mint: token_mint_input,
+            output_mint: token_mint_output,
+            amount_in: amount,
+            min_amount_out: min_output_amount,
+            signature: transaction.signatures.first().copied(),
+            status: TradeStatus::Simulated,
+            error: None,
+            created_at: now(),
+        };
 
-        let transaction = self.solana_api.get_transaction(signature).await?;
+        if simulate_only {
+            if let Some(nonce) = signed.nonce {
+                self.release_nonce(nonce).await;
+            }
+            let simulation_result = self.solana_api.simulate_transaction(transaction).await?;
+            info!(err = ?simulation_result.err, "Buy transaction simulated");
+            trace!(?simulation_result);
+
+            trade.error = simulation_result.err.map(|e| e.to_string());
+            let trade_id = self.store.save_trade(&trade)?;
+            self.store.save_trade_timings(trade_id, &timings)?;
+            info!(%timings, "Trade latency");
+            counter!(telemetry::TRANSACTIONS, "status" => TradeStatus::Simulated.as_str())
+                .increment(1);
+
+            return Ok(());
+        }
 
-        let Transaction {
-            transaction,
-            metadata,
-            signature,
-            ..
-        } = transaction;
-
-        println!("\nMetadata: {:#?}\n", &metadata);
-        println!("\nTransaction: {:#?}\n", &transaction);
-
-        if let EncodedTransaction::Json(ui_transaction) = transaction {
-            if let UiMessage::Parsed(ui_parsed_message) = ui_transaction.message {
-                for instruction in ui_parsed_message.instructions {
-                    if let UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(
-                        parsed_instruction,
-                    )) = instruction
-                    {
-                        if parsed_instruction.program_id
-                            == RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID.to_string()
-                        {
-                            println!("------------ New Pool Detected ------------");
-                            println!("    Tx Signature: {:#?}", &signature);
-                            println!("    Instruction: {:#?}", &parsed_instruction);
-
-                            let amm = &parsed_instruction.accounts[4];
-                            let amm_authority = &parsed_instruction.accounts[5];
-                            let amm_open_orders = &parsed_instruction.accounts[6];
-                            let amm_lp_mint = &parsed_instruction.accounts[7];
-                            let amm_coin_mint = &parsed_instruction.accounts[8];
-                            let amm_coin_vault = &parsed_instruction.accounts[10];
-                            let amm_pc_mint = &parsed_instruction.accounts[9];
-                            let amm_pc_vault = &parsed_instruction.accounts[11];
-                            let amm_target = &parsed_instruction.accounts[13];
-                            let market_program = &parsed_instruction.accounts[15];
-                            let market = &parsed_instruction.accounts[16];
-
-                            let amm_keys = AmmKeys {
-                                amm_pool: Pubkey::from_str(amm).unwrap(),
-                                amm_coin_mint: Pubkey::from_str(amm_coin_mint).unwrap(),
-                                amm_pc_mint: Pubkey::from_str(amm_pc_mint).unwrap(),
-                                amm_authority: Pubkey::from_str(amm_authority).unwrap(),
-                                amm_target: Pubkey::from_str(amm_target).unwrap(),
-                                amm_coin_vault: Pubkey::from_str(amm_coin_vault).unwrap(),
-                                amm_pc_vault: Pubkey::from_str(amm_pc_vault).unwrap(),
-                                amm_lp_mint: Pubkey::from_str(amm_lp_mint).unwrap(),
-                                amm_open_order: Pubkey::from_str(amm_open_orders).unwrap(),
-                                market_program: Pubkey::from_str(market_program).unwrap(),
-                                market: Pubkey::from_str(market).unwrap(),
-                                //fees: None,
-                                //state_data: None,
-                                nonce: 0,
-                            };
-
-                            let amm_coin_initial_balance =
-                                self.solana_api.get_token_balance(amm_coin_vault).await?;
-
-                            println!(
-                                "    AMM Coin Initial Balance: {:#?}",
-                                amm_coin_initial_balance
-                            );
-
-                            let amm_pc_initial_balance =
-                                self.solana_api.get_token_balance(amm_pc_vault).await?;
-                            println!("    AMM PC Initial Balance: {:#?}", amm_pc_initial_balance);
-
-                            let pool = Pool {
-                                amm: amm_keys,
-                                initial_coin_balance: amm_coin_initial_balance
-                                    .amount
-                                    .parse()
-                                    .with_context(|| "Failed to parse initial coin balance")?,
-                                initial_pc_balance: amm_pc_initial_balance
-                                    .amount
-                                    .parse()
-                                    .with_context(|| "Failed to parse initial pc balance")?,
-                            };
-
-                            println!("    Pool: {:#?}", &pool);
-                            println!("-------------------------------------------");
-
-                            let mut pools = self.pools.lock().await;
-                            pools.insert(pool.amm.amm_pool, pool.clone());
-
-                            self.subscribe_to_new_pool(pool.amm.amm_pool).await?;
-
-                            return Ok(());
-                        }
-                    }
+        let signature = transaction.signatures[0];
+        let senders = match self.sender.send(transaction, owner).await {
+            Ok(senders) => senders,
+            Err(e) => {
+                // Whether the nonce moved on is unknown: read it back.
+                if signed.nonce.is_some()
+                    && let Err(e) = self.refresh_nonce(&owner.pubkey()).await
+                {
+                    warn!("Failed to refresh the nonce: {:#}", e);
                 }
+                trade.status = TradeStatus::Failed;
+                trade.error = Some(format!("{:#}", e));
+                let trade_id = self.store.save_trade(&trade)?;
+                self.store.save_trade_timings(trade_id, &timings)?;
+                counter!(telemetry::TRANSACTIONS, "status" => TradeStatus::Failed.as_str())
+                    .increment(1);
+
+                return Err(e);
+            }
+        };
+        timings.mark(Stage::Sent);
+        let detection_to_send = timings.elapsed(Stage::Sent).unwrap_or_default();
+        histogram!(telemetry::DETECTION_TO_SEND).record(detection_to_send.as_secs_f64());
+        counter!(telemetry::TRANSACTIONS, "status" => TradeStatus::Sent.as_str()).increment(1);
+        info!(%signature, ?senders, ?detection_to_send, "Buy transaction sent");
+
+        // The transaction is out: a store failure must not stop it from being tracked, or its
+        // nonce would never be released.
+        trade.status = TradeStatus::Sent;
+        let trade_id = match self.store.save_trade(&trade) {
+            Ok(trade_id) => Some(trade_id),
+            Err(e) => {
+                warn!(%signature, "Failed to save the sent trade: {:#}", e);
+                None
             }
+        };
+        if let Some(trade_id) = trade_id
+            && let Err(e) = self.store.save_trade_timings(trade_id, &timings)
+        {
+            warn!(trade_id, "Failed to save trade timings: {:#}", e);
         }
+        self.add_in_flight(InFlightTrade {
+            trade_id,
+            signature,
+            last_valid_block_height: signed.last_valid_block_height,
+            nonce: signed.nonce,
+            timings,
+            senders,
+            transaction: transaction.clone(),
+            payer: Arc::new(owner.insecure_clone()),
+            position: Some(Position {
+                owner: owner.pubkey(),
+                mint: token_mint_output,
+                amm_pool: pool,
+                quote_amount: amount,
+                token_amount: quote,
+                entry_signature: Some(signature),
+                opened_at: trade.created_at,
+            }),
+        })
+        .await;
 
         Ok(())
     }
 
-    pub(crate) async fn subscribe_to_new_pool(&self, pubkey: Pubkey) -> anyhow::Result<()> {
-        println!("Subscribing to new pool: {:#?}", &pubkey);
-        let (mut accounts, unsubscriber) = self
-            .ws_client
-            .logs_subscribe(
-                RpcTransactionLogsFilter::Mentions(vec![pubkey.to_string()]),
-                RpcTransactionLogsConfig {
-                    commitment: Some(CommitmentConfig {
-                        commitment: CommitmentLevel::Processed,
-                    }),
-                },
-            )
-            .await?;
+    #[instrument(skip(self))]
+    pub async fn process_new_pool(self: &Arc<Self>, signature: &str) -> anyhow::Result<()> {
+        let pool = self.get_pool_from_create_transaction(signature).await?;
+        let amm_pool = pool.amm.amm_pool;
 
-        let mut subscriptions = self.subscriptions.lock().await;
-        subscriptions.insert(pubkey, unsubscriber);
+        self.track_pool(pool).await?;
+        self.subscribe_to_new_pool(amm_pool).await?;
 
-        while let Some(response) = accounts.next().await {
-            let logs = response.value.logs.clone();
-            let signature = response.value.signature.clone();
+        Ok(())
+    }
 
-            for log in &logs {
-                if log.to_lowercase().ends_with("swap") {
-                    println!("Signature: {:#?}", &signature);
-                    println!("------------------ Swap Detected ------------------");
-                    println!("Log: {:#?}", &log);
-                    //found = true;
-                    //break;
-                }
+    /// Keeps the pool in memory and flags it in the store so it is reloaded on restart.
+    pub(super) async fn track_pool(&self, pool: Pool) -> anyhow::Result<()> {
+        self.store.set_pool_tracked(&pool.amm.amm_pool, true)?;
 
-                if log.to_lowercase().ends_with("swap2")
-                    || log.to_lowercase().ends_with("multiswap")
-                {
-                    println!("Signature: {:#?}", &signature);
-                    println!("Log: {:#?}", &log);
-                }
-            }
-        }
+        let mut pools = self.pools.lock().await;
+        pools.insert(pool.amm.amm_pool, pool);
 
         Ok(())
     }
 
-    pub(crate) async fn get_market_keys(&self, pool: &Pool) -> anyhow::Result<MarketKeys> {
-        let account_data = self.solana_api.get_account_data(&pool.amm.market).await?;
-        let words = Self::remove_dex_account_padding(&account_data).map_err(anyhow::Error::msg)?;
-
-        let market_state: MarketState = {
-            let account_flags = Market::account_flags(&account_data)?;
-            if account_flags.intersects(AccountFlag::Permissioned) {
-                let state = transmute_one_pedantic::<MarketStateV2>(transmute_to_bytes(&words))
-                    .map_err(|e| e.without_src())?;
-                //state.check_flags(true)?;
-                state.inner
-            } else {
-                let state = transmute_one_pedantic::<MarketState>(transmute_to_bytes(&words))
-                    .map_err(|e| e.without_src())?;
-                //state.check_flags(true)?;
-                state
-            }
+    pub(crate) fn swap_exact_amount(
+        pc_vault_amount: u64,
+        coin_vault_amount: u64,
+        swap_fee_numerator: u64,
+        swap_fee_denominator: u64,
+        swap_direction: SwapDirection,
+        amount_specified: u64,
+        swap_base_in: bool,
+    ) -> anyhow::Result<u64> {
+        let other_amount_threshold = if swap_base_in {
+            let swap_fee = U128::from(amount_specified)
+                .checked_mul(swap_fee_numerator.into())
+                .and_then(|fee| fee.checked_ceil_div(swap_fee_denominator.into()))
+                .with_context(|| "Swap fee overflow")?
+                .0;
+            let swap_in_after_deduct_fee = U128::from(amount_specified)
+                .checked_sub(swap_fee)
+                .with_context(|| "Swap fee exceeds the amount in")?;
+
+            Self::swap_token_amount_base_in(
+                swap_in_after_deduct_fee,
+                pc_vault_amount.into(),
+                coin_vault_amount.into(),
+                swap_direction,
+            )?
+        } else {
+            let swap_in_before_add_fee = Self::swap_token_amount_base_out(
+                amount_specified.into(),
+                pc_vault_amount.into(),
+                coin_vault_amount.into(),
+                swap_direction,
+            )?;
+            let fee_denominator = swap_fee_denominator
+                .checked_sub(swap_fee_numerator)
+                .with_context(|| "Swap fee numerator exceeds its denominator")?;
+
+            swap_in_before_add_fee
+                .checked_mul(swap_fee_denominator.into())
+                .and_then(|amount| amount.checked_ceil_div(fee_denominator.into()))
+                .with_context(|| "Swap fee overflow")?
+                .0
+        };
+
+        to_u64(other_amount_threshold)
+    }
+
+    fn min_amount_with_slippage(amount: u64, slippage_bps: u64) -> anyhow::Result<u64> {
+        let kept_bps = TEN_THOUSAND
+            .checked_sub(slippage_bps)
+            .with_context(|| format!("Slippage of {} bps is over 100%", slippage_bps))?;
+
+        // Below `amount`, so it always fits back.
+        Ok((amount as u128 * kept_bps as u128 / TEN_THOUSAND as u128) as u64)
+    }
+
+    pub fn swap_token_amount_base_in(
+        amount_in: U128,
+        total_pc_without_take_pnl: U128,
+        total_coin_without_take_pnl: U128,
+        swap_direction: SwapDirection,
+    ) -> anyhow::Result<U128> {
+        let (reserve_in, reserve_out) = match swap_direction {
+            // (x + delta_x) * (y + delta_y) = x * y
+            // (coin + amount_in) * (pc - amount_out) = coin * pc
+            // => amount_out = pc - coin * pc / (coin + amount_in)
+            // => amount_out = ((pc * coin + pc * amount_in) - coin * pc) / (coin + amount_in)
+            // => amount_out =  pc * amount_in / (coin + amount_in)
+            SwapDirection::Coin2PC => (total_coin_without_take_pnl, total_pc_without_take_pnl),
+            // (x + delta_x) * (y + delta_y) = x * y
+            // (pc + amount_in) * (coin - amount_out) = coin * pc
+            // => amount_out = coin - coin * pc / (pc + amount_in)
+            // => amount_out = (coin * pc + coin * amount_in - coin * pc) / (pc + amount_in)
+            // => amount_out = coin * amount_in / (pc + amount_in)
+            SwapDirection::PC2Coin => (total_pc_without_take_pnl, total_coin_without_take_pnl),
         };
 
-        println!("Market State: {:#?}", &market_state);
-
-        let vault_signer_key = Self::gen_vault_signer_key(
-            market_state.vault_signer_nonce,
-            &pool.amm.market,
-            &pool.amm.market_program,
-        )?;
-
-        let market_keys = MarketKeys {
-            event_queue: Pubkey::try_from(transmute_one_to_bytes(&identity(market_state.event_q)))
-                .unwrap(),
-            bids: Pubkey::try_from(transmute_one_to_bytes(&identity(market_state.bids))).unwrap(),
-            asks: Pubkey::try_from(transmute_one_to_bytes(&identity(market_state.asks))).unwrap(),
-            coin_vault: Pubkey::try_from(transmute_one_to_bytes(&identity(
-                market_state.coin_vault,
-            )))
-            .unwrap(),
-            pc_vault: Pubkey::try_from(transmute_one_to_bytes(&identity(market_state.pc_vault)))
-                .unwrap(),
-            vault_signer_key,
+        let denominator = reserve_in
+            .checked_add(amount_in)
+            .with_context(|| "Reserve overflow")?;
+        reserve_out
+            .checked_mul(amount_in)
+            .and_then(|numerator| numerator.checked_div(denominator))
+            .with_context(|| format!("Cannot swap {} in on an empty pool", amount_in))
+    }
+
+    pub fn swap_token_amount_base_out(
+        amount_out: U128,
+        total_pc_without_take_pnl: U128,
+        total_coin_without_take_pnl: U128,
+        swap_direction: SwapDirection,
+    ) -> anyhow::Result<U128> {
+        let (reserve_in, reserve_out) = match swap_direction {
+            // (x + delta_x) * (y + delta_y) = x * y
+            // (coin + amount_in) * (pc - amount_out) = coin * pc
+            // => amount_in = coin * pc / (pc - amount_out) - coin
+            // => amount_in = (coin * pc - pc * coin + amount_out * coin) / (pc - amount_out)
+            // => amount_in = (amount_out * coin) / (pc - amount_out)
+            SwapDirection::Coin2PC => (total_coin_without_take_pnl, total_pc_without_take_pnl),
+            // (x + delta_x) * (y + delta_y) = x * y
+            // (pc + amount_in) * (coin - amount_out) = coin * pc
+            // => amount_in = coin * pc / (coin - amount_out) - pc
+            // => amount_in = (coin * pc - pc * coin + pc * amount_out) / (coin - amount_out)
+            // => amount_in = (pc * amount_out) / (coin - amount_out)
+            SwapDirection::PC2Coin => (total_pc_without_take_pnl, total_coin_without_take_pnl),
         };
 
-        Ok(market_keys)
+        let denominator = reserve_out
+            .checked_sub(amount_out)
+            .filter(|denominator| !denominator.is_zero())
+            .with_context(|| {
+                format!(
+                    "Cannot swap {} out of a reserve of {}",
+                    amount_out, reserve_out
+                )
+            })?;
+        Ok(reserve_in
+            .checked_mul(amount_out)
+            .and_then(|numerator| numerator.checked_ceil_div(denominator))
+            .with_context(|| "Swap amount overflow")?
+            .0)
     }
+}
 
-    fn remove_dex_account_padding<'a>(data: &'a [u8]) -> Result<Cow<'a, [u64]>, String> {
-        let head = &data[..ACCOUNT_HEAD_PADDING.len()];
-        if data.len() < ACCOUNT_HEAD_PADDING.len() + ACCOUNT_TAIL_PADDING.len() {
-            return Err(format!(
-                "dex account length {} is too small to contain valid padding",
-                data.len()
-            ));
-        }
+fn to_u64(value: U128) -> anyhow::Result<u64> {
+    anyhow::ensure!(
+        value <= U128::from(u64::MAX),
+        "Swap amount {} overflows u64",
+        value
+    );
 
-        if head != ACCOUNT_HEAD_PADDING {
-            return Err("dex account head padding mismatch".to_string());
-        }
+    Ok(value.as_u64())
+}
+
+pub trait CheckedCeilDiv: Sized {
+    /// Perform ceiling division
+    fn checked_ceil_div(&self, rhs: Self) -> Option<(Self, Self)>;
+}
 
-        let tail = &data[data.len() - ACCOUNT_TAIL_PADDING.len()..];
-        if tail != ACCOUNT_TAIL_PADDING {
-            return Err("dex account tail padding mismatch".to_string());
+impl CheckedCeilDiv for u128 {
+    fn checked_ceil_div(&self, mut rhs: Self) -> Option<(Self, Self)> {
+        let mut quotient = self.checked_div(rhs)?;
+        // Avoid dividing a small number by a big one and returning 1, and instead
+        // fail.
+        if quotient == 0 {
+            // return None;
+            if self.checked_mul(2_u128)? >= rhs {
+                return Some((1, 0));
+            } else {
+                return Some((0, 0));
+            }
         }
 
-        let inner_data_range =
-            ACCOUNT_HEAD_PADDING.len()..(data.len() - ACCOUNT_TAIL_PADDING.len());
+        // Ceiling the destination amount if there's any remainder, which will
+        // almost always be the case.
+        let remainder = self.checked_rem(rhs)?;
+        if remainder > 0 {
+            quotient = quotient.checked_add(1)?;
+            // calculate the minimum amount needed to get the dividend amount to
+            // avoid truncating too much
+            rhs = self.checked_div(quotient)?;
+            let remainder = self.checked_rem(quotient)?;
+            if remainder > 0 {
+                rhs = rhs.checked_add(1)?;
+            }
+        }
+        Some((quotient, rhs))
+    }
+}
 
-        let inner = &data[inner_data_range];
-        let words: Cow<'a, [u64]> = match transmute_many_pedantic::<u64>(inner) {
-            Ok(word_slice) => Cow::Borrowed(word_slice),
-            Err(transmute_error) => {
-                let word_vec = transmute_error.copy().map_err(|e| e.to_string())?;
-                Cow::Owned(word_vec)
+impl CheckedCeilDiv for U128 {
+    fn checked_ceil_div(&self, mut rhs: Self) -> Option<(Self, Self)> {
+        let mut quotient = self.checked_div(rhs)?;
+        // Avoid dividing a small number by a big one and returning 1, and instead
+        // fail.
+        let zero = U128::from(0);
+        let one = U128::from(1);
+        if quotient.is_zero() {
+            // return None;
+            if self.checked_mul(U128::from(2))? >= rhs {
+                return Some((one, zero));
+            } else {
+                return Some((zero, zero));
             }
-        };
+        }
 
-        Ok(words)
+        // Ceiling the destination amount if there's any remainder, which will
+        // almost always be the case.
+        let remainder = self.checked_rem(rhs)?;
+        if remainder > zero {
+            quotient = quotient.checked_add(one)?;
+            // calculate the minimum amount needed to get the dividend amount to
+            // avoid truncating too much
+            rhs = self.checked_div(quotient)?;
+            let remainder = self.checked_rem(quotient)?;
+            if remainder > zero {
+                rhs = rhs.checked_add(one)?;
+            }
+        }
+        Some((quotient, rhs))
     }
+}
 
-    #[inline]
-    fn gen_vault_signer_key(
-        nonce: u64,
-        market: &Pubkey,
-        program_id: &Pubkey,
-    ) -> Result<Pubkey, ProgramError> {
-        let seeds = Self::gen_vault_signer_seeds(&nonce, market);
-        Ok(Pubkey::create_program_address(&seeds, program_id)?)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn min_amount_with_slippage_holds_for_large_quotes() {
+        // A 9-decimal token quoted in the billions overflows u64 once multiplied by the bps.
+        let quote = 5_000_000_000_000_000_000;
+
+        assert_eq!(
+            EventProcessor::min_amount_with_slippage(quote, 1_000).unwrap(),
+            4_500_000_000_000_000_000
+        );
+        assert_eq!(
+            EventProcessor::min_amount_with_slippage(u64::MAX, 0).unwrap(),
+            u64::MAX
+        );
+        assert!(EventProcessor::min_amount_with_slippage(quote, 10_001).is_err());
     }
 
-    fn gen_vault_signer_seeds<'a>(nonce: &'a u64, market: &'a Pubkey) -> [&'a [u8]; 2] {
-        [market.as_ref(), bytes_of(nonce)]
+    #[test]
+    fn swapping_out_the_whole_reserve_is_an_error() {
+        let (pc, coin) = (1_000_000u64, 2_000_000u64);
+
+        for amount_out in [coin, coin + 1] {
+            assert!(EventProcessor::swap_exact_amount(
+                pc,
+                coin,
+                SWAP_FEE_NUMERATOR,
+                SWAP_FEE_DENOMINATOR,
+                SwapDirection::PC2Coin,
+                amount_out,
+                false,
+            )
+            .is_err());
+        }
     }
 
-    //fn swap_with_slippage(
-    //    pc_vault_amount: u64,
-    //    coin_vault_amount: u64,
-    //    swap_fee_numerator: u64,
-    //    swap_fee_denominator: u64,
-    //    swap_direction: SwapDirection,
-    //    amount_specified: u64,
-    //    swap_base_in: bool,
-    //    slippage_bps: u64,
-    //) -> anyhow::Result<(u64, u64)> {
-    //    let other_amount_threshold = Self::swap_exact_amount(
-    //        pc_vault_amount,
-    //        coin_vault_amount,
-    //        swap_fee_numerator,
-    //        swap_fee_denominator,
-    //        swap_direction,
-    //        amount_specified,
-    //        swap_base_in,
-    //    )?;
-    //
-    //    let quote = other_amount_threshold;
-    //    let other_amount_threshold = if swap_base_in {
-    //        // min out
-    //        Self::min_amount_with_slippage(other_amount_threshold, slippage_bps)
-    //    } else {
-    //        // max in
-    //        Self::max_amount_with_slippage(other_amount_threshold, slippage_bps)
-    //    };
-    //
-    //    Ok((quote, other_amount_threshold))
-    //
-    //    //let other_amount_threshold = if swap_base_in {
-    //    //    // min out
-    //    //    Self::min_amount_with_slippage(other_amount_threshold, slippage_bps)
-    //    //} else {
-    //    //    // max in
-    //    //    Self::max_amount_with_slippage(other_amount_threshold, slippage_bps)
-    //    //};
-    //    //Ok(other_amount_threshold)
-    //}
-    //
-    //fn swap_exact_amount(
-    //    pc_vault_amount: u64,
-    //    coin_vault_amount: u64,
-    //    swap_fee_numerator: u64,
-    //    swap_fee_denominator: u64,
-    //    swap_direction: SwapDirection,
-    //    amount_specified: u64,
-    //    swap_base_in: bool,
-    //) -> anyhow::Result<u64> {
-    //    let other_amount_threshold = if swap_base_in {
-    //        let swap_fee = U128::from(amount_specified)
-    //            .checked_mul(swap_fee_numerator.into())
-    //            .unwrap()
-    //            .checked_ceil_div(swap_fee_denominator.into())
-    //            .unwrap()
-    //            .0;
-    //        let swap_in_after_deduct_fee =
-    //            U128::from(amount_specified).checked_sub(swap_fee).unwrap();
-    //
-    //        Self::swap_token_amount_base_in(
-    //            swap_in_after_deduct_fee,
-    //            pc_vault_amount.into(),
-    //            coin_vault_amount.into(),
-    //            swap_direction,
-    //        )
-    //        .as_u64()
-    //    } else {
-    //        let swap_in_before_add_fee = Self::swap_token_amount_base_out(
-    //            amount_specified.into(),
-    //            pc_vault_amount.into(),
-    //            coin_vault_amount.into(),
-    //            swap_direction,
-    //        );
-    //
-    //        swap_in_before_add_fee
-    //            .checked_mul(swap_fee_denominator.into())
-    //            .unwrap()
-    //            .checked_ceil_div(
-    //                (swap_fee_denominator
-    //                    .checked_sub(swap_fee_numerator)
-    //                    .unwrap())
-    //                .into(),
-    //            )
-    //            .unwrap()
-    //            .0
-    //            .as_u64()
-    //    };
-    //
-    //    Ok(other_amount_threshold)
-    //}
-    //
-    //fn max_amount_with_slippage(input_amount: u64, slippage_bps: u64) -> u64 {
-    //    input_amount
-    //        .checked_mul(slippage_bps.checked_add(TEN_THOUSAND).unwrap())
-    //        .unwrap()
-    //        .checked_div(TEN_THOUSAND)
-    //        .unwrap()
-    //}
-    //
-    //fn min_amount_with_slippage(input_amount: u64, slippage_bps: u64) -> u64 {
-    //    input_amount
-    //        .checked_mul(TEN_THOUSAND.checked_sub(slippage_bps).unwrap())
-    //        .unwrap()
-    //        .checked_div(TEN_THOUSAND)
-    //        .unwrap()
-    //}
-    //
-    //pub fn swap_token_amount_base_in(
-    //    amount_in: U128,
-    //    total_pc_without_take_pnl: U128,
-    //    total_coin_without_take_pnl: U128,
-    //    swap_direction: SwapDirection,
-    //) -> U128 {
-    //    match swap_direction {
-    //        SwapDirection::Coin2PC => {
-    //            // (x + delta_x) * (y + delta_y) = x * y
-    //            // (coin + amount_in) * (pc - amount_out) = coin * pc
-    //            // => amount_out = pc - coin * pc / (coin + amount_in)
-    //            // => amount_out = ((pc * coin + pc * amount_in) - coin * pc) / (coin + amount_in)
-    //            // => amount_out =  pc * amount_in / (coin + amount_in)
-    //            let denominator = total_coin_without_take_pnl.checked_add(amount_in).unwrap();
-    //            total_pc_without_take_pnl
-    //                .checked_mul(amount_in)
-    //                .unwrap()
-    //                .checked_div(denominator)
-    //                .unwrap()
-    //        }
-    //        SwapDirection::PC2Coin => {
-    //            // (x + delta_x) * (y + delta_y) = x * y
-    //            // (pc + amount_in) * (coin - amount_out) = coin * pc
-    //            // => amount_out = coin - coin * pc / (pc + amount_in)
-    //            // => amount_out = (coin * pc + coin * amount_in - coin * pc) / (pc + amount_in)
-    //            // => amount_out = coin * amount_in / (pc + amount_in)
-    //            let denominator = total_pc_without_take_pnl.checked_add(amount_in).unwrap();
-    //            total_coin_without_take_pnl
-    //                .checked_mul(amount_in)
-    //                .unwrap()
-    //                .checked_div(denominator)
-    //                .unwrap()
-    //        }
-    //    }
-    //}
-    //
-    //pub fn swap_token_amount_base_out(
-    //    amount_out: U128,
-    //    total_pc_without_take_pnl: U128,
-    //    total_coin_without_take_pnl: U128,
-    //    swap_direction: SwapDirection,
-    //) -> U128 {
-    //    match swap_direction {
-    //        SwapDirection::Coin2PC => {
-    //            // (x + delta_x) * (y + delta_y) = x * y
-    //            // (coin + amount_in) * (pc - amount_out) = coin * pc
-    //            // => amount_in = coin * pc / (pc - amount_out) - coin
-    //            // => amount_in = (coin * pc - pc * coin + amount_out * coin) / (pc - amount_out)
-    //            // => amount_in = (amount_out * coin) / (pc - amount_out)
-    //            let denominator = total_pc_without_take_pnl.checked_sub(amount_out).unwrap();
-    //            total_coin_without_take_pnl
-    //                .checked_mul(amount_out)
-    //                .unwrap()
-    //                .checked_ceil_div(denominator)
-    //                .unwrap()
-    //                .0
-    //        }
-    //        SwapDirection::PC2Coin => {
-    //            // (x + delta_x) * (y + delta_y) = x * y
-    //            // (pc + amount_in) * (coin - amount_out) = coin * pc
-    //            // => amount_out = coin - coin * pc / (pc + amount_in)
-    //            // => amount_out = (coin * pc + coin * amount_in - coin * pc) / (pc + amount_in)
-    //            // => amount_out = coin * amount_in / (pc + amount_in)
-    //
-    //            // => amount_in = coin * pc / (coin - amount_out) - pc
-    //            // => amount_in = (coin * pc - pc * coin + pc * amount_out) / (coin - amount_out)
-    //            // => amount_in = (pc * amount_out) / (coin - amount_out)
-    //            let denominator = total_coin_without_take_pnl.checked_sub(amount_out).unwrap();
-    //            total_pc_without_take_pnl
-    //                .checked_mul(amount_out)
-    //                .unwrap()
-    //                .checked_ceil_div(denominator)
-    //                .unwrap()
-    //                .0
-    //        }
-    //    }
-    //}
-    //
-    //fn calc_total_without_take_pnl_no_orderbook<'a>(
-    //    pc_amount: u64,
-    //    coin_amount: u64,
-    //    amm: &'a AmmKeys,
-    //) -> anyhow::Result<(u64, u64)> {
-    //    let total_pc_without_take_pnl = pc_amount
-    //        //.checked_sub(amm.state_data.need_take_pnl_pc)
-    //        .checked_sub(0)
-    //        .with_context(|| "Failed to subtract take pnl pc")?;
-    //
-    //    let total_coin_without_take_pnl = coin_amount
-    //        //.checked_sub(amm.state_data.need_take_pnl_coin)
-    //        .checked_sub(0)
-    //        .with_context(|| "Failed to subtract take pnl coin")?;
-    //
-    //    Ok((total_pc_without_take_pnl, total_coin_without_take_pnl))
-    //}
+    #[test]
+    fn quotes_both_ways_with_the_fee() {
+        let (pc, coin) = (1_000_000_000u64, 2_000_000_000u64);
+
+        // 1000 in, less the 0.25% fee rounded up, at 2 coin per pc.
+        let out = EventProcessor::swap_exact_amount(
+            pc,
+            coin,
+            SWAP_FEE_NUMERATOR,
+            SWAP_FEE_DENOMINATOR,
+            SwapDirection::PC2Coin,
+            1_000,
+            true,
+        )
+        .unwrap();
+        assert_eq!(out, 1_993);
+
+        let needed = EventProcessor::swap_exact_amount(
+            pc,
+            coin,
+            SWAP_FEE_NUMERATOR,
+            SWAP_FEE_DENOMINATOR,
+            SwapDirection::PC2Coin,
+            out,
+            false,
+        )
+        .unwrap();
+        assert!((1_000..=1_001).contains(&needed));
+    }
 }
-
-//pub trait CheckedCeilDiv: Sized {
-//    /// Perform ceiling division
-//    fn checked_ceil_div(&self, rhs: Self) -> Option<(Self, Self)>;
-//}
-
-//impl CheckedCeilDiv for u128 {
-//    fn checked_ceil_div(&self, mut rhs: Self) -> Option<(Self, Self)> {
-//        let mut quotient = self.checked_div(rhs)?;
-//        // Avoid dividing a small number by a big one and returning 1, and instead
-//        // fail.
-//        if quotient == 0 {
-//            // return None;
-//            if self.checked_mul(2_u128)? >= rhs {
-//                return Some((1, 0));
-//            } else {
-//                return Some((0, 0));
-//            }
-//        }
-//
-//        // Ceiling the destination amount if there's any remainder, which will
-//        // almost always be the case.
-//        let remainder = self.checked_rem(rhs)?;
-//        if remainder > 0 {
-//            quotient = quotient.checked_add(1)?;
-//            // calculate the minimum amount needed to get the dividend amount to
-//            // avoid truncating too much
-//            rhs = self.checked_div(quotient)?;
-//            let remainder = self.checked_rem(quotient)?;
-//            if remainder > 0 {
-//                rhs = rhs.checked_add(1)?;
-//            }
-//        }
-//        Some((quotient, rhs))
-//    }
-//}
-//
-//impl CheckedCeilDiv for U128 {
-//    fn checked_ceil_div(&self, mut rhs: Self) -> Option<(Self, Self)> {
-//        let mut quotient = self.checked_div(rhs)?;
-//        // Avoid dividing a small number by a big one and returning 1, and instead
-//        // fail.
-//        let zero = U128::from(0);
-//        let one = U128::from(1);
-//        if quotient.is_zero() {
-//            // return None;
-//            if self.checked_mul(U128::from(2))? >= rhs {
-//                return Some((one, zero));
-//            } else {
-//                return Some((zero, zero));
-//            }
-//        }
-//
-//        // Ceiling the destination amount if there's any remainder, which will
-//        // almost always be the case.
-//        let remainder = self.checked_rem(rhs)?;
-//        if remainder > zero {
-//            quotient = quotient.checked_add(one)?;
-//            // calculate the minimum amount needed to get the dividend amount to
-//            // avoid truncating too much
-//            rhs = self.checked_div(quotient)?;
-//            let remainder = self.checked_rem(quotient)?;
-//            if remainder > zero {
-//                rhs = rhs.checked_add(one)?;
-//            }
-//        }
-//        Some((quotient, rhs))
-//    }
-//}
@@ -0,0 +1,172 @@
+use metrics::{counter, gauge};
+use solana_sdk::pubkey::Pubkey;
+use tracing::{debug, info, instrument};
+
+use crate::{
+    config::BuyOn,
+    dex::Dex,
+    pump_fun::{
+        bonding_curve_address, curve_progress_bps, events::PumpFunEvent, SOLD_OUT_PROGRESS_BPS,
+    },
+    telemetry,
+    wallets::Wallet,
+};
+
+use super::{pool_created::PoolRejected, timings::TradeTimings, EventProcessor};
+
+/// What we know of the bonding curve of a target launched on Pump.fun.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct CurveProgress {
+    pub bonding_curve: Pubkey,
+    pub progress_bps: u64,
+    pub complete: bool,
+    /// Bought on the curve at launch.
+    pub bought: bool,
+}
+
+impl EventProcessor {
+    /// Follows the target along its bonding curve, buying it at launch when
+    /// `pump_fun.buy_on` says so.
+    #[instrument(skip(self, wallets, logs, simulate_only, timings))]
+    pub(crate) async fn handle_pump_fun_events(
+        &self,
+        wallets: &[Wallet],
+        target: Pubkey,
+        logs: &[String],
+        signature: &str,
+        simulate_only: bool,
+        timings: TradeTimings,
+    ) -> anyhow::Result<()> {
+        let mut timings = Some(timings);
+
+        for event in PumpFunEvent::from_logs(logs) {
+            match event {
+                PumpFunEvent::Create(create) if create.mint == target => {
+                    info!(
+                        %target,
+                        bonding_curve = %create.bonding_curve,
+                        name = %create.name,
+                        symbol = %create.symbol,
+                        "Pump.fun launch detected"
+                    );
+                    self.pump_fun_curves.lock().await.insert(
+                        target,
+                        CurveProgress {
+                            bonding_curve: create.bonding_curve,
+                            progress_bps: 0,
+                            complete: false,
+                            bought: false,
+                        },
+                    );
+
+                    if self.config.pump_fun.buy_on == BuyOn::Launch
+                        && let Some(timings) = timings.take()
+                    {
+                        self.buy_pump_fun_launch(
+                            wallets,
+                            target,
+                            signature,
+                            simulate_only,
+                            timings,
+                        )
+                        .await?;
+                        if let Some(curve) = self.pump_fun_curves.lock().await.get_mut(&target) {
+                            curve.bought = true;
+                        }
+                    }
+                }
+                PumpFunEvent::Trade(trade) if trade.mint == target => {
+                    let progress_bps = curve_progress_bps(trade.virtual_token_reserves);
+                    self.pump_fun_curves
+                        .lock()
+                        .await
+                        .entry(target)
+                        .or_insert_with(|| CurveProgress {
+                            bonding_curve: bonding_curve_address(
+                                &self.pump_fun.program_id,
+                                &target,
+                            ),
+                            progress_bps,
+                            complete: false,
+                            bought: false,
+                        })
+                        .progress_bps = progress_bps;
+                    debug!(
+                        %target,
+                        is_buy = trade.is_buy,
+                        sol_amount = trade.sol_amount,
+                        token_amount = trade.token_amount,
+                        progress_bps,
+                        "Pump.fun trade"
+                    );
+                    gauge!(telemetry::PUMP_FUN_CURVE_PROGRESS, "mint" => target.to_string())
+                        .set(progress_bps as f64);
+                }
+                PumpFunEvent::Complete(complete) if complete.mint == target => {
+                    info!(
+                        %target,
+                        bonding_curve = %complete.bonding_curve,
+                        "Bonding curve complete, waiting for the migration"
+                    );
+                    let mut curves = self.pump_fun_curves.lock().await;
+                    let curve = curves.entry(target).or_insert(CurveProgress {
+                        bonding_curve: complete.bonding_curve,
+                        progress_bps: 0,
+                        complete: false,
+                        bought: false,
+                    });
+                    curve.progress_bps = SOLD_OUT_PROGRESS_BPS;
+                    curve.complete = true;
+                    gauge!(telemetry::PUMP_FUN_CURVE_PROGRESS, "mint" => target.to_string())
+                        .set(curve.progress_bps as f64);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Recognises a Raydium pool created for the target as the migration of the curve we
+    /// followed, if any.
+    pub(crate) async fn take_pump_fun_migration(&self, target: &Pubkey) -> Option<CurveProgress> {
+        let curve = self.pump_fun_curves.lock().await.remove(target)?;
+        info!(
+            %target,
+            bonding_curve = %curve.bonding_curve,
+            complete = curve.complete,
+            bought_at_launch = curve.bought,
+            "Pump.fun token migrated to Raydium"
+        );
+        counter!(telemetry::PUMP_FUN_MIGRATIONS).increment(1);
+
+        Some(curve)
+    }
+
+    /// Buys the target on its bonding curve with every wallet, right after its creation.
+    async fn buy_pump_fun_launch(
+        &self,
+        wallets: &[Wallet],
+        target: Pubkey,
+        signature: &str,
+        simulate_only: bool,
+        mut timings: TradeTimings,
+    ) -> anyhow::Result<()> {
+        let (creation, pool, initial_reserves) = self
+            .decode_new_pool(&self.pump_fun, signature, &mut timings)
+            .await?;
+        if creation.mint != target {
+            return Err(PoolRejected("Target not found in Pump.fun launch".to_string()).into());
+        }
+
+        let token_mint_output = self.check_pool_filters(
+            pool.bonding_curve,
+            self.pump_fun.mints(&creation),
+            &initial_reserves,
+        )?;
+        let plans = self.plan_per_wallet(wallets, &pool, token_mint_output);
+
+        self.buy_with_wallets(plans, initial_reserves, simulate_only, timings)
+            .await
+    }
+}
@@ -0,0 +1,203 @@
+use futures::{
+    future::BoxFuture,
+    stream::{select_all, BoxStream, SelectAll},
+    StreamExt,
+};
+use metrics::gauge;
+use solana_account_decoder::{UiAccount, UiAccountEncoding};
+use solana_client::{
+    nonblocking::pubsub_client::PubsubClient, rpc_config::RpcAccountInfoConfig,
+    rpc_response::Response,
+};
+use solana_sdk::pubkey::Pubkey;
+use tracing::{trace, warn};
+
+use crate::{
+    raydium::models::{AmmKeys, Pool},
+    storage::models::Position,
+    telemetry,
+};
+
+use super::{
+    pool_created::{SwapDirection, SWAP_FEE_DENOMINATOR, SWAP_FEE_NUMERATOR},
+    EventProcessor,
+};
+
+/// SPL token account `amount`.
+const TOKEN_ACCOUNT_AMOUNT_OFFSET: usize = 64;
+/// `AmmInfo::state_data.need_take_pnl_coin` and `need_take_pnl_pc`, right after the 16 header
+/// words and the 8 fee words.
+pub(crate) const AMM_NEED_TAKE_PNL_COIN_OFFSET: usize = 192;
+pub(crate) const AMM_NEED_TAKE_PNL_PC_OFFSET: usize = 200;
+
+type Unsubscriber = Box<dyn FnOnce() -> BoxFuture<'static, ()> + Send>;
+pub(crate) type ReserveUpdates<'a> =
+    SelectAll<BoxStream<'a, (ReserveAccount, Response<UiAccount>)>>;
+
+/// Pool account whose changes move the reserves.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum ReserveAccount {
+    CoinVault,
+    PcVault,
+    Amm,
+}
+
+impl EventProcessor {
+    /// Subscribes on `ws_client` to the changes of both vaults and of the AMM account of the
+    /// pool.
+    pub(crate) async fn subscribe_to_reserves<'a>(
+        &self,
+        ws_client: &'a PubsubClient,
+        amm: &AmmKeys,
+    ) -> anyhow::Result<(ReserveUpdates<'a>, Vec<Unsubscriber>)> {
+        let mut streams = Vec::new();
+        let mut unsubscribers: Vec<Unsubscriber> = Vec::new();
+
+        for (account, pubkey) in [
+            (ReserveAccount::CoinVault, amm.amm_coin_vault),
+            (ReserveAccount::PcVault, amm.amm_pc_vault),
+            (ReserveAccount::Amm, amm.amm_pool),
+        ] {
+            let subscribed = ws_client
+                .account_subscribe(
+                    &pubkey,
+                    Some(RpcAccountInfoConfig {
+                        encoding: Some(UiAccountEncoding::Base64),
+                        commitment: Some(self.commitment()),
+                        ..Default::default()
+                    }),
+                )
+                .await;
+            let (updates, unsubscriber) = match subscribed {
+                Ok(subscribed) => subscribed,
+                Err(e) => {
+                    for unsubscriber in unsubscribers {
+                        unsubscriber().await;
+                    }
+                    return Err(e.into());
+                }
+            };
+
+            streams.push(updates.map(move |update| (account, update)).boxed());
+            unsubscribers.push(unsubscriber);
+        }
+
+        Ok((select_all(streams), unsubscribers))
+    }
+
+    /// Applies an account change to the pool reserves, then refreshes the value of the
+    /// positions held on the pool.
+    pub(crate) async fn update_reserves(
+        &self,
+        amm_pool: Pubkey,
+        account: ReserveAccount,
+        update: Response<UiAccount>,
+    ) -> anyhow::Result<()> {
+        let data = update
+            .value
+            .data
+            .decode()
+            .ok_or_else(|| anyhow::anyhow!("Undecodable {:?} account data", account))?;
+        let word = |offset: usize| -> anyhow::Result<u64> {
+            let bytes = data
+                .get(offset..offset + 8)
+                .ok_or_else(|| anyhow::anyhow!("{:?} account is too short", account))?;
+            Ok(u64::from_le_bytes(bytes.try_into()?))
+        };
+
+        let reserves = {
+            let mut reserves = self.reserves.lock().await;
+            let Some(reserves) = reserves.get_mut(&amm_pool) else {
+                return Ok(());
+            };
+            match account {
+                ReserveAccount::CoinVault => {
+                    reserves.coin_vault = word(TOKEN_ACCOUNT_AMOUNT_OFFSET)?
+                }
+                ReserveAccount::PcVault => reserves.pc_vault = word(TOKEN_ACCOUNT_AMOUNT_OFFSET)?,
+                ReserveAccount::Amm => {
+                    reserves.need_take_pnl_coin = word(AMM_NEED_TAKE_PNL_COIN_OFFSET)?;
+                    reserves.need_take_pnl_pc = word(AMM_NEED_TAKE_PNL_PC_OFFSET)?;
+                }
+            }
+            reserves.slot = reserves.slot.max(update.context.slot);
+            *reserves
+        };
+        trace!(pool = %amm_pool, ?account, ?reserves, "Reserves updated");
+
+        let holds_position = self
+            .positions
+            .lock()
+            .await
+            .values()
+            .any(|position| position.amm_pool == amm_pool);
+        if holds_position {
+            self.refresh_unrealised_pnl().await;
+        }
+
+        Ok(())
+    }
+
+    /// Starts tracking the reserves of the pool from its initial ones.
+    pub(crate) async fn track_reserves(&self, pool: &Pool) {
+        self.reserves
+            .lock()
+            .await
+            .entry(pool.amm.amm_pool)
+            .or_insert_with(|| pool.initial_reserves());
+    }
+
+    pub(crate) async fn untrack_reserves(&self, amm_pool: &Pubkey) {
+        self.reserves.lock().await.remove(amm_pool);
+    }
+
+    /// What selling the whole position would return at the latest reserves, in the quote mint.
+    ///
+    /// `None` when the pool is not tracked.
+    pub async fn position_value(&self, position: &Position) -> Option<u64> {
+        let amm = self.pools.lock().await.get(&position.amm_pool)?.amm;
+        let reserves = *self.reserves.lock().await.get(&position.amm_pool)?;
+        let (pc, coin) = reserves.without_take_pnl().ok()?;
+        let direction = if position.mint == amm.amm_coin_mint {
+            SwapDirection::Coin2PC
+        } else {
+            SwapDirection::PC2Coin
+        };
+
+        match Self::swap_exact_amount(
+            pc,
+            coin,
+            SWAP_FEE_NUMERATOR,
+            SWAP_FEE_DENOMINATOR,
+            direction,
+            position.token_amount,
+            true,
+        ) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                warn!(mint = %position.mint, "Failed to value position: {:#}", e);
+                None
+            }
+        }
+    }
+
+    /// Recomputes the profit or loss of the open positions whose pool is tracked.
+    pub(crate) async fn refresh_unrealised_pnl(&self) {
+        let positions = self
+            .positions
+            .lock()
+            .await
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut unrealised_pnl = 0i128;
+        for position in &positions {
+            if let Some(value) = self.position_value(position).await {
+                unrealised_pnl += value as i128 - position.quote_amount as i128;
+            }
+        }
+
+        gauge!(telemetry::UNREALISED_PNL).set(unrealised_pnl as f64);
+    }
+}
@@ -0,0 +1,221 @@
+use anyhow::Context;
+use futures::future::join_all;
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::{account::Account, pubkey::Pubkey, signature::Signature};
+use tracing::{debug, instrument};
+
+use crate::{
+    dex::{Dex, PoolReserves, SwapPool},
+    orca::state::{WHIRLPOOL_DISCRIMINATOR, WHIRLPOOL_MINT_OFFSETS},
+    raydium::{
+        clmm,
+        cpmm::{self, decode_token_amount, CpmmKeys, CpmmPool},
+        dex::CpmmCreation,
+        pool::{decode_amm_keys, AmmV4Pool, AMM_INFO_MINT_OFFSETS, AMM_INFO_SIZE},
+    },
+    router::Candidate,
+};
+
+use super::{
+    reserves::{AMM_NEED_TAKE_PNL_COIN_OFFSET, AMM_NEED_TAKE_PNL_PC_OFFSET},
+    EventProcessor,
+};
+
+impl EventProcessor {
+    /// Every pool of the pair on the supported venues, decoded and ready to quote. Pools that
+    /// cannot be decoded, e.g. with swaps disabled, are left out.
+    #[instrument(skip(self))]
+    pub async fn find_pools(&self, mints: [Pubkey; 2]) -> anyhow::Result<Vec<Candidate>> {
+        let programs = &self.config.programs;
+        // CPMM, CLMM and Whirlpool pools keep their mints sorted, AMM V4 pools do not.
+        let mut sorted = mints;
+        sorted.sort();
+        let [mint_0, mint_1] = sorted;
+
+        let searches = [
+            (
+                programs.raydium_liquidity_pool_v4,
+                vec![RpcFilterType::DataSize(AMM_INFO_SIZE)],
+                AMM_INFO_MINT_OFFSETS,
+                [mint_0, mint_1],
+            ),
+            (
+                programs.raydium_liquidity_pool_v4,
+                vec![RpcFilterType::DataSize(AMM_INFO_SIZE)],
+                AMM_INFO_MINT_OFFSETS,
+                [mint_1, mint_0],
+            ),
+            (
+                programs.raydium_cpmm,
+                vec![discriminator(&cpmm::POOL_STATE_DISCRIMINATOR)],
+                cpmm::POOL_STATE_MINT_OFFSETS,
+                sorted,
+            ),
+            (
+                programs.raydium_clmm,
+                vec![discriminator(&clmm::state::POOL_STATE_DISCRIMINATOR)],
+                clmm::state::POOL_STATE_MINT_OFFSETS,
+                sorted,
+            ),
+            (
+                programs.orca_whirlpool,
+                vec![discriminator(&WHIRLPOOL_DISCRIMINATOR)],
+                WHIRLPOOL_MINT_OFFSETS,
+                sorted,
+            ),
+        ];
+        let found = join_all(searches.into_iter().map(
+            |(program_id, mut filters, offsets, mints)| {
+                filters.extend(offsets.into_iter().zip(mints).map(|(offset, mint)| {
+                    RpcFilterType::Memcmp(Memcmp::new_base58_encoded(offset, mint.as_ref()))
+                }));
+                async move {
+                    self.solana_api
+                        .get_program_accounts(&program_id, filters)
+                        .await
+                }
+            },
+        ))
+        .await
+        .into_iter()
+        .collect::<anyhow::Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+        debug!(pools = found.len(), "Pools of the pair found");
+
+        let decoded = join_all(
+            found
+                .iter()
+                .map(|(address, account)| self.decode_existing_pool(address, account)),
+        )
+        .await;
+
+        Ok(found
+            .iter()
+            .zip(decoded)
+            .filter_map(|((address, _), candidate)| match candidate {
+                Ok(candidate) => Some(candidate),
+                Err(e) => {
+                    debug!(pool = %address, "Pool left out: {:#}", e);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    /// Decodes an existing pool from its account, on whichever venue owns it, and fetches
+    /// what else it needs to be quoted.
+    pub async fn decode_existing_pool(
+        &self,
+        address: &Pubkey,
+        account: &Account,
+    ) -> anyhow::Result<Candidate> {
+        let programs = &self.config.programs;
+
+        if account.owner == programs.raydium_liquidity_pool_v4 {
+            let (pool, reserves) = self.get_amm_v4_pool(address, &account.data).await?;
+            Ok(candidate(
+                pool,
+                [pool.amm.amm_coin_mint, pool.amm.amm_pc_mint],
+                reserves,
+            ))
+        } else if account.owner == programs.raydium_cpmm {
+            let (pool, reserves) = self.get_cpmm_pool(address, &account.data).await?;
+            let mints = [pool.keys.token_0_mint, pool.keys.token_1_mint];
+            Ok(candidate(pool, mints, reserves))
+        } else if account.owner == programs.raydium_clmm {
+            let (pool, reserves) = self.get_clmm_pool(address, &account.data).await?;
+            let mints = [pool.state.token_mint_0, pool.state.token_mint_1];
+            Ok(candidate(pool, mints, reserves))
+        } else if account.owner == programs.orca_whirlpool {
+            let (pool, reserves) = self.get_whirlpool(address, &account.data).await?;
+            let mints = [pool.state.token_mint_a, pool.state.token_mint_b];
+            Ok(candidate(pool, mints, reserves))
+        } else {
+            Err(anyhow::anyhow!(
+                "Pool {} is owned by {}, not a supported venue",
+                address,
+                account.owner
+            ))
+        }
+    }
+
+    /// Rebuilds an existing AMM V4 pool from its `AmmInfo` account `data`, then fetches both
+    /// vaults and its market in a single request.
+    pub async fn get_amm_v4_pool(
+        &self,
+        pool: &Pubkey,
+        data: &[u8],
+    ) -> anyhow::Result<(AmmV4Pool, PoolReserves)> {
+        let program_id = self.config.programs.raydium_liquidity_pool_v4;
+        let amm = decode_amm_keys(&program_id, pool, data)?;
+
+        let accounts = [amm.amm_coin_vault, amm.amm_pc_vault, amm.market];
+        let fetched = self
+            .solana_api
+            .get_multiple_accounts_data(&accounts)
+            .await?;
+        let account = |index: usize| {
+            fetched
+                .get(index)
+                .and_then(Option::as_deref)
+                .with_context(|| format!("Missing AMM V4 pool account {}", accounts[index]))
+        };
+        let word = |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+
+        let reserves = PoolReserves {
+            coin_vault: decode_token_amount(account(0)?)?,
+            pc_vault: decode_token_amount(account(1)?)?,
+            need_take_pnl_coin: word(AMM_NEED_TAKE_PNL_COIN_OFFSET),
+            need_take_pnl_pc: word(AMM_NEED_TAKE_PNL_PC_OFFSET),
+            slot: 0,
+        };
+        let market_keys = Self::decode_market_keys(&amm.market, &amm.market_program, account(2)?)?;
+
+        Ok((
+            AmmV4Pool {
+                program_id,
+                amm,
+                market_keys,
+            },
+            reserves,
+        ))
+    }
+
+    /// Reads an existing CPMM pool from its `PoolState` account `data`, then decodes it the
+    /// same way as a new one.
+    pub async fn get_cpmm_pool(
+        &self,
+        pool: &Pubkey,
+        data: &[u8],
+    ) -> anyhow::Result<(CpmmPool, PoolReserves)> {
+        let creation = CpmmCreation {
+            keys: CpmmKeys::from_pool_state(&self.raydium_cpmm.program_id, pool, data)?,
+            signature: Signature::default(),
+            slot: 0,
+        };
+        let accounts = self
+            .fetch_pool_accounts(&self.raydium_cpmm, &creation)
+            .await?;
+
+        self.raydium_cpmm.decode_pool(&creation, &accounts)
+    }
+}
+
+fn candidate<P: SwapPool + 'static>(
+    pool: P,
+    mints: [Pubkey; 2],
+    reserves: PoolReserves,
+) -> Candidate {
+    Candidate {
+        pool: Box::new(pool),
+        mints,
+        reserves,
+    }
+}
+
+/// Anchor accounts of one type, from their discriminator.
+fn discriminator(discriminator: &[u8; 8]) -> RpcFilterType {
+    RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, discriminator))
+}
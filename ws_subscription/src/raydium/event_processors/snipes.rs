@@ -0,0 +1,303 @@
+use std::{collections::HashMap, str::FromStr};
+
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+};
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+use tracing::{debug, info, instrument, warn};
+
+use crate::{
+    config::Config,
+    dex::SwapPool,
+    raydium::{
+        models::{MarketKeys, Pool},
+        pool::AmmV4Pool,
+        utils::derive_amm_keys,
+    },
+    wallets::Wallet,
+};
+
+use super::{
+    lookup_tables::sign_transaction,
+    new_swap::RayInitLog,
+    nonces::SignedBuy,
+    timings::{Stage, TradeTimings},
+    EventProcessor, WSOL,
+};
+
+/// Everything about a buy that does not depend on the pool reserves, so it can be worked out
+/// as soon as the market exists, before the pool does.
+#[derive(Clone, Debug)]
+pub struct SnipePlan<P: SwapPool> {
+    pub pool: P,
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub token_account_input: Pubkey,
+    pub token_account_output: Pubkey,
+    /// The buy, signed ahead of time on a durable nonce.
+    pub presigned: Option<PresignedBuy>,
+}
+
+/// A buy signed before its pool exists, for a fixed amount and minimum output.
+#[derive(Clone, Debug)]
+pub struct PresignedBuy {
+    pub amount: u64,
+    pub min_output_amount: u64,
+    pub signed: SignedBuy,
+}
+
+impl<P: SwapPool> SnipePlan<P> {
+    pub fn new(owner: &Pubkey, pool: P, input_mint: Pubkey, output_mint: Pubkey) -> Self {
+        let token_account_input = get_associated_token_address_with_program_id(
+            owner,
+            &input_mint,
+            &pool.token_program(&input_mint),
+        );
+        let token_account_output = get_associated_token_address_with_program_id(
+            owner,
+            &output_mint,
+            &pool.token_program(&output_mint),
+        );
+
+        Self {
+            pool,
+            input_mint,
+            output_mint,
+            token_account_input,
+            token_account_output,
+            presigned: None,
+        }
+    }
+
+    /// The buy instructions, swapping `amount` of the input mint for at least
+    /// `min_output_amount` of the output mint.
+    pub fn instructions(
+        &self,
+        config: &Config,
+        owner: &Pubkey,
+        amount: u64,
+        min_output_amount: u64,
+    ) -> anyhow::Result<Vec<Instruction>> {
+        let input_token_program = self.pool.token_program(&self.input_mint);
+        let output_token_program = self.pool.token_program(&self.output_mint);
+
+        let mut instructions = vec![ComputeBudgetInstruction::set_compute_unit_price(
+            config.fees.compute_unit_price,
+        )];
+        if let Some(compute_unit_limit) = config.fees.compute_unit_limit {
+            instructions.push(ComputeBudgetInstruction::set_compute_unit_limit(
+                compute_unit_limit,
+            ));
+        }
+
+        let wrap_sol = self.input_mint == WSOL && !self.pool.takes_native_sol();
+        if self.input_mint != WSOL || wrap_sol {
+            instructions.push(create_associated_token_account_idempotent(
+                owner,
+                owner,
+                &self.input_mint,
+                &input_token_program,
+            ));
+        }
+        if wrap_sol {
+            instructions.push(solana_system_interface::instruction::transfer(
+                owner,
+                &self.token_account_input,
+                amount,
+            ));
+            instructions.push(spl_token::instruction::sync_native(
+                &input_token_program,
+                &self.token_account_input,
+            )?);
+        }
+        instructions.push(create_associated_token_account_idempotent(
+            owner,
+            owner,
+            &self.output_mint,
+            &output_token_program,
+        ));
+
+        instructions.extend(self.pool.swap_instructions(
+            owner,
+            &self.input_mint,
+            &self.token_account_input,
+            &self.token_account_output,
+            amount,
+            min_output_amount,
+        )?);
+
+        if wrap_sol {
+            // Unwrap whatever WSOL is left after the swap.
+            instructions.push(spl_token::instruction::close_account(
+                &input_token_program,
+                &self.token_account_input,
+                owner,
+                owner,
+                &[],
+            )?);
+        }
+
+        Ok(instructions)
+    }
+}
+
+impl EventProcessor {
+    /// Works out the buy of each of `wallets` on the pool `initialize2` will create for a new
+    /// market of the target mint, so only the quote is left to compute when it lands. With a
+    /// durable nonce and a fixed minimum output, nothing is: the buys are signed right away.
+    pub(crate) async fn stage_snipe(
+        &self,
+        wallets: &[Wallet],
+        market: Pubkey,
+        coin_mint: Pubkey,
+        pc_mint: Pubkey,
+        market_keys: MarketKeys,
+    ) {
+        let quote_mint = self.config.filters.quote_mint;
+        let output_mint = if pc_mint == quote_mint {
+            coin_mint
+        } else if coin_mint == quote_mint {
+            pc_mint
+        } else {
+            debug!(%market, "Market is not quoted in the quote mint, not staging");
+            return;
+        };
+
+        let mut staged = self.staged_snipes.lock().await;
+        if staged.contains_key(&market) {
+            return;
+        }
+
+        let amm = derive_amm_keys(
+            &self.config.programs.raydium_liquidity_pool_v4,
+            &self.config.programs.openbook_market,
+            &market,
+            &coin_mint,
+            &pc_mint,
+        );
+        let pool = AmmV4Pool {
+            program_id: self.config.programs.raydium_liquidity_pool_v4,
+            amm,
+            market_keys,
+        };
+        let mut plans = HashMap::with_capacity(wallets.len());
+        for (wallet, mut plan) in self.plan_per_wallet(wallets, &pool, output_mint) {
+            plan.presigned = match self.presign(&wallet.keypair, &plan, wallet.amount).await {
+                Ok(presigned) => presigned,
+                Err(e) => {
+                    warn!(%market, wallet = %wallet.pubkey(), "Failed to presign the snipe: {:#}", e);
+                    None
+                }
+            };
+            plans.insert(wallet.pubkey(), plan);
+        }
+        info!(
+            %market,
+            pool = %amm.amm_pool,
+            %output_mint,
+            wallets = plans.len(),
+            presigned = plans.values().filter(|plan| plan.presigned.is_some()).count(),
+            "Snipe staged"
+        );
+        staged.insert(market, plans);
+    }
+
+    /// Signs the buy of `plan` on the nonce of `owner`, when there is one and the minimum
+    /// output does not depend on the quote.
+    async fn presign<P: SwapPool>(
+        &self,
+        owner: &Keypair,
+        plan: &SnipePlan<P>,
+        amount: u64,
+    ) -> anyhow::Result<Option<PresignedBuy>> {
+        let (Some(min_output_amount), Some(nonce)) = (
+            self.config.strategy.min_output_amount,
+            self.peek_nonce(&owner.pubkey()).await,
+        ) else {
+            return Ok(None);
+        };
+
+        let instructions =
+            plan.instructions(&self.config, &owner.pubkey(), amount, min_output_amount)?;
+        let transaction = sign_transaction(
+            owner,
+            &nonce.with_advance(&instructions),
+            &self.lookup_tables_for(&plan.pool.id()).await,
+            nonce.blockhash,
+        )?;
+
+        Ok(Some(PresignedBuy {
+            amount,
+            min_output_amount,
+            signed: SignedBuy {
+                transaction,
+                last_valid_block_height: u64::MAX,
+                nonce: Some(nonce),
+            },
+        }))
+    }
+
+    /// Takes the snipe staged for the market the pool creation lists, if any, by wallet.
+    pub(crate) async fn take_staged_snipe(
+        &self,
+        init: &RayInitLog,
+    ) -> Option<HashMap<Pubkey, SnipePlan<AmmV4Pool>>> {
+        self.staged_snipes.lock().await.remove(&init.market)
+    }
+
+    /// Buys a pool whose snipe was staged with every wallet: the keys are already known and
+    /// the reserves come from the `initialize2` log, so nothing is fetched before sending.
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(skip(self, wallets, plans, init, simulate_only, timings))]
+    pub(crate) async fn buy_staged_pool(
+        &self,
+        wallets: &[Wallet],
+        mut plans: HashMap<Pubkey, SnipePlan<AmmV4Pool>>,
+        init: RayInitLog,
+        signature: &str,
+        slot: u64,
+        simulate_only: bool,
+        mut timings: TradeTimings,
+    ) -> anyhow::Result<()> {
+        let amm_pool = *plans
+            .values()
+            .next()
+            .map(|plan| &plan.pool)
+            .ok_or_else(|| anyhow::anyhow!("No wallet staged the snipe"))?;
+        let pool = Pool {
+            amm: amm_pool.amm,
+            initial_coin_balance: init.coin_amount,
+            initial_pc_balance: init.pc_amount,
+            creation_signature: Signature::from_str(signature)?,
+            slot,
+        };
+        timings.mark(Stage::PoolDecoded);
+        timings.mark(Stage::MarketKeysFetched);
+        timings.pool_slot = Some(slot);
+        self.record_new_pool(&pool)?;
+
+        let initial_reserves = pool.initial_reserves();
+        self.check_pool_filters(
+            pool.amm.amm_pool,
+            [pool.amm.amm_coin_mint, pool.amm.amm_pc_mint],
+            &initial_reserves,
+        )?;
+        let plans = wallets
+            .iter()
+            .filter_map(|wallet| Some((wallet, plans.remove(&wallet.pubkey())?)))
+            .collect();
+        self.buy_with_wallets(plans, initial_reserves, simulate_only, timings)
+            .await?;
+
+        self.track_pool(pool).await?;
+
+        Ok(())
+    }
+}
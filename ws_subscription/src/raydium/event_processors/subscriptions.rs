@@ -0,0 +1,545 @@
+use std::{collections::HashMap, future::Future, str::FromStr, sync::Arc, time::Duration};
+
+use anyhow::{bail, Context};
+use futures::{stream::select_all, Stream, StreamExt};
+use metrics::counter;
+use solana_client::{
+    nonblocking::pubsub_client::PubsubClient,
+    rpc_config::{RpcTransactionLogsConfig, RpcTransactionLogsFilter},
+    rpc_response::{Response, RpcLogsResponse},
+};
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use tokio::{
+    sync::{oneshot, Mutex},
+    task::JoinHandle,
+    time::{sleep, Instant},
+};
+use tracing::{debug, error, info, instrument, trace, warn};
+
+use crate::{storage::sqlite::now, telemetry};
+
+use super::{new_swap::RaySwapLog, EventProcessor};
+
+/// How many times a stream closed by the websocket is reopened before giving up on the pool.
+/// It stays tracked, so it is resumed on the next start.
+const MAX_RESUBSCRIBE_ATTEMPTS: u32 = 5;
+pub(super) const RESUBSCRIBE_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Why a pool stream stopped.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum StopReason {
+    /// Explicitly unsubscribed: the pool is no longer watched, even after a restart.
+    Unsubscribed,
+    /// The bot is shutting down: the pool stays tracked and is resumed on restart.
+    Shutdown,
+    /// No logs for longer than the inactivity timeout.
+    Expired,
+    /// The websocket closed the stream.
+    Closed,
+}
+
+struct PoolSubscription {
+    id: u64,
+    stop: oneshot::Sender<StopReason>,
+    task: JoinHandle<()>,
+}
+
+/// Pool log subscriptions, each streamed by its own task.
+///
+/// The lock only guards the bookkeeping and is never held while a stream is polled.
+pub struct SubscriptionManager {
+    max_subscriptions: usize,
+    inactivity_timeout: Duration,
+    next_id: Mutex<u64>,
+    subscriptions: Mutex<HashMap<Pubkey, PoolSubscription>>,
+}
+
+impl SubscriptionManager {
+    pub fn new(max_subscriptions: usize, inactivity_timeout: Duration) -> Self {
+        Self {
+            max_subscriptions,
+            inactivity_timeout,
+            next_id: Mutex::new(0),
+            subscriptions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Registers a subscription of `pool`, whose task `spawn` starts from its id and the
+    /// receiver of its stop reason.
+    ///
+    /// Does nothing if the pool is already subscribed, and fails once `max_subscriptions`
+    /// pools are.
+    async fn start(
+        &self,
+        pool: Pubkey,
+        spawn: impl FnOnce(u64, oneshot::Receiver<StopReason>) -> JoinHandle<()>,
+    ) -> anyhow::Result<()> {
+        let id = {
+            let mut next_id = self.next_id.lock().await;
+            *next_id += 1;
+            *next_id
+        };
+
+        let mut subscriptions = self.subscriptions.lock().await;
+        if subscriptions.contains_key(&pool) {
+            return Ok(());
+        }
+        if subscriptions.len() >= self.max_subscriptions {
+            bail!(
+                "Subscription cap of {} reached, not subscribing to pool {}",
+                self.max_subscriptions,
+                pool
+            );
+        }
+
+        info!(%pool, "Subscribing to pool");
+        let (stop, stopped) = oneshot::channel();
+        let task = spawn(id, stopped);
+        subscriptions.insert(pool, PoolSubscription { id, stop, task });
+
+        Ok(())
+    }
+
+    pub async fn len(&self) -> usize {
+        self.subscriptions.lock().await.len()
+    }
+
+    /// Forgets a subscription whose task ended by itself, unless it was replaced meanwhile.
+    async fn finished(&self, pool: &Pubkey, id: u64) {
+        let mut subscriptions = self.subscriptions.lock().await;
+        if subscriptions
+            .get(pool)
+            .is_some_and(|subscription| subscription.id == id)
+        {
+            subscriptions.remove(pool);
+        }
+    }
+}
+
+impl EventProcessor {
+    /// Starts streaming the logs of the pool in its own task, recording every swap.
+    ///
+    /// Does nothing if the pool is already subscribed, and fails once
+    /// `subscriptions.max_concurrent` pools are subscribed.
+    pub(crate) async fn subscribe_to_new_pool(
+        self: &Arc<Self>,
+        pubkey: Pubkey,
+    ) -> anyhow::Result<()> {
+        self.subscriptions
+            .start(pubkey, |id, stopped| {
+                tokio::spawn(Arc::clone(self).watch_pool(pubkey, id, stopped))
+            })
+            .await
+    }
+
+    /// Stops the pool stream and waits for its unsubscriber to run.
+    ///
+    /// With `untrack` the pool is also dropped from the watch list, so it is not resumed on
+    /// restart.
+    pub async fn unsubscribe(&self, pubkey: &Pubkey, untrack: bool) {
+        let subscription = self.subscriptions.subscriptions.lock().await.remove(pubkey);
+        let Some(subscription) = subscription else {
+            return;
+        };
+
+        let reason = if untrack {
+            StopReason::Unsubscribed
+        } else {
+            StopReason::Shutdown
+        };
+        // The task may have just ended by itself, in which case there is nobody to tell.
+        let _ = subscription.stop.send(reason);
+        if let Err(e) = subscription.task.await {
+            error!(pool = %pubkey, "Subscription task failed: {}", e);
+        }
+    }
+
+    /// Stops every pool stream, keeping the pools tracked for the next run.
+    pub async fn unsubscribe_all(&self) {
+        let pools = self
+            .subscriptions
+            .subscriptions
+            .lock()
+            .await
+            .keys()
+            .copied()
+            .collect::<Vec<_>>();
+
+        for pool in pools {
+            self.unsubscribe(&pool, false).await;
+        }
+    }
+
+    #[instrument(name = "pool", skip(self, id, stopped), fields(pool = %pubkey))]
+    async fn watch_pool(
+        self: Arc<Self>,
+        pubkey: Pubkey,
+        id: u64,
+        mut stopped: oneshot::Receiver<StopReason>,
+    ) {
+        let mut attempts = 0;
+        let reason = loop {
+            let ws_client = self.ws_client().await;
+            let reason = match self.stream_pool(&ws_client, pubkey, &mut stopped).await {
+                Ok(reason) => reason,
+                Err(e) => {
+                    warn!("Subscription failed: {:#}", e);
+                    StopReason::Closed
+                }
+            };
+            if reason != StopReason::Closed || attempts == MAX_RESUBSCRIBE_ATTEMPTS {
+                break reason;
+            }
+
+            attempts += 1;
+            warn!(attempts, "Stream closed, resubscribing");
+            if let Err(e) = self.reconnect_ws(&ws_client).await {
+                warn!("Failed to reconnect the websocket: {:#}", e);
+            }
+            tokio::select! {
+                reason = &mut stopped => break reason.unwrap_or(StopReason::Shutdown),
+                _ = sleep(RESUBSCRIBE_BACKOFF * attempts) => {}
+            }
+        };
+        info!(?reason, "Subscription stopped");
+
+        if matches!(reason, StopReason::Unsubscribed | StopReason::Expired) {
+            self.pools.lock().await.remove(&pubkey);
+            self.untrack_reserves(&pubkey).await;
+            if let Err(e) = self.store.set_pool_tracked(&pubkey, false) {
+                warn!("Failed to untrack pool: {:#}", e);
+            }
+        }
+
+        self.subscriptions.finished(&pubkey, id).await;
+    }
+
+    async fn stream_pool(
+        &self,
+        ws_client: &PubsubClient,
+        pubkey: Pubkey,
+        stopped: &mut oneshot::Receiver<StopReason>,
+    ) -> anyhow::Result<StopReason> {
+        let (mut accounts, unsubscriber) = ws_client
+            .logs_subscribe(
+                RpcTransactionLogsFilter::Mentions(vec![pubkey.to_string()]),
+                RpcTransactionLogsConfig {
+                    commitment: Some(self.commitment()),
+                },
+            )
+            .await?;
+
+        // The subscription buffers what arrives meanwhile, so nothing falls between the two.
+        if let Err(e) = self.backfill_pool_swaps(&pubkey).await {
+            warn!("Failed to backfill swaps: {:#}", e);
+        }
+
+        let pool = self.pools.lock().await.get(&pubkey).cloned();
+        let (mut reserve_updates, reserve_unsubscribers) = match pool {
+            Some(pool) if self.config.subscriptions.track_reserves => {
+                self.track_reserves(&pool).await;
+                match self.subscribe_to_reserves(ws_client, &pool.amm).await {
+                    Ok(subscribed) => subscribed,
+                    Err(e) => {
+                        drop(accounts);
+                        unsubscriber().await;
+                        return Err(e);
+                    }
+                }
+            }
+            _ => (select_all(Vec::new()), Vec::new()),
+        };
+
+        let reason = follow_pool(
+            &mut accounts,
+            &mut reserve_updates,
+            stopped,
+            self.subscriptions.inactivity_timeout,
+            || async move {
+                self.positions
+                    .lock()
+                    .await
+                    .values()
+                    .any(|position| position.amm_pool == pubkey)
+            },
+            |response| {
+                if let Err(e) = self.process_pool_logs(pubkey, response) {
+                    warn!("Failed to process pool logs: {:#}", e);
+                }
+            },
+            |(account, update)| async move {
+                if let Err(e) = self.update_reserves(pubkey, account, update).await {
+                    warn!(?account, "Failed to update reserves: {:#}", e);
+                }
+            },
+        )
+        .await;
+
+        drop(accounts);
+        drop(reserve_updates);
+        unsubscriber().await;
+        for unsubscriber in reserve_unsubscribers {
+            unsubscriber().await;
+        }
+
+        Ok(reason)
+    }
+
+    /// The websocket client every subscription is made on.
+    pub(super) async fn ws_client(&self) -> Arc<PubsubClient> {
+        Arc::clone(&*self.ws_client.lock().await)
+    }
+
+    /// Replaces `stale`, a client a stream closed on, by a new connection, unless another
+    /// stream already did.
+    ///
+    /// A `PubsubClient` never reconnects by itself: once its socket is gone every subscription
+    /// on it closes or fails, so resubscribing on it would loop.
+    pub(super) async fn reconnect_ws(&self, stale: &Arc<PubsubClient>) -> anyhow::Result<()> {
+        let mut ws_client = self.ws_client.lock().await;
+        if !Arc::ptr_eq(&ws_client, stale) {
+            return Ok(());
+        }
+
+        *ws_client = Arc::new(
+            PubsubClient::new(self.config.ws_url()?)
+                .await
+                .with_context(|| "Failed to reconnect WS client")?,
+        );
+        counter!(telemetry::WS_RECONNECTS).increment(1);
+        info!("Websocket reconnected");
+
+        Ok(())
+    }
+
+    fn process_pool_logs(
+        &self,
+        pubkey: Pubkey,
+        response: Response<RpcLogsResponse>,
+    ) -> anyhow::Result<()> {
+        let logs = response.value.logs;
+        let signature = response.value.signature;
+
+        trace!(%signature, ?logs, "Pool logs");
+
+        if response.value.err.is_none()
+            && let Some(swap) = RaySwapLog::from_logs(&logs)
+        {
+            debug!(
+                %signature,
+                base_in = swap.base_in,
+                amount_in = swap.amount_in,
+                amount_out = swap.amount_out,
+                "Swap detected"
+            );
+            self.record_swap(
+                pubkey,
+                Signature::from_str(&signature)?,
+                response.context.slot,
+                swap,
+                now(),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Hands the pool logs and reserve updates to their handlers until the subscription is stopped,
+/// `inactivity_timeout` passes without logs or the log stream closes.
+///
+/// A quiet pool is kept for as long as `holds_position` says a position is open in it.
+async fn follow_pool<L, R, F, H>(
+    logs: &mut L,
+    reserve_updates: &mut R,
+    stopped: &mut oneshot::Receiver<StopReason>,
+    inactivity_timeout: Duration,
+    mut holds_position: impl FnMut() -> H,
+    mut on_logs: impl FnMut(L::Item),
+    mut on_reserve_update: impl FnMut(R::Item) -> F,
+) -> StopReason
+where
+    L: Stream + Unpin,
+    R: Stream + Unpin,
+    F: Future<Output = ()>,
+    H: Future<Output = bool>,
+{
+    // Only logs keep the pool alive: every reserve change comes with a swap anyway.
+    let inactivity = sleep(inactivity_timeout);
+    tokio::pin!(inactivity);
+
+    loop {
+        tokio::select! {
+            reason = &mut *stopped => break reason.unwrap_or(StopReason::Shutdown),
+            _ = &mut inactivity => {
+                if !holds_position().await {
+                    break StopReason::Expired;
+                }
+                inactivity.as_mut().reset(Instant::now() + inactivity_timeout);
+            }
+            response = logs.next() => match response {
+                None => break StopReason::Closed,
+                Some(response) => {
+                    inactivity.as_mut().reset(Instant::now() + inactivity_timeout);
+                    on_logs(response);
+                }
+            },
+            Some(update) = reserve_updates.next() => on_reserve_update(update).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{channel::mpsc, stream};
+
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_millis(200);
+
+    async fn start(manager: &SubscriptionManager, pool: Pubkey) -> anyhow::Result<Option<u64>> {
+        let mut started = None;
+        manager
+            .start(pool, |id, _stopped| {
+                started = Some(id);
+                tokio::spawn(async {})
+            })
+            .await?;
+
+        Ok(started)
+    }
+
+    #[tokio::test]
+    async fn refuses_pools_past_the_cap() {
+        let manager = SubscriptionManager::new(2, TIMEOUT);
+        let (first, second, third) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+
+        let first_id = start(&manager, first).await.unwrap().unwrap();
+        assert!(start(&manager, second).await.unwrap().is_some());
+        // Already subscribed: nothing new is started, and it does not count twice.
+        assert_eq!(start(&manager, first).await.unwrap(), None);
+        assert_eq!(manager.len().await, 2);
+
+        let error = start(&manager, third).await.unwrap_err();
+        assert!(error.to_string().contains("cap of 2 reached"));
+
+        manager.finished(&first, first_id).await;
+        assert!(start(&manager, third).await.unwrap().is_some());
+        assert_eq!(manager.len().await, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn quiet_pools_expire() {
+        let (logs, mut log_stream) = mpsc::unbounded::<u32>();
+        let (_stop, mut stopped) = oneshot::channel();
+        tokio::spawn(async move {
+            sleep(TIMEOUT / 2).await;
+            logs.unbounded_send(1).unwrap();
+            // Keeps the stream open past the timeout.
+            sleep(TIMEOUT * 4).await;
+            drop(logs);
+        });
+
+        let started = Instant::now();
+        let mut received = Vec::new();
+        let reason = follow_pool(
+            &mut log_stream,
+            &mut stream::pending::<()>(),
+            &mut stopped,
+            TIMEOUT,
+            || async { false },
+            |log| received.push(log),
+            |()| async {},
+        )
+        .await;
+
+        assert_eq!(reason, StopReason::Expired);
+        assert_eq!(received, [1]);
+        // The log pushed the deadline back.
+        assert_eq!(started.elapsed(), TIMEOUT * 3 / 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn pools_holding_a_position_do_not_expire() {
+        let (_stop, mut stopped) = oneshot::channel();
+        let started = Instant::now();
+        let mut checks = 0;
+
+        let reason = follow_pool(
+            &mut stream::pending::<()>(),
+            &mut stream::pending::<()>(),
+            &mut stopped,
+            TIMEOUT,
+            || {
+                checks += 1;
+                // The position is closed after the second check.
+                let open = checks <= 2;
+                async move { open }
+            },
+            |()| {},
+            |()| async {},
+        )
+        .await;
+
+        assert_eq!(reason, StopReason::Expired);
+        assert_eq!(checks, 3);
+        assert_eq!(started.elapsed(), TIMEOUT * 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reserve_updates_do_not_keep_pools_alive() {
+        let (_stop, mut stopped) = oneshot::channel();
+        let mut updates = 0;
+
+        let reason = follow_pool(
+            &mut stream::pending::<()>(),
+            &mut stream::iter([1, 2, 3]).chain(stream::pending()),
+            &mut stopped,
+            TIMEOUT,
+            || async { false },
+            |()| {},
+            |_| {
+                updates += 1;
+                async {}
+            },
+        )
+        .await;
+
+        assert_eq!(reason, StopReason::Expired);
+        assert_eq!(updates, 3);
+    }
+
+    #[tokio::test]
+    async fn reports_why_the_stream_stopped() {
+        let (_stop, mut stopped) = oneshot::channel();
+        let closed = follow_pool(
+            &mut stream::empty::<()>(),
+            &mut stream::pending::<()>(),
+            &mut stopped,
+            TIMEOUT,
+            || async { false },
+            |()| {},
+            |()| async {},
+        )
+        .await;
+        assert_eq!(closed, StopReason::Closed);
+
+        let (stop, mut stopped) = oneshot::channel();
+        stop.send(StopReason::Unsubscribed).unwrap();
+        let unsubscribed = follow_pool(
+            &mut stream::pending::<()>(),
+            &mut stream::pending::<()>(),
+            &mut stopped,
+            TIMEOUT,
+            || async { false },
+            |()| {},
+            |()| async {},
+        )
+        .await;
+        assert_eq!(unsubscribed, StopReason::Unsubscribed);
+    }
+}
@@ -0,0 +1,123 @@
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use tracing::{info, instrument};
+
+use crate::router::{best_route, Route};
+
+use super::{
+    snipes::SnipePlan,
+    timings::{Stage, TradeTimings},
+    EventProcessor,
+};
+
+impl EventProcessor {
+    /// Buys the other mint of an existing pool with `amount` of the quote mint, on whichever
+    /// venue owns the pool.
+    #[instrument(skip(self, owner, amount, simulate_only))]
+    pub async fn swap_on_pool(
+        &self,
+        owner: &Keypair,
+        pool: Pubkey,
+        amount: u64,
+        simulate_only: bool,
+    ) -> anyhow::Result<()> {
+        let slot = self.solana_api.get_slot().await?;
+        let mut timings = TradeTimings::start(slot);
+
+        let account = self.solana_api.get_account_info(&pool).await?;
+        let candidate = self.decode_existing_pool(&pool, &account).await?;
+        timings.mark(Stage::PoolDecoded);
+
+        let token_mint_output =
+            self.check_pool_filters(pool, candidate.mints, &candidate.reserves)?;
+        self.swap_on_route(
+            owner,
+            Route::single(candidate),
+            token_mint_output,
+            amount,
+            simulate_only,
+            timings,
+        )
+        .await
+    }
+
+    /// Buys `mint` with `amount` of the quote mint through the pools of the pair returning the
+    /// most, on any supported venue.
+    #[instrument(skip(self, owner, amount, simulate_only))]
+    pub async fn swap_on_best_route(
+        &self,
+        owner: &Keypair,
+        mint: Pubkey,
+        amount: u64,
+        simulate_only: bool,
+    ) -> anyhow::Result<()> {
+        let slot = self.solana_api.get_slot().await?;
+        let mut timings = TradeTimings::start(slot);
+
+        let quote_mint = self.config.filters.quote_mint;
+        let candidates = self
+            .find_pools([quote_mint, mint])
+            .await?
+            .into_iter()
+            .filter(|candidate| {
+                let pool = candidate.pool.id();
+                match self.check_pool_filters(pool, candidate.mints, &candidate.reserves) {
+                    Ok(_) => true,
+                    Err(e) => {
+                        info!(%pool, "Pool left out: {:#}", e);
+                        false
+                    }
+                }
+            })
+            .collect();
+        timings.mark(Stage::PoolDecoded);
+
+        let route = best_route(candidates, &quote_mint, amount)?;
+        for leg in &route.legs {
+            info!(pool = %leg.pool.id(), share_bps = leg.share_bps, "Route leg");
+        }
+
+        self.swap_on_route(owner, route, mint, amount, simulate_only, timings)
+            .await
+    }
+
+    /// Buys through `route`, then waits for the trade to settle.
+    async fn swap_on_route(
+        &self,
+        owner: &Keypair,
+        route: Route,
+        token_mint_output: Pubkey,
+        amount: u64,
+        simulate_only: bool,
+        timings: TradeTimings,
+    ) -> anyhow::Result<()> {
+        let reserves = route.reserves();
+        let pools = route
+            .legs
+            .iter()
+            .map(|leg| leg.pool.id())
+            .collect::<Vec<_>>();
+        self.resolve_pool_lookup_tables(pools[0], &pools).await;
+        let plan = SnipePlan::new(
+            &owner.pubkey(),
+            route,
+            self.config.filters.quote_mint,
+            token_mint_output,
+        );
+
+        self.refresh_nonce(&owner.pubkey()).await?;
+        self.buy(owner, plan, reserves, amount, simulate_only, timings)
+            .await?;
+
+        let drained = self.drain_in_flight().await;
+        self.log_sender_stats();
+        info!(
+            landed = drained.landed,
+            failed = drained.failed,
+            expired = drained.expired,
+            unknown = drained.unknown,
+            "Swap settled"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use metrics::histogram;
+
+use crate::telemetry;
+
+/// A step of the snipe path, in the order a trade goes through them.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Stage {
+    /// `getTransaction` returned the pool creation.
+    TransactionFetched,
+    /// The pool keys and initial reserves are known.
+    PoolDecoded,
+    MarketKeysFetched,
+    QuoteComputed,
+    Signed,
+    Sent,
+    /// The transaction reached the required commitment.
+    Landed,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 7] = [
+        Stage::TransactionFetched,
+        Stage::PoolDecoded,
+        Stage::MarketKeysFetched,
+        Stage::QuoteComputed,
+        Stage::Signed,
+        Stage::Sent,
+        Stage::Landed,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Stage::TransactionFetched => "transaction_fetched",
+            Stage::PoolDecoded => "pool_decoded",
+            Stage::MarketKeysFetched => "market_keys_fetched",
+            Stage::QuoteComputed => "quote_computed",
+            Stage::Signed => "signed",
+            Stage::Sent => "sent",
+            Stage::Landed => "landed",
+        }
+    }
+}
+
+/// When each stage of a trade was reached, relative to the websocket notification that
+/// triggered it, along with the slots seen on the way.
+#[derive(Clone, Debug)]
+pub struct TradeTimings {
+    notified_at: Instant,
+    /// Slot of the websocket notification.
+    pub notification_slot: u64,
+    /// Slot the pool creation landed in.
+    pub pool_slot: Option<u64>,
+    /// Slot our transaction landed in.
+    pub landed_slot: Option<u64>,
+    stages: [Option<Duration>; Stage::ALL.len()],
+}
+
+impl TradeTimings {
+    /// Starts the clock when a notification is received.
+    pub fn start(notification_slot: u64) -> Self {
+        Self {
+            notified_at: Instant::now(),
+            notification_slot,
+            pool_slot: None,
+            landed_slot: None,
+            stages: Default::default(),
+        }
+    }
+
+    /// Records that `stage` is reached now. Only the first time counts.
+    pub fn mark(&mut self, stage: Stage) {
+        let elapsed = self.notified_at.elapsed();
+        let reached = &mut self.stages[stage as usize];
+        if reached.is_none() {
+            *reached = Some(elapsed);
+            histogram!(telemetry::TRADE_STAGE, "stage" => stage.as_str())
+                .record(elapsed.as_secs_f64());
+        }
+    }
+
+    /// Time from the notification to `stage`, if it was reached.
+    pub fn elapsed(&self, stage: Stage) -> Option<Duration> {
+        self.stages[stage as usize]
+    }
+
+    /// Slots between the notification and our transaction landing.
+    pub fn slots_to_land(&self) -> Option<u64> {
+        self.landed_slot
+            .map(|slot| slot.saturating_sub(self.notification_slot))
+    }
+}
+
+/// Time spent in each stage reached, i.e. since the previous one, so the slowest step stands
+/// out: `transaction_fetched=120ms pool_decoded=45ms ... total=410ms`.
+impl fmt::Display for TradeTimings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut previous = Duration::ZERO;
+        for stage in Stage::ALL {
+            if let Some(elapsed) = self.elapsed(stage) {
+                write!(
+                    f,
+                    "{}={:?} ",
+                    stage.as_str(),
+                    elapsed.saturating_sub(previous)
+                )?;
+                previous = elapsed;
+            }
+        }
+        write!(f, "total={:?}", previous)
+    }
+}
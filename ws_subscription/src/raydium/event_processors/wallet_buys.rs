@@ -0,0 +1,515 @@
+//! Buys spread over several wallets: each one buys its own amount in its own transaction, all
+//! at once, as long as its SOL balance covers it.
+
+use std::collections::BTreeMap;
+
+use futures::future::join_all;
+use solana_sdk::pubkey::Pubkey;
+use tracing::{info, warn};
+
+use crate::{
+    dex::{PoolReserves, SwapPool},
+    wallets::Wallet,
+};
+
+use super::{snipes::SnipePlan, timings::TradeTimings, EventProcessor, WSOL};
+
+/// Most accounts `getMultipleAccounts` returns at once.
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// The open positions on a mint, summed over the wallets holding it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AggregatedPosition {
+    pub wallets: usize,
+    pub quote_amount: u64,
+    pub token_amount: u64,
+    /// What selling every position would return, when the pool is tracked.
+    pub value: Option<u64>,
+}
+
+impl EventProcessor {
+    /// Fetches the SOL balance of every wallet, and keeps those that can afford their buy.
+    pub(crate) async fn check_wallet_balances(
+        &self,
+        wallets: Vec<Wallet>,
+    ) -> anyhow::Result<Vec<Wallet>> {
+        let pubkeys = wallets.iter().map(Wallet::pubkey).collect::<Vec<_>>();
+        let mut balances = Vec::with_capacity(pubkeys.len());
+        for chunk in pubkeys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let accounts = self.solana_api.get_multiple_accounts(chunk).await?;
+            balances.extend(
+                accounts
+                    .into_iter()
+                    .map(|account| account.map_or(0, |account| account.lamports)),
+            );
+        }
+
+        let mut wallet_balances = self.wallet_balances.lock().await;
+        wallet_balances.extend(pubkeys.into_iter().zip(balances));
+        let (funded, unfunded): (Vec<_>, Vec<_>) = wallets.into_iter().partition(|wallet| {
+            wallet_balances[&wallet.pubkey()] >= self.required_lamports(wallet)
+        });
+        for wallet in &unfunded {
+            warn!(
+                wallet = %wallet.pubkey(),
+                balance = wallet_balances[&wallet.pubkey()],
+                required = self.required_lamports(wallet),
+                "Wallet cannot afford its buy, leaving it out"
+            );
+        }
+        anyhow::ensure!(
+            !funded.is_empty(),
+            "No wallet can afford its buy of the {} loaded",
+            unfunded.len()
+        );
+        info!(
+            wallets = funded.len(),
+            amount = funded.iter().map(|wallet| wallet.amount).sum::<u64>(),
+            "Buying with {} wallets",
+            funded.len()
+        );
+
+        Ok(funded)
+    }
+
+    /// Lamports `wallet` needs for a buy: its amount when paying in SOL, and the reserve.
+    fn required_lamports(&self, wallet: &Wallet) -> u64 {
+        let spent = if self.config.filters.quote_mint == WSOL {
+            wallet.amount
+        } else {
+            0
+        };
+
+        spent + self.config.wallet.min_sol_reserve
+    }
+
+    /// A plan of the buy on `pool` for each of `wallets`.
+    pub(crate) fn plan_per_wallet<'a, P: SwapPool + Clone>(
+        &self,
+        wallets: &'a [Wallet],
+        pool: &P,
+        output_mint: Pubkey,
+    ) -> Vec<(&'a Wallet, SnipePlan<P>)> {
+        wallets
+            .iter()
+            .map(|wallet| {
+                (
+                    wallet,
+                    SnipePlan::new(
+                        &wallet.pubkey(),
+                        pool.clone(),
+                        self.config.filters.quote_mint,
+                        output_mint,
+                    ),
+                )
+            })
+            .collect()
+    }
+
+    /// Buys with each wallet of `plans` at once, leaving out those whose balance no longer
+    /// covers it. Fails only when no wallet bought.
+    pub(crate) async fn buy_with_wallets<P: SwapPool>(
+        &self,
+        plans: Vec<(&Wallet, SnipePlan<P>)>,
+        initial_reserves: PoolReserves,
+        simulate_only: bool,
+        timings: TradeTimings,
+    ) -> anyhow::Result<()> {
+        let plans = {
+            let balances = self.wallet_balances.lock().await;
+            let (funded, unfunded): (Vec<_>, Vec<_>) =
+                plans.into_iter().partition(|(wallet, _)| {
+                    balances
+                        .get(&wallet.pubkey())
+                        .is_none_or(|balance| *balance >= self.required_lamports(wallet))
+                });
+            for (wallet, _) in &unfunded {
+                warn!(wallet = %wallet.pubkey(), "Wallet spent its balance, skipping the buy");
+            }
+            funded
+        };
+        anyhow::ensure!(!plans.is_empty(), "No wallet can afford the buy");
+
+        let results = join_all(plans.into_iter().map(|(wallet, plan)| {
+            let timings = timings.clone();
+            async move {
+                let result = self
+                    .buy(
+                        &wallet.keypair,
+                        plan,
+                        initial_reserves,
+                        wallet.amount,
+                        simulate_only,
+                        timings,
+                    )
+                    .await;
+                (wallet, result)
+            }
+        }))
+        .await;
+
+        let mut bought = 0;
+        let mut errors = Vec::new();
+        for (wallet, result) in results {
+            match result {
+                Ok(()) => {
+                    bought += 1;
+                    if !simulate_only && self.config.filters.quote_mint == WSOL {
+                        self.wallet_balances
+                            .lock()
+                            .await
+                            .entry(wallet.pubkey())
+                            .and_modify(|balance| *balance = balance.saturating_sub(wallet.amount));
+                    }
+                }
+                Err(e) => {
+                    warn!(wallet = %wallet.pubkey(), "Buy failed: {:#}", e);
+                    errors.push(format!("{}: {:#}", wallet.pubkey(), e));
+                }
+            }
+        }
+
+        if bought == 0 {
+            anyhow::bail!("Every wallet failed to buy: {}", errors.join("; "));
+        }
+        if !errors.is_empty() {
+            info!(
+                bought,
+                failed = errors.len(),
+                "Bought with some of the wallets"
+            );
+        }
+
+        Ok(())
+    }
+
+    /// The open positions summed by mint over the wallets holding them.
+    pub async fn aggregated_positions(&self) -> BTreeMap<Pubkey, AggregatedPosition> {
+        let positions = self
+            .positions
+            .lock()
+            .await
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut aggregated = BTreeMap::<Pubkey, AggregatedPosition>::new();
+        for position in &positions {
+            let value = self.position_value(position).await;
+            let total = aggregated
+                .entry(position.mint)
+                .or_insert_with(|| AggregatedPosition {
+                    value: Some(0),
+                    ..Default::default()
+                });
+            total.wallets += 1;
+            total.quote_amount += position.quote_amount;
+            total.token_amount += position.token_amount;
+            total.value = total.value.zip(value).map(|(total, value)| total + value);
+        }
+
+        aggregated
+    }
+
+    /// Logs the open positions of every mint, over all the wallets.
+    pub(crate) async fn log_positions(&self) {
+        for (mint, position) in self.aggregated_positions().await {
+            info!(
+                %mint,
+                wallets = position.wallets,
+                quote_amount = position.quote_amount,
+                token_amount = position.token_amount,
+                value = ?position.value,
+                "Open position"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use mockito::{Matcher, Mock, Server, ServerGuard};
+    use serde_json::{json, Value};
+    use solana_sdk::{
+        hash::Hash,
+        instruction::Instruction,
+        signature::{Keypair, Signature},
+    };
+    use tokio::net::TcpListener;
+
+    use crate::{
+        config::Config,
+        raydium::models::{AmmKeys, Pool},
+        storage::models::{Position, TradeStatus},
+    };
+
+    use super::*;
+
+    const SOL: u64 = 1_000_000_000;
+
+    /// A processor on the mock node `server`, with a websocket that accepts connections and
+    /// never publishes anything.
+    async fn processor(server: &ServerGuard) -> EventProcessor {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ws_url = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    if let Ok(mut socket) = tokio_tungstenite::accept_async(stream).await {
+                        while let Some(Ok(_)) = socket.next().await {}
+                    }
+                });
+            }
+        });
+
+        let mut config = Config::default();
+        config.endpoints.rpc_url = Some(server.url());
+        config.endpoints.ws_url = Some(ws_url);
+        config.storage.path = ":memory:".to_string();
+        config.lookup_tables.pool_tables = false;
+
+        EventProcessor::new(&config).await.unwrap()
+    }
+
+    async fn mock_rpc(server: &mut ServerGuard, method: &str, result: Value) -> Mock {
+        server
+            .mock("POST", "/")
+            .match_body(Matcher::PartialJson(json!({ "method": method })))
+            .with_body(json!({ "jsonrpc": "2.0", "id": 1, "result": result }).to_string())
+            .create_async()
+            .await
+    }
+
+    fn wallet(amount: u64) -> Wallet {
+        Wallet {
+            keypair: Keypair::new(),
+            amount,
+        }
+    }
+
+    /// A pool without enough liquidity for buys over `max_amount_in`.
+    #[derive(Clone, Debug)]
+    struct ShallowPool {
+        id: Pubkey,
+        max_amount_in: u64,
+    }
+
+    impl SwapPool for ShallowPool {
+        fn id(&self) -> Pubkey {
+            self.id
+        }
+
+        fn quote(
+            &self,
+            _reserves: &PoolReserves,
+            _input_mint: &Pubkey,
+            amount_in: u64,
+        ) -> anyhow::Result<u64> {
+            anyhow::ensure!(amount_in <= self.max_amount_in, "Not enough liquidity");
+            Ok(amount_in * 2)
+        }
+
+        fn swap_instruction(
+            &self,
+            _owner: &Pubkey,
+            _input_mint: &Pubkey,
+            _input_account: &Pubkey,
+            _output_account: &Pubkey,
+            _amount_in: u64,
+            _min_amount_out: u64,
+        ) -> anyhow::Result<Instruction> {
+            Ok(Instruction::new_with_bytes(self.id, &[], Vec::new()))
+        }
+    }
+
+    #[tokio::test]
+    async fn leaves_out_the_wallets_that_cannot_afford_their_buy() {
+        let mut server = Server::new_async().await;
+        let (funded, unfunded, missing) = (wallet(SOL), wallet(SOL), wallet(SOL));
+        let account = |lamports: u64| {
+            json!({
+                "lamports": lamports,
+                "data": ["", "base64"],
+                "owner": Pubkey::default().to_string(),
+                "executable": false,
+                "rentEpoch": 0,
+                "space": 0,
+            })
+        };
+        mock_rpc(
+            &mut server,
+            "getMultipleAccounts",
+            json!({
+                "context": { "slot": 1 },
+                "value": [account(2 * SOL), account(SOL), null],
+            }),
+        )
+        .await;
+        let processor = processor(&server).await;
+
+        let kept = processor
+            .check_wallet_balances(vec![funded, unfunded, missing])
+            .await
+            .unwrap();
+        assert_eq!(kept.len(), 1);
+        let balances = processor.wallet_balances.lock().await;
+        assert_eq!(balances[&kept[0].pubkey()], 2 * SOL);
+        assert_eq!(balances.len(), 3);
+        drop(balances);
+
+        let error = processor
+            .check_wallet_balances(vec![wallet(5 * SOL)])
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "No wallet can afford its buy of the 1 loaded"
+        );
+    }
+
+    #[tokio::test]
+    async fn buys_with_the_wallets_that_can_and_fails_only_when_none_bought() {
+        let mut server = Server::new_async().await;
+        mock_rpc(
+            &mut server,
+            "getLatestBlockhash",
+            json!({
+                "context": { "slot": 1 },
+                "value": { "blockhash": Hash::new_unique().to_string(), "lastValidBlockHeight": 150 },
+            }),
+        )
+        .await;
+        mock_rpc(
+            &mut server,
+            "simulateTransaction",
+            json!({ "context": { "slot": 1 }, "value": { "err": null, "logs": [] } }),
+        )
+        .await;
+        let processor = processor(&server).await;
+        let pool = ShallowPool {
+            id: Pubkey::new_unique(),
+            max_amount_in: SOL,
+        };
+        let mint = Pubkey::new_unique();
+
+        // The first buys, the second spent its balance since, the third is too big for the pool.
+        let wallets = [wallet(SOL), wallet(SOL), wallet(2 * SOL)];
+        processor.wallet_balances.lock().await.extend([
+            (wallets[0].pubkey(), 10 * SOL),
+            (wallets[1].pubkey(), SOL / 2),
+            (wallets[2].pubkey(), 10 * SOL),
+        ]);
+        let reserves = PoolReserves::default();
+        processor
+            .buy_with_wallets(
+                processor.plan_per_wallet(&wallets, &pool, mint),
+                reserves,
+                true,
+                TradeTimings::start(1),
+            )
+            .await
+            .unwrap();
+
+        let trades = processor.store.trades(10, Some(&pool.id)).unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].owner, wallets[0].pubkey());
+        assert_eq!(trades[0].status, TradeStatus::Simulated);
+
+        let error = processor
+            .buy_with_wallets(
+                processor.plan_per_wallet(&wallets[2..], &pool, mint),
+                reserves,
+                true,
+                TradeTimings::start(1),
+            )
+            .await
+            .unwrap_err();
+        assert!(error.to_string().starts_with("Every wallet failed to buy"));
+
+        let error = processor
+            .buy_with_wallets(
+                processor.plan_per_wallet(&wallets[1..2], &pool, mint),
+                reserves,
+                true,
+                TradeTimings::start(1),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "No wallet can afford the buy");
+    }
+
+    #[tokio::test]
+    async fn sums_positions_over_wallets_and_values_tracked_pools_only() {
+        let server = Server::new_async().await;
+        let processor = processor(&server).await;
+        let tracked = Pool {
+            amm: AmmKeys {
+                amm_pool: Pubkey::new_unique(),
+                amm_coin_mint: Pubkey::new_unique(),
+                amm_pc_mint: WSOL,
+                amm_authority: Pubkey::new_unique(),
+                amm_target: Pubkey::new_unique(),
+                amm_coin_vault: Pubkey::new_unique(),
+                amm_pc_vault: Pubkey::new_unique(),
+                amm_lp_mint: Pubkey::new_unique(),
+                amm_open_order: Pubkey::new_unique(),
+                market_program: Pubkey::new_unique(),
+                market: Pubkey::new_unique(),
+                nonce: 254,
+            },
+            initial_coin_balance: 1_000_000_000,
+            initial_pc_balance: 5_000_000_000,
+            creation_signature: Signature::new_unique(),
+            slot: 42,
+        };
+        processor.track_reserves(&tracked).await;
+        processor
+            .pools
+            .lock()
+            .await
+            .insert(tracked.amm.amm_pool, tracked.clone());
+
+        let position =
+            |mint: Pubkey, amm_pool: Pubkey, quote_amount: u64, token_amount: u64| Position {
+                owner: Pubkey::new_unique(),
+                mint,
+                amm_pool,
+                quote_amount,
+                token_amount,
+                entry_signature: None,
+                opened_at: 0,
+            };
+        let untracked_mint = Pubkey::new_unique();
+        let positions = [
+            position(tracked.amm.amm_coin_mint, tracked.amm.amm_pool, 100, 1_000),
+            position(tracked.amm.amm_coin_mint, tracked.amm.amm_pool, 300, 2_000),
+            position(untracked_mint, Pubkey::new_unique(), 50, 500),
+            position(untracked_mint, Pubkey::new_unique(), 70, 700),
+        ];
+        let mut values = Vec::new();
+        for position in &positions {
+            values.push(processor.position_value(position).await);
+            processor
+                .positions
+                .lock()
+                .await
+                .insert((position.owner, position.mint), position.clone());
+        }
+
+        let aggregated = processor.aggregated_positions().await;
+        assert_eq!(aggregated.len(), 2);
+
+        let held = aggregated[&tracked.amm.amm_coin_mint];
+        assert_eq!(held.wallets, 2);
+        assert_eq!(held.quote_amount, 400);
+        assert_eq!(held.token_amount, 3_000);
+        assert_eq!(held.value, Some(values[0].unwrap() + values[1].unwrap()));
+
+        let untracked = aggregated[&untracked_mint];
+        assert_eq!(untracked.wallets, 2);
+        assert_eq!(untracked.quote_amount, 120);
+        assert_eq!(untracked.token_amount, 1_200);
+        assert_eq!(untracked.value, None);
+    }
+}
@@ -0,0 +1,96 @@
+use anyhow::Context;
+use solana_sdk::pubkey::Pubkey;
+use tracing::debug;
+
+use crate::{
+    dex::PoolReserves,
+    orca::{
+        state::{decode_tick_array, tick_array_address, WhirlpoolState},
+        WhirlpoolPool,
+    },
+    raydium::{clmm::token_program_of, cpmm::decode_token_amount},
+};
+
+use super::EventProcessor;
+
+/// Tick arrays fetched on each side of the current one: enough for the three a swap takes in
+/// either direction.
+const TICK_ARRAYS_AROUND_CURRENT: i32 = 3;
+
+impl EventProcessor {
+    /// Decodes a Whirlpool from its account `data`, then fetches its mints, vaults and the tick
+    /// arrays around the current price in a single request.
+    ///
+    /// The vault balances come along as reserves, for the liquidity filter only.
+    pub async fn get_whirlpool(
+        &self,
+        pool: &Pubkey,
+        data: &[u8],
+    ) -> anyhow::Result<(WhirlpoolPool, PoolReserves)> {
+        let program_id = self.config.programs.orca_whirlpool;
+        let state = WhirlpoolState::decode(data)
+            .with_context(|| format!("Failed to decode Whirlpool {}", pool))?;
+        debug!(?state, "Whirlpool state");
+
+        let ticks_per_array = state.ticks_per_array();
+        let current = state.tick_array_start_index(state.tick_current_index);
+        let starts = (-TICK_ARRAYS_AROUND_CURRENT..=TICK_ARRAYS_AROUND_CURRENT)
+            .map(|offset| current + offset * ticks_per_array)
+            .collect::<Vec<_>>();
+
+        let mut accounts = vec![
+            state.token_mint_a,
+            state.token_mint_b,
+            state.token_vault_a,
+            state.token_vault_b,
+        ];
+        let header = accounts.len();
+        accounts.extend(
+            starts
+                .iter()
+                .map(|start| tick_array_address(&program_id, pool, *start)),
+        );
+        let fetched = self.solana_api.get_multiple_accounts(&accounts).await?;
+        let account = |index: usize| {
+            fetched
+                .get(index)
+                .and_then(Option::as_ref)
+                .with_context(|| format!("Missing Whirlpool account {}", accounts[index]))
+        };
+
+        let token_a_program = token_program_of(&state.token_mint_a, &account(0)?.owner)?;
+        let token_b_program = token_program_of(&state.token_mint_b, &account(1)?.owner)?;
+        let reserves = PoolReserves {
+            coin_vault: decode_token_amount(&account(2)?.data)?,
+            pc_vault: decode_token_amount(&account(3)?.data)?,
+            ..Default::default()
+        };
+
+        // Arrays that do not exist hold no liquidity.
+        let tick_arrays = accounts[header..]
+            .iter()
+            .zip(&fetched[header..])
+            .filter_map(|(address, account)| {
+                account
+                    .as_ref()
+                    .map(|account| decode_tick_array(*address, &account.data, state.tick_spacing))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        debug!(
+            tick_arrays = tick_arrays.len(),
+            "Whirlpool tick arrays fetched"
+        );
+
+        Ok((
+            WhirlpoolPool {
+                program_id,
+                address: *pool,
+                state,
+                token_a_program,
+                token_b_program,
+                tick_arrays,
+            },
+            reserves,
+        ))
+    }
+}
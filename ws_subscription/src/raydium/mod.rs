@@ -1,6 +1,9 @@
+pub mod clmm;
+pub mod cpmm;
+pub mod dex;
 pub mod event_processors;
-mod models;
+pub mod models;
+pub mod pool;
+#[allow(clippy::module_inception, dead_code)]
 mod raydium;
 mod utils;
-
-pub use raydium::*;
@@ -1,8 +1,13 @@
+// Account layouts mirror the on-chain programs, so not every field or variant is read here.
+#![allow(dead_code)]
+
 use std::cell::{Ref, RefMut};
 
 use enumflags2::{bitflags, BitFlags};
 use safe_transmute::TriviallyTransmutable;
-use solana_sdk::pubkey::Pubkey;
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+
+use crate::dex::PoolReserves;
 
 use super::utils::ACCOUNT_HEAD_PADDING;
 
@@ -175,13 +180,12 @@ impl Market<'_> {
 
         BitFlags::from_bits(u64::from_le_bytes(flag_bytes))
             .map_err(|e| anyhow::Error::msg(e.to_string()))
-            .map(Into::into)
     }
 }
 
 #[derive(Copy, Clone)]
 #[cfg_attr(target_endian = "little", derive(Debug))]
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct MarketStateV2 {
     pub inner: MarketState,
     pub open_orders_authority: Pubkey,
@@ -222,7 +226,7 @@ impl MarketStateV2 {
 
 #[derive(Copy, Clone)]
 #[cfg_attr(target_endian = "little", derive(Debug))]
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct MarketState {
     // 0
     pub account_flags: u64, // Initialized, Market
@@ -337,4 +341,18 @@ pub struct Pool {
     pub amm: AmmKeys,
     pub initial_coin_balance: u64,
     pub initial_pc_balance: u64,
+    pub creation_signature: Signature,
+    pub slot: u64,
+}
+
+impl Pool {
+    /// Reserves right after the pool creation.
+    pub fn initial_reserves(&self) -> PoolReserves {
+        PoolReserves {
+            coin_vault: self.initial_coin_balance,
+            pc_vault: self.initial_pc_balance,
+            slot: self.slot,
+            ..Default::default()
+        }
+    }
 }
@@ -0,0 +1,148 @@
+use anyhow::Context;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+use crate::dex::{PoolReserves, SwapPool};
+
+use super::{
+    event_processors::{
+        pool_created::{SwapDirection, SWAP_FEE_DENOMINATOR, SWAP_FEE_NUMERATOR},
+        EventProcessor,
+    },
+    models::{AmmKeys, MarketKeys},
+    utils::compute_amm_authority_id,
+};
+
+/// Size of the `AmmInfo` account.
+pub const AMM_INFO_SIZE: u64 = 752;
+/// `AmmInfo::coin_vault_mint` and `pc_vault_mint`. The pubkeys start after the 16 header
+/// words, the 8 fee words and the 144 bytes of state data, with both vaults first.
+pub const AMM_INFO_MINT_OFFSETS: [usize; 2] = [400, 432];
+const AMM_INFO_NONCE_OFFSET: usize = 8;
+const AMM_INFO_COIN_VAULT_OFFSET: usize = 336;
+/// `AmmInfo::status` values swaps are allowed in: initialized, swap only and waiting for
+/// the open time, which the program checks itself.
+const AMM_STATUS_SWAPPABLE: [u64; 3] = [1, 6, 7];
+
+/// Rebuilds the keys of an existing pool from its `AmmInfo` account.
+pub fn decode_amm_keys(
+    program_id: &Pubkey,
+    amm_pool: &Pubkey,
+    data: &[u8],
+) -> anyhow::Result<AmmKeys> {
+    if data.len() as u64 != AMM_INFO_SIZE {
+        anyhow::bail!("Not an AMM V4 pool account");
+    }
+    let word = |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+    // Coin vault, pc vault, coin mint, pc mint, lp mint, open orders, market, market program
+    // and target orders, in that order.
+    let pubkey = |index: usize| {
+        let offset = AMM_INFO_COIN_VAULT_OFFSET + index * 32;
+        Pubkey::new_from_array(data[offset..offset + 32].try_into().unwrap())
+    };
+
+    let status = word(0);
+    if !AMM_STATUS_SWAPPABLE.contains(&status) {
+        anyhow::bail!("Swaps are disabled on pool {}, status {}", amm_pool, status);
+    }
+    let nonce = word(AMM_INFO_NONCE_OFFSET) as u8;
+
+    Ok(AmmKeys {
+        amm_pool: *amm_pool,
+        amm_coin_mint: pubkey(2),
+        amm_pc_mint: pubkey(3),
+        amm_authority: compute_amm_authority_id(program_id, nonce)
+            .map_err(|e| anyhow::anyhow!("{}", e))
+            .with_context(|| format!("Invalid nonce {} of pool {}", nonce, amm_pool))?,
+        amm_target: pubkey(8),
+        amm_coin_vault: pubkey(0),
+        amm_pc_vault: pubkey(1),
+        amm_lp_mint: pubkey(4),
+        amm_open_order: pubkey(5),
+        market_program: pubkey(7),
+        market: pubkey(6),
+        nonce,
+    })
+}
+
+/// A legacy AMM V4 pool, along with the keys of the OpenBook market it trades on.
+#[derive(Clone, Copy, Debug)]
+pub struct AmmV4Pool {
+    pub program_id: Pubkey,
+    pub amm: AmmKeys,
+    pub market_keys: MarketKeys,
+}
+
+impl SwapPool for AmmV4Pool {
+    fn id(&self) -> Pubkey {
+        self.amm.amm_pool
+    }
+
+    fn quote(
+        &self,
+        reserves: &PoolReserves,
+        input_mint: &Pubkey,
+        amount_in: u64,
+    ) -> anyhow::Result<u64> {
+        let direction = if *input_mint == self.amm.amm_coin_mint {
+            SwapDirection::Coin2PC
+        } else {
+            SwapDirection::PC2Coin
+        };
+        let (pc_amount, coin_amount) = reserves.without_take_pnl()?;
+
+        EventProcessor::swap_exact_amount(
+            pc_amount,
+            coin_amount,
+            SWAP_FEE_NUMERATOR,
+            SWAP_FEE_DENOMINATOR,
+            direction,
+            amount_in,
+            true,
+        )
+    }
+
+    fn swap_instruction(
+        &self,
+        owner: &Pubkey,
+        _input_mint: &Pubkey,
+        input_account: &Pubkey,
+        output_account: &Pubkey,
+        amount_in: u64,
+        min_amount_out: u64,
+    ) -> anyhow::Result<Instruction> {
+        let instruction_tag = 9u8; // "Swap" tag, https://github.com/reactive-biscuit/raydium-amm/blob/ae039d21cd49ef670d76b3a1cf5485ae0213dc5e/program/src/instruction.rs#L487
+        let mut swap_data = vec![instruction_tag];
+        swap_data.extend_from_slice(&amount_in.to_le_bytes());
+        swap_data.extend_from_slice(&min_amount_out.to_le_bytes());
+
+        let swap_accounts = vec![
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(self.amm.amm_pool, false),
+            AccountMeta::new_readonly(self.amm.amm_authority, false),
+            AccountMeta::new(self.amm.amm_open_order, false),
+            AccountMeta::new(self.amm.amm_target, false),
+            AccountMeta::new(self.amm.amm_coin_vault, false),
+            AccountMeta::new(self.amm.amm_pc_vault, false),
+            AccountMeta::new_readonly(self.amm.market_program, false),
+            AccountMeta::new(self.amm.market, false),
+            AccountMeta::new(self.market_keys.bids, false),
+            AccountMeta::new(self.market_keys.asks, false),
+            AccountMeta::new(self.market_keys.event_queue, false),
+            AccountMeta::new(self.market_keys.coin_vault, false),
+            AccountMeta::new(self.market_keys.pc_vault, false),
+            AccountMeta::new_readonly(self.market_keys.vault_signer_key, false),
+            AccountMeta::new(*input_account, false),
+            AccountMeta::new(*output_account, false),
+            AccountMeta::new_readonly(*owner, true),
+        ];
+
+        Ok(Instruction {
+            program_id: self.program_id,
+            accounts: swap_accounts,
+            data: swap_data,
+        })
+    }
+}
@@ -38,12 +38,20 @@
 //const TOKEN_PROGRAM: Pubkey = solana_sdk::pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
 //const SERUM_PROGRAM: Pubkey = solana_sdk::pubkey!("srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX");
 
+use std::sync::Arc;
+
+use crate::config::Config;
+
 use super::event_processors::EventProcessor;
 
 pub async fn execute_demo(ws_url: &str, rpc_url: &str) -> anyhow::Result<()> {
     let signature =
         "2LbMvKeJJbaUfYxpbgYw5LTQ3P1qviMyV8d72BnkyqkwdJ8T5k7knyUE2dPFUxqLwFAoGymmDcm3t73mLEwxhued";
-    let raydium_processor = EventProcessor::new(rpc_url, ws_url).await?;
+    let mut config = Config::default();
+    config.endpoints.rpc_url = Some(rpc_url.to_string());
+    config.endpoints.ws_url = Some(ws_url.to_string());
+
+    let raydium_processor = Arc::new(EventProcessor::new(&config).await?);
     //raydium_processor.execute().await?;
     raydium_processor.process_new_pool(signature).await?;
 
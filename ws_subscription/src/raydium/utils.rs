@@ -2,42 +2,57 @@ use std::{borrow::Cow, error::Error};
 
 use bytemuck::bytes_of;
 use safe_transmute::transmute_many_pedantic;
-use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_sdk::{
-    commitment_config::CommitmentConfig, program_error::ProgramError, pubkey::Pubkey,
-};
+use solana_sdk::{program_error::ProgramError, pubkey::Pubkey};
+
+use super::models::AmmKeys;
 
 pub const AUTHORITY_AMM: &[u8] = b"amm authority";
+// Seeds of the accounts `initialize2` creates, from
+// https://github.com/raydium-io/raydium-amm/blob/master/program/src/processor.rs
+pub const AMM_ASSOCIATED_SEED: &[u8] = b"amm_associated_seed";
+pub const TARGET_ASSOCIATED_SEED: &[u8] = b"target_associated_seed";
+pub const OPEN_ORDER_ASSOCIATED_SEED: &[u8] = b"open_order_associated_seed";
+pub const COIN_VAULT_ASSOCIATED_SEED: &[u8] = b"coin_vault_associated_seed";
+pub const PC_VAULT_ASSOCIATED_SEED: &[u8] = b"pc_vault_associated_seed";
+pub const LP_MINT_ASSOCIATED_SEED: &[u8] = b"lp_mint_associated_seed";
 pub const ACCOUNT_HEAD_PADDING: &[u8; 5] = b"serum";
 pub const ACCOUNT_TAIL_PADDING: &[u8; 7] = b"padding";
 
-pub async fn get_account<T>(
-    client: &RpcClient,
-    account: &Pubkey,
-) -> Result<Option<T>, Box<dyn Error>>
-where
-    T: Clone,
-{
-    let response = client
-        .get_account_with_commitment(account, CommitmentConfig::processed())
-        .await?;
-
-    if let Some(account) = response.value {
-        let account_data = account.data.as_slice();
-        let ret = unsafe { &*(&account_data[0] as *const u8 as *const T) };
-        Ok(Some(ret.clone()))
-    } else {
-        Ok(None)
-    }
+pub fn compute_amm_authority_id(program_id: &Pubkey, nonce: u8) -> Result<Pubkey, Box<dyn Error>> {
+    let result = Pubkey::create_program_address(&[AUTHORITY_AMM, &[nonce]], program_id)?;
+
+    Ok(result)
 }
 
-pub fn compute_amm_authority_id(
+/// Address of an account `initialize2` creates for the pool listing `market`.
+pub fn get_associated_address(program_id: &Pubkey, market: &Pubkey, seed: &[u8]) -> Pubkey {
+    Pubkey::find_program_address(&[program_id.as_ref(), market.as_ref(), seed], program_id).0
+}
+
+/// Keys of the pool `initialize2` will create for `market`, derived without any RPC call.
+pub fn derive_amm_keys(
     program_id: &Pubkey,
-    nonce: u8,
-) -> Result<Pubkey, Box<(dyn Error)>> {
-    let result = Pubkey::create_program_address(&[AUTHORITY_AMM, &[nonce]], program_id)?;
+    market_program: &Pubkey,
+    market: &Pubkey,
+    coin_mint: &Pubkey,
+    pc_mint: &Pubkey,
+) -> AmmKeys {
+    let (amm_authority, nonce) = Pubkey::find_program_address(&[AUTHORITY_AMM], program_id);
 
-    Ok(result)
+    AmmKeys {
+        amm_pool: get_associated_address(program_id, market, AMM_ASSOCIATED_SEED),
+        amm_coin_mint: *coin_mint,
+        amm_pc_mint: *pc_mint,
+        amm_authority,
+        amm_target: get_associated_address(program_id, market, TARGET_ASSOCIATED_SEED),
+        amm_coin_vault: get_associated_address(program_id, market, COIN_VAULT_ASSOCIATED_SEED),
+        amm_pc_vault: get_associated_address(program_id, market, PC_VAULT_ASSOCIATED_SEED),
+        amm_lp_mint: get_associated_address(program_id, market, LP_MINT_ASSOCIATED_SEED),
+        amm_open_order: get_associated_address(program_id, market, OPEN_ORDER_ASSOCIATED_SEED),
+        market_program: *market_program,
+        market: *market,
+        nonce,
+    }
 }
 
 pub fn remove_dex_account_padding<'a>(data: &'a [u8]) -> Result<Cow<'a, [u64]>, String> {
@@ -80,3 +95,94 @@ pub fn gen_vault_signer_key(
 fn gen_vault_signer_seeds<'a>(nonce: &'a u64, market: &'a Pubkey) -> [&'a [u8]; 2] {
     [market.as_ref(), bytes_of(nonce)]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROGRAM_ID: Pubkey = solana_sdk::pubkey!("675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8");
+    const OPENBOOK: Pubkey = solana_sdk::pubkey!("srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX");
+    // RAY-USDC
+    const RAY_USDC_POOL: Pubkey =
+        solana_sdk::pubkey!("6UmmUiYoBjSrhakAobJw8BvkmJtDVxaeBtbt7rxWo1mg");
+    const RAY_USDC_MARKET: Pubkey =
+        solana_sdk::pubkey!("2xiv8A5xrJ7RnGdxXB42uFEkYHJjszEhaJyKKt4WaLep");
+    const RAY: Pubkey = solana_sdk::pubkey!("4k3Dyjzvzp8eMZWUXbBCjEvwSkkk59S5iCNLY3QrkX6R");
+    const USDC: Pubkey = solana_sdk::pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+
+    fn ray_usdc() -> AmmKeys {
+        derive_amm_keys(&PROGRAM_ID, &OPENBOOK, &RAY_USDC_MARKET, &RAY, &USDC)
+    }
+
+    #[test]
+    fn derives_the_amm_authority_of_every_pool() {
+        let keys = ray_usdc();
+
+        assert_eq!(
+            keys.amm_authority,
+            solana_sdk::pubkey!("5Q544fKrFoe6tsEbD7S8EmxGTJYAKtTVhAW5Q5pge4j1")
+        );
+        assert_eq!(keys.nonce, 254);
+        assert_eq!(
+            compute_amm_authority_id(&PROGRAM_ID, keys.nonce).unwrap(),
+            keys.amm_authority
+        );
+    }
+
+    #[test]
+    fn derives_the_pool_id_from_its_market() {
+        assert_eq!(ray_usdc().amm_pool, RAY_USDC_POOL);
+    }
+
+    #[test]
+    fn keeps_the_market_and_mints() {
+        let keys = ray_usdc();
+
+        assert_eq!(keys.market, RAY_USDC_MARKET);
+        assert_eq!(keys.market_program, OPENBOOK);
+        assert_eq!(keys.amm_coin_mint, RAY);
+        assert_eq!(keys.amm_pc_mint, USDC);
+    }
+
+    /// SOL-USDC has been migrated since its listing, but its open orders are still the ones
+    /// derived from its market.
+    #[test]
+    fn derives_the_open_orders_of_sol_usdc() {
+        let sol_usdc_market = solana_sdk::pubkey!("8BnEgHoWFysVcuFFX7QztDmzuH8r5ZFvyP3sYwn1XTh6");
+        let sol = solana_sdk::pubkey!("So11111111111111111111111111111111111111112");
+        let keys = derive_amm_keys(&PROGRAM_ID, &OPENBOOK, &sol_usdc_market, &sol, &USDC);
+
+        assert_eq!(
+            keys.amm_open_order,
+            solana_sdk::pubkey!("HmiHHzq4Fym9e1D4qzLS6LDDM3tNsCTBPDWHTLZ763jY")
+        );
+    }
+
+    #[test]
+    fn derives_distinct_program_addresses() {
+        let keys = ray_usdc();
+        let addresses = [
+            keys.amm_pool,
+            keys.amm_authority,
+            keys.amm_open_order,
+            keys.amm_target,
+            keys.amm_coin_vault,
+            keys.amm_pc_vault,
+            keys.amm_lp_mint,
+        ];
+
+        for (index, address) in addresses.iter().enumerate() {
+            assert!(!address.is_on_curve(), "{} is on the curve", address);
+            assert!(!addresses[index + 1..].contains(address));
+        }
+    }
+
+    #[test]
+    fn derives_different_pools_for_different_markets() {
+        let other_market = solana_sdk::pubkey!("8BnEgHoWFysVcuFFX7QztDmzuH8r5ZFvyP3sYwn1XTh6");
+        let other = derive_amm_keys(&PROGRAM_ID, &OPENBOOK, &other_market, &RAY, &USDC);
+
+        assert_ne!(other.amm_pool, RAY_USDC_POOL);
+        assert_eq!(other.amm_authority, ray_usdc().amm_authority);
+    }
+}
@@ -0,0 +1,376 @@
+//! Best execution across venues: every pool of a pair is quoted locally, then the swap goes
+//! through the best one, or is split across the best two when that returns more.
+//!
+//! A route is itself a [`SwapPool`], so it goes through the same buy as a single pool and
+//! still lands as one transaction.
+
+use std::cmp::Reverse;
+
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+use tracing::debug;
+
+use crate::dex::{PoolReserves, SwapPool};
+
+const MAX_BPS: u64 = 10_000;
+/// Granularity of the splits tried between two pools.
+const SPLIT_STEP_BPS: u64 = 500;
+/// Only the pools quoting best on their own are tried in pairs.
+const MAX_SPLIT_CANDIDATES: usize = 4;
+
+/// A pool of the pair, decoded and ready to quote.
+#[derive(Debug)]
+pub struct Candidate {
+    pub pool: Box<dyn SwapPool>,
+    /// Both mints of the pool, in the order of its reserves.
+    pub mints: [Pubkey; 2],
+    pub reserves: PoolReserves,
+}
+
+/// The part of a route going through one pool.
+#[derive(Debug)]
+pub struct RouteLeg {
+    pub pool: Box<dyn SwapPool>,
+    pub reserves: PoolReserves,
+    /// Share of the input swapped on this pool.
+    pub share_bps: u64,
+}
+
+/// Pools a swap goes through, the largest share first.
+#[derive(Debug)]
+pub struct Route {
+    pub legs: Vec<RouteLeg>,
+}
+
+impl Route {
+    /// All of the swap through a single pool.
+    pub fn single(candidate: Candidate) -> Self {
+        Self {
+            legs: vec![RouteLeg {
+                pool: candidate.pool,
+                reserves: candidate.reserves,
+                share_bps: MAX_BPS,
+            }],
+        }
+    }
+
+    /// Reserves of the main pool.
+    pub fn reserves(&self) -> PoolReserves {
+        self.legs[0].reserves
+    }
+
+    /// The input of each leg: the last one takes what the others leave, rounding included.
+    fn leg_amounts(&self, amount_in: u64) -> Vec<u64> {
+        let mut remaining = amount_in;
+        let mut amounts = self
+            .legs
+            .iter()
+            .take(self.legs.len() - 1)
+            .map(|leg| {
+                let amount = (amount_in as u128 * leg.share_bps as u128 / MAX_BPS as u128) as u64;
+                remaining -= amount;
+                amount
+            })
+            .collect::<Vec<_>>();
+        amounts.push(remaining);
+
+        amounts
+    }
+
+    fn leg_quotes(&self, input_mint: &Pubkey, amount_in: u64) -> anyhow::Result<Vec<(u64, u64)>> {
+        self.legs
+            .iter()
+            .zip(self.leg_amounts(amount_in))
+            .map(|(leg, amount)| Ok((amount, leg.pool.quote(&leg.reserves, input_mint, amount)?)))
+            .collect()
+    }
+}
+
+impl SwapPool for Route {
+    /// The main pool, under which the trade is recorded.
+    fn id(&self) -> Pubkey {
+        self.legs[0].pool.id()
+    }
+
+    fn token_program(&self, mint: &Pubkey) -> Pubkey {
+        self.legs[0].pool.token_program(mint)
+    }
+
+    fn takes_native_sol(&self) -> bool {
+        self.legs[0].pool.takes_native_sol()
+    }
+
+    /// Each leg is quoted at its own reserves, the ones passed are ignored.
+    fn quote(
+        &self,
+        _reserves: &PoolReserves,
+        input_mint: &Pubkey,
+        amount_in: u64,
+    ) -> anyhow::Result<u64> {
+        Ok(self
+            .leg_quotes(input_mint, amount_in)?
+            .iter()
+            .map(|(_, quote)| quote)
+            .sum())
+    }
+
+    fn swap_instruction(
+        &self,
+        owner: &Pubkey,
+        input_mint: &Pubkey,
+        input_account: &Pubkey,
+        output_account: &Pubkey,
+        amount_in: u64,
+        min_amount_out: u64,
+    ) -> anyhow::Result<Instruction> {
+        match self.legs.as_slice() {
+            [leg] => leg.pool.swap_instruction(
+                owner,
+                input_mint,
+                input_account,
+                output_account,
+                amount_in,
+                min_amount_out,
+            ),
+            _ => Err(anyhow::anyhow!(
+                "A route split across {} pools takes one instruction each",
+                self.legs.len()
+            )),
+        }
+    }
+
+    /// One swap per leg, the minimum output shared out in proportion to their quotes.
+    fn swap_instructions(
+        &self,
+        owner: &Pubkey,
+        input_mint: &Pubkey,
+        input_account: &Pubkey,
+        output_account: &Pubkey,
+        amount_in: u64,
+        min_amount_out: u64,
+    ) -> anyhow::Result<Vec<Instruction>> {
+        let quotes = self.leg_quotes(input_mint, amount_in)?;
+        let total = quotes
+            .iter()
+            .map(|(_, quote)| *quote as u128)
+            .sum::<u128>()
+            .max(1);
+
+        self.legs
+            .iter()
+            .zip(quotes)
+            .map(|(leg, (amount, quote))| {
+                let min_leg_out = (min_amount_out as u128 * quote as u128 / total) as u64;
+                leg.pool.swap_instruction(
+                    owner,
+                    input_mint,
+                    input_account,
+                    output_account,
+                    amount,
+                    min_leg_out,
+                )
+            })
+            .collect()
+    }
+}
+
+/// The route returning the most for exactly `amount_in` of `input_mint`: the best pool on
+/// its own, or a split between two of the best pools when it beats it.
+pub fn best_route(
+    candidates: Vec<Candidate>,
+    input_mint: &Pubkey,
+    amount_in: u64,
+) -> anyhow::Result<Route> {
+    let mut quoted = candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            match candidate
+                .pool
+                .quote(&candidate.reserves, input_mint, amount_in)
+            {
+                Ok(quote) => Some((quote, candidate)),
+                Err(e) => {
+                    debug!(pool = %candidate.pool.id(), "Pool left out of the route: {:#}", e);
+                    None
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    quoted.sort_by_key(|(quote, _)| Reverse(*quote));
+    quoted.truncate(MAX_SPLIT_CANDIDATES);
+
+    let Some((best_single, _)) = quoted.first() else {
+        return Err(anyhow::anyhow!(
+            "No pool can quote {} of {}",
+            amount_in,
+            input_mint
+        ));
+    };
+
+    // (total, first pool, second pool, share of the first)
+    let mut best_split: Option<(u64, usize, usize, u64)> = None;
+    for first in 0..quoted.len() {
+        for second in first + 1..quoted.len() {
+            for share_bps in (SPLIT_STEP_BPS..MAX_BPS).step_by(SPLIT_STEP_BPS as usize) {
+                let first_amount = (amount_in as u128 * share_bps as u128 / MAX_BPS as u128) as u64;
+                let quote = |index: usize, amount: u64| {
+                    let candidate: &Candidate = &quoted[index].1;
+                    candidate
+                        .pool
+                        .quote(&candidate.reserves, input_mint, amount)
+                };
+                let (Ok(first_out), Ok(second_out)) = (
+                    quote(first, first_amount),
+                    quote(second, amount_in - first_amount),
+                ) else {
+                    continue;
+                };
+
+                let total = first_out.saturating_add(second_out);
+                if total > best_split.map_or(*best_single, |(best, ..)| best) {
+                    best_split = Some((total, first, second, share_bps));
+                }
+            }
+        }
+    }
+
+    let Some((total, first, second, share_bps)) = best_split else {
+        let (_, candidate) = quoted.swap_remove(0);
+        return Ok(Route::single(candidate));
+    };
+    debug!(total, best_single, share_bps, "Splitting the swap");
+
+    // Taken out from the back first so the front index stays valid.
+    let (_, second) = quoted.swap_remove(second);
+    let (_, first) = quoted.swap_remove(first);
+    let mut legs = vec![
+        RouteLeg {
+            pool: first.pool,
+            reserves: first.reserves,
+            share_bps,
+        },
+        RouteLeg {
+            pool: second.pool,
+            reserves: second.reserves,
+            share_bps: MAX_BPS - share_bps,
+        },
+    ];
+    legs.sort_by_key(|leg| Reverse(leg.share_bps));
+
+    Ok(Route { legs })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Constant product pool without fees, quoting from `reserves`.
+    #[derive(Debug)]
+    struct TestPool {
+        id: Pubkey,
+        input_mint: Pubkey,
+    }
+
+    impl SwapPool for TestPool {
+        fn id(&self) -> Pubkey {
+            self.id
+        }
+
+        fn quote(
+            &self,
+            reserves: &PoolReserves,
+            input_mint: &Pubkey,
+            amount_in: u64,
+        ) -> anyhow::Result<u64> {
+            anyhow::ensure!(*input_mint == self.input_mint, "Wrong input mint");
+            let (input, output) = (reserves.coin_vault as u128, reserves.pc_vault as u128);
+
+            Ok((output * amount_in as u128 / (input + amount_in as u128)) as u64)
+        }
+
+        fn swap_instruction(
+            &self,
+            _owner: &Pubkey,
+            _input_mint: &Pubkey,
+            _input_account: &Pubkey,
+            _output_account: &Pubkey,
+            amount_in: u64,
+            min_amount_out: u64,
+        ) -> anyhow::Result<Instruction> {
+            let mut data = amount_in.to_le_bytes().to_vec();
+            data.extend_from_slice(&min_amount_out.to_le_bytes());
+
+            Ok(Instruction {
+                program_id: self.id,
+                accounts: Vec::new(),
+                data,
+            })
+        }
+    }
+
+    fn candidate(input_mint: Pubkey, input_reserve: u64, output_reserve: u64) -> Candidate {
+        Candidate {
+            pool: Box::new(TestPool {
+                id: Pubkey::new_unique(),
+                input_mint,
+            }),
+            mints: [input_mint, Pubkey::new_unique()],
+            reserves: PoolReserves {
+                coin_vault: input_reserve,
+                pc_vault: output_reserve,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn small_swaps_go_through_the_deepest_pool() {
+        let input_mint = Pubkey::new_unique();
+        let deep = candidate(input_mint, 1_000_000_000, 1_000_000_000);
+        let deep_id = deep.pool.id();
+        let candidates = vec![candidate(input_mint, 100_000, 100_000), deep];
+
+        let route = best_route(candidates, &input_mint, 1_000).unwrap();
+
+        assert_eq!(route.legs.len(), 1);
+        assert_eq!(route.id(), deep_id);
+    }
+
+    #[test]
+    fn large_swaps_are_split_across_pools_of_the_same_depth() {
+        let input_mint = Pubkey::new_unique();
+        let candidates = vec![
+            candidate(input_mint, 1_000_000, 1_000_000),
+            candidate(input_mint, 1_000_000, 1_000_000),
+            candidate(Pubkey::new_unique(), 1_000_000, 1_000_000),
+        ];
+        let amount_in = 1_000_000;
+
+        let route = best_route(candidates, &input_mint, amount_in).unwrap();
+
+        assert_eq!(route.legs.len(), 2);
+        assert_eq!(route.leg_amounts(amount_in), vec![500_000, 500_000]);
+        // Either pool alone returns half the input.
+        let quote = route
+            .quote(&PoolReserves::default(), &input_mint, amount_in)
+            .unwrap();
+        assert!(quote > 500_000);
+
+        // Each leg gets its share of the input and of the minimum output.
+        let instructions = route
+            .swap_instructions(
+                &Pubkey::default(),
+                &input_mint,
+                &Pubkey::default(),
+                &Pubkey::default(),
+                amount_in,
+                quote,
+            )
+            .unwrap();
+        let min_out = instructions
+            .iter()
+            .map(|instruction| u64::from_le_bytes(instruction.data[8..].try_into().unwrap()))
+            .sum::<u64>();
+        assert_eq!(instructions.len(), 2);
+        assert!(quote - min_out <= 1);
+    }
+}
@@ -0,0 +1,346 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use metrics::counter;
+use solana_sdk::{
+    signature::{Keypair, Signature},
+    signer::Signer,
+    transaction::{Transaction, VersionedTransaction},
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    api::{
+        jito::{BundleStatus, JitoClient},
+        solana_rpc::SolanaApi,
+    },
+    telemetry,
+};
+
+use super::{LandingStats, Outcome, SenderStats, TransactionSender};
+
+/// A Jito bundle of the transaction and a tip to the block engine, sent once the block engine
+/// accepted it.
+///
+/// The sender gives up on a bundle that fails or does not land within `status_timeout`, for a
+/// [`super::Fallback`] to send the transaction some other way. It is the very same
+/// transaction, so it can only land once either way.
+pub struct BundleSender {
+    jito: JitoClient,
+    /// Where the blockhash the tip is signed on comes from.
+    solana_api: Arc<SolanaApi>,
+    tip_lamports: u64,
+    status_timeout: Duration,
+    /// Bundles not given up on yet and when they were sent, by the signature of their
+    /// transaction.
+    pending: Mutex<HashMap<Signature, (String, Instant)>>,
+    stats: SenderStats,
+}
+
+impl BundleSender {
+    pub fn new(
+        jito: JitoClient,
+        solana_api: Arc<SolanaApi>,
+        tip_lamports: u64,
+        status_timeout: Duration,
+    ) -> Self {
+        Self {
+            jito,
+            solana_api,
+            tip_lamports,
+            status_timeout,
+            pending: Mutex::new(HashMap::new()),
+            stats: SenderStats::default(),
+        }
+    }
+
+    /// Sends `transaction` with its tip, and returns the bundle id.
+    async fn send_bundle(
+        &self,
+        transaction: &VersionedTransaction,
+        payer: &Keypair,
+    ) -> anyhow::Result<String> {
+        // The transaction may be signed on a durable nonce, whose hash the runtime only takes
+        // along with an advance of the nonce: the tip needs a recent blockhash of its own.
+        let recent_blockhash = match self.solana_api.recent_blockhash() {
+            Some(cached) if !cached.stale => cached.blockhash,
+            cached => {
+                debug!(
+                    stale = cached.is_some(),
+                    "No fresh cached blockhash for the tip, fetching the latest"
+                );
+                self.solana_api.get_latest_blockhash().await?.0
+            }
+        };
+        let tip_accounts = self.jito.get_tip_accounts().await?;
+        // Spread over the tip accounts to avoid contending on a single one.
+        let tip_account =
+            tip_accounts[transaction.signatures[0].as_ref()[0] as usize % tip_accounts.len()];
+        let tip = Transaction::new_signed_with_payer(
+            &[solana_system_interface::instruction::transfer(
+                &payer.pubkey(),
+                &tip_account,
+                self.tip_lamports,
+            )],
+            Some(&payer.pubkey()),
+            &[payer],
+            recent_blockhash,
+        );
+
+        let bundle_id = self
+            .jito
+            .send_bundle(&[transaction.clone(), tip.into()])
+            .await?;
+        info!(%bundle_id, %tip_account, tip = self.tip_lamports, "Bundle sent");
+
+        Ok(bundle_id)
+    }
+}
+
+#[async_trait]
+impl TransactionSender for BundleSender {
+    fn name(&self) -> &str {
+        "bundle"
+    }
+
+    async fn send(
+        &self,
+        transaction: &VersionedTransaction,
+        payer: &Keypair,
+    ) -> anyhow::Result<Vec<String>> {
+        let started = Instant::now();
+        let sent = self.send_bundle(transaction, payer).await;
+        self.stats.record_send(self.name(), started, &sent);
+        let bundle_id = sent.inspect_err(|_| {
+            counter!(telemetry::BUNDLES, "status" => "rejected").increment(1);
+        })?;
+
+        let mut pending = self.pending.lock().unwrap();
+        // Bundles that settled with their transaction are never asked about again.
+        pending.retain(|_, (_, sent_at)| sent_at.elapsed() <= self.status_timeout);
+        pending.insert(transaction.signatures[0], (bundle_id, Instant::now()));
+
+        Ok(vec![self.name().to_string()])
+    }
+
+    async fn gave_up(&self, transaction: &VersionedTransaction) -> bool {
+        let signature = transaction.signatures[0];
+        let Some((bundle_id, sent_at)) = self.pending.lock().unwrap().get(&signature).cloned()
+        else {
+            return false;
+        };
+
+        if sent_at.elapsed() > self.status_timeout {
+            counter!(telemetry::BUNDLES, "status" => "timed_out").increment(1);
+            self.stats.record_outcome(self.name(), Outcome::Expired);
+            self.pending.lock().unwrap().remove(&signature);
+            warn!(%bundle_id, "Bundle still pending after {:?}", self.status_timeout);
+
+            return true;
+        }
+
+        match self.jito.get_inflight_bundle_status(&bundle_id).await {
+            Ok(BundleStatus::Failed) => {
+                counter!(telemetry::BUNDLES, "status" => "failed").increment(1);
+                self.stats.record_outcome(self.name(), Outcome::Failed);
+                self.pending.lock().unwrap().remove(&signature);
+                warn!(%bundle_id, "Bundle failed");
+
+                true
+            }
+            // Freshly sent bundles are not known to every region yet, and landed ones are
+            // settled with their transaction.
+            Ok(BundleStatus::Pending | BundleStatus::Invalid | BundleStatus::Landed) => false,
+            Err(e) => {
+                warn!(%bundle_id, "Failed to follow the bundle: {:#}", e);
+                false
+            }
+        }
+    }
+
+    fn record_outcome(&self, sender: &str, outcome: Outcome) {
+        if sender == self.name() {
+            let status = match outcome {
+                Outcome::Expired => "timed_out",
+                outcome => outcome.as_str(),
+            };
+            counter!(telemetry::BUNDLES, "status" => status).increment(1);
+            self.stats.record_outcome(sender, outcome);
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, LandingStats)> {
+        vec![(self.name().to_string(), self.stats.snapshot())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use mockito::{Matcher, Server, ServerGuard};
+    use serde_json::{json, Value};
+    use solana_sdk::{hash::Hash, pubkey::Pubkey};
+
+    use super::*;
+
+    const BLOCKHASH: Hash = Hash::new_from_array([7; 32]);
+    const TIP_ACCOUNT: Pubkey = Pubkey::new_from_array([9; 32]);
+
+    fn sender(server: &ServerGuard, status_timeout: Duration) -> BundleSender {
+        BundleSender::new(
+            JitoClient::new(&server.url()),
+            Arc::new(SolanaApi::new(&server.url(), None, None)),
+            1_000,
+            status_timeout,
+        )
+    }
+
+    fn result(result: Value) -> String {
+        json!({ "jsonrpc": "2.0", "id": 1, "result": result }).to_string()
+    }
+
+    /// A block engine accepting every bundle as `bundle`, and reporting it `status`.
+    async fn block_engine(status: &str) -> ServerGuard {
+        let mut server = tipping_block_engine(status).await;
+        server
+            .mock("POST", "/")
+            .match_body(Matcher::PartialJson(json!({ "method": "sendBundle" })))
+            .with_body(result(json!("bundle")))
+            .create_async()
+            .await;
+
+        server
+    }
+
+    /// A block engine that has yet to accept bundles, also serving the RPC the tip's
+    /// blockhash is fetched from.
+    async fn tipping_block_engine(status: &str) -> ServerGuard {
+        let mut server = Server::new_async().await;
+        server
+            .mock("POST", "/")
+            .match_body(Matcher::PartialJson(
+                json!({ "method": "getLatestBlockhash" }),
+            ))
+            .with_body(result(json!({
+                "context": { "slot": 1 },
+                "value": { "blockhash": BLOCKHASH.to_string(), "lastValidBlockHeight": 150 },
+            })))
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/")
+            .match_body(Matcher::PartialJson(json!({ "method": "getTipAccounts" })))
+            .with_body(result(json!([TIP_ACCOUNT.to_string()])))
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/")
+            .match_body(Matcher::PartialJson(
+                json!({ "method": "getInflightBundleStatuses" }),
+            ))
+            .with_body(result(json!({
+                "context": { "slot": 1 },
+                "value": [{ "bundle_id": "bundle", "status": status, "landed_slot": null }],
+            })))
+            .create_async()
+            .await;
+
+        server
+    }
+
+    fn transaction(payer: &Keypair) -> VersionedTransaction {
+        Transaction::new_signed_with_payer(
+            &[solana_system_interface::instruction::transfer(
+                &payer.pubkey(),
+                &Pubkey::new_unique(),
+                1_000,
+            )],
+            Some(&payer.pubkey()),
+            &[payer],
+            Hash::default(),
+        )
+        .into()
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_failed_bundles_without_waiting_for_them() {
+        let server = block_engine("Failed").await;
+        let sender = sender(&server, Duration::from_secs(60));
+        let payer = Keypair::new();
+        let transaction = transaction(&payer);
+
+        let accepted = sender.send(&transaction, &payer).await.unwrap();
+        assert_eq!(accepted, vec!["bundle"]);
+
+        assert!(sender.gave_up(&transaction).await);
+        // Only once: the fallback took over.
+        assert!(!sender.gave_up(&transaction).await);
+        let stats = sender.stats()[0].1;
+        assert_eq!((stats.sent, stats.failed), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_bundles_pending_past_the_timeout() {
+        let server = block_engine("Pending").await;
+        let sender = sender(&server, Duration::from_millis(100));
+        let payer = Keypair::new();
+        let transaction = transaction(&payer);
+
+        sender.send(&transaction, &payer).await.unwrap();
+        assert!(!sender.gave_up(&transaction).await);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(sender.gave_up(&transaction).await);
+        assert_eq!(sender.stats()[0].1.expired, 1);
+    }
+
+    #[tokio::test]
+    async fn keeps_bundles_it_did_not_send() {
+        let server = block_engine("Failed").await;
+        let sender = sender(&server, Duration::from_secs(60));
+
+        assert!(!sender.gave_up(&transaction(&Keypair::new())).await);
+    }
+
+    #[tokio::test]
+    async fn tips_on_a_recent_blockhash() {
+        let mut server = tipping_block_engine("Pending").await;
+        let payer = Keypair::new();
+        // Stands for a buy signed on a durable nonce.
+        let transaction = transaction(&payer);
+        let tip: VersionedTransaction = Transaction::new_signed_with_payer(
+            &[solana_system_interface::instruction::transfer(
+                &payer.pubkey(),
+                &TIP_ACCOUNT,
+                1_000,
+            )],
+            Some(&payer.pubkey()),
+            &[&payer],
+            BLOCKHASH,
+        )
+        .into();
+        let encode = |transaction: &VersionedTransaction| {
+            STANDARD.encode(bincode::serialize(transaction).unwrap())
+        };
+        let send = server
+            .mock("POST", "/")
+            .match_body(Matcher::PartialJson(json!({
+                "method": "sendBundle",
+                "params": [[encode(&transaction), encode(&tip)], { "encoding": "base64" }],
+            })))
+            .with_body(result(json!("tipped")))
+            .create_async()
+            .await;
+
+        sender(&server, Duration::from_secs(60))
+            .send(&transaction, &payer)
+            .await
+            .unwrap();
+
+        send.assert_async().await;
+    }
+}
@@ -0,0 +1,537 @@
+//! Ways of getting a signed transaction to the leader: RPC `sendTransaction`, the TPUs of the
+//! upcoming leaders, or a Jito bundle. Senders compose, so a transaction can fan out to several
+//! at once or fall back from one to another, and each keeps count of what it got to land.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use futures::future::join_all;
+use metrics::{counter, histogram};
+use solana_sdk::{signature::Keypair, transaction::VersionedTransaction};
+use tracing::{debug, warn};
+
+use crate::{
+    api::{jito::JitoClient, solana_rpc::SolanaApi},
+    config::Config,
+    telemetry,
+};
+
+use self::{bundle::BundleSender, rpc::RpcSender, tpu::TpuSender};
+
+pub mod bundle;
+pub mod rpc;
+pub mod tpu;
+
+/// How a transaction accepted by a sender ended.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Outcome {
+    Landed,
+    Failed,
+    Expired,
+}
+
+impl Outcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Outcome::Landed => "landed",
+            Outcome::Failed => "failed",
+            Outcome::Expired => "expired",
+        }
+    }
+}
+
+/// What became of the transactions given to one sender.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LandingStats {
+    /// Accepted by the sender.
+    pub sent: u64,
+    /// Refused by the sender, e.g. a failed preflight or an unreachable endpoint.
+    pub rejected: u64,
+    pub landed: u64,
+    pub failed: u64,
+    pub expired: u64,
+    /// Time the accepted transactions took to be accepted, summed.
+    pub send_time: Duration,
+}
+
+impl LandingStats {
+    /// Share of the accepted transactions that landed, when any was settled.
+    pub fn landing_rate(&self) -> Option<f64> {
+        let settled = self.landed + self.failed + self.expired;
+        (settled > 0).then(|| self.landed as f64 / settled as f64)
+    }
+
+    pub fn mean_send_time(&self) -> Option<Duration> {
+        (self.sent > 0).then(|| self.send_time / self.sent as u32)
+    }
+}
+
+/// Submits signed transactions.
+#[async_trait]
+pub trait TransactionSender: Send + Sync {
+    /// Names the sender in logs, metrics and stats.
+    fn name(&self) -> &str;
+
+    /// Submits `transaction`, and returns the names of the senders that accepted it. `payer`
+    /// pays for what the sender adds to it, e.g. the tip of a bundle.
+    async fn send(
+        &self,
+        transaction: &VersionedTransaction,
+        payer: &Keypair,
+    ) -> anyhow::Result<Vec<String>>;
+
+    /// Whether the sender gave up on landing `transaction`, which it accepted, while it may
+    /// still land some other way. Asked until the transaction settles.
+    async fn gave_up(&self, _transaction: &VersionedTransaction) -> bool {
+        false
+    }
+
+    /// Sends some other way a transaction still pending whose sender gave up on it. Returns
+    /// the senders now carrying it in place of `accepted`, or `None` when nothing changed.
+    async fn follow_up(
+        &self,
+        _accepted: &[String],
+        _transaction: &VersionedTransaction,
+        _payer: &Keypair,
+    ) -> Option<Vec<String>> {
+        None
+    }
+
+    /// Records how a transaction accepted by `sender` ended.
+    fn record_outcome(&self, sender: &str, outcome: Outcome);
+
+    /// Landing stats of the sender, or of each of the senders it is made of.
+    fn stats(&self) -> Vec<(String, LandingStats)>;
+}
+
+/// Landing stats of a single sender, kept in step with the `sniper_sender_*` metrics.
+#[derive(Debug, Default)]
+pub struct SenderStats {
+    stats: Mutex<LandingStats>,
+}
+
+impl SenderStats {
+    /// Records a send of `sender` that started at `started`.
+    pub fn record_send<T>(&self, sender: &str, started: Instant, result: &anyhow::Result<T>) {
+        let elapsed = started.elapsed();
+        let mut stats = self.stats.lock().unwrap();
+        match result {
+            Ok(_) => {
+                stats.sent += 1;
+                stats.send_time += elapsed;
+                histogram!(telemetry::SENDER_SEND_DURATION, "sender" => sender.to_string())
+                    .record(elapsed.as_secs_f64());
+                counter!(telemetry::SENDER_TRANSACTIONS, "sender" => sender.to_string(), "status" => "sent")
+                    .increment(1);
+            }
+            Err(_) => {
+                stats.rejected += 1;
+                counter!(telemetry::SENDER_TRANSACTIONS, "sender" => sender.to_string(), "status" => "rejected")
+                    .increment(1);
+            }
+        }
+    }
+
+    pub fn record_outcome(&self, sender: &str, outcome: Outcome) {
+        let mut stats = self.stats.lock().unwrap();
+        match outcome {
+            Outcome::Landed => stats.landed += 1,
+            Outcome::Failed => stats.failed += 1,
+            Outcome::Expired => stats.expired += 1,
+        }
+        counter!(telemetry::SENDER_TRANSACTIONS, "sender" => sender.to_string(), "status" => outcome.as_str())
+            .increment(1);
+    }
+
+    pub fn snapshot(&self) -> LandingStats {
+        *self.stats.lock().unwrap()
+    }
+}
+
+/// Sends every transaction through all of `senders` at once. It is sent as long as one of them
+/// accepts it, and can only land once since they all carry the same signature.
+pub struct FanOut {
+    senders: Vec<Box<dyn TransactionSender>>,
+}
+
+impl FanOut {
+    pub fn new(senders: Vec<Box<dyn TransactionSender>>) -> Self {
+        Self { senders }
+    }
+}
+
+#[async_trait]
+impl TransactionSender for FanOut {
+    fn name(&self) -> &str {
+        "fan_out"
+    }
+
+    async fn send(
+        &self,
+        transaction: &VersionedTransaction,
+        payer: &Keypair,
+    ) -> anyhow::Result<Vec<String>> {
+        let results = join_all(
+            self.senders
+                .iter()
+                .map(|sender| sender.send(transaction, payer)),
+        )
+        .await;
+
+        let mut accepted = Vec::new();
+        let mut errors = Vec::new();
+        for (sender, result) in self.senders.iter().zip(results) {
+            match result {
+                Ok(names) => accepted.extend(names),
+                Err(e) => {
+                    debug!(
+                        sender = sender.name(),
+                        "Sender refused the transaction: {:#}", e
+                    );
+                    errors.push(format!("{}: {:#}", sender.name(), e));
+                }
+            }
+        }
+
+        if accepted.is_empty() {
+            anyhow::bail!("Every sender failed: {}", errors.join("; "));
+        }
+
+        Ok(accepted)
+    }
+
+    async fn follow_up(
+        &self,
+        accepted: &[String],
+        transaction: &VersionedTransaction,
+        payer: &Keypair,
+    ) -> Option<Vec<String>> {
+        let mut followed_up = None;
+        for sender in &self.senders {
+            let current = followed_up.as_deref().unwrap_or(accepted);
+            if let Some(senders) = sender.follow_up(current, transaction, payer).await {
+                followed_up = Some(senders);
+            }
+        }
+
+        followed_up
+    }
+
+    fn record_outcome(&self, sender: &str, outcome: Outcome) {
+        for inner in &self.senders {
+            inner.record_outcome(sender, outcome);
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, LandingStats)> {
+        self.senders
+            .iter()
+            .flat_map(|sender| sender.stats())
+            .collect()
+    }
+}
+
+/// Sends through `primary`, and through `fallback` only when `primary` refuses the transaction
+/// or gives up on it.
+pub struct Fallback {
+    primary: Box<dyn TransactionSender>,
+    fallback: Box<dyn TransactionSender>,
+}
+
+impl Fallback {
+    pub fn new(primary: Box<dyn TransactionSender>, fallback: Box<dyn TransactionSender>) -> Self {
+        Self { primary, fallback }
+    }
+}
+
+#[async_trait]
+impl TransactionSender for Fallback {
+    fn name(&self) -> &str {
+        "fallback"
+    }
+
+    async fn send(
+        &self,
+        transaction: &VersionedTransaction,
+        payer: &Keypair,
+    ) -> anyhow::Result<Vec<String>> {
+        match self.primary.send(transaction, payer).await {
+            Ok(accepted) => Ok(accepted),
+            Err(e) => {
+                warn!(
+                    primary = self.primary.name(),
+                    fallback = self.fallback.name(),
+                    "Sending through the fallback: {:#}",
+                    e
+                );
+                self.fallback
+                    .send(transaction, payer)
+                    .await
+                    .with_context(|| format!("{} failed too", self.fallback.name()))
+            }
+        }
+    }
+
+    async fn follow_up(
+        &self,
+        accepted: &[String],
+        transaction: &VersionedTransaction,
+        payer: &Keypair,
+    ) -> Option<Vec<String>> {
+        if !self.primary.gave_up(transaction).await {
+            return self.fallback.follow_up(accepted, transaction, payer).await;
+        }
+
+        let primary = self
+            .primary
+            .stats()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        let mut senders = accepted
+            .iter()
+            .filter(|sender| !primary.contains(sender))
+            .cloned()
+            .collect::<Vec<_>>();
+        match self.fallback.send(transaction, payer).await {
+            Ok(accepted) => {
+                warn!(
+                    primary = self.primary.name(),
+                    fallback = self.fallback.name(),
+                    ?accepted,
+                    "Sent through the fallback"
+                );
+                senders.extend(accepted);
+            }
+            Err(e) => warn!(
+                fallback = self.fallback.name(),
+                "Fallback failed too: {:#}", e
+            ),
+        }
+
+        Some(senders)
+    }
+
+    fn record_outcome(&self, sender: &str, outcome: Outcome) {
+        self.primary.record_outcome(sender, outcome);
+        self.fallback.record_outcome(sender, outcome);
+    }
+
+    fn stats(&self) -> Vec<(String, LandingStats)> {
+        let mut stats = self.primary.stats();
+        stats.extend(self.fallback.stats());
+
+        stats
+    }
+}
+
+/// The senders enabled in `config`, fanned out to. With a block engine, each transaction is
+/// first sent as a bundle, and through them only once the bundle fails or does not land in
+/// time.
+pub async fn from_config(
+    config: &Config,
+    solana_api: Arc<SolanaApi>,
+) -> anyhow::Result<Box<dyn TransactionSender>> {
+    let mut senders: Vec<Box<dyn TransactionSender>> = Vec::new();
+    if config.senders.rpc {
+        senders.push(Box::new(RpcSender::new(
+            Arc::clone(&solana_api),
+            config.strategy.skip_preflight,
+        )));
+    }
+    if config.senders.tpu {
+        senders.push(Box::new(
+            TpuSender::new(
+                config.rpc_url()?,
+                config.ws_url()?,
+                config.senders.tpu_fanout_slots,
+            )
+            .await?,
+        ));
+    }
+    let direct: Box<dyn TransactionSender> = if senders.len() == 1 {
+        senders.remove(0)
+    } else {
+        Box::new(FanOut::new(senders))
+    };
+
+    let Some(url) = &config.jito.block_engine_url else {
+        return Ok(direct);
+    };
+    let bundle = BundleSender::new(
+        JitoClient::new(url),
+        solana_api,
+        config.jito.tip_lamports,
+        Duration::from_millis(config.jito.status_timeout_ms),
+    );
+
+    Ok(Box::new(Fallback::new(Box::new(bundle), direct)))
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_sdk::hash::Hash;
+
+    use super::*;
+
+    /// Accepts or refuses every transaction, and may give up on those it accepted.
+    struct TestSender {
+        name: String,
+        accepts: bool,
+        gives_up: bool,
+        stats: SenderStats,
+    }
+
+    fn sender(name: &str, accepts: bool) -> Box<dyn TransactionSender> {
+        Box::new(TestSender {
+            name: name.to_string(),
+            accepts,
+            gives_up: false,
+            stats: SenderStats::default(),
+        })
+    }
+
+    fn giving_up(name: &str) -> Box<dyn TransactionSender> {
+        Box::new(TestSender {
+            name: name.to_string(),
+            accepts: true,
+            gives_up: true,
+            stats: SenderStats::default(),
+        })
+    }
+
+    #[async_trait]
+    impl TransactionSender for TestSender {
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        async fn send(
+            &self,
+            _transaction: &VersionedTransaction,
+            _payer: &Keypair,
+        ) -> anyhow::Result<Vec<String>> {
+            let started = Instant::now();
+            let result = if self.accepts {
+                Ok(vec![self.name.clone()])
+            } else {
+                Err(anyhow::anyhow!("Refused"))
+            };
+            self.stats.record_send(&self.name, started, &result);
+
+            result
+        }
+
+        async fn gave_up(&self, _transaction: &VersionedTransaction) -> bool {
+            self.gives_up
+        }
+
+        fn record_outcome(&self, sender: &str, outcome: Outcome) {
+            if sender == self.name {
+                self.stats.record_outcome(sender, outcome);
+            }
+        }
+
+        fn stats(&self) -> Vec<(String, LandingStats)> {
+            vec![(self.name.clone(), self.stats.snapshot())]
+        }
+    }
+
+    fn transaction() -> VersionedTransaction {
+        solana_sdk::transaction::Transaction::new_unsigned(
+            solana_sdk::message::Message::new_with_blockhash(&[], None, &Hash::default()),
+        )
+        .into()
+    }
+
+    #[tokio::test]
+    async fn fan_out_sends_through_every_sender_and_credits_those_that_accepted() {
+        let fan_out = FanOut::new(vec![
+            sender("rpc", true),
+            sender("tpu", false),
+            Box::new(Fallback::new(
+                sender("bundle", false),
+                sender("backup", true),
+            )),
+        ]);
+
+        let accepted = fan_out.send(&transaction(), &Keypair::new()).await.unwrap();
+        for sender in &accepted {
+            fan_out.record_outcome(sender, Outcome::Landed);
+        }
+
+        assert_eq!(accepted, vec!["rpc", "backup"]);
+        let stats = fan_out.stats();
+        let names = stats
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["rpc", "tpu", "bundle", "backup"]);
+        let [rpc, tpu, bundle, backup] = [0, 1, 2, 3].map(|index| stats[index].1);
+        assert_eq!(
+            (rpc.sent, rpc.landed, rpc.landing_rate()),
+            (1, 1, Some(1.0))
+        );
+        assert_eq!((tpu.rejected, tpu.landing_rate()), (1, None));
+        assert_eq!((bundle.sent, bundle.rejected), (0, 1));
+        assert_eq!((backup.sent, backup.landed), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn fan_out_fails_when_every_sender_does() {
+        let fan_out = FanOut::new(vec![sender("rpc", false), sender("tpu", false)]);
+
+        let error = fan_out
+            .send(&transaction(), &Keypair::new())
+            .await
+            .unwrap_err();
+
+        assert!(error.to_string().contains("rpc: Refused; tpu: Refused"));
+    }
+
+    #[tokio::test]
+    async fn fallback_takes_over_once_the_primary_gives_up() {
+        let fallback = Fallback::new(giving_up("bundle"), sender("backup", true));
+        let payer = Keypair::new();
+
+        let accepted = fallback.send(&transaction(), &payer).await.unwrap();
+        assert_eq!(accepted, vec!["bundle"]);
+
+        let followed_up = fallback.follow_up(&accepted, &transaction(), &payer).await;
+        assert_eq!(followed_up, Some(vec!["backup".to_string()]));
+        let backup = fallback.stats()[1].1;
+        assert_eq!(backup.sent, 1);
+    }
+
+    #[tokio::test]
+    async fn fallback_leaves_transactions_the_primary_still_carries() {
+        let fallback = Fallback::new(sender("bundle", true), sender("backup", true));
+        let payer = Keypair::new();
+
+        let accepted = fallback.send(&transaction(), &payer).await.unwrap();
+        let followed_up = fallback.follow_up(&accepted, &transaction(), &payer).await;
+
+        assert_eq!(followed_up, None);
+        assert_eq!(fallback.stats()[1].1.sent, 0);
+    }
+
+    #[tokio::test]
+    async fn fan_out_follows_up_on_each_sender() {
+        let fan_out = FanOut::new(vec![
+            sender("rpc", true),
+            Box::new(Fallback::new(giving_up("bundle"), sender("backup", false))),
+        ]);
+        let payer = Keypair::new();
+
+        let accepted = fan_out.send(&transaction(), &payer).await.unwrap();
+        assert_eq!(accepted, vec!["rpc", "bundle"]);
+
+        // The fallback refusing it too leaves the transaction to the senders still carrying it.
+        let followed_up = fan_out.follow_up(&accepted, &transaction(), &payer).await;
+        assert_eq!(followed_up, Some(vec!["rpc".to_string()]));
+    }
+}
@@ -0,0 +1,60 @@
+use std::{sync::Arc, time::Instant};
+
+use async_trait::async_trait;
+use solana_sdk::{signature::Keypair, transaction::VersionedTransaction};
+
+use crate::api::solana_rpc::SolanaApi;
+
+use super::{LandingStats, Outcome, SenderStats, TransactionSender};
+
+/// `sendTransaction` through the RPC node, which forwards to the leaders itself.
+pub struct RpcSender {
+    solana_api: Arc<SolanaApi>,
+    /// Skip the simulation the node runs before forwarding, which costs a round trip's worth of
+    /// latency and refuses transactions that would only succeed once the pool is live.
+    skip_preflight: bool,
+    stats: SenderStats,
+}
+
+impl RpcSender {
+    pub fn new(solana_api: Arc<SolanaApi>, skip_preflight: bool) -> Self {
+        Self {
+            solana_api,
+            skip_preflight,
+            stats: SenderStats::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl TransactionSender for RpcSender {
+    fn name(&self) -> &str {
+        "rpc"
+    }
+
+    async fn send(
+        &self,
+        transaction: &VersionedTransaction,
+        _payer: &Keypair,
+    ) -> anyhow::Result<Vec<String>> {
+        let started = Instant::now();
+        let result = self
+            .solana_api
+            .send_transaction(transaction, self.skip_preflight)
+            .await
+            .map(|_| vec![self.name().to_string()]);
+        self.stats.record_send(self.name(), started, &result);
+
+        result
+    }
+
+    fn record_outcome(&self, sender: &str, outcome: Outcome) {
+        if sender == self.name() {
+            self.stats.record_outcome(sender, outcome);
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, LandingStats)> {
+        vec![(self.name().to_string(), self.stats.snapshot())]
+    }
+}
@@ -0,0 +1,76 @@
+use std::{sync::Arc, time::Instant};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use solana_client::{
+    nonblocking::{rpc_client::RpcClient, tpu_client::TpuClient},
+    tpu_client::TpuClientConfig,
+};
+use solana_quic_client::{QuicConfig, QuicConnectionManager, QuicPool};
+use solana_sdk::{signature::Keypair, transaction::VersionedTransaction};
+
+use super::{LandingStats, Outcome, SenderStats, TransactionSender};
+
+/// Straight to the TPUs of the current and upcoming leaders over QUIC, skipping the RPC node.
+///
+/// The leader schedule is fetched over RPC and followed over the websocket.
+pub struct TpuSender {
+    tpu_client: TpuClient<QuicPool, QuicConnectionManager, QuicConfig>,
+    stats: SenderStats,
+}
+
+impl TpuSender {
+    /// Connects to the leaders of the next `fanout_slots` slots.
+    pub async fn new(rpc_url: &str, ws_url: &str, fanout_slots: u64) -> anyhow::Result<Self> {
+        let rpc_client = Arc::new(RpcClient::new(rpc_url.to_string()));
+        let tpu_client = TpuClient::new(
+            "sniper_tpu",
+            rpc_client,
+            ws_url,
+            TpuClientConfig { fanout_slots },
+        )
+        .await
+        .with_context(|| "Failed to create TPU client")?;
+
+        Ok(Self {
+            tpu_client,
+            stats: SenderStats::default(),
+        })
+    }
+}
+
+#[async_trait]
+impl TransactionSender for TpuSender {
+    fn name(&self) -> &str {
+        "tpu"
+    }
+
+    async fn send(
+        &self,
+        transaction: &VersionedTransaction,
+        _payer: &Keypair,
+    ) -> anyhow::Result<Vec<String>> {
+        let started = Instant::now();
+        let wire_transaction =
+            bincode::serialize(transaction).with_context(|| "Failed to serialize transaction")?;
+        let result = self
+            .tpu_client
+            .try_send_wire_transaction(wire_transaction)
+            .await
+            .with_context(|| "Failed to send transaction to the leaders")
+            .map(|()| vec![self.name().to_string()]);
+        self.stats.record_send(self.name(), started, &result);
+
+        result
+    }
+
+    fn record_outcome(&self, sender: &str, outcome: Outcome) {
+        if sender == self.name() {
+            self.stats.record_outcome(sender, outcome);
+        }
+    }
+
+    fn stats(&self) -> Vec<(String, LandingStats)> {
+        vec![(self.name().to_string(), self.stats.snapshot())]
+    }
+}
@@ -0,0 +1,93 @@
+use std::process::ExitCode;
+
+use tokio::sync::watch;
+use tracing::{error, warn};
+
+/// Signal that asked the bot to stop.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Signal {
+    Interrupt,
+    Terminate,
+}
+
+/// Shared view of whether SIGINT or SIGTERM was received.
+///
+/// Long running commands poll it to stop taking new work and wind down cleanly. A second
+/// signal exits immediately.
+#[derive(Clone)]
+pub struct Shutdown {
+    receiver: watch::Receiver<Option<Signal>>,
+}
+
+impl Shutdown {
+    pub fn listen() -> Self {
+        let (sender, receiver) = watch::channel(None);
+
+        tokio::spawn(async move {
+            let signal = wait_for_signal().await;
+            warn!(?signal, "Shutting down, signal again to force");
+            let _ = sender.send(Some(signal));
+
+            let signal = wait_for_signal().await;
+            error!(?signal, "Signalled again, exiting now");
+            std::process::exit(signal.exit_code());
+        });
+
+        Self { receiver }
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.receiver.borrow().is_some()
+    }
+
+    /// Resolves once a shutdown is requested.
+    pub async fn requested(&self) {
+        let mut receiver = self.receiver.clone();
+        // Only fails if the listener is gone, and then no signal can arrive anymore.
+        if receiver.wait_for(Option::is_some).await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+
+    /// Conventional `128 + signal number` when interrupted, success otherwise.
+    pub fn exit_code(&self) -> ExitCode {
+        match *self.receiver.borrow() {
+            Some(signal) => ExitCode::from(signal.exit_code() as u8),
+            None => ExitCode::SUCCESS,
+        }
+    }
+}
+
+impl Signal {
+    fn exit_code(&self) -> i32 {
+        match self {
+            Signal::Interrupt => 130,
+            Signal::Terminate => 143,
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() -> Signal {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = match signal(SignalKind::terminate()) {
+        Ok(terminate) => terminate,
+        Err(e) => {
+            warn!("Failed to listen for SIGTERM: {}", e);
+            let _ = tokio::signal::ctrl_c().await;
+            return Signal::Interrupt;
+        }
+    };
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => Signal::Interrupt,
+        _ = terminate.recv() => Signal::Terminate,
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() -> Signal {
+    let _ = tokio::signal::ctrl_c().await;
+    Signal::Interrupt
+}
@@ -0,0 +1,122 @@
+use std::{fmt, str::FromStr};
+
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TradeSide {
+    Buy,
+    Sell,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TradeStatus {
+    /// Only simulated, nothing was sent.
+    Simulated,
+    /// Sent to the cluster, outcome unknown yet.
+    Sent,
+    Landed,
+    Failed,
+}
+
+/// A swap observed on a tracked pool, decoded from its `ray_log`.
+#[derive(Clone, Debug)]
+pub struct SwapRecord {
+    pub signature: Signature,
+    pub amm_pool: Pubkey,
+    pub slot: u64,
+    pub base_in: bool,
+    pub direction: u64,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub pool_coin: u64,
+    pub pool_pc: u64,
+    pub observed_at: i64,
+}
+
+/// A trade we built, with its outcome.
+#[derive(Clone, Debug)]
+pub struct TradeRecord {
+    pub id: Option<i64>,
+    pub amm_pool: Pubkey,
+    pub owner: Pubkey,
+    pub side: TradeSide,
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub amount_in: u64,
+    pub min_amount_out: u64,
+    pub signature: Option<Signature>,
+    pub status: TradeStatus,
+    pub error: Option<String>,
+    pub created_at: i64,
+}
+
+/// Tokens held from a buy that has not been sold yet.
+#[derive(Clone, Debug)]
+pub struct Position {
+    pub owner: Pubkey,
+    pub mint: Pubkey,
+    pub amm_pool: Pubkey,
+    pub quote_amount: u64,
+    /// Tokens bought, as quoted when buying.
+    pub token_amount: u64,
+    pub entry_signature: Option<Signature>,
+    pub opened_at: i64,
+}
+
+impl TradeSide {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TradeSide::Buy => "buy",
+            TradeSide::Sell => "sell",
+        }
+    }
+}
+
+impl FromStr for TradeSide {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "buy" => Ok(TradeSide::Buy),
+            "sell" => Ok(TradeSide::Sell),
+            _ => Err(anyhow::anyhow!("Unknown trade side {}", value)),
+        }
+    }
+}
+
+impl fmt::Display for TradeSide {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl TradeStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TradeStatus::Simulated => "simulated",
+            TradeStatus::Sent => "sent",
+            TradeStatus::Landed => "landed",
+            TradeStatus::Failed => "failed",
+        }
+    }
+}
+
+impl FromStr for TradeStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "simulated" => Ok(TradeStatus::Simulated),
+            "sent" => Ok(TradeStatus::Sent),
+            "landed" => Ok(TradeStatus::Landed),
+            "failed" => Ok(TradeStatus::Failed),
+            _ => Err(anyhow::anyhow!("Unknown trade status {}", value)),
+        }
+    }
+}
+
+impl fmt::Display for TradeStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
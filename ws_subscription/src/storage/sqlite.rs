@@ -0,0 +1,928 @@
+use std::{
+    str::FromStr,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use rusqlite::{params, Connection, Row};
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+
+use crate::raydium::{
+    cpmm::CpmmPool,
+    event_processors::timings::{Stage, TradeTimings},
+    models::{AmmKeys, MarketKeys, Pool},
+};
+
+use super::models::{Position, SwapRecord, TradeRecord, TradeStatus};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS pools (
+    amm_pool TEXT PRIMARY KEY,
+    amm_coin_mint TEXT NOT NULL,
+    amm_pc_mint TEXT NOT NULL,
+    amm_authority TEXT NOT NULL,
+    amm_target TEXT NOT NULL,
+    amm_coin_vault TEXT NOT NULL,
+    amm_pc_vault TEXT NOT NULL,
+    amm_lp_mint TEXT NOT NULL,
+    amm_open_order TEXT NOT NULL,
+    market_program TEXT NOT NULL,
+    market TEXT NOT NULL,
+    nonce INTEGER NOT NULL,
+    initial_coin_balance INTEGER NOT NULL,
+    initial_pc_balance INTEGER NOT NULL,
+    creation_signature TEXT NOT NULL,
+    slot INTEGER NOT NULL,
+    tracked INTEGER NOT NULL DEFAULT 0,
+    tracked_at INTEGER,
+    detected_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS cpmm_pools (
+    pool TEXT PRIMARY KEY,
+    amm_config TEXT NOT NULL,
+    token_0_mint TEXT NOT NULL,
+    token_1_mint TEXT NOT NULL,
+    token_0_vault TEXT NOT NULL,
+    token_1_vault TEXT NOT NULL,
+    token_0_program TEXT NOT NULL,
+    token_1_program TEXT NOT NULL,
+    lp_mint TEXT NOT NULL,
+    observation TEXT NOT NULL,
+    trade_fee_rate INTEGER NOT NULL,
+    initial_token_0_balance INTEGER NOT NULL,
+    initial_token_1_balance INTEGER NOT NULL,
+    creation_signature TEXT NOT NULL,
+    slot INTEGER NOT NULL,
+    detected_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS swaps (
+    signature TEXT NOT NULL,
+    amm_pool TEXT NOT NULL,
+    slot INTEGER NOT NULL,
+    base_in INTEGER NOT NULL,
+    direction INTEGER NOT NULL,
+    amount_in INTEGER NOT NULL,
+    amount_out INTEGER NOT NULL,
+    pool_coin INTEGER NOT NULL,
+    pool_pc INTEGER NOT NULL,
+    observed_at INTEGER NOT NULL,
+    PRIMARY KEY (signature, amm_pool)
+);
+
+CREATE TABLE IF NOT EXISTS trades (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    amm_pool TEXT NOT NULL,
+    owner TEXT NOT NULL,
+    side TEXT NOT NULL,
+    input_mint TEXT NOT NULL,
+    output_mint TEXT NOT NULL,
+    amount_in INTEGER NOT NULL,
+    min_amount_out INTEGER NOT NULL,
+    signature TEXT,
+    status TEXT NOT NULL,
+    error TEXT,
+    created_at INTEGER NOT NULL
+);
+
+-- Microseconds from the notification that triggered the trade to each stage.
+CREATE TABLE IF NOT EXISTS trade_timings (
+    trade_id INTEGER PRIMARY KEY REFERENCES trades (id),
+    notification_slot INTEGER NOT NULL,
+    pool_slot INTEGER,
+    landed_slot INTEGER,
+    transaction_fetched_us INTEGER,
+    pool_decoded_us INTEGER,
+    market_keys_fetched_us INTEGER,
+    quote_computed_us INTEGER,
+    signed_us INTEGER,
+    sent_us INTEGER,
+    landed_us INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS market_keys (
+    market TEXT PRIMARY KEY,
+    event_queue TEXT NOT NULL,
+    bids TEXT NOT NULL,
+    asks TEXT NOT NULL,
+    coin_vault TEXT NOT NULL,
+    pc_vault TEXT NOT NULL,
+    vault_signer_key TEXT NOT NULL,
+    cached_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS positions (
+    owner TEXT NOT NULL,
+    mint TEXT NOT NULL,
+    amm_pool TEXT NOT NULL,
+    quote_amount INTEGER NOT NULL,
+    token_amount INTEGER NOT NULL,
+    entry_signature TEXT,
+    opened_at INTEGER NOT NULL,
+    closed_at INTEGER,
+    PRIMARY KEY (owner, mint)
+);
+
+-- One row per closed position, kept when the wallet buys the mint again.
+CREATE TABLE IF NOT EXISTS closed_positions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    owner TEXT NOT NULL,
+    mint TEXT NOT NULL,
+    amm_pool TEXT NOT NULL,
+    quote_amount INTEGER NOT NULL,
+    proceeds INTEGER NOT NULL,
+    opened_at INTEGER NOT NULL,
+    closed_at INTEGER NOT NULL
+);
+";
+
+/// Local SQLite store for detected pools, observed swaps, our trades, open positions and
+/// cached market keys.
+pub struct Store {
+    connection: Mutex<Connection>,
+}
+
+impl Store {
+    pub fn open(path: &str) -> anyhow::Result<Self> {
+        let connection =
+            Connection::open(path).with_context(|| format!("Failed to open store {}", path))?;
+        connection
+            .execute_batch(SCHEMA)
+            .with_context(|| "Failed to create store schema")?;
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    pub fn save_pool(&self, pool: &Pool) -> anyhow::Result<()> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .execute(
+                "INSERT INTO pools (
+                    amm_pool, amm_coin_mint, amm_pc_mint, amm_authority, amm_target,
+                    amm_coin_vault, amm_pc_vault, amm_lp_mint, amm_open_order, market_program,
+                    market, nonce, initial_coin_balance, initial_pc_balance, creation_signature,
+                    slot, detected_at
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)
+                ON CONFLICT (amm_pool) DO NOTHING",
+                params![
+                    pool.amm.amm_pool.to_string(),
+                    pool.amm.amm_coin_mint.to_string(),
+                    pool.amm.amm_pc_mint.to_string(),
+                    pool.amm.amm_authority.to_string(),
+                    pool.amm.amm_target.to_string(),
+                    pool.amm.amm_coin_vault.to_string(),
+                    pool.amm.amm_pc_vault.to_string(),
+                    pool.amm.amm_lp_mint.to_string(),
+                    pool.amm.amm_open_order.to_string(),
+                    pool.amm.market_program.to_string(),
+                    pool.amm.market.to_string(),
+                    pool.amm.nonce,
+                    pool.initial_coin_balance as i64,
+                    pool.initial_pc_balance as i64,
+                    pool.creation_signature.to_string(),
+                    pool.slot as i64,
+                    now(),
+                ],
+            )
+            .with_context(|| format!("Failed to save pool {}", pool.amm.amm_pool))?;
+
+        Ok(())
+    }
+
+    pub fn save_cpmm_pool(&self, pool: &CpmmPool) -> anyhow::Result<()> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .execute(
+                "INSERT INTO cpmm_pools (
+                    pool, amm_config, token_0_mint, token_1_mint, token_0_vault, token_1_vault,
+                    token_0_program, token_1_program, lp_mint, observation, trade_fee_rate,
+                    initial_token_0_balance, initial_token_1_balance, creation_signature, slot,
+                    detected_at
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+                ON CONFLICT (pool) DO NOTHING",
+                params![
+                    pool.keys.pool.to_string(),
+                    pool.keys.amm_config.to_string(),
+                    pool.keys.token_0_mint.to_string(),
+                    pool.keys.token_1_mint.to_string(),
+                    pool.keys.token_0_vault.to_string(),
+                    pool.keys.token_1_vault.to_string(),
+                    pool.keys.token_0_program.to_string(),
+                    pool.keys.token_1_program.to_string(),
+                    pool.keys.lp_mint.to_string(),
+                    pool.keys.observation.to_string(),
+                    pool.trade_fee_rate as i64,
+                    pool.initial_reserves.coin_vault as i64,
+                    pool.initial_reserves.pc_vault as i64,
+                    pool.creation_signature.to_string(),
+                    pool.slot as i64,
+                    now(),
+                ],
+            )
+            .with_context(|| format!("Failed to save CPMM pool {}", pool.keys.pool))?;
+
+        Ok(())
+    }
+
+    pub fn set_pool_tracked(&self, amm_pool: &Pubkey, tracked: bool) -> anyhow::Result<()> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .execute(
+                "UPDATE pools SET tracked = ?2, tracked_at = ?3 WHERE amm_pool = ?1",
+                params![amm_pool.to_string(), tracked, tracked.then(now)],
+            )
+            .with_context(|| format!("Failed to update pool {}", amm_pool))?;
+
+        Ok(())
+    }
+
+    pub fn tracked_pools(&self) -> anyhow::Result<Vec<Pool>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare("SELECT * FROM pools WHERE tracked = 1")?;
+        let pools = statement
+            .query_map([], pool_from_row)?
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| "Failed to read tracked pools")?;
+
+        Ok(pools)
+    }
+
+    /// Pools to resubscribe to on start-up: those with an open position, plus those flagged for
+    /// watching since `tracked_since`.
+    pub fn pools_to_resume(&self, tracked_since: i64) -> anyhow::Result<Vec<Pool>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT * FROM pools
+            WHERE (tracked = 1 AND tracked_at >= ?1)
+                OR amm_pool IN (SELECT amm_pool FROM positions WHERE closed_at IS NULL)",
+        )?;
+        let pools = statement
+            .query_map(params![tracked_since], pool_from_row)?
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| "Failed to read pools to resume")?;
+
+        Ok(pools)
+    }
+
+    /// Slot of the most recent swap recorded on the pool.
+    pub fn last_swap_slot(&self, amm_pool: &Pubkey) -> anyhow::Result<Option<u64>> {
+        let connection = self.connection.lock().unwrap();
+        let slot: Option<i64> = connection
+            .query_row(
+                "SELECT MAX(slot) FROM swaps WHERE amm_pool = ?1",
+                params![amm_pool.to_string()],
+                |row| row.get(0),
+            )
+            .with_context(|| format!("Failed to read last swap of pool {}", amm_pool))?;
+
+        Ok(slot.map(|slot| slot as u64))
+    }
+
+    pub fn save_swap(&self, swap: &SwapRecord) -> anyhow::Result<()> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .execute(
+                "INSERT INTO swaps (
+                    signature, amm_pool, slot, base_in, direction, amount_in, amount_out,
+                    pool_coin, pool_pc, observed_at
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                ON CONFLICT (signature, amm_pool) DO NOTHING",
+                params![
+                    swap.signature.to_string(),
+                    swap.amm_pool.to_string(),
+                    swap.slot as i64,
+                    swap.base_in,
+                    swap.direction as i64,
+                    swap.amount_in as i64,
+                    swap.amount_out as i64,
+                    swap.pool_coin as i64,
+                    swap.pool_pc as i64,
+                    swap.observed_at,
+                ],
+            )
+            .with_context(|| format!("Failed to save swap {}", swap.signature))?;
+
+        Ok(())
+    }
+
+    pub fn save_trade(&self, trade: &TradeRecord) -> anyhow::Result<i64> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .execute(
+                "INSERT INTO trades (
+                    amm_pool, owner, side, input_mint, output_mint, amount_in, min_amount_out,
+                    signature, status, error, created_at
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    trade.amm_pool.to_string(),
+                    trade.owner.to_string(),
+                    trade.side.as_str(),
+                    trade.input_mint.to_string(),
+                    trade.output_mint.to_string(),
+                    trade.amount_in as i64,
+                    trade.min_amount_out as i64,
+                    trade.signature.map(|signature| signature.to_string()),
+                    trade.status.as_str(),
+                    trade.error,
+                    trade.created_at,
+                ],
+            )
+            .with_context(|| format!("Failed to save trade on pool {}", trade.amm_pool))?;
+
+        Ok(connection.last_insert_rowid())
+    }
+
+    pub fn update_trade_status(
+        &self,
+        id: i64,
+        status: TradeStatus,
+        error: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .execute(
+                "UPDATE trades SET status = ?2, error = COALESCE(?3, error) WHERE id = ?1",
+                params![id, status.as_str(), error],
+            )
+            .with_context(|| format!("Failed to update trade {}", id))?;
+
+        Ok(())
+    }
+
+    /// Saves the latency breakdown of a trade, replacing what was saved before as more stages
+    /// are reached.
+    pub fn save_trade_timings(&self, trade_id: i64, timings: &TradeTimings) -> anyhow::Result<()> {
+        let micros = |stage| {
+            timings
+                .elapsed(stage)
+                .map(|elapsed| elapsed.as_micros() as i64)
+        };
+        let connection = self.connection.lock().unwrap();
+        connection
+            .execute(
+                "INSERT OR REPLACE INTO trade_timings (
+                    trade_id, notification_slot, pool_slot, landed_slot, transaction_fetched_us,
+                    pool_decoded_us, market_keys_fetched_us, quote_computed_us, signed_us,
+                    sent_us, landed_us
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    trade_id,
+                    timings.notification_slot as i64,
+                    timings.pool_slot.map(|slot| slot as i64),
+                    timings.landed_slot.map(|slot| slot as i64),
+                    micros(Stage::TransactionFetched),
+                    micros(Stage::PoolDecoded),
+                    micros(Stage::MarketKeysFetched),
+                    micros(Stage::QuoteComputed),
+                    micros(Stage::Signed),
+                    micros(Stage::Sent),
+                    micros(Stage::Landed),
+                ],
+            )
+            .with_context(|| format!("Failed to save timings of trade {}", trade_id))?;
+
+        Ok(())
+    }
+
+    /// Most recent trades first, optionally restricted to one pool.
+    pub fn trades(
+        &self,
+        limit: usize,
+        amm_pool: Option<&Pubkey>,
+    ) -> anyhow::Result<Vec<TradeRecord>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT * FROM trades WHERE (?1 IS NULL OR amm_pool = ?1) ORDER BY id DESC LIMIT ?2",
+        )?;
+        let trades = statement
+            .query_map(
+                params![amm_pool.map(|pool| pool.to_string()), limit as i64],
+                trade_from_row,
+            )?
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| "Failed to read trades")?;
+
+        Ok(trades)
+    }
+
+    pub fn save_market_keys(&self, market: &Pubkey, keys: &MarketKeys) -> anyhow::Result<()> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .execute(
+                "INSERT INTO market_keys (
+                    market, event_queue, bids, asks, coin_vault, pc_vault, vault_signer_key,
+                    cached_at
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                ON CONFLICT (market) DO NOTHING",
+                params![
+                    market.to_string(),
+                    keys.event_queue.to_string(),
+                    keys.bids.to_string(),
+                    keys.asks.to_string(),
+                    keys.coin_vault.to_string(),
+                    keys.pc_vault.to_string(),
+                    keys.vault_signer_key.to_string(),
+                    now(),
+                ],
+            )
+            .with_context(|| format!("Failed to save keys of market {}", market))?;
+
+        Ok(())
+    }
+
+    /// Drops the market keys cached before `cached_since` and returns the others.
+    pub fn market_keys(&self, cached_since: i64) -> anyhow::Result<Vec<(Pubkey, MarketKeys)>> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .execute(
+                "DELETE FROM market_keys WHERE cached_at < ?1",
+                params![cached_since],
+            )
+            .with_context(|| "Failed to prune market keys")?;
+
+        let mut statement = connection.prepare("SELECT * FROM market_keys")?;
+        let keys = statement
+            .query_map([], |row| {
+                Ok((
+                    pubkey(row, "market")?,
+                    MarketKeys {
+                        event_queue: pubkey(row, "event_queue")?,
+                        bids: pubkey(row, "bids")?,
+                        asks: pubkey(row, "asks")?,
+                        coin_vault: pubkey(row, "coin_vault")?,
+                        pc_vault: pubkey(row, "pc_vault")?,
+                        vault_signer_key: pubkey(row, "vault_signer_key")?,
+                    },
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| "Failed to read market keys")?;
+
+        Ok(keys)
+    }
+
+    pub fn open_position(&self, position: &Position) -> anyhow::Result<()> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .execute(
+                "INSERT INTO positions (
+                    owner, mint, amm_pool, quote_amount, token_amount, entry_signature,
+                    opened_at, closed_at
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, NULL)
+                ON CONFLICT (owner, mint) DO UPDATE SET
+                    quote_amount = CASE WHEN closed_at IS NULL
+                        THEN quote_amount + excluded.quote_amount
+                        ELSE excluded.quote_amount END,
+                    token_amount = CASE WHEN closed_at IS NULL
+                        THEN token_amount + excluded.token_amount
+                        ELSE excluded.token_amount END,
+                    amm_pool = excluded.amm_pool,
+                    entry_signature = excluded.entry_signature,
+                    opened_at = CASE WHEN closed_at IS NULL THEN opened_at ELSE excluded.opened_at END,
+                    closed_at = NULL",
+                params![
+                    position.owner.to_string(),
+                    position.mint.to_string(),
+                    position.amm_pool.to_string(),
+                    position.quote_amount as i64,
+                    position.token_amount as i64,
+                    position.entry_signature.map(|signature| signature.to_string()),
+                    position.opened_at,
+                ],
+            )
+            .with_context(|| format!("Failed to save position on {}", position.mint))?;
+
+        Ok(())
+    }
+
+    /// Marks the position of `owner` on `mint` closed, for `proceeds` of the quote mint.
+    /// `false` when it was not open.
+    pub fn close_position(
+        &self,
+        owner: &Pubkey,
+        mint: &Pubkey,
+        proceeds: u64,
+    ) -> anyhow::Result<bool> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        let closed_at = now();
+        let closed = transaction
+            .execute(
+                "INSERT INTO closed_positions (
+                    owner, mint, amm_pool, quote_amount, proceeds, opened_at, closed_at
+                )
+                SELECT owner, mint, amm_pool, quote_amount, ?3, opened_at, ?4 FROM positions
+                WHERE owner = ?1 AND mint = ?2 AND closed_at IS NULL",
+                params![
+                    owner.to_string(),
+                    mint.to_string(),
+                    proceeds as i64,
+                    closed_at
+                ],
+            )
+            .and_then(|_| {
+                transaction.execute(
+                    "UPDATE positions SET closed_at = ?3
+                    WHERE owner = ?1 AND mint = ?2 AND closed_at IS NULL",
+                    params![owner.to_string(), mint.to_string(), closed_at],
+                )
+            })
+            .and_then(|closed| transaction.commit().map(|()| closed))
+            .with_context(|| format!("Failed to close position on {}", mint))?;
+
+        Ok(closed > 0)
+    }
+
+    /// What the closed positions were sold for minus what they cost, in the quote mint.
+    pub fn realised_pnl(&self) -> anyhow::Result<i64> {
+        let connection = self.connection.lock().unwrap();
+        let pnl = connection
+            .query_row(
+                "SELECT COALESCE(SUM(proceeds - quote_amount), 0) FROM closed_positions",
+                [],
+                |row| row.get(0),
+            )
+            .with_context(|| "Failed to read realised PnL")?;
+
+        Ok(pnl)
+    }
+
+    pub fn open_positions(&self) -> anyhow::Result<Vec<Position>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare("SELECT * FROM positions WHERE closed_at IS NULL ORDER BY opened_at")?;
+        let positions = statement
+            .query_map([], position_from_row)?
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| "Failed to read open positions")?;
+
+        Ok(positions)
+    }
+}
+
+/// Current unix timestamp in seconds.
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+fn pubkey(row: &Row, column: &str) -> rusqlite::Result<Pubkey> {
+    let value: String = row.get(column)?;
+    Pubkey::from_str(&value).map_err(|e| conversion_error(column, e))
+}
+
+fn signature(row: &Row, column: &str) -> rusqlite::Result<Option<Signature>> {
+    let value: Option<String> = row.get(column)?;
+    value
+        .map(|value| Signature::from_str(&value).map_err(|e| conversion_error(column, e)))
+        .transpose()
+}
+
+fn conversion_error<E>(column: &str, error: E) -> rusqlite::Error
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    rusqlite::Error::FromSqlConversionFailure(
+        0,
+        rusqlite::types::Type::Text,
+        format!("{}: {}", column, error).into(),
+    )
+}
+
+fn pool_from_row(row: &Row) -> rusqlite::Result<Pool> {
+    Ok(Pool {
+        amm: AmmKeys {
+            amm_pool: pubkey(row, "amm_pool")?,
+            amm_coin_mint: pubkey(row, "amm_coin_mint")?,
+            amm_pc_mint: pubkey(row, "amm_pc_mint")?,
+            amm_authority: pubkey(row, "amm_authority")?,
+            amm_target: pubkey(row, "amm_target")?,
+            amm_coin_vault: pubkey(row, "amm_coin_vault")?,
+            amm_pc_vault: pubkey(row, "amm_pc_vault")?,
+            amm_lp_mint: pubkey(row, "amm_lp_mint")?,
+            amm_open_order: pubkey(row, "amm_open_order")?,
+            market_program: pubkey(row, "market_program")?,
+            market: pubkey(row, "market")?,
+            nonce: row.get("nonce")?,
+        },
+        initial_coin_balance: row.get::<_, i64>("initial_coin_balance")? as u64,
+        initial_pc_balance: row.get::<_, i64>("initial_pc_balance")? as u64,
+        creation_signature: signature(row, "creation_signature")?.unwrap_or_default(),
+        slot: row.get::<_, i64>("slot")? as u64,
+    })
+}
+
+fn trade_from_row(row: &Row) -> rusqlite::Result<TradeRecord> {
+    let side: String = row.get("side")?;
+    let status: String = row.get("status")?;
+
+    Ok(TradeRecord {
+        id: row.get("id")?,
+        amm_pool: pubkey(row, "amm_pool")?,
+        owner: pubkey(row, "owner")?,
+        side: side.parse().map_err(|e: anyhow::Error| {
+            conversion_error("side", std::io::Error::other(e.to_string()))
+        })?,
+        input_mint: pubkey(row, "input_mint")?,
+        output_mint: pubkey(row, "output_mint")?,
+        amount_in: row.get::<_, i64>("amount_in")? as u64,
+        min_amount_out: row.get::<_, i64>("min_amount_out")? as u64,
+        signature: signature(row, "signature")?,
+        status: status.parse().map_err(|e: anyhow::Error| {
+            conversion_error("status", std::io::Error::other(e.to_string()))
+        })?,
+        error: row.get("error")?,
+        created_at: row.get("created_at")?,
+    })
+}
+
+fn position_from_row(row: &Row) -> rusqlite::Result<Position> {
+    Ok(Position {
+        owner: pubkey(row, "owner")?,
+        mint: pubkey(row, "mint")?,
+        amm_pool: pubkey(row, "amm_pool")?,
+        quote_amount: row.get::<_, i64>("quote_amount")? as u64,
+        token_amount: row.get::<_, i64>("token_amount")? as u64,
+        entry_signature: signature(row, "entry_signature")?,
+        opened_at: row.get("opened_at")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::storage::models::TradeSide;
+
+    use super::*;
+
+    fn store() -> Store {
+        Store::open(":memory:").unwrap()
+    }
+
+    fn pool() -> Pool {
+        Pool {
+            amm: AmmKeys {
+                amm_pool: Pubkey::new_unique(),
+                amm_coin_mint: Pubkey::new_unique(),
+                amm_pc_mint: Pubkey::new_unique(),
+                amm_authority: Pubkey::new_unique(),
+                amm_target: Pubkey::new_unique(),
+                amm_coin_vault: Pubkey::new_unique(),
+                amm_pc_vault: Pubkey::new_unique(),
+                amm_lp_mint: Pubkey::new_unique(),
+                amm_open_order: Pubkey::new_unique(),
+                market_program: Pubkey::new_unique(),
+                market: Pubkey::new_unique(),
+                nonce: 254,
+            },
+            initial_coin_balance: 1_000_000_000,
+            initial_pc_balance: 5_000_000_000,
+            creation_signature: Signature::new_unique(),
+            slot: 42,
+        }
+    }
+
+    fn swap(signature: Signature, amm_pool: Pubkey, slot: u64) -> SwapRecord {
+        SwapRecord {
+            signature,
+            amm_pool,
+            slot,
+            base_in: true,
+            direction: 1,
+            amount_in: 100,
+            amount_out: 90,
+            pool_coin: 1_000,
+            pool_pc: 2_000,
+            observed_at: now(),
+        }
+    }
+
+    fn trade(amm_pool: Pubkey) -> TradeRecord {
+        TradeRecord {
+            id: None,
+            amm_pool,
+            owner: Pubkey::new_unique(),
+            side: TradeSide::Buy,
+            input_mint: Pubkey::new_unique(),
+            output_mint: Pubkey::new_unique(),
+            amount_in: 10_000_000,
+            min_amount_out: 900_000,
+            signature: Some(Signature::new_unique()),
+            status: TradeStatus::Sent,
+            error: None,
+            created_at: now(),
+        }
+    }
+
+    fn position(owner: Pubkey, mint: Pubkey, quote_amount: u64, token_amount: u64) -> Position {
+        Position {
+            owner,
+            mint,
+            amm_pool: Pubkey::new_unique(),
+            quote_amount,
+            token_amount,
+            entry_signature: Some(Signature::new_unique()),
+            opened_at: now(),
+        }
+    }
+
+    #[test]
+    fn saves_and_reloads_tracked_pools() {
+        let store = store();
+        let (tracked, untracked) = (pool(), pool());
+        store.save_pool(&tracked).unwrap();
+        store.save_pool(&untracked).unwrap();
+        // Saving a pool again keeps the first record.
+        store
+            .save_pool(&Pool {
+                slot: 43,
+                ..tracked.clone()
+            })
+            .unwrap();
+        store.set_pool_tracked(&tracked.amm.amm_pool, true).unwrap();
+
+        let pools = store.tracked_pools().unwrap();
+        assert_eq!(pools.len(), 1);
+        let reloaded = &pools[0];
+        assert_eq!(reloaded.amm.amm_pool, tracked.amm.amm_pool);
+        assert_eq!(reloaded.amm.amm_coin_mint, tracked.amm.amm_coin_mint);
+        assert_eq!(reloaded.amm.amm_pc_mint, tracked.amm.amm_pc_mint);
+        assert_eq!(reloaded.amm.amm_authority, tracked.amm.amm_authority);
+        assert_eq!(reloaded.amm.amm_target, tracked.amm.amm_target);
+        assert_eq!(reloaded.amm.amm_coin_vault, tracked.amm.amm_coin_vault);
+        assert_eq!(reloaded.amm.amm_pc_vault, tracked.amm.amm_pc_vault);
+        assert_eq!(reloaded.amm.amm_lp_mint, tracked.amm.amm_lp_mint);
+        assert_eq!(reloaded.amm.amm_open_order, tracked.amm.amm_open_order);
+        assert_eq!(reloaded.amm.market_program, tracked.amm.market_program);
+        assert_eq!(reloaded.amm.market, tracked.amm.market);
+        assert_eq!(reloaded.amm.nonce, tracked.amm.nonce);
+        assert_eq!(reloaded.initial_coin_balance, tracked.initial_coin_balance);
+        assert_eq!(reloaded.initial_pc_balance, tracked.initial_pc_balance);
+        assert_eq!(reloaded.creation_signature, tracked.creation_signature);
+        assert_eq!(reloaded.slot, 42);
+
+        let resumed = store.pools_to_resume(now() + 60).unwrap();
+        assert!(resumed.is_empty());
+        store
+            .set_pool_tracked(&tracked.amm.amm_pool, false)
+            .unwrap();
+        assert!(store.tracked_pools().unwrap().is_empty());
+    }
+
+    #[test]
+    fn resumes_pools_with_an_open_position() {
+        let store = store();
+        let held = pool();
+        store.save_pool(&held).unwrap();
+        let mut open = position(Pubkey::new_unique(), Pubkey::new_unique(), 1, 1);
+        open.amm_pool = held.amm.amm_pool;
+        store.open_position(&open).unwrap();
+
+        let resumed = store.pools_to_resume(now() + 60).unwrap();
+        assert_eq!(resumed.len(), 1);
+        assert_eq!(resumed[0].amm.amm_pool, held.amm.amm_pool);
+
+        assert!(store.close_position(&open.owner, &open.mint, 0).unwrap());
+        assert!(store.pools_to_resume(now() + 60).unwrap().is_empty());
+    }
+
+    #[test]
+    fn records_each_swap_once() {
+        let store = store();
+        let amm_pool = Pubkey::new_unique();
+        let signature = Signature::new_unique();
+        assert_eq!(store.last_swap_slot(&amm_pool).unwrap(), None);
+
+        store.save_swap(&swap(signature, amm_pool, 10)).unwrap();
+        store.save_swap(&swap(signature, amm_pool, 30)).unwrap();
+        store
+            .save_swap(&swap(Signature::new_unique(), amm_pool, 20))
+            .unwrap();
+        // The same transaction swapping on another pool is another swap.
+        store
+            .save_swap(&swap(signature, Pubkey::new_unique(), 50))
+            .unwrap();
+
+        assert_eq!(store.last_swap_slot(&amm_pool).unwrap(), Some(20));
+        let count: i64 = store
+            .connection
+            .lock()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM swaps", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn updates_trade_statuses() {
+        let store = store();
+        let amm_pool = Pubkey::new_unique();
+        let sent = trade(amm_pool);
+        let id = store.save_trade(&sent).unwrap();
+        let other = store.save_trade(&trade(Pubkey::new_unique())).unwrap();
+
+        store
+            .update_trade_status(id, TradeStatus::Failed, Some("Blockhash expired"))
+            .unwrap();
+        let trades = store.trades(10, Some(&amm_pool)).unwrap();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].id, Some(id));
+        assert_eq!(trades[0].status, TradeStatus::Failed);
+        assert_eq!(trades[0].error.as_deref(), Some("Blockhash expired"));
+        assert_eq!(trades[0].signature, sent.signature);
+        assert_eq!(trades[0].side, TradeSide::Buy);
+        assert_eq!(trades[0].amount_in, sent.amount_in);
+        assert_eq!(trades[0].min_amount_out, sent.min_amount_out);
+
+        // A status without an error keeps the one recorded before.
+        store
+            .update_trade_status(id, TradeStatus::Landed, None)
+            .unwrap();
+        let trades = store.trades(10, None).unwrap();
+        assert_eq!(
+            trades.iter().map(|trade| trade.id).collect::<Vec<_>>(),
+            [Some(other), Some(id)]
+        );
+        assert_eq!(trades[1].status, TradeStatus::Landed);
+        assert_eq!(trades[1].error.as_deref(), Some("Blockhash expired"));
+        assert_eq!(store.trades(1, None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn aggregates_positions_until_they_close() {
+        let store = store();
+        let (owner, other_owner, mint) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let first = position(owner, mint, 100, 1_000);
+        store.open_position(&first).unwrap();
+        store
+            .open_position(&position(owner, mint, 50, 400))
+            .unwrap();
+        store
+            .open_position(&position(other_owner, mint, 10, 90))
+            .unwrap();
+
+        let positions = store.open_positions().unwrap();
+        assert_eq!(positions.len(), 2);
+        let held = positions
+            .iter()
+            .find(|position| position.owner == owner)
+            .unwrap();
+        assert_eq!((held.quote_amount, held.token_amount), (150, 1_400));
+        assert_eq!(held.opened_at, first.opened_at);
+
+        assert!(store.close_position(&owner, &mint, 200).unwrap());
+        assert!(!store.close_position(&owner, &mint, 200).unwrap());
+        let positions = store.open_positions().unwrap();
+        assert_eq!(positions.len(), 1);
+        assert_eq!(positions[0].owner, other_owner);
+
+        // Buying again after closing starts a new position.
+        store
+            .open_position(&position(owner, mint, 20, 300))
+            .unwrap();
+        let reopened = store
+            .open_positions()
+            .unwrap()
+            .into_iter()
+            .find(|position| position.owner == owner)
+            .unwrap();
+        assert_eq!((reopened.quote_amount, reopened.token_amount), (20, 300));
+    }
+
+    #[test]
+    fn realises_pnl_as_positions_close() {
+        let store = store();
+        let (owner, other_owner, mint) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        assert_eq!(store.realised_pnl().unwrap(), 0);
+
+        store
+            .open_position(&position(owner, mint, 100, 1_000))
+            .unwrap();
+        store
+            .open_position(&position(other_owner, mint, 50, 400))
+            .unwrap();
+        assert!(store.close_position(&owner, &mint, 180).unwrap());
+        assert!(store.close_position(&other_owner, &mint, 20).unwrap());
+        assert_eq!(store.realised_pnl().unwrap(), 80 - 30);
+
+        // Reopening the position does not forget what the first one realised, and closing a
+        // position that is not open realises nothing.
+        store.open_position(&position(owner, mint, 10, 90)).unwrap();
+        assert!(!store.close_position(&other_owner, &mint, 1_000).unwrap());
+        assert!(store.close_position(&owner, &mint, 15).unwrap());
+        assert_eq!(store.realised_pnl().unwrap(), 80 - 30 + 5);
+    }
+}
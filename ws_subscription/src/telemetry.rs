@@ -0,0 +1,95 @@
+use std::net::SocketAddr;
+
+use anyhow::Context;
+use metrics::{describe_counter, describe_gauge, describe_histogram, Unit};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder};
+
+pub const POOLS_DETECTED: &str = "sniper_pools_detected_total";
+pub const DETECTION_TO_SEND: &str = "sniper_detection_to_send_seconds";
+pub const TRADE_STAGE: &str = "sniper_trade_stage_seconds";
+pub const TRANSACTIONS: &str = "sniper_transactions_total";
+pub const RPC_REQUEST_DURATION: &str = "sniper_rpc_request_duration_seconds";
+pub const RPC_ERRORS: &str = "sniper_rpc_errors_total";
+pub const WS_RECONNECTS: &str = "sniper_ws_reconnects_total";
+pub const OPEN_POSITIONS: &str = "sniper_open_positions";
+pub const REALISED_PNL: &str = "sniper_realised_pnl_lamports";
+pub const UNREALISED_PNL: &str = "sniper_unrealised_pnl_lamports";
+pub const PUMP_FUN_CURVE_PROGRESS: &str = "sniper_pump_fun_curve_progress_bps";
+pub const PUMP_FUN_MIGRATIONS: &str = "sniper_pump_fun_migrations_total";
+pub const BUNDLES: &str = "sniper_bundles_total";
+pub const SENDER_TRANSACTIONS: &str = "sniper_sender_transactions_total";
+pub const SENDER_SEND_DURATION: &str = "sniper_sender_send_seconds";
+
+/// Buckets for every `*_seconds` histogram, from 1ms to 10s.
+const LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Serves the Prometheus `/metrics` endpoint on `listen` for the rest of the process.
+///
+/// Without it every metric is a no-op, so instrumented code does not need to care.
+pub fn install(listen: SocketAddr) -> anyhow::Result<()> {
+    PrometheusBuilder::new()
+        .with_http_listener(listen)
+        .set_buckets_for_metric(Matcher::Suffix("_seconds".to_string()), LATENCY_BUCKETS)?
+        .install()
+        .with_context(|| format!("Failed to serve metrics on {}", listen))?;
+
+    describe_counter!(POOLS_DETECTED, "Raydium pool creations decoded");
+    describe_histogram!(
+        DETECTION_TO_SEND,
+        Unit::Seconds,
+        "Time from the pool creation notification to our buy transaction being sent"
+    );
+    describe_histogram!(
+        TRADE_STAGE,
+        Unit::Seconds,
+        "Time from the pool creation notification to each stage of our trade"
+    );
+    describe_counter!(
+        TRANSACTIONS,
+        "Our transactions by outcome: simulated, sent, landed or failed"
+    );
+    describe_histogram!(
+        RPC_REQUEST_DURATION,
+        Unit::Seconds,
+        "RPC request latency by method"
+    );
+    describe_counter!(RPC_ERRORS, "Failed RPC requests by method");
+    describe_counter!(
+        WS_RECONNECTS,
+        "Websocket connections reopened after a subscription closed on them"
+    );
+    describe_gauge!(OPEN_POSITIONS, "Positions bought and not sold yet");
+    describe_gauge!(
+        REALISED_PNL,
+        "What the closed positions were sold for minus what they cost, in lamports of the quote mint"
+    );
+    describe_gauge!(
+        UNREALISED_PNL,
+        "Value of the open positions at the live pool reserves, minus what they cost"
+    );
+    describe_gauge!(
+        PUMP_FUN_CURVE_PROGRESS,
+        "Share of a target's Pump.fun bonding curve sold, in basis points, by mint"
+    );
+    describe_counter!(
+        PUMP_FUN_MIGRATIONS,
+        "Pump.fun targets seen migrating to a Raydium pool"
+    );
+    describe_counter!(
+        BUNDLES,
+        "Jito bundles by outcome: landed, failed, timed_out or rejected"
+    );
+    describe_counter!(
+        SENDER_TRANSACTIONS,
+        "Our transactions by sender and outcome: sent, rejected, landed, failed or expired"
+    );
+    describe_histogram!(
+        SENDER_SEND_DURATION,
+        Unit::Seconds,
+        "Time each sender took to accept a transaction"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,158 @@
+//! The wallets snipes buy with, each in its own transaction and for its own amount, so a buy
+//! is spread over several accounts.
+
+use std::{fs, path::Path};
+
+use anyhow::Context;
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::{EncodableKey, Signer},
+};
+
+use crate::config::Config;
+
+/// A wallet and what it spends per buy.
+#[derive(Debug)]
+pub struct Wallet {
+    pub keypair: Keypair,
+    /// Amount of the quote mint spent per buy.
+    pub amount: u64,
+}
+
+impl Wallet {
+    pub fn pubkey(&self) -> Pubkey {
+        self.keypair.pubkey()
+    }
+}
+
+/// Loads the wallets of `wallet.wallets`, `wallet.owner_file_path` and `wallet.wallets_dir`,
+/// in that order. A keypair listed more than once is loaded the first time only.
+pub fn load(config: &Config) -> anyhow::Result<Vec<Wallet>> {
+    let mut files = config
+        .wallet
+        .wallets
+        .iter()
+        .map(|wallet| (wallet.path.clone(), wallet.amount))
+        .collect::<Vec<_>>();
+    if let Some(path) = &config.wallet.owner_file_path {
+        files.push((path.clone(), None));
+    }
+    if let Some(dir) = &config.wallet.wallets_dir {
+        let mut paths = fs::read_dir(dir)
+            .with_context(|| format!("Failed to read wallets directory {}", dir))?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<std::io::Result<Vec<_>>>()
+            .with_context(|| format!("Failed to read wallets directory {}", dir))?;
+        paths.retain(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        });
+        paths.sort();
+        files.extend(
+            paths
+                .into_iter()
+                .map(|path| (path.to_string_lossy().into_owned(), None)),
+        );
+    }
+    anyhow::ensure!(
+        !files.is_empty(),
+        "A wallet is required (--owner-file-path, --wallets-dir, wallet.owner_file_path, \
+         wallet.wallets_dir or wallet.wallets)"
+    );
+
+    let mut wallets: Vec<Wallet> = Vec::with_capacity(files.len());
+    for (path, amount) in files {
+        let keypair = read_keypair(&path)?;
+        if wallets
+            .iter()
+            .any(|wallet| wallet.pubkey() == keypair.pubkey())
+        {
+            continue;
+        }
+        let amount = amount.or(config.strategy.amount).with_context(|| {
+            format!(
+                "Amount is required for wallet {} (--amount, strategy.amount or its amount in \
+                 wallet.wallets)",
+                path
+            )
+        })?;
+
+        wallets.push(Wallet { keypair, amount });
+    }
+
+    Ok(wallets)
+}
+
+fn read_keypair(path: impl AsRef<Path>) -> anyhow::Result<Keypair> {
+    let path = path.as_ref();
+    Keypair::read_from_file(path)
+        .map_err(|e| anyhow::Error::msg(e.to_string()))
+        .with_context(|| format!("Error parsing private key {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, path::PathBuf};
+
+    use crate::config::WalletFile;
+
+    use super::*;
+
+    /// A directory removed once dropped, even when the test fails.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let dir = env::temp_dir().join(format!("sniper-wallets-{}", Pubkey::new_unique()));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn loads_every_wallet_once_with_its_own_amount() {
+        let temp_dir = TempDir::new();
+        let dir = &temp_dir.0;
+        let [whale, owner, other] = [0, 1, 2].map(|_| Keypair::new());
+        let write = |name: &str, keypair: &Keypair| {
+            let path = dir.join(name);
+            keypair.write_to_file(&path).unwrap();
+            path.to_string_lossy().into_owned()
+        };
+        let whale_path = write("a.json", &whale);
+        let owner_path = write("b.json", &owner);
+        write("c.json", &other);
+        fs::write(dir.join("notes.txt"), "not a keypair").unwrap();
+
+        let mut config = Config::default();
+        config.strategy.amount = Some(1_000);
+        config.wallet.owner_file_path = Some(owner_path);
+        config.wallet.wallets_dir = Some(dir.to_string_lossy().into_owned());
+        config.wallet.wallets = vec![WalletFile {
+            path: whale_path,
+            amount: Some(5_000),
+        }];
+
+        let wallets = load(&config).unwrap();
+
+        let loaded = wallets
+            .iter()
+            .map(|wallet| (wallet.pubkey(), wallet.amount))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            loaded,
+            vec![
+                (whale.pubkey(), 5_000),
+                (owner.pubkey(), 1_000),
+                (other.pubkey(), 1_000),
+            ]
+        );
+    }
+}